@@ -8,6 +8,7 @@ use kernel::arch::x86_64::port::{inb, outb};
 use kernel::device::{CharDevice, Device, DeviceType, FileOperations};
 use kernel::error::{Error, Result};
 use kernel::interrupt::{register_interrupt_handler, IrqHandler};
+use kernel::softirq::{tasklet_schedule, Tasklet};
 use kernel::sync::{Arc, Spinlock};
 
 /// PS/2 keyboard controller ports
@@ -86,18 +87,37 @@ static KEYBOARD_STATE: Spinlock<KeyboardState> = Spinlock::new(KeyboardState::ne
 #[derive(Debug)]
 pub struct KeyboardIrqHandler;
 
+/// Scan codes read by the interrupt handler, not yet processed
+static SCANCODES: Spinlock<VecDeque<u8>> = Spinlock::new(VecDeque::new());
+
+/// Processes scan codes outside hard-IRQ context
+static KEYBOARD_TASKLET: Tasklet = Tasklet::new(keyboard_tasklet, 0);
+
 impl IrqHandler for KeyboardIrqHandler {
 	fn handle_irq(&self, _irq: u32) -> Result<()> {
 		// Read scan code from keyboard data port
 		let scancode = unsafe { inb(KEYBOARD_DATA_PORT) };
 
-		// Process the scan code
-		process_scancode(scancode);
+		// Defer processing to the tasklet
+		let mut scancodes = SCANCODES.lock();
+		if scancodes.len() < 64 {
+			scancodes.push_back(scancode);
+		}
+		drop(scancodes);
+		tasklet_schedule(&KEYBOARD_TASKLET);
 
 		Ok(())
 	}
 }
 
+fn keyboard_tasklet(_data: usize) {
+	while let Some(scancode) =
+		kernel::interrupt::without_interrupts(|| SCANCODES.lock().pop_front())
+	{
+		process_scancode(scancode);
+	}
+}
+
 /// Process a keyboard scan code
 fn process_scancode(scancode: u8) {
 	let mut keyboard = KEYBOARD_STATE.lock();
@@ -136,6 +156,11 @@ fn process_scancode(scancode: u8) {
 		_ => {
 			// Convert scan code to ASCII
 			if let Some(ascii) = scancode_to_ascii(scancode, &keyboard) {
+				// Session input goes to the tty layer
+				if kernel::tty::receive_char(kernel::tty::CONSOLE_TTY, ascii) {
+					return;
+				}
+
 				keyboard.push_key(ascii);
 
 				// Echo to console for now
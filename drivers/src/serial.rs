@@ -8,6 +8,7 @@ use kernel::arch::x86_64::port::{inb, outb};
 use kernel::device::{CharDevice, Device, DeviceType, FileOperations};
 use kernel::error::{Error, Result};
 use kernel::interrupt::{register_interrupt_handler, IrqHandler};
+use kernel::softirq::{tasklet_schedule, Tasklet};
 use kernel::sync::{Arc, Spinlock};
 
 /// Standard COM port addresses
@@ -179,6 +180,9 @@ impl SerialPort {
 	pub fn handle_receive_interrupt(&mut self) {
 		while self.is_receive_ready() {
 			let byte = unsafe { inb(self.base + UART_DATA) };
+			if kernel::tty::receive_char(kernel::tty::SERIAL_TTY, byte) {
+				continue;
+			}
 			if self.rx_buffer.len() < 1024 {
 				// Prevent buffer overflow
 				self.rx_buffer.push_back(byte);
@@ -202,19 +206,26 @@ impl SerialIrqHandler {
 	}
 }
 
+/// Drains the COM1 receive FIFO outside hard-IRQ context
+static COM1_TASKLET: Tasklet = Tasklet::new(serial_rx_tasklet, COM1_BASE as usize);
+
 impl IrqHandler for SerialIrqHandler {
 	fn handle_irq(&self, _irq: u32) -> Result<()> {
 		// Handle COM1 interrupt
 		if self.port_base == COM1_BASE {
-			if let Some(ref mut port) = *COM1.lock() {
-				port.handle_receive_interrupt();
-			}
+			tasklet_schedule(&COM1_TASKLET);
 		}
 
 		Ok(())
 	}
 }
 
+fn serial_rx_tasklet(_port_base: usize) {
+	if let Some(ref mut port) = *COM1.lock() {
+		port.handle_receive_interrupt();
+	}
+}
+
 /// Serial console file operations
 #[derive(Debug)]
 pub struct SerialConsoleOps;
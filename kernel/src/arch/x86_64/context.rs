@@ -4,6 +4,42 @@
 
 use core::arch::asm;
 
+use crate::error::{Error, Result};
+use crate::usermode::{USER_CS, USER_DS};
+
+const EFLAGS_CF: u64 = 1 << 0;
+const EFLAGS_PF: u64 = 1 << 2;
+const EFLAGS_AF: u64 = 1 << 4;
+const EFLAGS_ZF: u64 = 1 << 6;
+const EFLAGS_SF: u64 = 1 << 7;
+const EFLAGS_TF: u64 = 1 << 8;
+const EFLAGS_IF: u64 = 1 << 9;
+const EFLAGS_DF: u64 = 1 << 10;
+const EFLAGS_OF: u64 = 1 << 11;
+const EFLAGS_IOPL: u64 = 3 << 12;
+const EFLAGS_AC: u64 = 1 << 18;
+const EFLAGS_ID: u64 = 1 << 21;
+
+/// Flags a tracer may change with PTRACE_SETREGS
+const EFLAGS_USER_MASK: u64 = EFLAGS_CF
+	| EFLAGS_PF
+	| EFLAGS_AF
+	| EFLAGS_ZF
+	| EFLAGS_SF
+	| EFLAGS_TF
+	| EFLAGS_DF
+	| EFLAGS_OF
+	| EFLAGS_AC
+	| EFLAGS_ID;
+
+/// First address above the canonical lower half used by user space
+const USER_ADDR_END: u64 = 0x0000_8000_0000_0000;
+
+/// Whether `addr` is a canonical user-space address
+pub fn is_user_canonical(addr: u64) -> bool {
+	addr < USER_ADDR_END
+}
+
 /// CPU context for x86_64
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
@@ -100,6 +136,78 @@ impl Context {
 		ctx
 	}
 
+	/// Registers in the layout of struct user_regs_struct, as used by
+	/// ptrace and core dumps
+	pub fn user_regs(&self, orig_rax: u64, fs_base: u64) -> UserRegs {
+		UserRegs {
+			r15: self.r15,
+			r14: self.r14,
+			r13: self.r13,
+			r12: self.r12,
+			rbp: self.rbp,
+			rbx: self.rbx,
+			r11: self.r11,
+			r10: self.r10,
+			r9: self.r9,
+			r8: self.r8,
+			rax: self.rax,
+			rcx: self.rcx,
+			rdx: self.rdx,
+			rsi: self.rsi,
+			rdi: self.rdi,
+			orig_rax,
+			rip: self.rip,
+			cs: self.cs as u64,
+			eflags: self.rflags,
+			rsp: self.rsp,
+			ss: self.ss as u64,
+			fs_base,
+			gs_base: 0,
+			ds: self.ds as u64,
+			es: self.es as u64,
+			fs: self.fs as u64,
+			gs: self.gs as u64,
+		}
+	}
+
+	/// Load registers from a struct user_regs_struct. Only the
+	/// user-changeable flags are taken and segment selectors are forced to
+	/// the user segments; a non-canonical `rip` or `fs_base` is rejected
+	/// with EIO before anything is changed.
+	pub fn set_user_regs(&mut self, regs: &UserRegs) -> Result<()> {
+		if !is_user_canonical(regs.rip) || !is_user_canonical(regs.fs_base) {
+			return Err(Error::EIO);
+		}
+		self.r15 = regs.r15;
+		self.r14 = regs.r14;
+		self.r13 = regs.r13;
+		self.r12 = regs.r12;
+		self.rbp = regs.rbp;
+		self.rbx = regs.rbx;
+		self.r11 = regs.r11;
+		self.r10 = regs.r10;
+		self.r9 = regs.r9;
+		self.r8 = regs.r8;
+		self.rax = regs.rax;
+		self.rcx = regs.rcx;
+		self.rdx = regs.rdx;
+		self.rsi = regs.rsi;
+		self.rdi = regs.rdi;
+		self.rip = regs.rip;
+		self.rflags = (self.rflags & !EFLAGS_USER_MASK)
+			| (regs.eflags & EFLAGS_USER_MASK)
+			| EFLAGS_IF;
+		self.rflags &= !EFLAGS_IOPL;
+		self.rsp = regs.rsp;
+		self.cs = USER_CS;
+		self.ss = USER_DS;
+		self.ds = USER_DS;
+		self.es = USER_DS;
+		self.fs = USER_DS;
+		self.gs = USER_DS;
+		Ok(())
+	}
+
 	/// Save current CPU context
 	pub fn save_current(&mut self) {
 		unsafe {
@@ -252,6 +360,59 @@ impl Context {
 	}
 }
 
+/// User registers, laid out like Linux's struct user_regs_struct
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UserRegs {
+	pub r15: u64,
+	pub r14: u64,
+	pub r13: u64,
+	pub r12: u64,
+	pub rbp: u64,
+	pub rbx: u64,
+	pub r11: u64,
+	pub r10: u64,
+	pub r9: u64,
+	pub r8: u64,
+	pub rax: u64,
+	pub rcx: u64,
+	pub rdx: u64,
+	pub rsi: u64,
+	pub rdi: u64,
+	pub orig_rax: u64,
+	pub rip: u64,
+	pub cs: u64,
+	pub eflags: u64,
+	pub rsp: u64,
+	pub ss: u64,
+	pub fs_base: u64,
+	pub gs_base: u64,
+	pub ds: u64,
+	pub es: u64,
+	pub fs: u64,
+	pub gs: u64,
+}
+
+impl UserRegs {
+	pub fn as_bytes(&self) -> &[u8] {
+		unsafe {
+			core::slice::from_raw_parts(
+				self as *const Self as *const u8,
+				core::mem::size_of::<Self>(),
+			)
+		}
+	}
+
+	pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+		unsafe {
+			core::slice::from_raw_parts_mut(
+				self as *mut Self as *mut u8,
+				core::mem::size_of::<Self>(),
+			)
+		}
+	}
+}
+
 /// Context switch from old context to new context
 pub unsafe fn switch_context(old_ctx: &mut Context, new_ctx: &Context) {
 	// Save current context
@@ -292,3 +453,17 @@ pub fn restore_fpu_state(buffer: &[u8; 512]) {
 		asm!("fxrstor [{}]", in(reg) buffer.as_ptr());
 	}
 }
+
+/// Load the FS segment base used for user thread-local storage
+pub fn set_fs_base(base: u64) {
+	const MSR_FS_BASE: u32 = 0xC0000100;
+	unsafe {
+		asm!(
+		    "wrmsr",
+		    in("ecx") MSR_FS_BASE,
+		    in("eax") (base & 0xFFFFFFFF) as u32,
+		    in("edx") (base >> 32) as u32,
+		    options(nostack, preserves_flags)
+		);
+	}
+}
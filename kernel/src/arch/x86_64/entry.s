@@ -0,0 +1,114 @@
+# SPDX-License-Identifier: GPL-2.0
+# Exception entry
+#
+# Each stub pushes a zero error code for exceptions where the CPU pushes
+# none, then its vector, and jumps to exception_common. That saves the
+# registers below the interrupt frame in the layout of ExceptionContext
+# (idt.rs) and passes exception_handler a pointer to it. The handler may
+# change the saved registers, which are loaded on return. If it returns
+# false the interrupted task is gone, and exception_task_gone runs
+# something else instead.
+
+.macro EXCEPTION vector
+exception_stub_\vector:
+    pushq $0
+    pushq $\vector
+    jmp exception_common
+.endm
+
+.macro EXCEPTION_ERRCODE vector
+exception_stub_\vector:
+    pushq $\vector
+    jmp exception_common
+.endm
+
+.section .text
+EXCEPTION 0
+EXCEPTION 1
+EXCEPTION 2
+EXCEPTION 3
+EXCEPTION 4
+EXCEPTION 5
+EXCEPTION 6
+EXCEPTION 7
+EXCEPTION_ERRCODE 8
+EXCEPTION 9
+EXCEPTION_ERRCODE 10
+EXCEPTION_ERRCODE 11
+EXCEPTION_ERRCODE 12
+EXCEPTION_ERRCODE 13
+EXCEPTION_ERRCODE 14
+EXCEPTION 15
+EXCEPTION 16
+EXCEPTION_ERRCODE 17
+EXCEPTION 18
+EXCEPTION 19
+
+exception_common:
+    pushq %rax
+    pushq %rcx
+    pushq %rdx
+    pushq %rbx
+    pushq %rbp
+    pushq %rsi
+    pushq %rdi
+    pushq %r8
+    pushq %r9
+    pushq %r10
+    pushq %r11
+    pushq %r12
+    pushq %r13
+    pushq %r14
+    pushq %r15
+    mov %ds, %eax
+    pushq %rax
+    mov %es, %eax
+    pushq %rax
+    mov %fs, %eax
+    pushq %rax
+    mov %gs, %eax
+    pushq %rax
+
+    cld
+    mov %rsp, %rdi
+    mov %rsp, %rbx                  # Callee-saved, survives the call
+    and $-16, %rsp
+    call exception_handler
+    test %al, %al
+    jnz 1f
+    call exception_task_gone        # Never returns
+1:
+    mov %rbx, %rsp
+
+    # Writing fs or gs would clear the base the task set, keep them
+    add $16, %rsp
+    popq %rax
+    mov %ax, %es
+    popq %rax
+    mov %ax, %ds
+    popq %r15
+    popq %r14
+    popq %r13
+    popq %r12
+    popq %r11
+    popq %r10
+    popq %r9
+    popq %r8
+    popq %rdi
+    popq %rsi
+    popq %rbp
+    popq %rbx
+    popq %rdx
+    popq %rcx
+    popq %rax
+    add $16, %rsp                   # Vector and error code
+    iretq
+
+# Stub addresses for the IDT, indexed by vector
+.section .rodata
+.balign 8
+.global exception_stubs
+exception_stubs:
+.irp vector, 0,1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16,17,18,19
+    .quad exception_stub_\vector
+.endr
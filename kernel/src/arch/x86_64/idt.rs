@@ -4,7 +4,9 @@
 
 use core::mem::size_of;
 
+use crate::arch::x86_64::context::Context;
 use crate::arch::x86_64::port::outb;
+use crate::process::ProcessState;
 
 /// IDT Entry structure for x86_64
 #[derive(Debug, Clone, Copy)]
@@ -33,8 +35,12 @@ impl IdtEntry {
 	}
 
 	pub fn set_handler(&mut self, handler: extern "C" fn(), selector: u16, type_attr: u8) {
-		let addr = handler as u64;
+		self.set_handler_addr(handler as usize as u64, selector, type_attr);
+	}
 
+	/// Point the entry at code that isn't a Rust function, like an
+	/// assembly stub
+	pub fn set_handler_addr(&mut self, addr: u64, selector: u16, type_attr: u8) {
 		self.offset_low = (addr & 0xFFFF) as u16;
 		self.offset_middle = ((addr >> 16) & 0xFFFF) as u16;
 		self.offset_high = ((addr >> 32) & 0xFFFFFFFF) as u32;
@@ -57,6 +63,9 @@ pub struct IdtPointer {
 /// IDT constants
 pub const IDT_ENTRIES: usize = 256;
 
+/// Trap flag in RFLAGS, raising a debug exception after each instruction
+const X86_EFLAGS_TF: u64 = 1 << 8;
+
 /// IDT type and attribute flags
 pub mod type_attr {
 	pub const PRESENT: u8 = 1 << 7;
@@ -72,608 +81,22 @@ pub mod type_attr {
 /// Global IDT
 static mut IDT: [IdtEntry; IDT_ENTRIES] = [IdtEntry::new(); IDT_ENTRIES];
 
-/// Exception handler stubs implemented in Rust
-#[no_mangle]
-pub extern "C" fn divide_error_handler() {
-	let ctx = ExceptionContext {
-		gs: 0,
-		fs: 0,
-		es: 0,
-		ds: 0,
-		r15: 0,
-		r14: 0,
-		r13: 0,
-		r12: 0,
-		r11: 0,
-		r10: 0,
-		r9: 0,
-		r8: 0,
-		rdi: 0,
-		rsi: 0,
-		rbp: 0,
-		rbx: 0,
-		rdx: 0,
-		rcx: 0,
-		rax: 0,
-		vector: 0,
-		error_code: 0,
-		rip: 0,
-		cs: 0,
-		eflags: 0,
-		rsp: 0,
-		ss: 0,
-	};
-	handle_divide_error(&ctx);
-}
-
-#[no_mangle]
-pub extern "C" fn debug_handler() {
-	let ctx = ExceptionContext {
-		gs: 0,
-		fs: 0,
-		es: 0,
-		ds: 0,
-		r15: 0,
-		r14: 0,
-		r13: 0,
-		r12: 0,
-		r11: 0,
-		r10: 0,
-		r9: 0,
-		r8: 0,
-		rdi: 0,
-		rsi: 0,
-		rbp: 0,
-		rbx: 0,
-		rdx: 0,
-		rcx: 0,
-		rax: 0,
-		vector: 1,
-		error_code: 0,
-		rip: 0,
-		cs: 0,
-		eflags: 0,
-		rsp: 0,
-		ss: 0,
-	};
-	handle_debug(&ctx);
-}
-
-#[no_mangle]
-pub extern "C" fn nmi_handler() {
-	let ctx = ExceptionContext {
-		gs: 0,
-		fs: 0,
-		es: 0,
-		ds: 0,
-		r15: 0,
-		r14: 0,
-		r13: 0,
-		r12: 0,
-		r11: 0,
-		r10: 0,
-		r9: 0,
-		r8: 0,
-		rdi: 0,
-		rsi: 0,
-		rbp: 0,
-		rbx: 0,
-		rdx: 0,
-		rcx: 0,
-		rax: 0,
-		vector: 2,
-		error_code: 0,
-		rip: 0,
-		cs: 0,
-		eflags: 0,
-		rsp: 0,
-		ss: 0,
-	};
-	handle_nmi(&ctx);
-}
-
-#[no_mangle]
-pub extern "C" fn breakpoint_handler() {
-	let ctx = ExceptionContext {
-		gs: 0,
-		fs: 0,
-		es: 0,
-		ds: 0,
-		r15: 0,
-		r14: 0,
-		r13: 0,
-		r12: 0,
-		r11: 0,
-		r10: 0,
-		r9: 0,
-		r8: 0,
-		rdi: 0,
-		rsi: 0,
-		rbp: 0,
-		rbx: 0,
-		rdx: 0,
-		rcx: 0,
-		rax: 0,
-		vector: 3,
-		error_code: 0,
-		rip: 0,
-		cs: 0,
-		eflags: 0,
-		rsp: 0,
-		ss: 0,
-	};
-	handle_breakpoint(&ctx);
-}
-
-#[no_mangle]
-pub extern "C" fn overflow_handler() {
-	let ctx = ExceptionContext {
-		gs: 0,
-		fs: 0,
-		es: 0,
-		ds: 0,
-		r15: 0,
-		r14: 0,
-		r13: 0,
-		r12: 0,
-		r11: 0,
-		r10: 0,
-		r9: 0,
-		r8: 0,
-		rdi: 0,
-		rsi: 0,
-		rbp: 0,
-		rbx: 0,
-		rdx: 0,
-		rcx: 0,
-		rax: 0,
-		vector: 4,
-		error_code: 0,
-		rip: 0,
-		cs: 0,
-		eflags: 0,
-		rsp: 0,
-		ss: 0,
-	};
-	handle_overflow(&ctx);
-}
-
-#[no_mangle]
-pub extern "C" fn bound_range_exceeded_handler() {
-	let ctx = ExceptionContext {
-		gs: 0,
-		fs: 0,
-		es: 0,
-		ds: 0,
-		r15: 0,
-		r14: 0,
-		r13: 0,
-		r12: 0,
-		r11: 0,
-		r10: 0,
-		r9: 0,
-		r8: 0,
-		rdi: 0,
-		rsi: 0,
-		rbp: 0,
-		rbx: 0,
-		rdx: 0,
-		rcx: 0,
-		rax: 0,
-		vector: 5,
-		error_code: 0,
-		rip: 0,
-		cs: 0,
-		eflags: 0,
-		rsp: 0,
-		ss: 0,
-	};
-	handle_bound_range_exceeded(&ctx);
-}
-
-#[no_mangle]
-pub extern "C" fn invalid_opcode_handler() {
-	let ctx = ExceptionContext {
-		gs: 0,
-		fs: 0,
-		es: 0,
-		ds: 0,
-		r15: 0,
-		r14: 0,
-		r13: 0,
-		r12: 0,
-		r11: 0,
-		r10: 0,
-		r9: 0,
-		r8: 0,
-		rdi: 0,
-		rsi: 0,
-		rbp: 0,
-		rbx: 0,
-		rdx: 0,
-		rcx: 0,
-		rax: 0,
-		vector: 6,
-		error_code: 0,
-		rip: 0,
-		cs: 0,
-		eflags: 0,
-		rsp: 0,
-		ss: 0,
-	};
-	handle_invalid_opcode(&ctx);
-}
-
-#[no_mangle]
-pub extern "C" fn device_not_available_handler() {
-	let ctx = ExceptionContext {
-		gs: 0,
-		fs: 0,
-		es: 0,
-		ds: 0,
-		r15: 0,
-		r14: 0,
-		r13: 0,
-		r12: 0,
-		r11: 0,
-		r10: 0,
-		r9: 0,
-		r8: 0,
-		rdi: 0,
-		rsi: 0,
-		rbp: 0,
-		rbx: 0,
-		rdx: 0,
-		rcx: 0,
-		rax: 0,
-		vector: 7,
-		error_code: 0,
-		rip: 0,
-		cs: 0,
-		eflags: 0,
-		rsp: 0,
-		ss: 0,
-	};
-	handle_device_not_available(&ctx);
-}
-
-#[no_mangle]
-pub extern "C" fn double_fault_handler() {
-	let ctx = ExceptionContext {
-		gs: 0,
-		fs: 0,
-		es: 0,
-		ds: 0,
-		r15: 0,
-		r14: 0,
-		r13: 0,
-		r12: 0,
-		r11: 0,
-		r10: 0,
-		r9: 0,
-		r8: 0,
-		rdi: 0,
-		rsi: 0,
-		rbp: 0,
-		rbx: 0,
-		rdx: 0,
-		rcx: 0,
-		rax: 0,
-		vector: 8,
-		error_code: 0,
-		rip: 0,
-		cs: 0,
-		eflags: 0,
-		rsp: 0,
-		ss: 0,
-	};
-	handle_double_fault(&ctx);
-}
-
-#[no_mangle]
-pub extern "C" fn invalid_tss_handler() {
-	let ctx = ExceptionContext {
-		gs: 0,
-		fs: 0,
-		es: 0,
-		ds: 0,
-		r15: 0,
-		r14: 0,
-		r13: 0,
-		r12: 0,
-		r11: 0,
-		r10: 0,
-		r9: 0,
-		r8: 0,
-		rdi: 0,
-		rsi: 0,
-		rbp: 0,
-		rbx: 0,
-		rdx: 0,
-		rcx: 0,
-		rax: 0,
-		vector: 10,
-		error_code: 0,
-		rip: 0,
-		cs: 0,
-		eflags: 0,
-		rsp: 0,
-		ss: 0,
-	};
-	handle_invalid_tss(&ctx);
-}
-
-#[no_mangle]
-pub extern "C" fn segment_not_present_handler() {
-	let ctx = ExceptionContext {
-		gs: 0,
-		fs: 0,
-		es: 0,
-		ds: 0,
-		r15: 0,
-		r14: 0,
-		r13: 0,
-		r12: 0,
-		r11: 0,
-		r10: 0,
-		r9: 0,
-		r8: 0,
-		rdi: 0,
-		rsi: 0,
-		rbp: 0,
-		rbx: 0,
-		rdx: 0,
-		rcx: 0,
-		rax: 0,
-		vector: 11,
-		error_code: 0,
-		rip: 0,
-		cs: 0,
-		eflags: 0,
-		rsp: 0,
-		ss: 0,
-	};
-	handle_segment_not_present(&ctx);
-}
-
-#[no_mangle]
-pub extern "C" fn stack_segment_fault_handler() {
-	let ctx = ExceptionContext {
-		gs: 0,
-		fs: 0,
-		es: 0,
-		ds: 0,
-		r15: 0,
-		r14: 0,
-		r13: 0,
-		r12: 0,
-		r11: 0,
-		r10: 0,
-		r9: 0,
-		r8: 0,
-		rdi: 0,
-		rsi: 0,
-		rbp: 0,
-		rbx: 0,
-		rdx: 0,
-		rcx: 0,
-		rax: 0,
-		vector: 12,
-		error_code: 0,
-		rip: 0,
-		cs: 0,
-		eflags: 0,
-		rsp: 0,
-		ss: 0,
-	};
-	handle_stack_segment_fault(&ctx);
-}
-
-#[no_mangle]
-pub extern "C" fn general_protection_fault_handler() {
-	let ctx = ExceptionContext {
-		gs: 0,
-		fs: 0,
-		es: 0,
-		ds: 0,
-		r15: 0,
-		r14: 0,
-		r13: 0,
-		r12: 0,
-		r11: 0,
-		r10: 0,
-		r9: 0,
-		r8: 0,
-		rdi: 0,
-		rsi: 0,
-		rbp: 0,
-		rbx: 0,
-		rdx: 0,
-		rcx: 0,
-		rax: 0,
-		vector: 13,
-		error_code: 0,
-		rip: 0,
-		cs: 0,
-		eflags: 0,
-		rsp: 0,
-		ss: 0,
-	};
-	handle_general_protection_fault(&ctx);
-}
-
-#[no_mangle]
-pub extern "C" fn page_fault_handler() {
-	let ctx = ExceptionContext {
-		gs: 0,
-		fs: 0,
-		es: 0,
-		ds: 0,
-		r15: 0,
-		r14: 0,
-		r13: 0,
-		r12: 0,
-		r11: 0,
-		r10: 0,
-		r9: 0,
-		r8: 0,
-		rdi: 0,
-		rsi: 0,
-		rbp: 0,
-		rbx: 0,
-		rdx: 0,
-		rcx: 0,
-		rax: 0,
-		vector: 14,
-		error_code: 0,
-		rip: 0,
-		cs: 0,
-		eflags: 0,
-		rsp: 0,
-		ss: 0,
-	};
-	handle_page_fault(&ctx);
-}
-
-#[no_mangle]
-pub extern "C" fn x87_fpu_error_handler() {
-	let ctx = ExceptionContext {
-		gs: 0,
-		fs: 0,
-		es: 0,
-		ds: 0,
-		r15: 0,
-		r14: 0,
-		r13: 0,
-		r12: 0,
-		r11: 0,
-		r10: 0,
-		r9: 0,
-		r8: 0,
-		rdi: 0,
-		rsi: 0,
-		rbp: 0,
-		rbx: 0,
-		rdx: 0,
-		rcx: 0,
-		rax: 0,
-		vector: 16,
-		error_code: 0,
-		rip: 0,
-		cs: 0,
-		eflags: 0,
-		rsp: 0,
-		ss: 0,
-	};
-	handle_x87_fpu_error(&ctx);
-}
-
-#[no_mangle]
-pub extern "C" fn alignment_check_handler() {
-	let ctx = ExceptionContext {
-		gs: 0,
-		fs: 0,
-		es: 0,
-		ds: 0,
-		r15: 0,
-		r14: 0,
-		r13: 0,
-		r12: 0,
-		r11: 0,
-		r10: 0,
-		r9: 0,
-		r8: 0,
-		rdi: 0,
-		rsi: 0,
-		rbp: 0,
-		rbx: 0,
-		rdx: 0,
-		rcx: 0,
-		rax: 0,
-		vector: 17,
-		error_code: 0,
-		rip: 0,
-		cs: 0,
-		eflags: 0,
-		rsp: 0,
-		ss: 0,
-	};
-	handle_alignment_check(&ctx);
-}
+core::arch::global_asm!(include_str!("entry.s"), options(att_syntax));
 
-#[no_mangle]
-pub extern "C" fn machine_check_handler() {
-	let ctx = ExceptionContext {
-		gs: 0,
-		fs: 0,
-		es: 0,
-		ds: 0,
-		r15: 0,
-		r14: 0,
-		r13: 0,
-		r12: 0,
-		r11: 0,
-		r10: 0,
-		r9: 0,
-		r8: 0,
-		rdi: 0,
-		rsi: 0,
-		rbp: 0,
-		rbx: 0,
-		rdx: 0,
-		rcx: 0,
-		rax: 0,
-		vector: 18,
-		error_code: 0,
-		rip: 0,
-		cs: 0,
-		eflags: 0,
-		rsp: 0,
-		ss: 0,
-	};
-	handle_machine_check(&ctx);
-}
-
-#[no_mangle]
-pub extern "C" fn simd_exception_handler() {
-	let ctx = ExceptionContext {
-		gs: 0,
-		fs: 0,
-		es: 0,
-		ds: 0,
-		r15: 0,
-		r14: 0,
-		r13: 0,
-		r12: 0,
-		r11: 0,
-		r10: 0,
-		r9: 0,
-		r8: 0,
-		rdi: 0,
-		rsi: 0,
-		rbp: 0,
-		rbx: 0,
-		rdx: 0,
-		rcx: 0,
-		rax: 0,
-		vector: 19,
-		error_code: 0,
-		rip: 0,
-		cs: 0,
-		eflags: 0,
-		rsp: 0,
-		ss: 0,
-	};
-	handle_simd_exception(&ctx);
+extern "C" {
+	/// Entry stubs of exceptions 0-19, in entry.s
+	static exception_stubs: [u64; 20];
 }
 
 // Hardware interrupt handlers
 #[no_mangle]
 pub extern "C" fn default_irq_handler() {
 	// Default IRQ handler - does nothing but send EOI
+	crate::interrupt::irq_enter();
 	unsafe {
 		crate::arch::x86_64::pic::send_eoi(0);
 	}
+	crate::interrupt::irq_exit();
 }
 
 // Timer interrupt handler (to be registered)
@@ -694,6 +117,7 @@ pub fn register_timer_handler(handler: extern "C" fn()) {
 
 #[no_mangle]
 pub extern "C" fn timer_irq_wrapper() {
+	crate::interrupt::irq_enter();
 	unsafe {
 		if let Some(handler) = TIMER_HANDLER {
 			handler();
@@ -702,102 +126,26 @@ pub extern "C" fn timer_irq_wrapper() {
 			crate::arch::x86_64::pic::send_eoi(0);
 		}
 	}
+	crate::interrupt::irq_exit();
 }
 
 /// Initialize IDT
 pub fn init() {
 	unsafe {
-		// Set up exception handlers
-		IDT[0].set_handler(
-			divide_error_handler,
-			0x08,
-			type_attr::PRESENT | type_attr::INTERRUPT_GATE,
-		);
-		IDT[1].set_handler(
-			debug_handler,
-			0x08,
-			type_attr::PRESENT | type_attr::INTERRUPT_GATE,
-		);
-		IDT[2].set_handler(
-			nmi_handler,
-			0x08,
-			type_attr::PRESENT | type_attr::INTERRUPT_GATE,
-		);
-		IDT[3].set_handler(
-			breakpoint_handler,
-			0x08,
-			type_attr::PRESENT | type_attr::INTERRUPT_GATE | type_attr::RING_3,
-		);
-		IDT[4].set_handler(
-			overflow_handler,
-			0x08,
-			type_attr::PRESENT | type_attr::INTERRUPT_GATE,
-		);
-		IDT[5].set_handler(
-			bound_range_exceeded_handler,
-			0x08,
-			type_attr::PRESENT | type_attr::INTERRUPT_GATE,
-		);
-		IDT[6].set_handler(
-			invalid_opcode_handler,
-			0x08,
-			type_attr::PRESENT | type_attr::INTERRUPT_GATE,
-		);
-		IDT[7].set_handler(
-			device_not_available_handler,
-			0x08,
-			type_attr::PRESENT | type_attr::INTERRUPT_GATE,
-		);
-		IDT[8].set_handler(
-			double_fault_handler,
-			0x08,
-			type_attr::PRESENT | type_attr::INTERRUPT_GATE,
-		);
-		IDT[10].set_handler(
-			invalid_tss_handler,
-			0x08,
-			type_attr::PRESENT | type_attr::INTERRUPT_GATE,
-		);
-		IDT[11].set_handler(
-			segment_not_present_handler,
-			0x08,
-			type_attr::PRESENT | type_attr::INTERRUPT_GATE,
-		);
-		IDT[12].set_handler(
-			stack_segment_fault_handler,
-			0x08,
-			type_attr::PRESENT | type_attr::INTERRUPT_GATE,
-		);
-		IDT[13].set_handler(
-			general_protection_fault_handler,
-			0x08,
-			type_attr::PRESENT | type_attr::INTERRUPT_GATE,
-		);
-		IDT[14].set_handler(
-			page_fault_handler,
-			0x08,
-			type_attr::PRESENT | type_attr::INTERRUPT_GATE,
-		);
-		IDT[16].set_handler(
-			x87_fpu_error_handler,
-			0x08,
-			type_attr::PRESENT | type_attr::INTERRUPT_GATE,
-		);
-		IDT[17].set_handler(
-			alignment_check_handler,
-			0x08,
-			type_attr::PRESENT | type_attr::INTERRUPT_GATE,
-		);
-		IDT[18].set_handler(
-			machine_check_handler,
-			0x08,
-			type_attr::PRESENT | type_attr::INTERRUPT_GATE,
-		);
-		IDT[19].set_handler(
-			simd_exception_handler,
-			0x08,
-			type_attr::PRESENT | type_attr::INTERRUPT_GATE,
-		);
+		// Set up exception handlers. Breakpoints may be raised from
+		// user mode with int3.
+		for (vector, &stub) in exception_stubs.iter().enumerate() {
+			let dpl = if vector == 3 {
+				type_attr::RING_3
+			} else {
+				type_attr::RING_0
+			};
+			IDT[vector].set_handler_addr(
+				stub,
+				0x08,
+				type_attr::PRESENT | type_attr::INTERRUPT_GATE | dpl,
+			);
+		}
 
 		// Set up hardware interrupt handlers
 		// Timer interrupt (IRQ 0 -> IDT 32)
@@ -838,7 +186,7 @@ pub fn init() {
 }
 
 /// Exception context structure passed from assembly
-#[derive(Debug)]
+#[derive(Debug, Default)]
 #[repr(C)]
 pub struct ExceptionContext {
 	// Segment registers
@@ -876,10 +224,30 @@ pub struct ExceptionContext {
 	pub ss: u64,
 }
 
-/// Exception handler called from assembly
+/// Exception handler called from the entry stubs. Returns whether to
+/// resume the interrupted code, false if an exception in user mode killed
+/// the process.
+///
+/// # Safety
+///
+/// `context` must point to the registers saved at the exception.
 #[no_mangle]
-pub extern "C" fn exception_handler(context: *const ExceptionContext) {
-	let ctx = unsafe { &*context };
+pub unsafe extern "C" fn exception_handler(context: *mut ExceptionContext) -> bool {
+	let ctx = unsafe { &mut *context };
+	let user = from_user(ctx);
+
+	// Read before interrupts are back on, a nested fault would clobber it
+	let fault_addr: u64;
+	unsafe {
+		core::arch::asm!("mov {}, cr2", out(reg) fault_addr);
+	}
+
+	// User exceptions run like system calls, free to sleep. A ptrace stop
+	// waits for the tracer to run.
+	let irqs_were_enabled = crate::interrupt::are_enabled();
+	if user {
+		crate::interrupt::enable();
+	}
 
 	match ctx.vector {
 		0 => handle_divide_error(ctx),
@@ -895,30 +263,144 @@ pub extern "C" fn exception_handler(context: *const ExceptionContext) {
 		11 => handle_segment_not_present(ctx),
 		12 => handle_stack_segment_fault(ctx),
 		13 => handle_general_protection_fault(ctx),
-		14 => handle_page_fault(ctx),
+		14 => handle_page_fault(ctx, fault_addr),
 		16 => handle_x87_fpu_error(ctx),
 		17 => handle_alignment_check(ctx),
 		18 => handle_machine_check(ctx),
 		19 => handle_simd_exception(ctx),
 		_ => handle_unknown_exception(ctx),
 	}
+
+	if !irqs_were_enabled {
+		crate::interrupt::disable();
+	}
+	if !user {
+		return true;
+	}
+
+	// Never return to a process the exception killed
+	let state = crate::process::with_current(|process| process.state);
+	!matches!(
+		state,
+		None | Some(ProcessState::Zombie | ProcessState::Dead)
+	)
+}
+
+/// Called by the entry stubs instead of returning to a process an
+/// exception killed
+#[no_mangle]
+extern "C" fn exception_task_gone() -> ! {
+	crate::interrupt::enable();
+	loop {
+		crate::scheduler::schedule();
+		crate::timer::cpu_idle();
+	}
+}
+
+/// Whether an exception interrupted user mode
+fn from_user(ctx: &ExceptionContext) -> bool {
+	ctx.cs & 3 == 3
+}
+
+/// Registers of user code saved at an exception
+fn saved_context(ctx: &ExceptionContext) -> Context {
+	let mut regs = Context::new();
+	regs.rax = ctx.rax;
+	regs.rbx = ctx.rbx;
+	regs.rcx = ctx.rcx;
+	regs.rdx = ctx.rdx;
+	regs.rsi = ctx.rsi;
+	regs.rdi = ctx.rdi;
+	regs.rbp = ctx.rbp;
+	regs.rsp = ctx.rsp;
+	regs.r8 = ctx.r8;
+	regs.r9 = ctx.r9;
+	regs.r10 = ctx.r10;
+	regs.r11 = ctx.r11;
+	regs.r12 = ctx.r12;
+	regs.r13 = ctx.r13;
+	regs.r14 = ctx.r14;
+	regs.r15 = ctx.r15;
+	regs.rip = ctx.rip;
+	regs.rflags = ctx.eflags;
+	regs.cs = ctx.cs as u16;
+	regs.ss = ctx.ss as u16;
+	regs.ds = ctx.ds as u16;
+	regs.es = ctx.es as u16;
+	regs.fs = ctx.fs as u16;
+	regs.gs = ctx.gs as u16;
+	regs
+}
+
+/// Load the registers user code returns to from an exception
+fn restore_context(ctx: &mut ExceptionContext, regs: &Context) {
+	ctx.rax = regs.rax;
+	ctx.rbx = regs.rbx;
+	ctx.rcx = regs.rcx;
+	ctx.rdx = regs.rdx;
+	ctx.rsi = regs.rsi;
+	ctx.rdi = regs.rdi;
+	ctx.rbp = regs.rbp;
+	ctx.rsp = regs.rsp;
+	ctx.r8 = regs.r8;
+	ctx.r9 = regs.r9;
+	ctx.r10 = regs.r10;
+	ctx.r11 = regs.r11;
+	ctx.r12 = regs.r12;
+	ctx.r13 = regs.r13;
+	ctx.r14 = regs.r14;
+	ctx.r15 = regs.r15;
+	ctx.rip = regs.rip;
+	ctx.eflags = regs.rflags;
+}
+
+/// Kill the current process with `signal` if the exception came from user
+/// mode. Returns false for exceptions in the kernel.
+fn user_fault(ctx: &ExceptionContext, signal: i32) -> bool {
+	from_user(ctx) && crate::process::force_sig_fault(signal, saved_context(ctx)).is_ok()
+}
+
+/// Stop a traced process at a trap in user mode, resuming with the
+/// registers its tracer left. Other processes get SIGTRAP. Returns false
+/// for traps in the kernel.
+fn user_trap(ctx: &mut ExceptionContext, single_step: bool) -> bool {
+	if !from_user(ctx) {
+		return false;
+	}
+	let mut regs = saved_context(ctx);
+	if crate::ptrace::debug_trap(&mut regs, single_step) {
+		restore_context(ctx, &regs);
+		return true;
+	}
+	user_fault(ctx, crate::signal::SIGTRAP)
 }
 
 // Individual exception handlers
 fn handle_divide_error(ctx: &ExceptionContext) {
+	if user_fault(ctx, crate::signal::SIGFPE) {
+		return;
+	}
 	crate::error!("Divide by zero error at RIP: 0x{:x}", ctx.rip);
 	panic!("Divide by zero exception");
 }
 
-fn handle_debug(ctx: &ExceptionContext) {
+fn handle_debug(ctx: &mut ExceptionContext) {
+	if user_trap(ctx, true) {
+		return;
+	}
 	crate::info!("Debug exception at RIP: 0x{:x}", ctx.rip);
+	// Single-stepping the kernel isn't supported, don't trap again
+	ctx.eflags &= !X86_EFLAGS_TF;
 }
 
 fn handle_nmi(ctx: &ExceptionContext) {
 	crate::error!("Non-maskable interrupt at RIP: 0x{:x}", ctx.rip);
 }
 
-fn handle_breakpoint(ctx: &ExceptionContext) {
+fn handle_breakpoint(ctx: &mut ExceptionContext) {
+	if user_trap(ctx, false) {
+		return;
+	}
 	crate::info!("Breakpoint at RIP: 0x{:x}", ctx.rip);
 }
 
@@ -933,6 +415,9 @@ fn handle_bound_range_exceeded(ctx: &ExceptionContext) {
 }
 
 fn handle_invalid_opcode(ctx: &ExceptionContext) {
+	if user_fault(ctx, crate::signal::SIGILL) {
+		return;
+	}
 	crate::error!("Invalid opcode at RIP: 0x{:x}", ctx.rip);
 	panic!("Invalid opcode");
 }
@@ -979,6 +464,9 @@ fn handle_stack_segment_fault(ctx: &ExceptionContext) {
 }
 
 fn handle_general_protection_fault(ctx: &ExceptionContext) {
+	if user_fault(ctx, crate::signal::SIGSEGV) {
+		return;
+	}
 	crate::error!(
 		"General protection fault at RIP: 0x{:x}, error code: 0x{:x}",
 		ctx.rip,
@@ -987,11 +475,13 @@ fn handle_general_protection_fault(ctx: &ExceptionContext) {
 	panic!("General protection fault");
 }
 
-fn handle_page_fault(ctx: &ExceptionContext) {
-	// Get the faulting address from CR2
-	let fault_addr: u64;
-	unsafe {
-		core::arch::asm!("mov {}, cr2", out(reg) fault_addr);
+fn handle_page_fault(ctx: &ExceptionContext, fault_addr: u64) {
+	// A user-mode access to a missing page may be stack growth
+	if ctx.error_code & 0x5 == 0x4 && crate::usermode::grow_user_stack(fault_addr).is_ok() {
+		return;
+	}
+	if user_fault(ctx, crate::signal::SIGSEGV) {
+		return;
 	}
 
 	crate::error!(
@@ -1004,6 +494,9 @@ fn handle_page_fault(ctx: &ExceptionContext) {
 }
 
 fn handle_x87_fpu_error(ctx: &ExceptionContext) {
+	if user_fault(ctx, crate::signal::SIGFPE) {
+		return;
+	}
 	crate::error!("x87 FPU error at RIP: 0x{:x}", ctx.rip);
 	panic!("x87 FPU error");
 }
@@ -1023,6 +516,9 @@ fn handle_machine_check(ctx: &ExceptionContext) {
 }
 
 fn handle_simd_exception(ctx: &ExceptionContext) {
+	if user_fault(ctx, crate::signal::SIGFPE) {
+		return;
+	}
 	crate::error!("SIMD exception at RIP: 0x{:x}", ctx.rip);
 	panic!("SIMD exception");
 }
@@ -0,0 +1,164 @@
+# SPDX-License-Identifier: GPL-2.0
+# vDSO text, copied into the vDSO image mapped in every process
+#
+# The code is position independent. It finds the vvar data page at a fixed
+# distance before itself: the data page is mapped right before the image,
+# whose text starts at offset 0x1000. The data page layout is VdsoData in
+# vdso.rs.
+
+.set VVAR_OFFSET, 0x2000            # From the text start to the data page
+.set VD_SEQ, 0
+.set VD_FLAGS, 4
+.set VD_CYCLE_LAST, 8
+.set VD_MONO_NS, 16
+.set VD_REAL_OFFSET, 24
+.set VD_MULT, 32
+.set VDSO_TSC, 1                    # Flag: the TSC can be read
+.set VDSO_RDTSCP, 2                 # Flag: RDTSCP returns the CPU number
+
+.set NSEC_PER_SEC, 1000000000
+.set SYS_CLOCK_GETTIME, 228
+
+.section .rodata.vdso, "a"
+.balign 16
+.global vdso_text_start
+vdso_text_start:
+
+# Nanoseconds of a clock. In: ecx bit 0 for realtime, bit 1 for the
+# coarse clock of the last tick. Out: rax. Clobbers rdx, r8-r11.
+vdso_read_ns:
+    lea vdso_text_start - VVAR_OFFSET(%rip), %r8
+1:
+    mov VD_SEQ(%r8), %r9d
+    test $1, %r9d                   # Odd while the kernel updates it
+    jnz 4f
+    mov VD_MONO_NS(%r8), %r10
+    test $2, %ecx
+    jnz 2f
+    testl $VDSO_TSC, VD_FLAGS(%r8)
+    jz 2f
+    lfence
+    rdtsc
+    shl $32, %rdx
+    or %rdx, %rax
+    sub VD_CYCLE_LAST(%r8), %rax
+    jae 5f
+    xor %eax, %eax                  # The TSC went backwards
+5:
+    mov VD_MULT(%r8), %r11
+    mul %r11                        # Cycles to ns: (delta * mult) >> 32
+    shrd $32, %rdx, %rax
+    add %rax, %r10
+2:
+    test $1, %ecx
+    jz 3f
+    add VD_REAL_OFFSET(%r8), %r10
+3:
+    cmp VD_SEQ(%r8), %r9d           # Retry if an update came in between
+    jne 1b
+    mov %r10, %rax
+    ret
+4:
+    pause
+    jmp 1b
+
+# Split rax nanoseconds into seconds in rax and nanoseconds in rdx
+vdso_split_ns:
+    xor %edx, %edx
+    mov $NSEC_PER_SEC, %r11
+    div %r11
+    ret
+
+# int clock_gettime(clockid_t clock, struct timespec *ts)
+.global vdso_clock_gettime
+vdso_clock_gettime:
+    cmp $7, %edi
+    ja 1f
+    mov %edi, %eax
+    lea vdso_clock_modes(%rip), %rcx
+    movzbl (%rcx, %rax), %ecx
+    cmp $0xff, %ecx
+    je 1f
+    call vdso_read_ns
+    call vdso_split_ns
+    mov %rax, (%rsi)
+    mov %rdx, 8(%rsi)
+    xor %eax, %eax
+    ret
+1:
+    mov $SYS_CLOCK_GETTIME, %eax    # CPU-time and unknown clocks
+    syscall
+    ret
+
+# vdso_read_ns mode of each clock ID, 0xff for the system call
+vdso_clock_modes:
+    .byte 1                         # CLOCK_REALTIME
+    .byte 0                         # CLOCK_MONOTONIC
+    .byte 0xff                      # CLOCK_PROCESS_CPUTIME_ID
+    .byte 0xff                      # CLOCK_THREAD_CPUTIME_ID
+    .byte 0                         # CLOCK_MONOTONIC_RAW
+    .byte 3                         # CLOCK_REALTIME_COARSE
+    .byte 2                         # CLOCK_MONOTONIC_COARSE
+    .byte 0                         # CLOCK_BOOTTIME
+
+# int gettimeofday(struct timeval *tv, struct timezone *tz)
+.global vdso_gettimeofday
+vdso_gettimeofday:
+    test %rdi, %rdi
+    jz 1f
+    mov $1, %ecx
+    call vdso_read_ns
+    call vdso_split_ns
+    mov %rax, (%rdi)
+    mov %rdx, %rax
+    xor %edx, %edx
+    mov $1000, %r11d
+    div %r11
+    mov %rax, 8(%rdi)
+1:
+    test %rsi, %rsi
+    jz 2f
+    movq $0, (%rsi)                 # UTC, no DST
+2:
+    xor %eax, %eax
+    ret
+
+# time_t time(time_t *tloc)
+.global vdso_time
+vdso_time:
+    mov $3, %ecx
+    call vdso_read_ns
+    call vdso_split_ns
+    test %rdi, %rdi
+    jz 1f
+    mov %rax, (%rdi)
+1:
+    ret
+
+# int getcpu(unsigned *cpu, unsigned *node, void *cache)
+.global vdso_getcpu
+vdso_getcpu:
+    xor %eax, %eax
+    lea vdso_text_start - VVAR_OFFSET(%rip), %r8
+    testl $VDSO_RDTSCP, VD_FLAGS(%r8)
+    jz 1f
+    rdtscp                          # TSC_AUX holds node << 12 | cpu
+    mov %ecx, %eax
+1:
+    test %rdi, %rdi
+    jz 2f
+    mov %eax, %ecx
+    and $0xfff, %ecx
+    mov %ecx, (%rdi)
+2:
+    test %rsi, %rsi
+    jz 3f
+    shr $12, %eax
+    mov %eax, (%rsi)
+3:
+    xor %eax, %eax
+    ret
+
+.global vdso_text_end
+vdso_text_end:
+.previous
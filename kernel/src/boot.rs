@@ -72,6 +72,17 @@ pub fn get_boot_info() -> &'static BootInfo {
 	unsafe { &BOOT_INFO }
 }
 
+/// Value of a `name=value` argument on the kernel command line
+pub fn cmdline_param(name: &str) -> Option<alloc::string::String> {
+	get_boot_info()
+		.command_line
+		.as_deref()?
+		.split_whitespace()
+		.filter_map(|arg| arg.split_once('='))
+		.find(|(key, _)| *key == name)
+		.map(|(_, value)| value.to_string())
+}
+
 /// Update boot information
 pub unsafe fn update_boot_info<F>(f: F)
 where
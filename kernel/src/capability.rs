@@ -0,0 +1,169 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! POSIX capabilities - Linux compatible
+
+use crate::error::{Error, Result};
+
+pub const CAP_CHOWN: u32 = 0;
+pub const CAP_DAC_OVERRIDE: u32 = 1;
+pub const CAP_DAC_READ_SEARCH: u32 = 2;
+pub const CAP_FOWNER: u32 = 3;
+pub const CAP_FSETID: u32 = 4;
+pub const CAP_KILL: u32 = 5;
+pub const CAP_SETGID: u32 = 6;
+pub const CAP_SETUID: u32 = 7;
+pub const CAP_SETPCAP: u32 = 8;
+pub const CAP_LINUX_IMMUTABLE: u32 = 9;
+pub const CAP_NET_BIND_SERVICE: u32 = 10;
+pub const CAP_NET_BROADCAST: u32 = 11;
+pub const CAP_NET_ADMIN: u32 = 12;
+pub const CAP_NET_RAW: u32 = 13;
+pub const CAP_IPC_LOCK: u32 = 14;
+pub const CAP_IPC_OWNER: u32 = 15;
+pub const CAP_SYS_MODULE: u32 = 16;
+pub const CAP_SYS_RAWIO: u32 = 17;
+pub const CAP_SYS_CHROOT: u32 = 18;
+pub const CAP_SYS_PTRACE: u32 = 19;
+pub const CAP_SYS_PACCT: u32 = 20;
+pub const CAP_SYS_ADMIN: u32 = 21;
+pub const CAP_SYS_BOOT: u32 = 22;
+pub const CAP_SYS_NICE: u32 = 23;
+pub const CAP_SYS_RESOURCE: u32 = 24;
+pub const CAP_SYS_TIME: u32 = 25;
+pub const CAP_SYS_TTY_CONFIG: u32 = 26;
+pub const CAP_MKNOD: u32 = 27;
+pub const CAP_LEASE: u32 = 28;
+pub const CAP_AUDIT_WRITE: u32 = 29;
+pub const CAP_AUDIT_CONTROL: u32 = 30;
+pub const CAP_SETFCAP: u32 = 31;
+pub const CAP_MAC_OVERRIDE: u32 = 32;
+pub const CAP_MAC_ADMIN: u32 = 33;
+pub const CAP_SYSLOG: u32 = 34;
+pub const CAP_WAKE_ALARM: u32 = 35;
+pub const CAP_BLOCK_SUSPEND: u32 = 36;
+pub const CAP_AUDIT_READ: u32 = 37;
+pub const CAP_PERFMON: u32 = 38;
+pub const CAP_BPF: u32 = 39;
+pub const CAP_CHECKPOINT_RESTORE: u32 = 40;
+
+/// Highest capability number
+pub const CAP_LAST_CAP: u32 = CAP_CHECKPOINT_RESTORE;
+
+/// capget/capset ABI version with 64-bit sets
+pub const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+/// Capability names, indexed by capability number
+const CAP_NAMES: [&str; CAP_LAST_CAP as usize + 1] = [
+	"cap_chown",
+	"cap_dac_override",
+	"cap_dac_read_search",
+	"cap_fowner",
+	"cap_fsetid",
+	"cap_kill",
+	"cap_setgid",
+	"cap_setuid",
+	"cap_setpcap",
+	"cap_linux_immutable",
+	"cap_net_bind_service",
+	"cap_net_broadcast",
+	"cap_net_admin",
+	"cap_net_raw",
+	"cap_ipc_lock",
+	"cap_ipc_owner",
+	"cap_sys_module",
+	"cap_sys_rawio",
+	"cap_sys_chroot",
+	"cap_sys_ptrace",
+	"cap_sys_pacct",
+	"cap_sys_admin",
+	"cap_sys_boot",
+	"cap_sys_nice",
+	"cap_sys_resource",
+	"cap_sys_time",
+	"cap_sys_tty_config",
+	"cap_mknod",
+	"cap_lease",
+	"cap_audit_write",
+	"cap_audit_control",
+	"cap_setfcap",
+	"cap_mac_override",
+	"cap_mac_admin",
+	"cap_syslog",
+	"cap_wake_alarm",
+	"cap_block_suspend",
+	"cap_audit_read",
+	"cap_perfmon",
+	"cap_bpf",
+	"cap_checkpoint_restore",
+];
+
+/// Get the name of a capability
+pub fn cap_name(cap: u32) -> Option<&'static str> {
+	CAP_NAMES.get(cap as usize).copied()
+}
+
+/// Set of capabilities as a bitmask, bit N for capability N
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CapSet(pub u64);
+
+impl CapSet {
+	pub const fn empty() -> Self {
+		Self(0)
+	}
+
+	/// Every defined capability
+	pub const fn full() -> Self {
+		Self((1u64 << (CAP_LAST_CAP + 1)) - 1)
+	}
+
+	pub fn add(&mut self, cap: u32) {
+		if cap <= CAP_LAST_CAP {
+			self.0 |= 1u64 << cap;
+		}
+	}
+
+	pub fn remove(&mut self, cap: u32) {
+		if cap <= CAP_LAST_CAP {
+			self.0 &= !(1u64 << cap);
+		}
+	}
+
+	pub fn contains(&self, cap: u32) -> bool {
+		cap <= CAP_LAST_CAP && self.0 & (1u64 << cap) != 0
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.0 == 0
+	}
+
+	pub fn union(self, other: Self) -> Self {
+		Self(self.0 | other.0)
+	}
+
+	pub fn intersect(self, other: Self) -> Self {
+		Self(self.0 & other.0)
+	}
+
+	pub fn is_subset(&self, other: Self) -> bool {
+		self.0 & !other.0 == 0
+	}
+
+	/// Iterate over the capabilities in the set
+	pub fn iter(self) -> impl Iterator<Item = u32> {
+		(0..=CAP_LAST_CAP).filter(move |&cap| self.contains(cap))
+	}
+}
+
+/// Check whether the current process has a capability in its effective set
+pub fn capable(cap: u32) -> bool {
+	crate::cred::current_cred().capable(cap)
+}
+
+/// Fail with EPERM unless the current process has a capability
+pub fn require(cap: u32) -> Result<()> {
+	if capable(cap) {
+		Ok(())
+	} else {
+		Err(Error::EPERM)
+	}
+}
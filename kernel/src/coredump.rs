@@ -0,0 +1,400 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! ELF core dumps of processes killed by a signal whose default action
+//! dumps core
+//!
+//! The dump is an ELF64 ET_CORE file written to /var/core/<name>.<pid>. A
+//! PT_NOTE segment holds the process and register state (NT_PRSTATUS and
+//! NT_FPREGSET for each thread, NT_PRPSINFO, NT_AUXV and NT_FILE), followed
+//! by a PT_LOAD segment for each mapped region. The file is cut off at the
+//! process's RLIMIT_CORE, and only its owner may read it.
+//!
+//! Signals that dump core can be sent from softirq context, so the dump is
+//! written later by the system workqueue from a snapshot of the process.
+
+use alloc::{collections::VecDeque, format, string::String, vec, vec::Vec};
+
+use crate::error::Result;
+use crate::memory::{VirtAddr, VmaArea, PROT_EXEC, PROT_READ, PROT_WRITE};
+use crate::process::{Process, Thread};
+use crate::resource::RLIMIT_CORE;
+use crate::sync::Spinlock;
+use crate::usermode::{
+	AT_EGID, AT_ENTRY, AT_EUID, AT_GID, AT_NULL, AT_PAGESZ, AT_SYSINFO_EHDR, AT_UID,
+};
+use crate::workqueue::{schedule_work, Work};
+
+/// Directory core files are written to
+pub const CORE_DIR: &str = "/var/core";
+
+/// Permission bits of a core file
+pub const CORE_FILE_MODE: u32 = 0o600;
+
+const PAGE_SIZE: usize = 4096;
+
+// ELF identification and header values
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const EV_CURRENT: u8 = 1;
+const ELFOSABI_NONE: u8 = 0;
+const ET_CORE: u16 = 4;
+const EM_X86_64: u16 = 62;
+
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+
+// Program header types and flags
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+// Note types
+const NT_PRSTATUS: u32 = 1;
+const NT_FPREGSET: u32 = 2;
+const NT_PRPSINFO: u32 = 3;
+const NT_AUXV: u32 = 6;
+const NT_FILE: u32 = 0x4649_4c45;
+
+/// Size of struct elf_prstatus on x86_64
+const PRSTATUS_SIZE: usize = 336;
+/// Offset of pr_reg in struct elf_prstatus
+const PRSTATUS_REG_OFFSET: usize = 112;
+/// Size of struct elf_prpsinfo on x86_64
+const PRPSINFO_SIZE: usize = 136;
+
+fn put_u16(buf: &mut Vec<u8>, value: u16) {
+	buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+	buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, value: u64) {
+	buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn pad_to(buf: &mut Vec<u8>, align: usize) {
+	let len = buf.len().div_ceil(align) * align;
+	buf.resize(len, 0);
+}
+
+fn write_at(buf: &mut [u8], offset: usize, bytes: &[u8]) {
+	buf[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+/// Append an ELF note owned by "CORE"
+fn put_note(buf: &mut Vec<u8>, note_type: u32, desc: &[u8]) {
+	const NAME: &[u8] = b"CORE\0";
+	put_u32(buf, NAME.len() as u32);
+	put_u32(buf, desc.len() as u32);
+	put_u32(buf, note_type);
+	buf.extend_from_slice(NAME);
+	pad_to(buf, 4);
+	buf.extend_from_slice(desc);
+	pad_to(buf, 4);
+}
+
+/// Nanoseconds as a struct timeval
+fn timeval(ns: u64) -> [u8; 16] {
+	let mut tv = [0u8; 16];
+	tv[..8].copy_from_slice(&(ns / 1_000_000_000).to_le_bytes());
+	tv[8..].copy_from_slice(&(ns % 1_000_000_000 / 1000).to_le_bytes());
+	tv
+}
+
+/// struct elf_prstatus of a thread
+fn prstatus(process: &Process, thread: &Thread, signal: i32) -> Vec<u8> {
+	let mut desc = vec![0u8; PRSTATUS_SIZE];
+	write_at(&mut desc, 0, &signal.to_le_bytes()); // si_signo
+	write_at(&mut desc, 12, &(signal as u16).to_le_bytes()); // pr_cursig
+	write_at(&mut desc, 16, &process.pending_signals.0.to_le_bytes());
+	write_at(&mut desc, 32, &thread.tid.0.to_le_bytes());
+	write_at(
+		&mut desc,
+		36,
+		&process.parent.map_or(0, |pid| pid.0).to_le_bytes(),
+	);
+	write_at(&mut desc, 40, &process.pgid.0.to_le_bytes());
+	write_at(&mut desc, 44, &process.sid.0.to_le_bytes());
+	write_at(&mut desc, 48, &timeval(thread.utime));
+	write_at(&mut desc, 64, &timeval(thread.stime));
+	write_at(&mut desc, 80, &timeval(process.children_usage.utime));
+	write_at(&mut desc, 96, &timeval(process.children_usage.stime));
+	// orig_rax is -1 outside a system call
+	let regs = thread.context.user_regs(u64::MAX, thread.fs_base);
+	write_at(&mut desc, PRSTATUS_REG_OFFSET, regs.as_bytes());
+	write_at(&mut desc, 328, &1i32.to_le_bytes()); // pr_fpvalid
+	desc
+}
+
+/// struct elf_prpsinfo of the process
+fn prpsinfo(process: &Process) -> Vec<u8> {
+	let mut desc = vec![0u8; PRPSINFO_SIZE];
+	desc[1] = b'Z'; // pr_sname, dying
+	desc[2] = 1; // pr_zomb
+	desc[3] = process
+		.threads
+		.first()
+		.map_or(0, |thread| thread.nice as i8) as u8;
+	write_at(&mut desc, 16, &process.cred.uid.0.to_le_bytes());
+	write_at(&mut desc, 20, &process.cred.gid.0.to_le_bytes());
+	write_at(&mut desc, 24, &process.pid.0.to_le_bytes());
+	write_at(
+		&mut desc,
+		28,
+		&process.parent.map_or(0, |pid| pid.0).to_le_bytes(),
+	);
+	write_at(&mut desc, 32, &process.pgid.0.to_le_bytes());
+	write_at(&mut desc, 36, &process.sid.0.to_le_bytes());
+	let name = program_name(process).as_bytes();
+	let fname = &name[..name.len().min(15)];
+	write_at(&mut desc, 40, fname);
+	let psargs = &process.name.as_bytes()[..process.name.len().min(79)];
+	write_at(&mut desc, 56, psargs);
+	desc
+}
+
+/// Auxiliary vector the process was started with
+fn auxv(process: &Process) -> Vec<u8> {
+	if !process.auxv.is_empty() {
+		let mut desc = Vec::new();
+		for &(key, value) in &process.auxv {
+			put_u64(&mut desc, key);
+			put_u64(&mut desc, value);
+		}
+		return desc;
+	}
+
+	// Processes started without one get the basic entries
+	let entry = process
+		.mappings
+		.iter()
+		.find(|vma| vma.vm_prot & PROT_EXEC != 0)
+		.map_or(0, |vma| vma.vm_start.as_usize() as u64);
+	let cred = &process.cred;
+	let vdso = process.vdso_base.map_or(0, |base| base.as_usize() as u64);
+	let mut desc = Vec::new();
+	for (key, value) in [
+		(AT_SYSINFO_EHDR, vdso),
+		(AT_PAGESZ, PAGE_SIZE as u64),
+		(AT_ENTRY, entry),
+		(AT_UID, cred.uid.0 as u64),
+		(AT_EUID, cred.euid.0 as u64),
+		(AT_GID, cred.gid.0 as u64),
+		(AT_EGID, cred.egid.0 as u64),
+		(AT_NULL, 0),
+	]
+	.into_iter()
+	.filter(|&(key, value)| key != AT_SYSINFO_EHDR || value != 0)
+	{
+		put_u64(&mut desc, key);
+		put_u64(&mut desc, value);
+	}
+	desc
+}
+
+/// Mappings of the program image, as an NT_FILE note
+fn file_note(process: &Process) -> Vec<u8> {
+	let files: Vec<&VmaArea> = process
+		.mappings
+		.iter()
+		.filter(|vma| {
+			vma.vm_prot & PROT_EXEC != 0 && Some(vma.vm_start) != process.vdso_base
+		})
+		.collect();
+	let mut desc = Vec::new();
+	put_u64(&mut desc, files.len() as u64);
+	put_u64(&mut desc, PAGE_SIZE as u64);
+	let mut offset = 0;
+	for vma in &files {
+		put_u64(&mut desc, vma.vm_start.as_usize() as u64);
+		put_u64(&mut desc, vma.vm_end.as_usize() as u64);
+		put_u64(&mut desc, offset);
+		offset += ((vma.vm_end - vma.vm_start) / PAGE_SIZE) as u64;
+	}
+	for _ in &files {
+		desc.extend_from_slice(process.name.as_bytes());
+		desc.push(0);
+	}
+	desc
+}
+
+/// Program name without its directory
+fn program_name(process: &Process) -> &str {
+	process.name.rsplit('/').next().unwrap_or(&process.name)
+}
+
+fn segment_flags(prot: u32) -> u32 {
+	let mut flags = 0;
+	if prot & PROT_READ != 0 {
+		flags |= PF_R;
+	}
+	if prot & PROT_WRITE != 0 {
+		flags |= PF_W;
+	}
+	if prot & PROT_EXEC != 0 {
+		flags |= PF_X;
+	}
+	flags
+}
+
+/// Copy a page of user memory, leaving it zeroed if it isn't accessible
+fn read_user_page(process: &Process, addr: VirtAddr, page: &mut [u8]) {
+	if crate::memory::access_process_vm(&process.mappings, addr, page, false).is_err() {
+		page.fill(0);
+	}
+}
+
+/// Appends to the core file, stopping at RLIMIT_CORE
+struct CoreWriter {
+	path: String,
+	written: u64,
+	limit: u64,
+}
+
+impl CoreWriter {
+	/// Write `data`, returning false once the limit is reached
+	fn write(&mut self, data: &[u8]) -> Result<bool> {
+		let room = self.limit.saturating_sub(self.written);
+		let len = (data.len() as u64).min(room) as usize;
+		if len > 0 {
+			crate::memfs::fs_write(&self.path, &data[..len])?;
+			self.written += len as u64;
+		}
+		Ok(len == data.len())
+	}
+}
+
+/// Check whether a process gets a core dump: its RLIMIT_CORE must allow
+/// one and it must not have changed credentials, like Linux with
+/// suid_dumpable=0
+pub fn wants_dump(process: &Process) -> bool {
+	process.dumpable && process.rlimits.cur(RLIMIT_CORE) != 0
+}
+
+/// Snapshots of dying processes and their signals, waiting to be dumped
+static PENDING_DUMPS: Spinlock<VecDeque<(Process, i32)>> = Spinlock::new(VecDeque::new());
+
+/// Writes the core files of PENDING_DUMPS in process context
+static DUMP_WORK: Work = Work::new(write_pending_dumps, 0);
+
+fn write_pending_dumps(_data: usize) {
+	while let Some((process, signal)) =
+		crate::interrupt::without_interrupts(|| PENDING_DUMPS.lock().pop_front())
+	{
+		if let Err(err) = do_coredump(&process, signal) {
+			crate::warn!("Core dump of process {} failed: {:?}", process.pid, err);
+		}
+	}
+}
+
+/// Queue the core dump of a process killed by `signal`. `process` is a
+/// snapshot taken when the signal was sent; the file is written from the
+/// system workqueue.
+pub fn queue_coredump(process: Process, signal: i32) {
+	crate::interrupt::without_interrupts(|| PENDING_DUMPS.lock().push_back((process, signal)));
+	schedule_work(&DUMP_WORK);
+}
+
+/// Write the ELF core file of a process killed by `signal`, if it is
+/// dumpable. Returns whether a core file was written.
+pub fn do_coredump(process: &Process, signal: i32) -> Result<bool> {
+	if !wants_dump(process) {
+		return Ok(false);
+	}
+	let limit = process.rlimits.cur(RLIMIT_CORE);
+
+	let mut notes = Vec::new();
+	for thread in &process.threads {
+		put_note(&mut notes, NT_PRSTATUS, &prstatus(process, thread, signal));
+		put_note(&mut notes, NT_FPREGSET, &thread.context.fpu_state);
+	}
+	put_note(&mut notes, NT_PRPSINFO, &prpsinfo(process));
+	put_note(&mut notes, NT_AUXV, &auxv(process));
+	put_note(&mut notes, NT_FILE, &file_note(process));
+
+	let phnum = 1 + process.mappings.len();
+	let notes_offset = EHDR_SIZE + phnum * PHDR_SIZE;
+	let data_offset = (notes_offset + notes.len()).div_ceil(PAGE_SIZE) * PAGE_SIZE;
+
+	let mut header = Vec::with_capacity(data_offset);
+	header.extend_from_slice(b"\x7fELF");
+	header.extend_from_slice(&[ELFCLASS64, ELFDATA2LSB, EV_CURRENT, ELFOSABI_NONE]);
+	header.resize(16, 0);
+	put_u16(&mut header, ET_CORE);
+	put_u16(&mut header, EM_X86_64);
+	put_u32(&mut header, EV_CURRENT as u32);
+	put_u64(&mut header, 0); // e_entry
+	put_u64(&mut header, EHDR_SIZE as u64); // e_phoff
+	put_u64(&mut header, 0); // e_shoff
+	put_u32(&mut header, 0); // e_flags
+	put_u16(&mut header, EHDR_SIZE as u16);
+	put_u16(&mut header, PHDR_SIZE as u16);
+	put_u16(&mut header, phnum as u16);
+	put_u16(&mut header, 0); // e_shentsize
+	put_u16(&mut header, 0); // e_shnum
+	put_u16(&mut header, 0); // e_shstrndx
+
+	put_u32(&mut header, PT_NOTE);
+	put_u32(&mut header, 0);
+	put_u64(&mut header, notes_offset as u64);
+	put_u64(&mut header, 0); // p_vaddr
+	put_u64(&mut header, 0); // p_paddr
+	put_u64(&mut header, notes.len() as u64);
+	put_u64(&mut header, 0); // p_memsz
+	put_u64(&mut header, 0); // p_align
+
+	let mut offset = data_offset as u64;
+	for vma in &process.mappings {
+		let size = (vma.vm_end - vma.vm_start) as u64;
+		put_u32(&mut header, PT_LOAD);
+		put_u32(&mut header, segment_flags(vma.vm_prot));
+		put_u64(&mut header, offset);
+		put_u64(&mut header, vma.vm_start.as_usize() as u64);
+		put_u64(&mut header, 0); // p_paddr
+		put_u64(&mut header, size); // p_filesz
+		put_u64(&mut header, size); // p_memsz
+		put_u64(&mut header, PAGE_SIZE as u64);
+		offset += size;
+	}
+	header.extend_from_slice(&notes);
+	header.resize(data_offset, 0);
+
+	let _ = crate::memfs::fs_create_dir_mode("/var", 0o755, 0, 0);
+	let _ = crate::memfs::fs_create_dir_mode(CORE_DIR, 0o755, 0, 0);
+	let path = format!("{}/{}.{}", CORE_DIR, program_name(process), process.pid);
+	let _ = crate::memfs::fs_remove(&path);
+	let cred = &process.cred;
+	crate::memfs::fs_create_file_mode(&path, CORE_FILE_MODE, cred.fsuid.0, cred.fsgid.0)?;
+
+	let mut writer = CoreWriter {
+		path,
+		written: 0,
+		limit,
+	};
+	let mut complete = writer.write(&header)?;
+	let mut page = vec![0u8; PAGE_SIZE];
+	for vma in &process.mappings {
+		let mut addr = vma.vm_start;
+		while complete && addr < vma.vm_end {
+			read_user_page(process, addr, &mut page);
+			complete = writer.write(&page)?;
+			addr = addr + PAGE_SIZE;
+		}
+		if !complete {
+			break;
+		}
+	}
+
+	crate::info!(
+		"Process {} ({}) dumped core to {}{}",
+		process.pid,
+		process.name,
+		writer.path,
+		if complete { "" } else { " (truncated)" }
+	);
+	Ok(true)
+}
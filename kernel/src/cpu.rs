@@ -3,6 +3,17 @@
 //! CPU management
 use crate::error::Result;
 
+/// CPUs that per-CPU data has room for
+pub const NR_CPUS: usize = 8;
+
+/// Number of the CPU the caller runs on. Only the bootstrap processor is
+/// brought up, so this is always 0; per-CPU data is indexed by it so it
+/// stays correct once application processors are started.
+#[inline]
+pub fn smp_processor_id() -> usize {
+	0
+}
+
 pub fn init() -> Result<()> {
 	Ok(())
 }
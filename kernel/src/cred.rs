@@ -0,0 +1,328 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Process credentials - Linux compatible
+
+use alloc::vec::Vec;
+
+use crate::capability::{self, CapSet};
+use crate::error::{Error, Result};
+use crate::fs::mode;
+use crate::types::{Gid, Uid};
+
+/// Permission request bits for inode access checks
+pub const MAY_EXEC: u32 = 0x1;
+pub const MAY_WRITE: u32 = 0x2;
+pub const MAY_READ: u32 = 0x4;
+
+/// Maximum number of supplementary groups
+pub const NGROUPS_MAX: usize = 65536;
+
+/// Default file creation mask
+pub const DEFAULT_UMASK: u32 = 0o022;
+
+/// Process credentials - similar to Linux struct cred
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+	pub uid: Uid,   // Real user ID
+	pub euid: Uid,  // Effective user ID
+	pub suid: Uid,  // Saved set-user-ID
+	pub fsuid: Uid, // User ID for filesystem access
+	pub gid: Gid,
+	pub egid: Gid,
+	pub sgid: Gid,
+	pub fsgid: Gid,
+	pub groups: Vec<Gid>, // Supplementary groups
+	pub umask: u32,
+	pub cap_inheritable: CapSet, // Kept across exec
+	pub cap_permitted: CapSet,   // Limit for the effective set
+	pub cap_effective: CapSet,   // Used for permission checks
+	pub cap_bset: CapSet,        // Bounding set
+}
+
+impl Credentials {
+	/// Credentials with every ID set to the given user and group. Root
+	/// starts with every capability.
+	pub fn new(uid: Uid, gid: Gid) -> Self {
+		let caps = if uid == Uid(0) {
+			CapSet::full()
+		} else {
+			CapSet::empty()
+		};
+		Self {
+			uid,
+			euid: uid,
+			suid: uid,
+			fsuid: uid,
+			gid,
+			egid: gid,
+			sgid: gid,
+			fsgid: gid,
+			groups: Vec::new(),
+			umask: DEFAULT_UMASK,
+			cap_inheritable: CapSet::empty(),
+			cap_permitted: caps,
+			cap_effective: caps,
+			cap_bset: CapSet::full(),
+		}
+	}
+
+	/// Root credentials used by the kernel and init
+	pub fn root() -> Self {
+		Self::new(Uid(0), Gid(0))
+	}
+
+	/// Check for a capability in the effective set
+	pub fn capable(&self, cap: u32) -> bool {
+		self.cap_effective.contains(cap)
+	}
+
+	/// Check whether the filesystem group or a supplementary group matches
+	pub fn in_group(&self, gid: Gid) -> bool {
+		self.fsgid == gid || self.groups.contains(&gid)
+	}
+
+	/// Check whether these credentials own an inode
+	pub fn owns(&self, uid: Uid) -> bool {
+		self.fsuid == uid || self.capable(capability::CAP_FOWNER)
+	}
+
+	/// Change user IDs (setuid semantics)
+	pub fn set_uid(&mut self, uid: Uid) -> Result<()> {
+		let old = (self.uid, self.euid, self.suid);
+		if self.capable(capability::CAP_SETUID) {
+			self.uid = uid;
+			self.suid = uid;
+		} else if uid != self.uid && uid != self.suid {
+			return Err(Error::EPERM);
+		}
+		self.euid = uid;
+		self.fsuid = uid;
+		self.fixup_setuid_caps(old);
+		Ok(())
+	}
+
+	/// Change group IDs (setgid semantics)
+	pub fn set_gid(&mut self, gid: Gid) -> Result<()> {
+		if self.capable(capability::CAP_SETGID) {
+			self.gid = gid;
+			self.sgid = gid;
+		} else if gid != self.gid && gid != self.sgid {
+			return Err(Error::EPERM);
+		}
+		self.egid = gid;
+		self.fsgid = gid;
+		Ok(())
+	}
+
+	/// Change real, effective and saved user IDs; None leaves an ID as is
+	pub fn set_resuid(
+		&mut self,
+		ruid: Option<Uid>,
+		euid: Option<Uid>,
+		suid: Option<Uid>,
+	) -> Result<()> {
+		let old = (self.uid, self.euid, self.suid);
+		if !self.capable(capability::CAP_SETUID) {
+			let allowed =
+				|id: Uid| id == self.uid || id == self.euid || id == self.suid;
+			if ![ruid, euid, suid].iter().flatten().all(|&id| allowed(id)) {
+				return Err(Error::EPERM);
+			}
+		}
+
+		if let Some(uid) = ruid {
+			self.uid = uid;
+		}
+		if let Some(uid) = euid {
+			self.euid = uid;
+		}
+		if let Some(uid) = suid {
+			self.suid = uid;
+		}
+		self.fsuid = self.euid;
+		self.fixup_setuid_caps(old);
+		Ok(())
+	}
+
+	/// Change real, effective and saved group IDs; None leaves an ID as is
+	pub fn set_resgid(
+		&mut self,
+		rgid: Option<Gid>,
+		egid: Option<Gid>,
+		sgid: Option<Gid>,
+	) -> Result<()> {
+		if !self.capable(capability::CAP_SETGID) {
+			let allowed =
+				|id: Gid| id == self.gid || id == self.egid || id == self.sgid;
+			if ![rgid, egid, sgid].iter().flatten().all(|&id| allowed(id)) {
+				return Err(Error::EPERM);
+			}
+		}
+
+		if let Some(gid) = rgid {
+			self.gid = gid;
+		}
+		if let Some(gid) = egid {
+			self.egid = gid;
+		}
+		if let Some(gid) = sgid {
+			self.sgid = gid;
+		}
+		self.fsgid = self.egid;
+		Ok(())
+	}
+
+	/// Replace the supplementary group list
+	pub fn set_groups(&mut self, groups: Vec<Gid>) -> Result<()> {
+		if !self.capable(capability::CAP_SETGID) {
+			return Err(Error::EPERM);
+		}
+		if groups.len() > NGROUPS_MAX {
+			return Err(Error::EINVAL);
+		}
+		self.groups = groups;
+		Ok(())
+	}
+
+	/// Check whether moving to `new` changes who the process acts as: its
+	/// effective or filesystem IDs, or capabilities it did not have. Like
+	/// Linux's commit_creds(), such a change makes the process
+	/// non-dumpable.
+	pub fn changes_identity(&self, new: &Credentials) -> bool {
+		self.euid != new.euid
+			|| self.egid != new.egid
+			|| self.fsuid != new.fsuid
+			|| self.fsgid != new.fsgid
+			|| !new.cap_permitted.is_subset(self.cap_permitted)
+	}
+
+	/// Apply set-user-ID and set-group-ID bits of an executable on exec
+	pub fn apply_exec(&mut self, file_mode: u32, file_uid: Uid, file_gid: Gid) {
+		if file_mode & mode::S_ISUID != 0 {
+			self.euid = file_uid;
+		}
+		// Without group execute, S_ISGID means mandatory locking
+		if file_mode & mode::S_ISGID != 0 && file_mode & mode::S_IXGRP != 0 {
+			self.egid = file_gid;
+		}
+		self.suid = self.euid;
+		self.fsuid = self.euid;
+		self.sgid = self.egid;
+		self.fsgid = self.egid;
+
+		// Without file capabilities only root keeps capabilities across
+		// exec, limited by the bounding set
+		if self.uid == Uid(0) || self.euid == Uid(0) {
+			self.cap_permitted = self.cap_bset.union(self.cap_inheritable);
+		} else {
+			self.cap_permitted = CapSet::empty();
+		}
+		self.cap_effective = if self.euid == Uid(0) {
+			self.cap_permitted
+		} else {
+			CapSet::empty()
+		};
+	}
+
+	/// Adjust capabilities after a user ID change, like Linux does for
+	/// processes switching to or from root
+	fn fixup_setuid_caps(&mut self, old: (Uid, Uid, Uid)) {
+		let root = Uid(0);
+		let (old_uid, old_euid, old_suid) = old;
+		let was_root = old_uid == root || old_euid == root || old_suid == root;
+		let is_root = self.uid == root || self.euid == root || self.suid == root;
+
+		if was_root && !is_root {
+			self.cap_permitted = CapSet::empty();
+			self.cap_effective = CapSet::empty();
+		}
+		if old_euid == root && self.euid != root {
+			self.cap_effective = CapSet::empty();
+		}
+		if old_euid != root && self.euid == root {
+			self.cap_effective = self.cap_permitted;
+		}
+	}
+
+	/// Replace the capability sets (capset semantics)
+	pub fn set_caps(
+		&mut self,
+		effective: CapSet,
+		permitted: CapSet,
+		inheritable: CapSet,
+	) -> Result<()> {
+		// New inheritable capabilities must come from the permitted set
+		// unless CAP_SETPCAP is held, and stay within the bounding set
+		let inheritable_limit = if self.capable(capability::CAP_SETPCAP) {
+			self.cap_inheritable.union(self.cap_bset)
+		} else {
+			self.cap_inheritable.union(self.cap_permitted)
+		};
+		if !inheritable.is_subset(inheritable_limit)
+			|| !inheritable.is_subset(self.cap_inheritable.union(self.cap_bset))
+			|| !permitted.is_subset(self.cap_permitted)
+			|| !effective.is_subset(permitted)
+		{
+			return Err(Error::EPERM);
+		}
+
+		self.cap_effective = effective;
+		self.cap_permitted = permitted;
+		self.cap_inheritable = inheritable;
+		Ok(())
+	}
+}
+
+/// Check access to an object with the given mode and owner.
+///
+/// `mask` is a combination of MAY_READ, MAY_WRITE and MAY_EXEC. The owner
+/// class is checked first, then the group class, then others; only the
+/// first matching class applies, like on Linux.
+pub fn generic_permission(
+	cred: &Credentials,
+	file_mode: u32,
+	uid: Uid,
+	gid: Gid,
+	mask: u32,
+) -> Result<()> {
+	let mask = mask & (MAY_READ | MAY_WRITE | MAY_EXEC);
+
+	let granted = if cred.fsuid == uid {
+		(file_mode >> 6) & 0o7
+	} else if cred.in_group(gid) {
+		(file_mode >> 3) & 0o7
+	} else {
+		file_mode & 0o7
+	};
+
+	if granted & mask == mask {
+		return Ok(());
+	}
+
+	// CAP_DAC_OVERRIDE bypasses read/write checks, and execute checks
+	// when anyone can execute the file or it is a directory
+	let is_dir = mode::s_isdir(file_mode);
+	if cred.capable(capability::CAP_DAC_OVERRIDE) {
+		let any_exec = file_mode & (mode::S_IXUSR | mode::S_IXGRP | mode::S_IXOTH) != 0;
+		if mask & MAY_EXEC == 0 || any_exec || is_dir {
+			return Ok(());
+		}
+	}
+
+	// CAP_DAC_READ_SEARCH allows reading files and searching directories
+	if cred.capable(capability::CAP_DAC_READ_SEARCH) {
+		let search = is_dir && mask & !(MAY_READ | MAY_EXEC) == 0;
+		if mask == MAY_READ || search {
+			return Ok(());
+		}
+	}
+
+	Err(Error::EACCES)
+}
+
+/// Get a copy of the current process credentials
+pub fn current_cred() -> Credentials {
+	crate::process::current_process()
+		.map(|p| p.cred)
+		.unwrap_or_else(Credentials::root)
+}
@@ -11,6 +11,7 @@ use alloc::{
 
 use crate::device::Device;
 use crate::error::{Error, Result};
+use crate::rcu::RcuCell;
 use crate::sync::Spinlock; // Add ToString
 
 /// Driver trait - Linux compatible
@@ -244,6 +245,13 @@ pub enum UsbSpeed {
 /// Global driver subsystem
 static DRIVER_SUBSYSTEM: Spinlock<DriverSubsystem> = Spinlock::new(DriverSubsystem::new());
 
+/// Names of registered drivers, read without locking
+static DRIVER_NAMES: spin::once::Once<RcuCell<Vec<String>>> = spin::once::Once::new();
+
+fn driver_names() -> &'static RcuCell<Vec<String>> {
+	DRIVER_NAMES.call_once(|| RcuCell::new(Vec::new()))
+}
+
 /// Driver subsystem state
 struct DriverSubsystem {
 	drivers: BTreeMap<String, Box<dyn Driver>>,
@@ -268,17 +276,24 @@ impl DriverSubsystem {
 			return Err(Error::Busy);
 		}
 		self.drivers.insert(name, driver);
+		self.publish_names();
 		Ok(())
 	}
 
 	fn unregister_driver(&mut self, name: &str) -> Result<()> {
 		if self.drivers.remove(name).is_some() {
+			self.publish_names();
 			Ok(())
 		} else {
 			Err(Error::NotFound)
 		}
 	}
 
+	/// Publish the driver names for list_drivers
+	fn publish_names(&self) {
+		driver_names().replace(self.drivers.keys().cloned().collect());
+	}
+
 	#[allow(dead_code)]
 	fn find_driver(&self, name: &str) -> Option<&dyn Driver> {
 		self.drivers.get(name).map(|d| d.as_ref())
@@ -358,8 +373,7 @@ pub fn match_driver(device: &Device) -> Option<String> {
 
 /// Get list of registered drivers
 pub fn list_drivers() -> Vec<String> {
-	let subsystem = DRIVER_SUBSYSTEM.lock();
-	subsystem.drivers.keys().cloned().collect()
+	driver_names().read_with(|names| names.clone())
 }
 
 /// Module macros for easier driver registration
@@ -2,9 +2,14 @@
 
 //! Driver initialization and management
 
+use core::sync::atomic::{AtomicBool, Ordering};
+
 use crate::error::Result;
 use crate::{info, warn};
 
+/// Whether a Ctrl key is currently held down
+static CTRL_PRESSED: AtomicBool = AtomicBool::new(false);
+
 /// Initialize all built-in drivers
 pub fn init_drivers() -> Result<()> {
 	info!("Initializing built-in drivers");
@@ -89,11 +94,24 @@ fn init_ramdisk_driver() -> Result<()> {
 fn keyboard_interrupt_handler(irq: u32, dev_id: *mut u8) -> crate::interrupt::IrqReturn {
 	// Read the scan code from the keyboard controller
 	let scancode = unsafe { crate::arch::x86_64::port::inb(0x60) };
+	crate::random::add_input_randomness(scancode as u64);
+
+	// Track the Ctrl modifier so Ctrl-C/Ctrl-Z reach the terminal layer
+	match scancode {
+		0x1D => CTRL_PRESSED.store(true, Ordering::Relaxed),
+		0x9D => CTRL_PRESSED.store(false, Ordering::Relaxed),
+		_ => {}
+	}
 
 	// Convert scan code to ASCII (simplified)
 	if scancode < 128 {
-		let ascii = SCANCODE_TO_ASCII[scancode as usize];
-		if ascii != 0 {
+		let mut ascii = SCANCODE_TO_ASCII[scancode as usize];
+		if ascii != 0 && CTRL_PRESSED.load(Ordering::Relaxed) {
+			ascii &= 0x1F;
+		}
+
+		// Terminals owned by a user session take the input first
+		if ascii != 0 && !crate::tty::receive_char(crate::tty::CONSOLE_TTY, ascii) {
 			// Send character to kernel shell
 			if let Err(e) = crate::shell::shell_input(ascii as char) {
 				crate::warn!("Failed to process shell input: {}", e);
@@ -553,8 +553,15 @@ pub fn get_current_task() -> Option<Tid> {
 	with_scheduler_read(|scheduler| scheduler.current_task()).flatten()
 }
 
-/// Set task priority
+/// Set task priority. Raising the priority needs CAP_SYS_NICE.
 pub fn set_task_priority(tid: Tid, priority: Priority) -> Result<()> {
+	let current = with_scheduler_read(|scheduler| scheduler.get_task(tid).map(|t| t.priority))
+		.ok_or(Error::NotInitialized)?
+		.ok_or(Error::NotFound)?;
+	if priority < current {
+		crate::capability::require(crate::capability::CAP_SYS_NICE)?;
+	}
+
 	with_scheduler(|scheduler| scheduler.set_priority(tid, priority))
 		.unwrap_or(Err(Error::NotInitialized))
 }
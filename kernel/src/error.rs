@@ -65,6 +65,8 @@ pub enum Error {
 	EINVAL,
 	/// No space left on device (ENOSPC)
 	ENOSPC,
+	/// File too large (EFBIG)
+	EFBIG,
 	/// Inappropriate ioctl for device (ENOTTY)
 	ENOTTY,
 	/// Illegal seek (ESPIPE)
@@ -85,6 +87,28 @@ pub enum Error {
 	ECHILD,
 	/// No such process (ESRCH)
 	ESRCH,
+	/// Permission denied (EACCES)
+	EACCES,
+	/// Too many open files (EMFILE)
+	EMFILE,
+	/// Resource deadlock would occur (EDEADLK)
+	EDEADLK,
+	/// Bad address (EFAULT)
+	EFAULT,
+	/// Argument list too long (E2BIG)
+	E2BIG,
+	/// Exec format error (ENOEXEC)
+	ENOEXEC,
+	/// Broken pipe (EPIPE)
+	EPIPE,
+	/// Result too large (ERANGE)
+	ERANGE,
+	/// File name too long (ENAMETOOLONG)
+	ENAMETOOLONG,
+	/// Invalid cross-device link (EXDEV)
+	EXDEV,
+	/// Too many levels of symbolic links (ELOOP)
+	ELOOP,
 }
 
 impl Error {
@@ -115,6 +139,7 @@ impl Error {
 			Error::ENODEV => -19,              // ENODEV
 			Error::EINVAL => -22,              // EINVAL
 			Error::ENOSPC => -28,              // ENOSPC
+			Error::EFBIG => -27,               // EFBIG
 			Error::ENOTTY => -25,              // ENOTTY
 			Error::ESPIPE => -29,              // ESPIPE
 			Error::ENODATA => -61,             // ENODATA
@@ -125,6 +150,17 @@ impl Error {
 			Error::ENOTEMPTY => -39,           // ENOTEMPTY
 			Error::ECHILD => -10,              // ECHILD
 			Error::ESRCH => -3,                // ESRCH
+			Error::EACCES => -13,              // EACCES
+			Error::EMFILE => -24,              // EMFILE
+			Error::EDEADLK => -35,             // EDEADLK
+			Error::EFAULT => -14,              // EFAULT
+			Error::E2BIG => -7,                // E2BIG
+			Error::ENOEXEC => -8,              // ENOEXEC
+			Error::EPIPE => -32,               // EPIPE
+			Error::ERANGE => -34,              // ERANGE
+			Error::ENAMETOOLONG => -36,        // ENAMETOOLONG
+			Error::EXDEV => -18,               // EXDEV
+			Error::ELOOP => -40,               // ELOOP
 			Error::NetworkUnreachable => -101, // ENETUNREACH
 			Error::NetworkDown => -100,        // ENETDOWN
 			Error::DeviceNotFound => -19,      // ENODEV
@@ -133,6 +169,12 @@ impl Error {
 			Error::EIO => -5,                  // EIO
 		}
 	}
+
+	/// Value a failed system call returns in rax: the negative errno
+	/// sign-extended to 64 bits, as the C library expects
+	pub fn to_syscall_ret(self) -> u64 {
+		self.to_errno() as i64 as u64
+	}
 }
 
 impl fmt::Display for Error {
@@ -167,6 +209,7 @@ impl fmt::Display for Error {
 			Error::ENODEV => write!(f, "No such device"),
 			Error::EINVAL => write!(f, "Invalid argument"),
 			Error::ENOSPC => write!(f, "No space left on device"),
+			Error::EFBIG => write!(f, "File too large"),
 			Error::ENOTTY => write!(f, "Inappropriate ioctl for device"),
 			Error::ESPIPE => write!(f, "Illegal seek"),
 			Error::ENODATA => write!(f, "No data available"),
@@ -177,6 +220,17 @@ impl fmt::Display for Error {
 			Error::ENOTEMPTY => write!(f, "Directory not empty"),
 			Error::ECHILD => write!(f, "No child processes"),
 			Error::ESRCH => write!(f, "No such process"),
+			Error::EACCES => write!(f, "Permission denied"),
+			Error::EMFILE => write!(f, "Too many open files"),
+			Error::EDEADLK => write!(f, "Resource deadlock avoided"),
+			Error::EFAULT => write!(f, "Bad address"),
+			Error::E2BIG => write!(f, "Argument list too long"),
+			Error::ENOEXEC => write!(f, "Exec format error"),
+			Error::EPIPE => write!(f, "Broken pipe"),
+			Error::ERANGE => write!(f, "Result too large"),
+			Error::ENAMETOOLONG => write!(f, "File name too long"),
+			Error::EXDEV => write!(f, "Invalid cross-device link"),
+			Error::ELOOP => write!(f, "Too many levels of symbolic links"),
 			Error::EIO => write!(f, "Input/output error"),
 		}
 	}
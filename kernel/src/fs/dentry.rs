@@ -6,7 +6,8 @@ use alloc::{format, string::String, vec::Vec}; // Add format macro
 use core::sync::atomic::{AtomicU32, Ordering};
 
 use crate::error::Result;
-use crate::sync::{Arc, Mutex};
+use crate::rcu::RcuCell;
+use crate::sync::{Arc, SpinMutex};
 
 /// Dentry structure - similar to Linux struct dentry
 #[derive(Debug)]
@@ -18,7 +19,7 @@ pub struct Dentry {
 	/// Parent dentry
 	pub d_parent: Option<Arc<Dentry>>,
 	/// Child entries (for directories)
-	pub d_subdirs: Mutex<Vec<Arc<Dentry>>>,
+	pub d_subdirs: SpinMutex<Vec<Arc<Dentry>>>,
 	/// Dentry operations
 	pub d_op: Option<Arc<dyn DentryOperations>>,
 	/// Superblock
@@ -38,7 +39,7 @@ impl Dentry {
 			d_name: name,
 			d_inode: inode,
 			d_parent: None,
-			d_subdirs: Mutex::new(Vec::new()),
+			d_subdirs: SpinMutex::new(Vec::new()),
 			d_op: None,
 			d_sb: None,
 			d_count: AtomicU32::new(1),
@@ -199,10 +200,10 @@ impl DentryOperations for GenericDentryOps {
 
 /// Dentry cache (dcache) - simplified version
 pub struct DentryCache {
-	/// Cached dentries
-	cache: Mutex<alloc::collections::BTreeMap<String, Arc<Dentry>>>,
+	/// Cached dentries, read without locking
+	cache: RcuCell<alloc::collections::BTreeMap<String, Arc<Dentry>>>,
 	/// Hash buckets for faster lookup
-	hash_table: Vec<Mutex<Vec<Arc<Dentry>>>>,
+	hash_table: Vec<SpinMutex<Vec<Arc<Dentry>>>>,
 }
 
 impl DentryCache {
@@ -211,25 +212,24 @@ impl DentryCache {
 		const HASH_BUCKETS: usize = 256;
 		let mut hash_table = Vec::with_capacity(HASH_BUCKETS);
 		for _ in 0..HASH_BUCKETS {
-			hash_table.push(Mutex::new(Vec::new()));
+			hash_table.push(SpinMutex::new(Vec::new()));
 		}
 
 		Self {
-			cache: Mutex::new(alloc::collections::BTreeMap::new()),
+			cache: RcuCell::new(alloc::collections::BTreeMap::new()),
 			hash_table,
 		}
 	}
 
 	/// Look up dentry by path
 	pub fn lookup(&self, path: &str) -> Option<Arc<Dentry>> {
-		let cache = self.cache.lock();
-		cache.get(path).cloned()
+		self.cache.read_with(|cache| cache.get(path).cloned())
 	}
 
 	/// Insert dentry into cache
 	pub fn insert(&self, path: String, dentry: Arc<Dentry>) {
-		let mut cache = self.cache.lock();
-		cache.insert(path, dentry.clone());
+		self.cache
+			.update(|cache| cache.insert(path, dentry.clone()));
 
 		// Also insert into hash table
 		let hash = dentry.d_hash as usize % self.hash_table.len();
@@ -239,14 +239,14 @@ impl DentryCache {
 
 	/// Remove dentry from cache
 	pub fn remove(&self, path: &str) -> Option<Arc<Dentry>> {
-		let mut cache = self.cache.lock();
-		cache.remove(path)
+		self.cache.update(|cache| cache.remove(path))
 	}
 
 	/// Prune unused dentries
 	pub fn prune(&self) {
-		let mut cache = self.cache.lock();
-		cache.retain(|_, dentry| dentry.d_count.load(Ordering::Relaxed) > 1);
+		self.cache.update(|cache| {
+			cache.retain(|_, dentry| dentry.d_count.load(Ordering::Relaxed) > 1)
+		});
 
 		// Also prune hash table
 		for bucket in &self.hash_table {
@@ -213,41 +213,154 @@ impl CharDevOperations for FullDevOps {
 	}
 }
 
-/// /dev/random device operations (simplified)
+/// /dev/random and /dev/urandom device operations
 #[derive(Debug)]
-pub struct RandomDevOps;
+pub struct RandomDevOps {
+	/// Reads wait until the generator is seeded (/dev/random)
+	blocking: bool,
+}
+
+impl RandomDevOps {
+	pub fn new(blocking: bool) -> Self {
+		Self { blocking }
+	}
+}
 
 impl CharDevOperations for RandomDevOps {
-	fn read(&self, _file: &File, buf: UserSlicePtr, count: usize) -> Result<isize> {
-		// Generate pseudo-random data
-		// TODO: Use proper random number generator
-		let mut random_data = vec![0u8; count];
-		for i in 0..count {
-			random_data[i] = (i * 37 + 13) as u8; // Very simple PRNG
+	fn read(&self, file: &File, buf: UserSlicePtr, count: usize) -> Result<isize> {
+		if self.blocking && !crate::random::crng_ready() {
+			if file.get_flags() & flags::O_NONBLOCK != 0 {
+				return Err(Error::WouldBlock);
+			}
+			crate::random::wait_for_random_bytes()?;
+		}
+		let done = crate::random::get_random_bytes_chunked(count, |offset, chunk| {
+			// Stays within the `count` bytes of buf
+			let part =
+				unsafe { UserSlicePtr::new(buf.as_ptr().add(offset), chunk.len()) };
+			part.copy_from_slice(chunk)
+		})?;
+		Ok(done as isize)
+	}
+
+	fn write(&self, _file: &File, buf: UserSlicePtr, count: usize) -> Result<isize> {
+		// Mixed into the pool, but credited no entropy
+		let mut data = [0u8; 256];
+		for offset in (0..count).step_by(data.len()) {
+			let len = (count - offset).min(data.len());
+			// Stays within the `count` bytes of buf
+			let part = unsafe { UserSlicePtr::new(buf.as_ptr().add(offset), len) };
+			part.copy_to_slice(&mut data[..len])?;
+			crate::random::add_entropy(&data[..len], 0);
 		}
-		buf.copy_from_slice(&random_data)?;
 		Ok(count as isize)
 	}
 
-	fn write(&self, _file: &File, _buf: UserSlicePtr, count: usize) -> Result<isize> {
-		// TODO: Add entropy to random pool
-		Ok(count as isize)
+	fn ioctl(&self, _file: &File, cmd: u32, arg: usize) -> Result<isize> {
+		crate::random::random_ioctl(cmd, arg)
 	}
 
-	fn ioctl(&self, _file: &File, _cmd: u32, _arg: usize) -> Result<isize> {
-		// TODO: Implement random device ioctls
-		Err(Error::ENOTTY)
+	fn mmap(&self, _file: &File, _vma: &mut crate::memory::VmaArea) -> Result<()> {
+		Err(Error::ENODEV)
+	}
+
+	fn poll(&self, _file: &File, wait: &mut PollWait) -> Result<u32> {
+		// Writes are always taken, reads once the generator is seeded
+		let mut mask = POLLOUT | POLLWRNORM;
+		if !self.blocking || crate::random::crng_ready() {
+			mask |= POLLIN | POLLRDNORM;
+		} else {
+			wait.add(&crate::random::ready_wait_queue());
+		}
+		Ok(mask)
+	}
+
+	fn open(&self, _inode: &Inode, _file: &File) -> Result<()> {
+		Ok(())
+	}
+
+	fn release(&self, _inode: &Inode, _file: &File) -> Result<()> {
+		Ok(())
+	}
+}
+
+/// Terminal device operations
+#[derive(Debug)]
+pub struct TtyDevOps {
+	/// Terminal index, or None for the caller's controlling terminal
+	index: Option<usize>,
+}
+
+impl TtyDevOps {
+	pub fn new(index: Option<usize>) -> Self {
+		Self { index }
+	}
+
+	fn resolve(&self) -> Result<usize> {
+		match self.index {
+			Some(index) => Ok(index),
+			None => crate::process::current_process()
+				.and_then(|p| p.ctty)
+				.ok_or(Error::ENODEV),
+		}
+	}
+}
+
+impl CharDevOperations for TtyDevOps {
+	fn read(&self, file: &File, buf: UserSlicePtr, count: usize) -> Result<isize> {
+		let index = self.resolve()?;
+		let mut data = vec![0u8; count];
+
+		// Block for input unless the file is non-blocking
+		if file.get_flags() & flags::O_NONBLOCK == 0 && !crate::tty::has_input(index) {
+			crate::sync::might_sleep();
+			crate::sync::wait_until(|| {
+				crate::tty::has_input(index) || crate::process::signal_pending()
+			});
+			if !crate::tty::has_input(index) {
+				return Err(Error::Interrupted);
+			}
+		}
+		let read = crate::tty::read(index, &mut data)?;
+		buf.copy_from_slice(&data[..read])?;
+		Ok(read as isize)
+	}
+
+	fn write(&self, _file: &File, buf: UserSlicePtr, count: usize) -> Result<isize> {
+		let mut data = vec![0u8; count];
+		buf.copy_to_slice(&mut data)?;
+		Ok(crate::tty::write(self.resolve()?, &data)? as isize)
+	}
+
+	fn ioctl(&self, _file: &File, cmd: u32, arg: usize) -> Result<isize> {
+		Ok(crate::tty::tty_ioctl(self.resolve()?, cmd, arg)? as isize)
 	}
 
 	fn mmap(&self, _file: &File, _vma: &mut crate::memory::VmaArea) -> Result<()> {
 		Err(Error::ENODEV)
 	}
 
-	fn poll(&self, _file: &File, _wait: &mut PollWait) -> Result<u32> {
-		Ok(POLLIN | POLLOUT) // Always ready
+	fn poll(&self, _file: &File, wait: &mut PollWait) -> Result<u32> {
+		let index = self.resolve()?;
+		if let Some(queue) = crate::tty::wait_queue(index) {
+			wait.add(&queue);
+		}
+		// Output never blocks
+		let mut mask = POLLOUT | POLLWRNORM;
+		if crate::tty::has_input(index) {
+			mask |= POLLIN | POLLRDNORM;
+		}
+		Ok(mask)
 	}
 
-	fn open(&self, _inode: &Inode, _file: &File) -> Result<()> {
+	fn open(&self, _inode: &Inode, file: &File) -> Result<()> {
+		let index = self.resolve()?;
+
+		// Session leaders acquire the terminal unless O_NOCTTY is set
+		let open_flags = file.flags.load(core::sync::atomic::Ordering::Relaxed);
+		if self.index.is_some() && open_flags & flags::O_NOCTTY == 0 {
+			let _ = crate::tty::set_controlling_tty(index);
+		}
 		Ok(())
 	}
 
@@ -273,13 +386,13 @@ pub fn create_char_device_inode(
 /// DevFS - device filesystem for /dev
 pub struct DevFs {
 	/// Device entries
-	devices: crate::sync::Mutex<alloc::collections::BTreeMap<String, Arc<Inode>>>,
+	devices: crate::sync::SpinMutex<alloc::collections::BTreeMap<String, Arc<Inode>>>,
 }
 
 impl DevFs {
 	pub fn new() -> Self {
 		let mut devfs = Self {
-			devices: crate::sync::Mutex::new(alloc::collections::BTreeMap::new()),
+			devices: crate::sync::SpinMutex::new(alloc::collections::BTreeMap::new()),
 		};
 
 		devfs.create_standard_devices();
@@ -299,13 +412,44 @@ impl DevFs {
 		let full_inode = create_char_device_inode(1, 7, 0o666, Arc::new(FullDevOps));
 		self.add_device("full", full_inode);
 
-		// Create /dev/random
-		let random_inode = create_char_device_inode(1, 8, 0o666, Arc::new(RandomDevOps));
+		// Create /dev/random, which blocks until seeded
+		let random_inode =
+			create_char_device_inode(1, 8, 0o666, Arc::new(RandomDevOps::new(true)));
 		self.add_device("random", random_inode);
 
-		// Create /dev/urandom (same as random for now)
-		let urandom_inode = create_char_device_inode(1, 9, 0o666, Arc::new(RandomDevOps));
+		// Create /dev/urandom, which never blocks
+		let urandom_inode =
+			create_char_device_inode(1, 9, 0o666, Arc::new(RandomDevOps::new(false)));
 		self.add_device("urandom", urandom_inode);
+
+		// Create terminals: /dev/tty0, /dev/ttyS0 and /dev/tty
+		let tty0_inode = create_char_device_inode(
+			4,
+			0,
+			0o620,
+			Arc::new(TtyDevOps::new(Some(crate::tty::CONSOLE_TTY))),
+		);
+		self.add_device("tty0", tty0_inode);
+
+		let ttys0_inode = create_char_device_inode(
+			4,
+			64,
+			0o620,
+			Arc::new(TtyDevOps::new(Some(crate::tty::SERIAL_TTY))),
+		);
+		self.add_device("ttyS0", ttys0_inode);
+
+		let tty_inode =
+			create_char_device_inode(5, 0, 0o666, Arc::new(TtyDevOps::new(None)));
+		self.add_device("tty", tty_inode);
+
+		let console_inode = create_char_device_inode(
+			5,
+			1,
+			0o600,
+			Arc::new(TtyDevOps::new(Some(crate::tty::CONSOLE_TTY))),
+		);
+		self.add_device("console", console_inode);
 	}
 
 	pub fn add_device(&mut self, name: &str, inode: Arc<Inode>) {
@@ -419,6 +563,54 @@ impl InodeOperations for DevFsInodeOps {
 	}
 }
 
+/// Device nodes shown under /dev of the root file system
+static STANDARD_DEVICES: crate::sync::Spinlock<Option<Arc<DevFs>>> =
+	crate::sync::Spinlock::new(None);
+
+/// The standard devices, created on first use
+fn standard_devices() -> Arc<DevFs> {
+	if let Some(devfs) = STANDARD_DEVICES.lock().clone() {
+		return devfs;
+	}
+	// Built unlocked, DevFs::new takes its own locks
+	let devfs = Arc::new(DevFs::new());
+	STANDARD_DEVICES.lock().get_or_insert(devfs).clone()
+}
+
+/// Open a standard device by its /dev path. Returns None for other paths.
+pub fn open_device(path: &str, open_flags: u32) -> Result<Option<Arc<File>>> {
+	let name = match path.strip_prefix("/dev/") {
+		Some(name) => name,
+		None => return Ok(None),
+	};
+	let inode = match standard_device(name) {
+		Some(inode) => inode,
+		None => return Ok(None),
+	};
+	may_open(&inode, open_flags)?;
+
+	let mode = inode.i_mode.load(core::sync::atomic::Ordering::Relaxed);
+	let mut file = File::new(path, open_flags, mode)?;
+	if let Some(ref ops) = inode.i_fop {
+		file.set_operations(ops.clone());
+	}
+	file.inode = Some(inode.clone());
+	if let Some(ref ops) = file.f_op {
+		ops.open(&inode, &file)?;
+	}
+	Ok(Some(Arc::new(file)))
+}
+
+/// List the standard device names
+pub fn standard_device_names() -> alloc::vec::Vec<String> {
+	standard_devices().list_devices()
+}
+
+/// Look up a standard device inode by name
+pub fn standard_device(name: &str) -> Option<Arc<Inode>> {
+	standard_devices().get_device(name)
+}
+
 /// Mount devfs
 pub fn mount_devfs(_dev_name: &str, _flags: u32, _data: Option<&str>) -> Result<Arc<SuperBlock>> {
 	let mut sb = SuperBlock::new("devfs")?;
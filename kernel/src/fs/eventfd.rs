@@ -0,0 +1,129 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! eventfd - a 64-bit counter as a file
+//!
+//! Writes add to the counter and reads take it, or take 1 in semaphore
+//! mode.
+
+use core::fmt;
+
+use crate::error::{Error, Result};
+use crate::fs::*;
+use crate::memory::UserSlicePtr;
+use crate::sync::{Arc, Spinlock, WaitQueue};
+
+/// eventfd2 flags
+pub const EFD_SEMAPHORE: u32 = 1;
+pub const EFD_CLOEXEC: u32 = flags::O_CLOEXEC;
+pub const EFD_NONBLOCK: u32 = flags::O_NONBLOCK;
+
+/// Highest value the counter holds
+const EVENTFD_MAX: u64 = u64::MAX - 1;
+
+/// An eventfd counter
+pub struct EventFd {
+	count: Spinlock<u64>,
+	semaphore: bool,
+	queue: Arc<WaitQueue>, // Woken when the counter changes
+}
+
+impl fmt::Debug for EventFd {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("EventFd")
+			.field("count", &*self.count.lock())
+			.field("semaphore", &self.semaphore)
+			.finish()
+	}
+}
+
+impl FileOperations for EventFd {
+	fn read(&self, file: &File, buf: UserSlicePtr, count: usize) -> Result<isize> {
+		if count < 8 {
+			return Err(Error::EINVAL);
+		}
+		loop {
+			wait_event(file, || *self.count.lock() > 0)?;
+			let value = {
+				let mut counter = self.count.lock();
+				let value = match *counter {
+					// Another reader took it first
+					0 => continue,
+					_ if self.semaphore => 1,
+					value => value,
+				};
+				*counter -= value;
+				value
+			};
+			self.queue.wake_up();
+			buf.copy_from_slice(&value.to_ne_bytes())?;
+			return Ok(8);
+		}
+	}
+
+	fn write(&self, file: &File, buf: UserSlicePtr, count: usize) -> Result<isize> {
+		if count < 8 {
+			return Err(Error::EINVAL);
+		}
+		let mut bytes = [0u8; 8];
+		buf.copy_to_slice(&mut bytes)?;
+		let value = u64::from_ne_bytes(bytes);
+		if value == u64::MAX {
+			return Err(Error::EINVAL);
+		}
+
+		loop {
+			wait_event(file, || EVENTFD_MAX - *self.count.lock() >= value)?;
+			{
+				let mut counter = self.count.lock();
+				if EVENTFD_MAX - *counter < value {
+					continue;
+				}
+				*counter += value;
+			}
+			self.queue.wake_up();
+			return Ok(8);
+		}
+	}
+
+	fn seek(&self, _file: &File, _offset: i64, _whence: i32) -> Result<i64> {
+		Err(Error::ESPIPE)
+	}
+
+	fn ioctl(&self, _file: &File, _cmd: u32, _arg: usize) -> Result<isize> {
+		Err(Error::ENOTTY)
+	}
+
+	fn mmap(&self, _file: &File, _vma: &mut crate::memory::VmaArea) -> Result<()> {
+		Err(Error::ENODEV)
+	}
+
+	fn fsync(&self, _file: &File, _datasync: bool) -> Result<()> {
+		Err(Error::EINVAL)
+	}
+
+	fn poll(&self, _file: &File, wait: &mut PollWait) -> Result<u32> {
+		wait.add(&self.queue);
+		let count = *self.count.lock();
+		let mut mask = 0;
+		if count > 0 {
+			mask |= POLLIN | POLLRDNORM;
+		}
+		if count < EVENTFD_MAX {
+			mask |= POLLOUT | POLLWRNORM;
+		}
+		Ok(mask)
+	}
+}
+
+/// Create an eventfd starting at `initval`. `efd_flags` takes
+/// EFD_SEMAPHORE and EFD_NONBLOCK.
+pub fn create_eventfd(initval: u32, efd_flags: u32) -> Result<Arc<File>> {
+	let open_flags = flags::O_RDWR | (efd_flags & EFD_NONBLOCK);
+	let mut file = File::new("anon_inode:[eventfd]", open_flags, 0o600)?;
+	file.set_operations(Arc::new(EventFd {
+		count: Spinlock::new(initval as u64),
+		semaphore: efd_flags & EFD_SEMAPHORE != 0,
+		queue: Arc::new(WaitQueue::new()),
+	}));
+	Ok(Arc::new(file))
+}
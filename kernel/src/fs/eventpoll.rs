@@ -0,0 +1,251 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! epoll instances
+//!
+//! An epoll file watches a set of descriptors. Level-triggered entries
+//! are reported while they have events; edge-triggered ones only after
+//! a wake-up of the file's wait queues since they were last reported.
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Weak;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::error::{Error, Result};
+use crate::fs::*;
+use crate::memory::UserSlicePtr;
+use crate::sync::{Arc, Spinlock};
+
+/// epoll_ctl operations
+pub const EPOLL_CTL_ADD: i32 = 1;
+pub const EPOLL_CTL_DEL: i32 = 2;
+pub const EPOLL_CTL_MOD: i32 = 3;
+
+/// Input flags of epoll events, besides the poll events
+pub const EPOLLEXCLUSIVE: u32 = 1 << 28;
+pub const EPOLLWAKEUP: u32 = 1 << 29;
+pub const EPOLLONESHOT: u32 = 1 << 30;
+pub const EPOLLET: u32 = 1 << 31;
+
+/// Deepest chain of epoll instances watching each other, as in Linux
+const EP_MAX_NESTS: usize = 4;
+
+/// Held while adding an epoll instance to another, so two such adds
+/// can't close a cycle together
+static EPOLL_NESTING: Spinlock<()> = Spinlock::new(());
+
+/// Flags that survive an EPOLLONESHOT entry being disabled
+const EP_PRIVATE_BITS: u32 = EPOLLWAKEUP | EPOLLONESHOT | EPOLLET | EPOLLEXCLUSIVE;
+
+/// A watched descriptor
+#[derive(Clone)]
+struct EpItem {
+	file: Weak<File>, // Closing the file ends the watch
+	events: u32,
+	data: u64,
+	armed: PollWait, // The file's wait queues since the last report
+	fresh: bool,     // Added or modified since the last report
+	generation: u64, // Changes with every epoll_ctl on the entry
+}
+
+/// An epoll instance
+pub struct EventPoll {
+	items: Spinlock<BTreeMap<i32, EpItem>>,
+	generation: AtomicU64,
+}
+
+impl core::fmt::Debug for EventPoll {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		f.debug_struct("EventPoll")
+			.field("items", &self.items.lock().len())
+			.finish()
+	}
+}
+
+impl EventPoll {
+	/// Add, modify or remove the watch of `file`, open as `fd`
+	pub fn ctl(
+		&self,
+		op: i32,
+		fd: i32,
+		file: &Arc<File>,
+		events: u32,
+		data: u64,
+	) -> Result<()> {
+		// Regular files and directories are always ready
+		if mode::s_isreg(file.mode) || mode::s_isdir(file.mode) {
+			return Err(Error::EPERM);
+		}
+		let item = EpItem {
+			file: Arc::downgrade(file),
+			events: events | POLLERR | POLLHUP,
+			data,
+			armed: PollWait::new(),
+			fresh: true,
+			generation: self.generation.fetch_add(1, Ordering::Relaxed),
+		};
+
+		// Watching an epoll instance must not close a cycle or nest too
+		// deep, so polling one never recurses without end
+		let target = file.operations::<EventPoll>();
+		let _nesting = target.map(|_| EPOLL_NESTING.lock());
+		if let Some(target) = target {
+			if op == EPOLL_CTL_ADD && self.reachable_from(target, 1) {
+				return Err(Error::ELOOP);
+			}
+		}
+
+		let mut items = self.items.lock();
+		// An entry whose file was closed no longer counts
+		let live = items
+			.get(&fd)
+			.is_some_and(|item| item.file.strong_count() > 0);
+		match op {
+			EPOLL_CTL_ADD if live => return Err(Error::EEXIST),
+			EPOLL_CTL_MOD | EPOLL_CTL_DEL if !live => return Err(Error::ENOENT),
+			EPOLL_CTL_MOD if events & EPOLLEXCLUSIVE != 0 => return Err(Error::EINVAL),
+			EPOLL_CTL_ADD | EPOLL_CTL_MOD => items.insert(fd, item),
+			EPOLL_CTL_DEL => items.remove(&fd),
+			_ => return Err(Error::EINVAL),
+		};
+		Ok(())
+	}
+
+	/// Check whether this instance is `epoll` or watched by it through
+	/// other epoll instances, or whether the chain below `epoll`, found
+	/// at `depth`, is too deep
+	fn reachable_from(&self, epoll: &EventPoll, depth: usize) -> bool {
+		if core::ptr::eq(self, epoll) || depth > EP_MAX_NESTS {
+			return true;
+		}
+		let files: Vec<Arc<File>> = epoll
+			.items
+			.lock()
+			.values()
+			.filter_map(|item| item.file.upgrade())
+			.collect();
+		files.iter()
+			.filter_map(|file| file.operations::<EventPoll>())
+			.any(|child| self.reachable_from(child, depth + 1))
+	}
+
+	/// Poll the watched files, adding their wait queues to `wait`, and
+	/// return up to `max` ready entries as (events, data). With `consume`,
+	/// edge-triggered and one-shot entries are rearmed or disabled as
+	/// epoll_wait does.
+	fn scan(&self, wait: &mut PollWait, max: usize, consume: bool) -> Vec<(u32, u64)> {
+		// Files are polled without the lock held, as one may be another
+		// epoll instance
+		let snapshot: Vec<(i32, EpItem)> = {
+			let mut items = self.items.lock();
+			items.retain(|_, item| item.file.strong_count() > 0);
+			items.iter().map(|(fd, item)| (*fd, item.clone())).collect()
+		};
+
+		let mut ready = Vec::new();
+		let mut updates = Vec::new();
+		for (fd, item) in snapshot {
+			if ready.len() >= max {
+				break;
+			}
+			let file = match item.file.upgrade() {
+				Some(file) => file,
+				None => continue,
+			};
+			let mut polled = PollWait::new();
+			let mask = file.poll(&mut polled).unwrap_or(POLLERR) & item.events;
+			wait.extend(&polled);
+
+			let edge = item.events & EPOLLET != 0;
+			let triggered = item.fresh || item.armed.woken();
+			if mask == 0 || (edge && !triggered) {
+				if edge && triggered && consume {
+					updates.push((fd, item.generation, item.events, polled));
+				}
+				continue;
+			}
+			ready.push((mask, item.data));
+			if consume && (edge || item.events & EPOLLONESHOT != 0) {
+				let mut events = item.events;
+				if events & EPOLLONESHOT != 0 {
+					events &= EP_PRIVATE_BITS;
+				}
+				updates.push((fd, item.generation, events, polled));
+			}
+		}
+
+		// Entries changed by epoll_ctl meanwhile keep the new settings
+		let mut items = self.items.lock();
+		for (fd, generation, events, armed) in updates {
+			let item = items
+				.get_mut(&fd)
+				.filter(|item| item.generation == generation);
+			if let Some(item) = item {
+				item.events = events;
+				item.armed = armed;
+				item.fresh = false;
+			}
+		}
+		ready
+	}
+
+	/// Wait up to `deadline` for ready entries, at most `max`
+	pub fn wait(&self, max: usize, deadline: Option<u64>) -> Result<Vec<(u32, u64)>> {
+		loop {
+			let mut wait = PollWait::new();
+			let ready = self.scan(&mut wait, max, true);
+			let expired = deadline.is_some_and(|d| crate::time::get_time_ns() >= d);
+			if !ready.is_empty() || expired {
+				return Ok(ready);
+			}
+			select::poll_sleep(&wait, deadline)?;
+		}
+	}
+}
+
+impl FileOperations for EventPoll {
+	fn read(&self, _file: &File, _buf: UserSlicePtr, _count: usize) -> Result<isize> {
+		Err(Error::EINVAL)
+	}
+
+	fn write(&self, _file: &File, _buf: UserSlicePtr, _count: usize) -> Result<isize> {
+		Err(Error::EINVAL)
+	}
+
+	fn seek(&self, _file: &File, _offset: i64, _whence: i32) -> Result<i64> {
+		Err(Error::ESPIPE)
+	}
+
+	fn ioctl(&self, _file: &File, _cmd: u32, _arg: usize) -> Result<isize> {
+		Err(Error::ENOTTY)
+	}
+
+	fn mmap(&self, _file: &File, _vma: &mut crate::memory::VmaArea) -> Result<()> {
+		Err(Error::ENODEV)
+	}
+
+	fn fsync(&self, _file: &File, _datasync: bool) -> Result<()> {
+		Err(Error::EINVAL)
+	}
+
+	fn poll(&self, _file: &File, wait: &mut PollWait) -> Result<u32> {
+		// Readable while an entry is ready. ctl keeps watches between
+		// epoll instances acyclic, so the scan ends.
+		let ready = !self.scan(wait, 1, false).is_empty();
+		Ok(if ready { POLLIN | POLLRDNORM } else { 0 })
+	}
+
+	fn as_any(&self) -> Option<&dyn core::any::Any> {
+		Some(self)
+	}
+}
+
+/// Create an epoll instance
+pub fn create_epoll() -> Result<Arc<File>> {
+	let mut file = File::new("anon_inode:[eventpoll]", flags::O_RDWR, 0o600)?;
+	file.set_operations(Arc::new(EventPoll {
+		items: Spinlock::new(BTreeMap::new()),
+		generation: AtomicU64::new(0),
+	}));
+	Ok(Arc::new(file))
+}
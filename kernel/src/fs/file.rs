@@ -137,6 +137,11 @@ impl File {
 		}
 	}
 
+	/// Get the operations of the file as a `T`, if they are one
+	pub fn operations<T: 'static>(&self) -> Option<&T> {
+		self.f_op.as_ref()?.as_any()?.downcast_ref()
+	}
+
 	/// Poll file for events
 	pub fn poll(&self, wait: &mut super::PollWait) -> Result<u32> {
 		if let Some(ref ops) = self.f_op {
@@ -146,6 +151,15 @@ impl File {
 		}
 	}
 
+	/// Read directory entries from the file position on
+	pub fn readdir(&self, ctx: &mut super::DirContext) -> Result<()> {
+		if let Some(ref ops) = self.f_op {
+			ops.readdir(self, ctx)
+		} else {
+			Err(Error::ENOTDIR)
+		}
+	}
+
 	/// Get current file position
 	pub fn get_pos(&self) -> i64 {
 		self.pos.load(Ordering::Relaxed)
@@ -216,6 +230,12 @@ pub trait FileOperations: Send + Sync + core::fmt::Debug {
 	/// Poll for events
 	fn poll(&self, file: &File, wait: &mut super::PollWait) -> Result<u32>;
 
+	/// The operations as `Any`, for syscalls that work on one kind of
+	/// file only (optional)
+	fn as_any(&self) -> Option<&dyn core::any::Any> {
+		None
+	}
+
 	/// Open file (optional)
 	fn open(&self, inode: &super::Inode, file: &File) -> Result<()> {
 		Ok(())
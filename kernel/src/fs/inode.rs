@@ -7,7 +7,7 @@ use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 
 use crate::device::DeviceNumber;
 use crate::error::{Error, Result};
-use crate::sync::{Arc, Mutex};
+use crate::sync::{Arc, SpinMutex};
 use crate::time::{get_current_time, TimeSpec};
 
 /// Inode structure - similar to Linux struct inode
@@ -32,11 +32,11 @@ pub struct Inode {
 	/// Number of blocks
 	pub i_blocks: AtomicU64,
 	/// Access time
-	pub i_atime: Mutex<TimeSpec>,
+	pub i_atime: SpinMutex<TimeSpec>,
 	/// Modification time
-	pub i_mtime: Mutex<TimeSpec>,
+	pub i_mtime: SpinMutex<TimeSpec>,
 	/// Status change time
-	pub i_ctime: Mutex<TimeSpec>,
+	pub i_ctime: SpinMutex<TimeSpec>,
 	/// Inode operations
 	pub i_op: Option<Arc<dyn InodeOperations>>,
 	/// File operations (for regular files)
@@ -65,9 +65,9 @@ impl Inode {
 			i_size: AtomicU64::new(0),
 			i_blksize: 4096,
 			i_blocks: AtomicU64::new(0),
-			i_atime: Mutex::new(now),
-			i_mtime: Mutex::new(now),
-			i_ctime: Mutex::new(now),
+			i_atime: SpinMutex::new(now),
+			i_mtime: SpinMutex::new(now),
+			i_ctime: SpinMutex::new(now),
 			i_op: None,
 			i_fop: None,
 			i_sb: None,
@@ -188,7 +188,11 @@ impl Inode {
 	/// Create a new file in this directory
 	pub fn create(&self, name: &str, mode: u32) -> Result<Arc<Inode>> {
 		if let Some(ref ops) = self.i_op {
-			ops.create(self, name, mode)
+			super::operations::may_create(self)?;
+			let cred = crate::cred::current_cred();
+			let inode = ops.create(self, name, mode & !cred.umask)?;
+			self.set_new_owner(&inode, &cred);
+			Ok(inode)
 		} else {
 			Err(Error::ENOSYS)
 		}
@@ -197,6 +201,7 @@ impl Inode {
 	/// Look up a file in this directory
 	pub fn lookup(&self, name: &str) -> Result<Arc<Inode>> {
 		if let Some(ref ops) = self.i_op {
+			super::operations::check_permissions(self, crate::cred::MAY_EXEC)?;
 			ops.lookup(self, name)
 		} else {
 			Err(Error::ENOSYS)
@@ -206,7 +211,11 @@ impl Inode {
 	/// Create a directory
 	pub fn mkdir(&self, name: &str, mode: u32) -> Result<Arc<Inode>> {
 		if let Some(ref ops) = self.i_op {
-			ops.mkdir(self, name, mode)
+			super::operations::may_create(self)?;
+			let cred = crate::cred::current_cred();
+			let inode = ops.mkdir(self, name, mode & !cred.umask)?;
+			self.set_new_owner(&inode, &cred);
+			Ok(inode)
 		} else {
 			Err(Error::ENOSYS)
 		}
@@ -215,6 +224,8 @@ impl Inode {
 	/// Remove a file
 	pub fn unlink(&self, name: &str) -> Result<()> {
 		if let Some(ref ops) = self.i_op {
+			let victim = self.lookup(name)?;
+			super::operations::may_delete(self, &victim)?;
 			ops.unlink(self, name)
 		} else {
 			Err(Error::ENOSYS)
@@ -224,11 +235,26 @@ impl Inode {
 	/// Remove a directory
 	pub fn rmdir(&self, name: &str) -> Result<()> {
 		if let Some(ref ops) = self.i_op {
+			let victim = self.lookup(name)?;
+			super::operations::may_delete(self, &victim)?;
 			ops.rmdir(self, name)
 		} else {
 			Err(Error::ENOSYS)
 		}
 	}
+
+	/// Give an inode created in this directory to the creating user
+	fn set_new_owner(&self, inode: &Inode, cred: &crate::cred::Credentials) {
+		inode.i_uid.store(cred.fsuid.0, Ordering::Relaxed);
+
+		// New entries in set-group-ID directories inherit its group
+		let gid = if self.i_mode.load(Ordering::Relaxed) & super::mode::S_ISGID != 0 {
+			self.i_gid.load(Ordering::Relaxed)
+		} else {
+			cred.fsgid.0
+		};
+		inode.i_gid.store(gid, Ordering::Relaxed);
+	}
 }
 
 unsafe impl Send for Inode {}
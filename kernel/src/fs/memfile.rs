@@ -0,0 +1,346 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Files of the in-memory root file system
+//!
+//! Regular files and directories opened through the VFS are backed by
+//! memfs, addressed by their absolute path. Access is checked against the
+//! owner and mode of each node, like inodes of other file systems.
+
+use alloc::{string::String, vec, vec::Vec};
+
+use crate::cred::MAY_EXEC;
+use crate::error::{Error, Result};
+use crate::fs::*;
+use crate::memfs::{self, FileType};
+use crate::memory::UserSlicePtr;
+use crate::sync::Arc;
+use crate::types::{Gid, Uid};
+
+/// Translate memfs errors into the errno values of the file syscalls
+fn errno(err: Error) -> Error {
+	match err {
+		Error::NotFound | Error::NotInitialized => Error::ENOENT,
+		Error::PermissionDenied => Error::EACCES,
+		Error::InvalidOperation => Error::ENOTDIR,
+		err => err,
+	}
+}
+
+/// Inode number of a path, stable while the file keeps its name
+fn inode_number(path: &str) -> u64 {
+	// FNV-1a
+	path.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+		(hash ^ byte as u64).wrapping_mul(0x100000001b3)
+	})
+}
+
+/// File type bits of a memfs node
+fn type_bits(file_type: FileType) -> u32 {
+	match file_type {
+		FileType::RegularFile => mode::S_IFREG,
+		FileType::Directory => mode::S_IFDIR,
+		FileType::SymbolicLink => mode::S_IFLNK,
+		FileType::CharDevice => mode::S_IFCHR,
+		FileType::BlockDevice => mode::S_IFBLK,
+	}
+}
+
+/// Mode and owner of a file, for the permission checks
+fn owner(path: &str) -> Result<FileOwner> {
+	let (file_type, perms, _) = memfs::fs_stat(path).map_err(errno)?;
+	let (uid, gid) = memfs::fs_owner(path).map_err(errno)?;
+	Ok((type_bits(file_type) | perms.0, Uid(uid), Gid(gid)))
+}
+
+/// Check search permission on every directory leading to `path`
+fn may_lookup(path: &str) -> Result<()> {
+	let mut dir = String::from("/");
+	let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+	for component in components.iter().take(components.len().saturating_sub(1)) {
+		let dir_owner = owner(&dir)?;
+		if !mode::s_isdir(dir_owner.0) {
+			return Err(Error::ENOTDIR);
+		}
+		owner_permission(dir_owner, MAY_EXEC)?;
+		dir = join_paths(&dir, component);
+	}
+	Ok(())
+}
+
+/// Owner of a new file in `dir`: the caller's filesystem IDs, with the
+/// group of the directory if it is set-group-ID
+fn new_owner(dir: FileOwner) -> (u32, u32) {
+	let cred = crate::cred::current_cred();
+	let (dir_mode, _, dir_gid) = dir;
+	let gid = if dir_mode & mode::S_ISGID != 0 {
+		dir_gid
+	} else {
+		cred.fsgid
+	};
+	(cred.fsuid.0, gid.0)
+}
+
+/// Get the attributes of a file
+pub fn stat(path: &str) -> Result<KStat> {
+	let (file_type, perms, size) = memfs::fs_stat(path).map_err(errno)?;
+	let (uid, gid) = memfs::fs_owner(path).map_err(errno)?;
+	Ok(KStat {
+		st_dev: 0,
+		st_ino: inode_number(path),
+		st_nlink: if file_type == FileType::Directory {
+			2
+		} else {
+			1
+		},
+		st_mode: type_bits(file_type) | perms.0,
+		st_uid: uid,
+		st_gid: gid,
+		st_rdev: 0,
+		st_size: size as i64,
+		st_blksize: 4096,
+		st_blocks: (size as u64 + 511) / 512,
+		st_atime: 0,
+		st_atime_nsec: 0,
+		st_mtime: 0,
+		st_mtime_nsec: 0,
+		st_ctime: 0,
+		st_ctime_nsec: 0,
+	})
+}
+
+/// Open a file or directory, creating or truncating it as `flags` ask
+pub fn open(path: &str, open_flags: u32, create_mode: u32) -> Result<Arc<File>> {
+	may_lookup(path)?;
+	let (file_type, perms) = match memfs::fs_stat(path) {
+		Ok(_) if open_flags & flags::O_CREAT != 0 && open_flags & flags::O_EXCL != 0 => {
+			return Err(Error::EEXIST);
+		}
+		Ok((file_type, perms, _)) => {
+			may_open_owner(owner(path)?, open_flags)?;
+			(file_type, perms)
+		}
+		Err(Error::NotFound) if open_flags & flags::O_CREAT != 0 => {
+			let dir = owner(dirname(path))?;
+			may_create_owner(dir)?;
+			let perms = create_mode & 0o7777 & !crate::cred::current_cred().umask;
+			let (uid, gid) = new_owner(dir);
+			memfs::fs_create_file_mode(path, perms, uid, gid).map_err(errno)?;
+			(FileType::RegularFile, memfs::FileMode::new(perms))
+		}
+		Err(err) => return Err(errno(err)),
+	};
+
+	let is_dir = file_type == FileType::Directory;
+	let access = open_flags & flags::O_ACCMODE;
+	if open_flags & flags::O_DIRECTORY != 0 && !is_dir {
+		return Err(Error::ENOTDIR);
+	}
+	if is_dir && (access != flags::O_RDONLY || open_flags & flags::O_TRUNC != 0) {
+		return Err(Error::EISDIR);
+	}
+	if open_flags & flags::O_TRUNC != 0 {
+		memfs::fs_truncate(path, 0).map_err(errno)?;
+	}
+
+	let mut file = File::new(path, open_flags, type_bits(file_type) | perms.0)?;
+	file.set_operations(Arc::new(MemFileOps));
+	Ok(Arc::new(file))
+}
+
+/// Create a directory
+pub fn mkdir(path: &str, create_mode: u32) -> Result<()> {
+	may_lookup(path)?;
+	if memfs::fs_stat(path).is_ok() {
+		return Err(Error::EEXIST);
+	}
+	let dir = owner(dirname(path))?;
+	may_create_owner(dir)?;
+	let (uid, gid) = new_owner(dir);
+	// Subdirectories of set-group-ID directories are set-group-ID too
+	let mut perms = create_mode & 0o7777 & !crate::cred::current_cred().umask;
+	perms |= dir.0 & mode::S_ISGID;
+	memfs::fs_create_dir_mode(path, perms, uid, gid).map_err(errno)
+}
+
+/// Whether a directory has no entries
+fn is_empty_dir(path: &str) -> Result<bool> {
+	Ok(memfs::fs_list(path).map_err(errno)?.is_empty())
+}
+
+/// Remove a file, or an empty directory with `dir`
+pub fn unlink(path: &str, dir: bool) -> Result<()> {
+	may_lookup(path)?;
+	let (file_type, _, _) = memfs::fs_stat(path).map_err(errno)?;
+	match (file_type == FileType::Directory, dir) {
+		(true, false) => return Err(Error::EISDIR),
+		(false, true) => return Err(Error::ENOTDIR),
+		(true, true) if path == "/" => return Err(Error::Busy),
+		(true, true) if !is_empty_dir(path)? => return Err(Error::ENOTEMPTY),
+		_ => {}
+	}
+	may_delete_owner(owner(dirname(path))?, owner(path)?)?;
+	memfs::fs_remove(path).map_err(errno)
+}
+
+/// Rename a file or directory, replacing a compatible target. With
+/// `noreplace`, an existing target is an error.
+pub fn rename(old_path: &str, new_path: &str, noreplace: bool) -> Result<()> {
+	may_lookup(old_path)?;
+	may_lookup(new_path)?;
+	let (old_type, _, _) = memfs::fs_stat(old_path).map_err(errno)?;
+	let old_is_dir = old_type == FileType::Directory;
+	if old_path == "/" || new_path == "/" {
+		return Err(Error::Busy);
+	}
+	// A directory can't move below itself
+	if new_path
+		.strip_prefix(old_path)
+		.map_or(false, |rest| rest.starts_with('/'))
+	{
+		return Err(Error::EINVAL);
+	}
+
+	may_delete_owner(owner(dirname(old_path))?, owner(old_path)?)?;
+	let new_dir = owner(dirname(new_path))?;
+	match memfs::fs_stat(new_path) {
+		Ok(_) if noreplace => return Err(Error::EEXIST),
+		Ok(_) if old_path == new_path => return Ok(()),
+		Ok((new_type, _, _)) => {
+			match (old_is_dir, new_type == FileType::Directory) {
+				(true, false) => return Err(Error::ENOTDIR),
+				(false, true) => return Err(Error::EISDIR),
+				(true, true) if !is_empty_dir(new_path)? => {
+					return Err(Error::ENOTEMPTY)
+				}
+				_ => {}
+			}
+			may_delete_owner(new_dir, owner(new_path)?)?;
+		}
+		Err(Error::NotFound) => may_create_owner(new_dir)?,
+		Err(err) => return Err(errno(err)),
+	}
+	memfs::fs_rename(old_path, new_path).map_err(errno)
+}
+
+/// Read from a file at `offset` without moving its position
+pub fn read_at(file: &File, offset: usize, buf: &mut [u8]) -> Result<usize> {
+	if mode::s_isdir(file.mode) {
+		return Err(Error::EISDIR);
+	}
+	memfs::fs_read_at(&file.path, offset, buf).map_err(errno)
+}
+
+/// Write to a file at `offset` without moving its position. A write
+/// starting at or beyond RLIMIT_FSIZE raises SIGXFSZ and fails with EFBIG,
+/// one crossing it is cut short.
+pub fn write_at(file: &File, offset: usize, data: &[u8]) -> Result<usize> {
+	if mode::s_isdir(file.mode) {
+		return Err(Error::EISDIR);
+	}
+	let limit = crate::resource::rlimit(crate::resource::RLIMIT_FSIZE);
+	let end = (offset as u64).saturating_add(data.len() as u64);
+	let data = if end <= limit {
+		data
+	} else if (offset as u64) < limit {
+		&data[..(limit - offset as u64) as usize]
+	} else {
+		if let Some(pid) = crate::process::current_process_pid() {
+			let _ = crate::process::send_signal(pid, crate::signal::SIGXFSZ);
+		}
+		return Err(Error::EFBIG);
+	};
+	memfs::fs_write_at(&file.path, offset, data).map_err(errno)
+}
+
+/// Current size of a file
+fn file_size(file: &File) -> Result<usize> {
+	let (_, _, size) = memfs::fs_stat(&file.path).map_err(errno)?;
+	Ok(size)
+}
+
+/// File operations of memfs files and directories
+#[derive(Debug)]
+pub struct MemFileOps;
+
+impl FileOperations for MemFileOps {
+	fn read(&self, file: &File, buf: UserSlicePtr, count: usize) -> Result<isize> {
+		let pos = file.get_pos();
+		let mut data = vec![0u8; count];
+		let read = read_at(file, pos as usize, &mut data)?;
+		buf.copy_from_slice(&data[..read])?;
+		file.set_pos(pos + read as i64);
+		Ok(read as isize)
+	}
+
+	fn write(&self, file: &File, buf: UserSlicePtr, count: usize) -> Result<isize> {
+		let mut data = vec![0u8; count];
+		buf.copy_to_slice(&mut data)?;
+		let pos = if file.get_flags() & flags::O_APPEND != 0 {
+			file_size(file)?
+		} else {
+			file.get_pos() as usize
+		};
+		let written = write_at(file, pos, &data)?;
+		file.set_pos((pos + written) as i64);
+		Ok(written as isize)
+	}
+
+	fn seek(&self, file: &File, offset: i64, whence: i32) -> Result<i64> {
+		let base = match whence {
+			SEEK_SET => 0,
+			SEEK_CUR => file.get_pos(),
+			SEEK_END => file_size(file)? as i64,
+			_ => return Err(Error::EINVAL),
+		};
+		base.checked_add(offset)
+			.filter(|pos| *pos >= 0)
+			.ok_or(Error::EINVAL)
+	}
+
+	fn ioctl(&self, _file: &File, _cmd: u32, _arg: usize) -> Result<isize> {
+		Err(Error::ENOTTY)
+	}
+
+	fn mmap(&self, _file: &File, _vma: &mut crate::memory::VmaArea) -> Result<()> {
+		Err(Error::ENODEV)
+	}
+
+	fn fsync(&self, _file: &File, _datasync: bool) -> Result<()> {
+		Ok(())
+	}
+
+	fn poll(&self, _file: &File, _wait: &mut PollWait) -> Result<u32> {
+		Ok(POLLIN | POLLOUT)
+	}
+
+	fn readdir(&self, file: &File, ctx: &mut DirContext) -> Result<()> {
+		if !mode::s_isdir(file.mode) {
+			return Err(Error::ENOTDIR);
+		}
+
+		let mut entries: Vec<(String, u8)> =
+			vec![(String::from("."), DT_DIR), (String::from(".."), DT_DIR)];
+		for (name, file_type, _) in memfs::fs_list(&file.path).map_err(errno)? {
+			let d_type = match file_type {
+				FileType::RegularFile => DT_REG,
+				FileType::Directory => DT_DIR,
+				FileType::SymbolicLink => DT_LNK,
+				FileType::CharDevice => DT_CHR,
+				FileType::BlockDevice => DT_BLK,
+			};
+			entries.push((name, d_type));
+		}
+		// Device nodes live outside memfs
+		if file.path == "/dev" {
+			for name in devfs::standard_device_names() {
+				entries.push((name, DT_CHR));
+			}
+		}
+
+		for (name, d_type) in entries.into_iter().skip(ctx.pos.max(0) as usize) {
+			let ino = inode_number(&join_paths(&file.path, &name));
+			ctx.add_entry(ino, &name, d_type);
+		}
+		Ok(())
+	}
+}
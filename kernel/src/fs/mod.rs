@@ -7,16 +7,23 @@
 
 pub mod dentry;
 pub mod devfs;
+pub mod eventfd;
+pub mod eventpoll;
 pub mod file;
 pub mod inode;
+pub mod memfile;
 pub mod mode;
 pub mod mount;
 pub mod operations;
 pub mod path;
+pub mod pipe;
 pub mod procfs;
 pub mod ramfs;
+pub mod select;
+pub mod signalfd;
 pub mod super_block; // Add mode module
 		     // pub mod advanced;  // Advanced file system operations (removed for now)
+pub mod timerfd;
 
 use alloc::collections::BTreeMap;
 use alloc::string::String;
@@ -32,7 +39,7 @@ pub use super_block::*;
 
 use crate::error::{Error, Result};
 use crate::memory::{UserPtr, UserSlicePtr};
-use crate::sync::{Arc, Mutex};
+use crate::sync::{Arc, SpinMutex, WaitQueue};
 
 /// File access modes - Linux compatible
 pub mod flags {
@@ -142,12 +149,18 @@ pub const DT_SOCK: u8 = 12;
 pub const DT_WHT: u8 = 14;
 
 /// Global VFS state
-static VFS: Mutex<Vfs> = Mutex::new(Vfs::new());
+static VFS: SpinMutex<Vfs> = SpinMutex::new(Vfs::new());
 
 /// Global file descriptor table (simplified - in reality this would be
 /// per-process)
-static GLOBAL_FD_TABLE: Mutex<BTreeMap<i32, Arc<File>>> = Mutex::new(BTreeMap::new());
-static NEXT_FD: core::sync::atomic::AtomicI32 = core::sync::atomic::AtomicI32::new(3); // Start after stdin/stdout/stderr
+static GLOBAL_FD_TABLE: SpinMutex<BTreeMap<i32, FdEntry>> = SpinMutex::new(BTreeMap::new());
+
+/// Open file behind a descriptor
+#[derive(Clone)]
+struct FdEntry {
+	file: Arc<File>,
+	cloexec: bool, // Closed on execve (FD_CLOEXEC)
+}
 
 /// Virtual File System state
 pub struct Vfs {
@@ -180,6 +193,8 @@ impl Vfs {
 		flags: u32,
 		data: Option<&str>,
 	) -> Result<()> {
+		crate::capability::require(crate::capability::CAP_SYS_ADMIN)?;
+
 		// TODO: Implement proper mount logic
 		// For now, just create a basic mount
 		let sb = Arc::new(SuperBlock::new(fstype)?);
@@ -235,39 +250,148 @@ pub fn init() -> Result<()> {
 /// Get a file descriptor from the table
 pub fn get_file_descriptor(fd: i32) -> Option<Arc<File>> {
 	let table = GLOBAL_FD_TABLE.lock();
-	table.get(&fd).cloned()
+	table.get(&fd).map(|entry| entry.file.clone())
 }
 
-/// Allocate a new file descriptor
+/// Allocate the lowest free file descriptor
 pub fn allocate_file_descriptor(file: Arc<File>) -> Result<i32> {
-	let fd = NEXT_FD.fetch_add(1, core::sync::atomic::Ordering::SeqCst);
+	install_file_descriptor(file, 0, false)
+}
+
+/// Install a file at the lowest free descriptor not below `min_fd`
+pub fn install_file_descriptor(file: Arc<File>, min_fd: i32, cloexec: bool) -> Result<i32> {
+	let limit = crate::resource::rlimit(crate::resource::RLIMIT_NOFILE);
 	let mut table = GLOBAL_FD_TABLE.lock();
-	table.insert(fd, file);
+	let mut fd = min_fd.max(0);
+	// Keys are sorted, so the first gap at or after min_fd is free
+	for &used in table.range(fd..).map(|(used, _)| used) {
+		if used != fd {
+			break;
+		}
+		fd += 1;
+	}
+	if fd as u64 >= limit {
+		return Err(Error::EMFILE);
+	}
+	table.insert(fd, FdEntry { file, cloexec });
 	Ok(fd)
 }
 
+/// Install a file at descriptor `fd`, closing what was open there
+pub fn replace_file_descriptor(fd: i32, file: Arc<File>, cloexec: bool) -> Result<()> {
+	let limit = crate::resource::rlimit(crate::resource::RLIMIT_NOFILE);
+	if fd < 0 || fd as u64 >= limit {
+		return Err(Error::EBADF);
+	}
+	// The old file is released after the table is unlocked
+	let old = GLOBAL_FD_TABLE.lock().insert(fd, FdEntry { file, cloexec });
+	drop(old);
+	Ok(())
+}
+
 /// Close a file descriptor
 pub fn close_file_descriptor(fd: i32) -> Result<()> {
+	let old = GLOBAL_FD_TABLE.lock().remove(&fd).ok_or(Error::EBADF)?;
+	drop(old);
+	Ok(())
+}
+
+/// Get the close-on-exec flag of a descriptor
+pub fn fd_cloexec(fd: i32) -> Result<bool> {
+	let table = GLOBAL_FD_TABLE.lock();
+	table.get(&fd)
+		.map(|entry| entry.cloexec)
+		.ok_or(Error::EBADF)
+}
+
+/// Set the close-on-exec flag of a descriptor
+pub fn set_fd_cloexec(fd: i32, cloexec: bool) -> Result<()> {
 	let mut table = GLOBAL_FD_TABLE.lock();
-	table.remove(&fd).ok_or(Error::EBADF)?;
+	table.get_mut(&fd).ok_or(Error::EBADF)?.cloexec = cloexec;
 	Ok(())
 }
 
-/// Open a file
+/// Close every descriptor marked close-on-exec
+pub fn close_on_exec() {
+	let closed: Vec<FdEntry> = {
+		let mut table = GLOBAL_FD_TABLE.lock();
+		let fds: Vec<i32> = table
+			.iter()
+			.filter(|(_, entry)| entry.cloexec)
+			.map(|(fd, _)| *fd)
+			.collect();
+		fds.iter().filter_map(|fd| table.remove(fd)).collect()
+	};
+	// Files are released after the table is unlocked
+	drop(closed);
+}
+
+/// Open the console as standard input, output and error of the first
+/// user process
+pub fn console_on_rootfs() -> Result<()> {
+	let console = devfs::open_device("/dev/console", flags::O_RDWR)?.ok_or(Error::ENODEV)?;
+	for fd in 0..3 {
+		replace_file_descriptor(fd, console.clone(), false)?;
+	}
+	Ok(())
+}
+
+/// Open a file by absolute path: a standard device, a file of the memfs
+/// root file system, or a VFS file under /proc
 pub fn open_file(path: &str, flags: i32, mode: u32) -> Result<Arc<File>> {
+	if let Some(file) = devfs::open_device(path, flags as u32)? {
+		return Ok(file);
+	}
+	if path != "/proc" && !path.starts_with("/proc/") {
+		return memfile::open(path, flags as u32, mode);
+	}
+
 	// For now, create a simple file structure
 	// In a full implementation, this would:
 	// 1. Parse the path
 	// 2. Walk the directory tree
-	// 3. Check permissions
-	// 4. Create inode/dentry structures
-	// 5. Return file handle
+	// 3. Create inode/dentry structures
+	// 4. Return file handle
+
+	// Check access when the path resolves to an inode. A missing file may
+	// only be created where the directory allows it.
+	match path_lookup(path, LOOKUP_FOLLOW) {
+		Ok(found) => {
+			if let Some(inode) = found.dentry.and_then(|d| d.d_inode.clone()) {
+				may_open(&inode, flags as u32)?;
+			}
+		}
+		Err(Error::ENOENT) if flags as u32 & self::flags::O_CREAT != 0 => {
+			let (parent, _) = path_parent_and_name(path)?;
+			if let Some(dir) = parent.dentry.and_then(|d| d.d_inode.clone()) {
+				may_create(&dir)?;
+			}
+		}
+		Err(e) => return Err(e),
+	}
 
 	let file = File::new(path, flags as u32, mode)?;
 
 	Ok(Arc::new(file))
 }
 
+/// Get the attributes of an open file
+pub fn file_stat(file: &File) -> Result<KStat> {
+	// Files of the root file system have no inode, they are found by path
+	if file.inode.is_none() && (mode::s_isreg(file.mode) || mode::s_isdir(file.mode)) {
+		return memfile::stat(&file.path);
+	}
+	file.stat()
+}
+
+/// Get the attributes of the file at an absolute path
+pub fn path_stat(path: &str) -> Result<KStat> {
+	if let Some(inode) = path.strip_prefix("/dev/").and_then(devfs::standard_device) {
+		return inode.stat();
+	}
+	memfile::stat(path)
+}
+
 /// Read from a file
 pub fn read_file(file: &Arc<File>, buf: &mut [u8]) -> Result<usize> {
 	if let Some(ops) = &file.f_op {
@@ -407,20 +531,66 @@ pub const POLLOUT: u32 = 0x004;
 pub const POLLERR: u32 = 0x008;
 pub const POLLHUP: u32 = 0x010;
 pub const POLLNVAL: u32 = 0x020;
-
-/// Poll wait structure (simplified)
+pub const POLLRDNORM: u32 = 0x040;
+pub const POLLRDBAND: u32 = 0x080;
+pub const POLLWRNORM: u32 = 0x100;
+pub const POLLWRBAND: u32 = 0x200;
+pub const POLLRDHUP: u32 = 0x2000;
+
+/// Poll table - the wait queues of the files polled so far, with the
+/// wake-up count each had when it was added
+#[derive(Clone, Default)]
 pub struct PollWait {
-	// TODO: Implement proper poll/select mechanism
+	entries: Vec<(Arc<WaitQueue>, u64)>,
 }
 
 impl PollWait {
 	pub fn new() -> Self {
-		Self {}
+		Self {
+			entries: Vec::new(),
+		}
+	}
+
+	/// Add a wait queue that wakes the poller when the file's events
+	/// change, like Linux poll_wait. Poll methods call this before they
+	/// look at the state, so no wake-up is missed in between.
+	pub fn add(&mut self, queue: &Arc<WaitQueue>) {
+		self.entries.push((queue.clone(), queue.wakeups()));
+	}
+
+	/// Add the wait queues of another table
+	pub fn extend(&mut self, other: &PollWait) {
+		self.entries.extend(other.entries.iter().cloned());
+	}
+
+	/// Whether a wait queue was woken since it was added
+	pub fn woken(&self) -> bool {
+		self.entries
+			.iter()
+			.any(|(queue, seen)| queue.wakeups() != *seen)
+	}
+}
+
+/// Sleep until `ready` holds for a read or write on `file`. Fails with
+/// EAGAIN on a non-blocking file and EINTR if a signal arrives first.
+pub fn wait_event(file: &File, ready: impl Fn() -> bool) -> Result<()> {
+	if ready() {
+		return Ok(());
+	}
+	if file.get_flags() & flags::O_NONBLOCK != 0 {
+		return Err(Error::WouldBlock);
+	}
+	crate::sync::might_sleep();
+	crate::sync::wait_until(|| ready() || crate::process::signal_pending());
+	if ready() {
+		Ok(())
+	} else {
+		Err(Error::Interrupted)
 	}
 }
 
 /// Global root filesystem
-static ROOT_FS: Mutex<Option<Arc<SuperBlock>>> = Mutex::new(None);
+static ROOT_FS: SpinMutex<Option<Arc<SuperBlock>>> = SpinMutex::new(None);
 
 /// Initialize root filesystem
 pub fn init_root_fs() -> Result<()> {
@@ -6,7 +6,7 @@ use alloc::{format, string::String, vec::Vec}; // Add format macro
 use core::sync::atomic::{AtomicU32, Ordering};
 
 use crate::error::{Error, Result};
-use crate::sync::{Arc, Mutex};
+use crate::sync::{Arc, SpinMutex};
 
 /// VFS mount structure - similar to Linux struct vfsmount
 #[derive(Debug)]
@@ -20,7 +20,7 @@ pub struct VfsMount {
 	/// Parent mount
 	pub mnt_parent: Option<Arc<VfsMount>>,
 	/// Child mounts
-	pub mnt_children: Mutex<Vec<Arc<VfsMount>>>,
+	pub mnt_children: SpinMutex<Vec<Arc<VfsMount>>>,
 	/// Reference count
 	pub mnt_count: AtomicU32,
 	/// Device name
@@ -37,7 +37,7 @@ impl VfsMount {
 			mnt_mountpoint: String::from(mountpoint),
 			mnt_flags: AtomicU32::new(flags),
 			mnt_parent: None,
-			mnt_children: Mutex::new(Vec::new()),
+			mnt_children: SpinMutex::new(Vec::new()),
 			mnt_count: AtomicU32::new(1),
 			mnt_devname: None,
 			mnt_opts: None,
@@ -123,11 +123,12 @@ unsafe impl Send for VfsMount {}
 unsafe impl Sync for VfsMount {}
 
 /// Mount namespace - similar to Linux struct mnt_namespace
+#[derive(Debug)]
 pub struct MountNamespace {
 	/// Root mount
 	pub root: Option<Arc<VfsMount>>,
 	/// All mounts in this namespace
-	pub mounts: Mutex<Vec<Arc<VfsMount>>>,
+	pub mounts: SpinMutex<Vec<Arc<VfsMount>>>,
 	/// Namespace ID
 	pub ns_id: u64,
 	/// Reference count
@@ -139,7 +140,7 @@ impl MountNamespace {
 	pub fn new(ns_id: u64) -> Self {
 		Self {
 			root: None,
-			mounts: Mutex::new(Vec::new()),
+			mounts: SpinMutex::new(Vec::new()),
 			ns_id,
 			count: AtomicU32::new(1),
 		}
@@ -191,18 +192,77 @@ impl MountNamespace {
 		self.root = Some(root.clone());
 		self.add_mount(root);
 	}
+
+	/// Copy the mount tree into a new namespace (unshare/CLONE_NEWNS).
+	/// The copies share superblocks with the originals.
+	pub fn copy_tree(&self, ns_id: u64) -> Result<Self> {
+		let old_mounts = self.mounts.lock();
+		let mut new_mounts: Vec<Arc<VfsMount>> = Vec::with_capacity(old_mounts.len());
+
+		for old in old_mounts.iter() {
+			let mut mount = VfsMount::new(
+				old.mnt_sb.clone(),
+				&old.mnt_mountpoint,
+				old.get_flags(),
+			)?;
+			mount.mnt_devname = old.mnt_devname.clone();
+			mount.mnt_opts = old.mnt_opts.clone();
+
+			// Parents are listed before their children
+			let parent = old.mnt_parent.as_ref().and_then(|parent| {
+				old_mounts
+					.iter()
+					.position(|m| Arc::ptr_eq(m, parent))
+					.and_then(|index| new_mounts.get(index).cloned())
+			});
+			if let Some(ref parent) = parent {
+				mount.set_parent(parent.clone());
+			}
+
+			let mount = Arc::new(mount);
+			if let Some(parent) = parent {
+				parent.add_child(mount.clone());
+			}
+			new_mounts.push(mount);
+		}
+
+		let root = self.root.as_ref().and_then(|root| {
+			old_mounts
+				.iter()
+				.position(|m| Arc::ptr_eq(m, root))
+				.and_then(|index| new_mounts.get(index).cloned())
+		});
+
+		Ok(Self {
+			root,
+			mounts: SpinMutex::new(new_mounts),
+			ns_id,
+			count: AtomicU32::new(1),
+		})
+	}
 }
 
 /// Global mount namespace
-static INIT_MNT_NS: spin::once::Once<Mutex<MountNamespace>> = spin::once::Once::new();
-
-fn get_init_mnt_ns() -> &'static Mutex<MountNamespace> {
-	INIT_MNT_NS.call_once(|| Mutex::new(MountNamespace::new(1)))
+static INIT_MNT_NS: spin::once::Once<Arc<SpinMutex<MountNamespace>>> = spin::once::Once::new();
+
+fn get_init_mnt_ns() -> &'static Arc<SpinMutex<MountNamespace>> {
+	INIT_MNT_NS.call_once(|| {
+		Arc::new(SpinMutex::new(MountNamespace::new(
+			crate::namespace::alloc_ns_id(),
+		)))
+	})
 }
 
 /// Get the init mount namespace
-pub fn get_init_ns() -> &'static Mutex<MountNamespace> {
-	get_init_mnt_ns()
+pub fn get_init_ns() -> Arc<SpinMutex<MountNamespace>> {
+	get_init_mnt_ns().clone()
+}
+
+/// Get the mount namespace of the current process
+pub fn current_ns() -> Arc<SpinMutex<MountNamespace>> {
+	crate::process::current_process()
+		.map(|p| p.nsproxy.mnt_ns)
+		.unwrap_or_else(get_init_ns)
 }
 
 /// Mount a filesystem
@@ -218,7 +278,7 @@ pub fn do_mount(
 	let sb = Arc::new(super::SuperBlock::new(type_name)?);
 	let mount = Arc::new(VfsMount::new(sb, dir_name, flags)?);
 
-	let ns = get_init_ns();
+	let ns = current_ns();
 	let ns = ns.lock();
 	ns.add_mount(mount);
 
@@ -231,7 +291,7 @@ pub fn do_mount(
 
 /// Unmount a filesystem
 pub fn do_umount(dir_name: &str, flags: u32) -> Result<()> {
-	let ns = get_init_ns();
+	let ns = current_ns();
 	let ns = ns.lock();
 
 	if let Some(mount) = ns.remove_mount(dir_name) {
@@ -245,14 +305,14 @@ pub fn do_umount(dir_name: &str, flags: u32) -> Result<()> {
 
 /// Get mount information for a path
 pub fn path_get_mount(path: &str) -> Option<Arc<VfsMount>> {
-	let ns = get_init_ns();
+	let ns = current_ns();
 	let ns = ns.lock();
 	ns.find_mount(path)
 }
 
 /// Check if a path is a mount point
 pub fn is_mountpoint(path: &str) -> bool {
-	let ns = get_init_ns();
+	let ns = current_ns();
 	let ns = ns.lock();
 	let mounts = ns.mounts.lock();
 	mounts.iter().any(|m| m.get_path() == path)
@@ -260,14 +320,14 @@ pub fn is_mountpoint(path: &str) -> bool {
 
 /// Get all mount points
 pub fn get_all_mounts() -> Vec<String> {
-	let ns = get_init_ns();
+	let ns = current_ns();
 	let ns = ns.lock();
 	ns.get_mount_points()
 }
 
 /// Remount a filesystem with new flags
 pub fn do_remount(dir_name: &str, flags: u32, data: Option<&str>) -> Result<()> {
-	let ns = get_init_ns();
+	let ns = current_ns();
 	let ns = ns.lock();
 
 	if let Some(mount) = ns.find_mount(dir_name) {
@@ -290,7 +350,7 @@ pub fn do_remount(dir_name: &str, flags: u32, data: Option<&str>) -> Result<()>
 
 /// Bind mount - create a bind mount
 pub fn do_bind_mount(old_path: &str, new_path: &str, flags: u32) -> Result<()> {
-	let ns = get_init_ns();
+	let ns = current_ns();
 	let ns = ns.lock();
 
 	if let Some(old_mount) = ns.find_mount(old_path) {
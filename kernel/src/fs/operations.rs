@@ -2,9 +2,14 @@
 
 //! Various VFS operations and utilities
 
+use core::sync::atomic::Ordering;
+
+use crate::capability::{CAP_CHOWN, CAP_FSETID};
+use crate::cred::{MAY_EXEC, MAY_READ, MAY_WRITE};
 use crate::error::{Error, Result};
 use crate::memory::UserSlicePtr;
 use crate::sync::Arc;
+use crate::types::{Gid, Uid};
 
 /// Address space operations trait - similar to Linux address_space_operations
 pub trait AddressSpaceOperations: Send + Sync {
@@ -409,13 +414,129 @@ pub fn get_file_operations(inode: &super::Inode) -> Arc<dyn super::FileOperation
 	}
 }
 
-/// Check file permissions
+/// Mode and owner of a file, as the permission checks see it
+pub type FileOwner = (u32, Uid, Gid);
+
+/// Check file permissions of the current process against an inode
 pub fn check_permissions(inode: &super::Inode, mask: u32) -> Result<()> {
-	// TODO: Implement proper permission checking
-	// For now, allow all operations
+	owner_permission(inode_owner(inode), mask)
+}
+
+/// Check file permissions of the current process against a mode and
+/// owner, for files that have no inode
+pub fn owner_permission((mode, uid, gid): FileOwner, mask: u32) -> Result<()> {
+	let cred = crate::cred::current_cred();
+	crate::cred::generic_permission(&cred, mode, uid, gid, mask)
+}
+
+/// Get the mode and owner of an inode
+fn inode_owner(inode: &super::Inode) -> FileOwner {
+	(
+		inode.i_mode.load(Ordering::Relaxed),
+		Uid(inode.i_uid.load(Ordering::Relaxed)),
+		Gid(inode.i_gid.load(Ordering::Relaxed)),
+	)
+}
+
+/// Check whether a file may be opened with the given open flags
+pub fn may_open(inode: &super::Inode, flags: u32) -> Result<()> {
+	may_open_owner(inode_owner(inode), flags)
+}
+
+/// Like may_open, for a file known by its mode and owner
+pub fn may_open_owner(owner: FileOwner, flags: u32) -> Result<()> {
+	let mut mask = match flags & super::flags::O_ACCMODE {
+		super::flags::O_WRONLY => MAY_WRITE,
+		super::flags::O_RDWR => MAY_READ | MAY_WRITE,
+		_ => MAY_READ,
+	};
+	if flags & super::flags::O_TRUNC != 0 {
+		mask |= MAY_WRITE;
+	}
+
+	if super::mode::s_isdir(owner.0) && mask & MAY_WRITE != 0 {
+		return Err(Error::EISDIR);
+	}
+	owner_permission(owner, mask)
+}
+
+/// Check whether an entry may be created in a directory
+pub fn may_create(dir: &super::Inode) -> Result<()> {
+	may_create_owner(inode_owner(dir))
+}
+
+/// Like may_create, for a directory known by its mode and owner
+pub fn may_create_owner(dir: FileOwner) -> Result<()> {
+	if !super::mode::s_isdir(dir.0) {
+		return Err(Error::ENOTDIR);
+	}
+	owner_permission(dir, MAY_WRITE | MAY_EXEC)
+}
+
+/// Check whether an entry may be removed from a directory
+pub fn may_delete(dir: &super::Inode, victim: &super::Inode) -> Result<()> {
+	may_delete_owner(inode_owner(dir), inode_owner(victim))
+}
+
+/// Like may_delete, for files known by their mode and owner
+pub fn may_delete_owner(dir: FileOwner, victim: FileOwner) -> Result<()> {
+	may_create_owner(dir)?;
+
+	// In sticky directories only the owner of the entry or directory may
+	// remove it
+	let cred = crate::cred::current_cred();
+	let (dir_mode, dir_uid, _) = dir;
+	let (_, victim_uid, _) = victim;
+	if dir_mode & super::mode::S_ISVTX != 0 && !cred.owns(dir_uid) && !cred.owns(victim_uid) {
+		return Err(Error::EPERM);
+	}
 	Ok(())
 }
 
+/// Check an attribute change and return the attributes to apply.
+///
+/// Only the owner may change the mode, changing the owner needs
+/// CAP_CHOWN, and the group may only be changed to one the caller belongs
+/// to.
+pub fn may_setattr(
+	inode: &super::Inode,
+	attr: &super::inode::InodeAttr,
+) -> Result<super::inode::InodeAttr> {
+	let cred = crate::cred::current_cred();
+	let (_, uid, gid) = inode_owner(inode);
+	let mut attr = *attr;
+
+	if let Some(new_uid) = attr.uid {
+		if Uid(new_uid) != uid && !cred.capable(CAP_CHOWN) {
+			return Err(Error::EPERM);
+		}
+	}
+	if let Some(new_gid) = attr.gid {
+		let member = cred.owns(uid) && cred.in_group(Gid(new_gid));
+		if Gid(new_gid) != gid && !member && !cred.capable(CAP_CHOWN) {
+			return Err(Error::EPERM);
+		}
+	}
+	if let Some(mode) = attr.mode {
+		if !cred.owns(uid) {
+			return Err(Error::EPERM);
+		}
+		// Non-members can't create set-group-ID files
+		let group = Gid(attr.gid.unwrap_or(gid.0));
+		if !cred.capable(CAP_FSETID) && !cred.in_group(group) {
+			attr.mode = Some(mode & !super::mode::S_ISGID);
+		}
+	}
+	Ok(attr)
+}
+
+/// Change the permission bits of an inode (chmod)
+pub fn do_chmod(inode: &super::Inode, mode: u32) -> Result<()> {
+	let file_type = inode.i_mode.load(Ordering::Relaxed) & super::mode::S_IFMT;
+	let attr = super::inode::InodeAttr::new().with_mode(file_type | (mode & 0o7777));
+	notify_change(inode, &attr)
+}
+
 /// Update access time
 pub fn update_atime(inode: &super::Inode) {
 	inode.update_atime();
@@ -438,8 +559,9 @@ pub fn do_truncate(inode: &super::Inode, size: u64) -> Result<()> {
 
 /// Notify directory change
 pub fn notify_change(inode: &super::Inode, attr: &super::inode::InodeAttr) -> Result<()> {
+	let attr = may_setattr(inode, attr)?;
 	if let Some(ref ops) = inode.i_op {
-		ops.setattr(inode, attr)
+		ops.setattr(inode, &attr)
 	} else {
 		Ok(())
 	}
@@ -0,0 +1,220 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Anonymous pipes
+
+use alloc::{collections::VecDeque, format, vec};
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::error::{Error, Result};
+use crate::fs::*;
+use crate::memory::{copy_to_user, UserPtr, UserSlicePtr};
+use crate::sync::{Arc, Spinlock, WaitQueue};
+
+/// Bytes a pipe buffers before writers block
+const PIPE_SIZE: usize = 65536;
+
+/// Report the bytes buffered in a pipe
+const FIONREAD: u32 = 0x541B;
+
+/// Inode numbers shown in the pipe:[ino] names
+static NEXT_PIPE_INO: AtomicU64 = AtomicU64::new(1);
+
+/// Data shared by both ends of a pipe
+struct PipeBuffer {
+	data: VecDeque<u8>,
+	readers: usize,
+	writers: usize,
+}
+
+/// One end of a pipe
+pub struct PipeEnd {
+	pipe: Arc<Spinlock<PipeBuffer>>,
+	queue: Arc<WaitQueue>, // Woken on new data, new space and closed ends
+	write: bool,
+}
+
+impl fmt::Debug for PipeEnd {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("PipeEnd")
+			.field("write", &self.write)
+			.finish()
+	}
+}
+
+impl Drop for PipeEnd {
+	fn drop(&mut self) {
+		let mut pipe = self.pipe.lock();
+		if self.write {
+			pipe.writers -= 1;
+		} else {
+			pipe.readers -= 1;
+		}
+		drop(pipe);
+		self.queue.wake_up();
+	}
+}
+
+impl PipeEnd {
+	/// Sleep until `ready` holds. Fails with EAGAIN on a non-blocking file
+	/// and EINTR if a signal arrives first.
+	fn wait(&self, file: &File, ready: impl Fn(&PipeBuffer) -> bool) -> Result<()> {
+		if file.get_flags() & flags::O_NONBLOCK != 0 {
+			return Err(Error::WouldBlock);
+		}
+		crate::sync::might_sleep();
+		crate::sync::wait_until(|| {
+			ready(&self.pipe.lock()) || crate::process::signal_pending()
+		});
+		if ready(&self.pipe.lock()) {
+			Ok(())
+		} else {
+			Err(Error::Interrupted)
+		}
+	}
+}
+
+impl FileOperations for PipeEnd {
+	fn read(&self, file: &File, buf: UserSlicePtr, count: usize) -> Result<isize> {
+		if self.write {
+			return Err(Error::EBADF);
+		}
+		if count == 0 {
+			return Ok(0);
+		}
+
+		loop {
+			{
+				let mut pipe = self.pipe.lock();
+				if !pipe.data.is_empty() {
+					let len = count.min(pipe.data.len());
+					let data: alloc::vec::Vec<u8> =
+						pipe.data.drain(..len).collect();
+					drop(pipe);
+					self.queue.wake_up();
+					buf.copy_from_slice(&data)?;
+					return Ok(len as isize);
+				}
+				// All writers gone: end of file
+				if pipe.writers == 0 {
+					return Ok(0);
+				}
+			}
+			self.wait(file, |pipe| !pipe.data.is_empty() || pipe.writers == 0)?;
+		}
+	}
+
+	fn write(&self, file: &File, buf: UserSlicePtr, count: usize) -> Result<isize> {
+		if !self.write {
+			return Err(Error::EBADF);
+		}
+		let mut data = vec![0u8; count];
+		buf.copy_to_slice(&mut data)?;
+
+		let mut written = 0;
+		while written < count {
+			{
+				let mut pipe = self.pipe.lock();
+				if pipe.readers == 0 {
+					drop(pipe);
+					if let Some(pid) = crate::process::current_process_pid() {
+						let _ = crate::process::send_signal(
+							pid,
+							crate::signal::SIGPIPE,
+						);
+					}
+					return Err(Error::EPIPE);
+				}
+				let len = (count - written).min(PIPE_SIZE - pipe.data.len());
+				pipe.data.extend(&data[written..written + len]);
+				written += len;
+			}
+			self.queue.wake_up();
+			if written == count {
+				break;
+			}
+			let ready = |pipe: &PipeBuffer| {
+				pipe.data.len() < PIPE_SIZE || pipe.readers == 0
+			};
+			match self.wait(file, ready) {
+				Ok(()) => {}
+				// A partial write reports what went through
+				Err(_) if written > 0 => break,
+				Err(err) => return Err(err),
+			}
+		}
+		Ok(written as isize)
+	}
+
+	fn seek(&self, _file: &File, _offset: i64, _whence: i32) -> Result<i64> {
+		Err(Error::ESPIPE)
+	}
+
+	fn ioctl(&self, _file: &File, cmd: u32, arg: usize) -> Result<isize> {
+		match cmd {
+			FIONREAD => {
+				let len = self.pipe.lock().data.len() as i32;
+				copy_to_user(UserPtr::new(arg as *mut u8)?, &len.to_ne_bytes())?;
+				Ok(0)
+			}
+			_ => Err(Error::ENOTTY),
+		}
+	}
+
+	fn mmap(&self, _file: &File, _vma: &mut crate::memory::VmaArea) -> Result<()> {
+		Err(Error::ENODEV)
+	}
+
+	fn fsync(&self, _file: &File, _datasync: bool) -> Result<()> {
+		Err(Error::EINVAL)
+	}
+
+	fn poll(&self, _file: &File, wait: &mut PollWait) -> Result<u32> {
+		wait.add(&self.queue);
+		let pipe = self.pipe.lock();
+		let mut mask = 0;
+		if self.write {
+			if pipe.readers == 0 {
+				mask |= POLLERR;
+			} else if pipe.data.len() < PIPE_SIZE {
+				mask |= POLLOUT | POLLWRNORM;
+			}
+		} else {
+			if !pipe.data.is_empty() {
+				mask |= POLLIN | POLLRDNORM;
+			}
+			if pipe.writers == 0 {
+				mask |= POLLHUP;
+			}
+		}
+		Ok(mask)
+	}
+}
+
+/// Create a pipe and return its read and write ends. Only O_NONBLOCK of
+/// `open_flags` applies to the files.
+pub fn create_pipe(open_flags: u32) -> Result<(Arc<File>, Arc<File>)> {
+	let pipe = Arc::new(Spinlock::new(PipeBuffer {
+		data: VecDeque::new(),
+		readers: 1,
+		writers: 1,
+	}));
+	let queue = Arc::new(WaitQueue::new());
+	let name = format!("pipe:[{}]", NEXT_PIPE_INO.fetch_add(1, Ordering::Relaxed));
+	let nonblock = open_flags & flags::O_NONBLOCK;
+
+	let mut read_end = File::new(&name, flags::O_RDONLY | nonblock, mode::S_IFIFO | 0o600)?;
+	read_end.set_operations(Arc::new(PipeEnd {
+		pipe: pipe.clone(),
+		queue: queue.clone(),
+		write: false,
+	}));
+	let mut write_end = File::new(&name, flags::O_WRONLY | nonblock, mode::S_IFIFO | 0o600)?;
+	write_end.set_operations(Arc::new(PipeEnd {
+		pipe,
+		queue,
+		write: true,
+	}));
+
+	Ok((Arc::new(read_end), Arc::new(write_end)))
+}
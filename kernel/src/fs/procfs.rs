@@ -8,7 +8,7 @@ use core::sync::atomic::{AtomicU64, Ordering};
 use crate::error::{Error, Result};
 use crate::fs::*;
 use crate::memory::UserSlicePtr;
-use crate::sync::{Arc, Mutex};
+use crate::sync::{Arc, SpinMutex};
 
 /// Proc filesystem entry
 #[derive(Debug)]
@@ -24,7 +24,7 @@ pub struct ProcEntry {
 	/// Write function
 	pub write: Option<fn(&ProcEntry, &str) -> Result<()>>,
 	/// Child entries (for directories)
-	pub children: Mutex<Vec<Arc<ProcEntry>>>,
+	pub children: SpinMutex<Vec<Arc<ProcEntry>>>,
 	/// Parent entry
 	pub parent: Option<Arc<ProcEntry>>,
 	/// Private data
@@ -50,7 +50,7 @@ impl ProcEntry {
 			mode,
 			read: Some(read_fn),
 			write: None,
-			children: Mutex::new(Vec::new()),
+			children: SpinMutex::new(Vec::new()),
 			parent: None,
 			private_data: None,
 		}
@@ -63,7 +63,7 @@ impl ProcEntry {
 			mode,
 			read: None,
 			write: None,
-			children: Mutex::new(Vec::new()),
+			children: SpinMutex::new(Vec::new()),
 			parent: None,
 			private_data: None,
 		}
@@ -95,7 +95,7 @@ pub struct ProcFs {
 	/// Next inode number
 	next_ino: AtomicU64,
 	/// Entry to inode mapping
-	entries: Mutex<BTreeMap<*const ProcEntry, u64>>,
+	entries: SpinMutex<BTreeMap<*const ProcEntry, u64>>,
 }
 
 impl ProcFs {
@@ -104,7 +104,7 @@ impl ProcFs {
 		let mut fs = Self {
 			root,
 			next_ino: AtomicU64::new(1),
-			entries: Mutex::new(BTreeMap::new()),
+			entries: SpinMutex::new(BTreeMap::new()),
 		};
 
 		fs.create_default_entries();
@@ -177,6 +177,14 @@ impl ProcFs {
 		));
 		self.root.add_child(stat_entry);
 
+		// Create /proc/softirqs
+		let softirqs_entry = Arc::new(ProcEntry::new_file(
+			String::from("softirqs"),
+			0o444,
+			proc_softirqs_read,
+		));
+		self.root.add_child(softirqs_entry);
+
 		// Create /proc/mounts
 		let mounts_entry = Arc::new(ProcEntry::new_file(
 			String::from("mounts"),
@@ -293,8 +301,115 @@ impl ProcInodeOps {
 unsafe impl Send for ProcInodeOps {}
 unsafe impl Sync for ProcInodeOps {}
 
+/// Inode numbers of per-process entries: the PID is kept in bits 4..36
+/// and the entry kind in the low bits, above the range of static entries
+const PID_INO_BASE: u64 = 1 << 40;
+const PID_DIR: u64 = 1;
+const PID_STATUS: u64 = 2;
+const PID_NS_DIR: u64 = 3;
+const PID_NS_MNT: u64 = 4;
+const PID_NS_PID: u64 = 5;
+
+fn pid_ino(pid: crate::types::Pid, kind: u64) -> u64 {
+	PID_INO_BASE | ((pid.0 as u64) << 4) | kind
+}
+
+fn decode_pid_ino(ino: u64) -> Option<(crate::types::Pid, u64)> {
+	if ino & PID_INO_BASE == 0 {
+		return None;
+	}
+	Some((
+		crate::types::Pid(((ino >> 4) & 0xFFFF_FFFF) as u32),
+		ino & 0xF,
+	))
+}
+
+/// Resolve a /proc/<pid> directory name. Numbers are looked up in the
+/// viewer's PID namespace, so processes outside it don't show up.
+fn resolve_pid_dir(name: &str) -> Option<crate::types::Pid> {
+	let current = crate::process::current_process();
+	if name == "self" {
+		return current.map(|p| p.pid);
+	}
+	let nr = name.parse::<u32>().ok()?;
+	let pid_ns = current
+		.map(|p| p.pid_ns)
+		.unwrap_or_else(crate::namespace::init_pid_ns);
+	crate::process::find_pid_in_ns(&pid_ns, nr)
+}
+
+/// Parse a /proc/<pid>/ns/<type> path as used by setns(). Returns the PID
+/// number (None for "self") and the namespace type.
+pub fn parse_ns_path(path: &str) -> Option<(Option<u32>, crate::process::NsType)> {
+	let mut parts = path.strip_prefix("/proc/")?.split('/');
+	let pid = match parts.next()? {
+		"self" => None,
+		nr => Some(nr.parse::<u32>().ok()?),
+	};
+	if parts.next()? != "ns" {
+		return None;
+	}
+	let ns_type = match parts.next()? {
+		"mnt" => crate::process::NsType::Mount,
+		"pid" => crate::process::NsType::Pid,
+		_ => return None,
+	};
+	parts.next().is_none().then_some((pid, ns_type))
+}
+
+impl ProcInodeOps {
+	/// Create the inode of a per-process entry
+	fn pid_inode(&self, pid: crate::types::Pid, kind: u64) -> Arc<Inode> {
+		let mode = match kind {
+			PID_DIR | PID_NS_DIR => mode::S_IFDIR | 0o555,
+			PID_NS_MNT | PID_NS_PID => mode::S_IFLNK | 0o777,
+			_ => mode::S_IFREG | 0o444,
+		};
+
+		let mut inode = Inode::new(pid_ino(pid, kind), mode);
+		inode.set_operations(Arc::new(ProcInodeOps::new(self.get_fs())));
+		if kind == PID_STATUS {
+			let mut entry = ProcEntry::new_file(
+				String::from("status"),
+				0o444,
+				proc_pid_status_read,
+			);
+			entry.private_data = Some(pid.0 as usize as *mut u8);
+			inode.set_file_operations(Arc::new(ProcFileOps::new(Arc::new(entry))));
+		}
+		Arc::new(inode)
+	}
+
+	/// Look up a name inside a per-process directory
+	fn lookup_pid_entry(
+		&self,
+		pid: crate::types::Pid,
+		kind: u64,
+		name: &str,
+	) -> Result<Arc<Inode>> {
+		if crate::process::find_process(pid).is_none() {
+			return Err(Error::ENOENT);
+		}
+		let child = match (kind, name) {
+			(PID_DIR, "status") => PID_STATUS,
+			(PID_DIR, "ns") => PID_NS_DIR,
+			(PID_NS_DIR, "mnt") => PID_NS_MNT,
+			(PID_NS_DIR, "pid") => PID_NS_PID,
+			_ => return Err(Error::ENOENT),
+		};
+		Ok(self.pid_inode(pid, child))
+	}
+}
+
 impl InodeOperations for ProcInodeOps {
 	fn lookup(&self, dir: &Inode, name: &str) -> Result<Arc<Inode>> {
+		if let Some((pid, kind)) = decode_pid_ino(dir.i_ino) {
+			return self.lookup_pid_entry(pid, kind, name);
+		}
+		if let Some(pid) = resolve_pid_dir(name) {
+			return Ok(self.pid_inode(pid, PID_DIR));
+		}
+
 		let fs = self.get_fs();
 		// Find the proc entry for this inode
 		// This is a simplified implementation
@@ -356,7 +471,14 @@ impl InodeOperations for ProcInodeOps {
 	}
 
 	fn readlink(&self, inode: &Inode) -> Result<String> {
-		Err(Error::EINVAL)
+		// Namespace links read as "<type>:[<id>]", like on Linux
+		let (pid, kind) = decode_pid_ino(inode.i_ino).ok_or(Error::EINVAL)?;
+		let process = crate::process::find_process(pid).ok_or(Error::ENOENT)?;
+		match kind {
+			PID_NS_MNT => Ok(format!("mnt:[{}]", process.nsproxy.mnt_ns.lock().ns_id)),
+			PID_NS_PID => Ok(format!("pid:[{}]", process.pid_ns.id)),
+			_ => Err(Error::EINVAL),
+		}
 	}
 
 	fn follow_link(&self, inode: &Inode) -> Result<Arc<Inode>> {
@@ -439,23 +561,54 @@ fn proc_uptime_read(_entry: &ProcEntry, content: &mut String) -> Result<()> {
 }
 
 fn proc_loadavg_read(_entry: &ProcEntry, content: &mut String) -> Result<()> {
-	// TODO: Get actual load average
-	content.push_str("0.00 0.00 0.00 1/1 1\n");
+	use crate::loadavg::{get_avenrun, load_frac, load_int};
+
+	let tasks = crate::process::task_counts();
+	for load in get_avenrun() {
+		content.push_str(&format!("{}.{:02} ", load_int(load), load_frac(load)));
+	}
+	content.push_str(&format!(
+		"{}/{} {}\n",
+		crate::scheduler::nr_running(),
+		tasks.threads,
+		crate::process::last_pid().0
+	));
 	Ok(())
 }
 
 fn proc_stat_read(_entry: &ProcEntry, content: &mut String) -> Result<()> {
-	// TODO: Get actual system statistics
-	content.push_str(
-		"cpu  0 0 0 1000 0 0 0 0 0 0\n\
-         cpu0 0 0 0 1000 0 0 0 0 0 0\n\
-         intr 0\n\
-         ctxt 0\n\
-         btime 0\n\
-         processes 1\n\
-         procs_running 1\n\
-         procs_blocked 0\n",
+	use crate::perf::{perf_counter_get, CounterType};
+	use crate::resource::ns_to_clock_t;
+
+	// Time not charged to any thread was spent idle
+	let (user, system) = crate::resource::cpu_times();
+	let idle = crate::time::get_time_ns().saturating_sub(user + system);
+	let cpu = format!(
+		"{} 0 {} {} 0 0 0 0 0 0",
+		ns_to_clock_t(user),
+		ns_to_clock_t(system),
+		ns_to_clock_t(idle)
 	);
+	content.push_str(&format!("cpu  {}\ncpu0 {}\n", cpu, cpu));
+
+	content.push_str(&format!(
+		"intr {}\nctxt {}\nbtime {}\nprocesses {}\nprocs_running {}\nprocs_blocked 0\n",
+		perf_counter_get(CounterType::Interrupts).unwrap_or(0),
+		perf_counter_get(CounterType::ContextSwitches).unwrap_or(0),
+		crate::time::boot_time_secs(),
+		crate::process::last_pid().0,
+		crate::scheduler::nr_running()
+	));
+	Ok(())
+}
+
+fn proc_softirqs_read(_entry: &ProcEntry, content: &mut String) -> Result<()> {
+	use crate::softirq::{softirq_count, NR_SOFTIRQS, SOFTIRQ_NAMES};
+
+	content.push_str(&format!("{:>12} {:>10}\n", "", "CPU0"));
+	for nr in 0..NR_SOFTIRQS {
+		content.push_str(&format!("{:>12}: {:>10}\n", SOFTIRQ_NAMES[nr], softirq_count(nr)));
+	}
 	Ok(())
 }
 
@@ -468,6 +621,60 @@ fn proc_mounts_read(_entry: &ProcEntry, content: &mut String) -> Result<()> {
 	Ok(())
 }
 
+fn proc_pid_status_read(entry: &ProcEntry, content: &mut String) -> Result<()> {
+	let pid = crate::types::Pid(entry.private_data.ok_or(Error::ENOENT)? as usize as u32);
+	let process = crate::process::find_process(pid).ok_or(Error::ENOENT)?;
+
+	// PIDs are shown as seen from the reader's namespace
+	let viewer_ns = crate::process::current_process()
+		.map(|p| p.pid_ns)
+		.unwrap_or_else(crate::namespace::init_pid_ns);
+	let nr = process.pid_nr_ns(&viewer_ns).ok_or(Error::ENOENT)?;
+	let ppid = process
+		.parent
+		.map(|parent| crate::process::pid_nr_ns(parent, &viewer_ns))
+		.unwrap_or(0);
+	let tracer = crate::ptrace::tracer_of(pid)
+		.map(|tracer| crate::process::pid_nr_ns(tracer, &viewer_ns))
+		.unwrap_or(0);
+	let nspid: Vec<String> = process.ns_pids[viewer_ns.level..]
+		.iter()
+		.map(|nr| format!("{}", nr))
+		.collect();
+
+	content.push_str(&format!(
+		"Name:\t{}\n\
+         State:\t{:?}\n\
+         Pid:\t{}\n\
+         PPid:\t{}\n\
+         TracerPid:\t{}\n\
+         Uid:\t{}\t{}\t{}\t{}\n\
+         Gid:\t{}\t{}\t{}\t{}\n\
+         NSpid:\t{}\n\
+         NoNewPrivs:\t{}\n\
+         Seccomp:\t{}\n\
+         Seccomp_filters:\t{}\n",
+		process.name,
+		process.state,
+		nr,
+		ppid,
+		tracer,
+		process.cred.uid.0,
+		process.cred.euid.0,
+		process.cred.suid.0,
+		process.cred.fsuid.0,
+		process.cred.gid.0,
+		process.cred.egid.0,
+		process.cred.sgid.0,
+		process.cred.fsgid.0,
+		nspid.join("\t"),
+		process.no_new_privs as u32,
+		process.seccomp.mode,
+		process.seccomp.filters.len()
+	));
+	Ok(())
+}
+
 /// Mount proc filesystem
 pub fn mount_procfs(_dev_name: &str, _flags: u32, _data: Option<&str>) -> Result<Arc<SuperBlock>> {
 	let mut sb = SuperBlock::new("proc")?;
@@ -8,7 +8,7 @@ use core::sync::atomic::{AtomicU64, Ordering};
 use crate::error::{Error, Result};
 use crate::fs::inode::GenericInodeOps;
 use crate::fs::*;
-use crate::sync::{Arc, Mutex};
+use crate::sync::{Arc, SpinMutex};
 
 const NAME_MAX: usize = 255;
 
@@ -17,17 +17,17 @@ pub struct RamFs {
 	/// Next inode number
 	next_ino: AtomicU64,
 	/// Inode storage
-	inodes: Mutex<BTreeMap<u64, Arc<Inode>>>,
+	inodes: SpinMutex<BTreeMap<u64, Arc<Inode>>>,
 	/// Directory entries
-	entries: Mutex<BTreeMap<u64, Vec<Arc<Dentry>>>>,
+	entries: SpinMutex<BTreeMap<u64, Vec<Arc<Dentry>>>>,
 }
 
 impl RamFs {
 	pub fn new() -> Self {
 		Self {
 			next_ino: AtomicU64::new(1),
-			inodes: Mutex::new(BTreeMap::new()),
-			entries: Mutex::new(BTreeMap::new()),
+			inodes: SpinMutex::new(BTreeMap::new()),
+			entries: SpinMutex::new(BTreeMap::new()),
 		}
 	}
 
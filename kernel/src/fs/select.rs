@@ -0,0 +1,148 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! poll and select
+//!
+//! Both poll every file with one poll table, and sleep until one of the
+//! wait queues the files added is woken, a signal arrives or the timeout
+//! passes.
+
+use alloc::vec::Vec;
+
+use crate::error::{Error, Result};
+use crate::fs::*;
+
+/// Events select reports in the read, write and except sets
+const POLLIN_SET: u32 = POLLRDNORM | POLLRDBAND | POLLIN | POLLHUP | POLLERR;
+const POLLOUT_SET: u32 = POLLWRBAND | POLLWRNORM | POLLOUT | POLLERR;
+const POLLEX_SET: u32 = POLLPRI;
+
+/// One entry of the poll array, like struct pollfd
+#[derive(Debug, Clone, Copy)]
+pub struct PollFd {
+	pub fd: i32,
+	pub events: u16,
+	pub revents: u16,
+}
+
+/// Sleep until a wait queue in `wait` is woken or `deadline` (ns since
+/// boot) passes. Fails with EINTR if a signal arrives first.
+pub fn poll_sleep(wait: &PollWait, deadline: Option<u64>) -> Result<()> {
+	let woken = || wait.woken() || crate::process::signal_pending();
+	crate::sync::might_sleep();
+	match deadline {
+		Some(deadline) => {
+			crate::sync::wait_until_deadline(woken, deadline);
+		}
+		None => crate::sync::wait_until(woken),
+	}
+	if crate::process::signal_pending() {
+		return Err(Error::Interrupted);
+	}
+	Ok(())
+}
+
+/// Whether `deadline` has passed
+fn expired(deadline: Option<u64>) -> bool {
+	deadline.is_some_and(|deadline| crate::time::get_time_ns() >= deadline)
+}
+
+/// Wait for events on the descriptors of `fds` and fill in their
+/// `revents`. Returns how many have events, 0 on timeout.
+pub fn do_poll(fds: &mut [PollFd], deadline: Option<u64>) -> Result<usize> {
+	loop {
+		let mut wait = PollWait::new();
+		let mut count = 0;
+		for pollfd in fds.iter_mut() {
+			pollfd.revents = 0;
+			// Negative descriptors are skipped
+			if pollfd.fd < 0 {
+				continue;
+			}
+			let mask = match get_file_descriptor(pollfd.fd) {
+				Some(file) => {
+					let events = pollfd.events as u32 | POLLERR | POLLHUP;
+					file.poll(&mut wait).unwrap_or(POLLERR) & events
+				}
+				None => POLLNVAL,
+			};
+			pollfd.revents = mask as u16;
+			if mask != 0 {
+				count += 1;
+			}
+		}
+		if count > 0 || expired(deadline) {
+			return Ok(count);
+		}
+		poll_sleep(&wait, deadline)?;
+	}
+}
+
+/// Descriptor sets of select, one bit per descriptor in 64-bit words
+#[derive(Debug, Clone, Default)]
+pub struct FdSets {
+	pub read: Vec<u64>,
+	pub write: Vec<u64>,
+	pub except: Vec<u64>,
+}
+
+fn is_set(set: &[u64], fd: usize) -> bool {
+	set.get(fd / 64)
+		.is_some_and(|word| word & (1 << (fd % 64)) != 0)
+}
+
+fn set_bit(set: &mut [u64], fd: usize) {
+	if let Some(word) = set.get_mut(fd / 64) {
+		*word |= 1 << (fd % 64);
+	}
+}
+
+/// Wait until one of the first `nfds` descriptors in `sets` is ready and
+/// replace the sets with the ready ones. Returns the number of bits set,
+/// 0 on timeout.
+pub fn do_select(nfds: usize, sets: &mut FdSets, deadline: Option<u64>) -> Result<usize> {
+	let wanted: Vec<(usize, u32)> = (0..nfds)
+		.filter_map(|fd| {
+			let mut events = 0;
+			if is_set(&sets.read, fd) {
+				events |= POLLIN_SET;
+			}
+			if is_set(&sets.write, fd) {
+				events |= POLLOUT_SET;
+			}
+			if is_set(&sets.except, fd) {
+				events |= POLLEX_SET;
+			}
+			(events != 0).then_some((fd, events))
+		})
+		.collect();
+
+	let words = nfds.div_ceil(64);
+	loop {
+		let mut ready = FdSets {
+			read: alloc::vec![0; words],
+			write: alloc::vec![0; words],
+			except: alloc::vec![0; words],
+		};
+		let mut wait = PollWait::new();
+		let mut count = 0;
+		for &(fd, events) in &wanted {
+			let file = get_file_descriptor(fd as i32).ok_or(Error::EBADF)?;
+			let mask = file.poll(&mut wait).unwrap_or(POLLERR) & events;
+			for (set, bits) in [
+				(&mut ready.read, POLLIN_SET),
+				(&mut ready.write, POLLOUT_SET),
+				(&mut ready.except, POLLEX_SET),
+			] {
+				if mask & bits != 0 {
+					set_bit(set, fd);
+					count += 1;
+				}
+			}
+		}
+		if count > 0 || expired(deadline) {
+			*sets = ready;
+			return Ok(count);
+		}
+		poll_sleep(&wait, deadline)?;
+	}
+}
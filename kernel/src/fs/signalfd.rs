@@ -0,0 +1,126 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! signalfd - read pending signals as records
+//!
+//! A read takes the reader's pending signals that are in the mask. The
+//! signals are normally blocked, so they stay pending until read.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::error::{Error, Result};
+use crate::fs::*;
+use crate::memory::UserSlicePtr;
+use crate::signal::{self, SigSet};
+use crate::sync::{Arc, Spinlock};
+
+/// signalfd4 flags
+pub const SFD_CLOEXEC: u32 = flags::O_CLOEXEC;
+pub const SFD_NONBLOCK: u32 = flags::O_NONBLOCK;
+
+/// Size of struct signalfd_siginfo
+const SIGINFO_SIZE: usize = 128;
+
+/// si_code of a signal sent by kill
+const SI_USER: i32 = 0;
+
+/// A signalfd and the signals it reads
+pub struct SignalFd {
+	mask: Spinlock<SigSet>,
+}
+
+impl fmt::Debug for SignalFd {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("SignalFd")
+			.field("mask", &*self.mask.lock())
+			.finish()
+	}
+}
+
+impl SignalFd {
+	/// Replace the signals read. SIGKILL and SIGSTOP are never read.
+	pub fn set_mask(&self, mut mask: SigSet) {
+		mask.remove(signal::SIGKILL);
+		mask.remove(signal::SIGSTOP);
+		*self.mask.lock() = mask;
+	}
+
+	/// Whether the current process has a signal to read
+	fn ready(&self) -> bool {
+		let mask = *self.mask.lock();
+		crate::process::with_current(|p| p.pending_signals.0 & mask.0 != 0).unwrap_or(false)
+	}
+}
+
+impl FileOperations for SignalFd {
+	fn read(&self, file: &File, buf: UserSlicePtr, count: usize) -> Result<isize> {
+		let max = count / SIGINFO_SIZE;
+		if max == 0 {
+			return Err(Error::EINVAL);
+		}
+		loop {
+			wait_event(file, || self.ready())?;
+			let mask = *self.mask.lock();
+			let signals = crate::process::dequeue_signals(mask, max)?;
+			if signals.is_empty() {
+				continue;
+			}
+
+			// Senders aren't recorded, so ssi_pid and ssi_uid stay
+			// 0
+			let mut records = Vec::with_capacity(signals.len() * SIGINFO_SIZE);
+			for signal in &signals {
+				let mut info = [0u8; SIGINFO_SIZE];
+				info[0..4].copy_from_slice(&(*signal as u32).to_ne_bytes());
+				info[8..12].copy_from_slice(&SI_USER.to_ne_bytes());
+				records.extend_from_slice(&info);
+			}
+			buf.copy_from_slice(&records)?;
+			return Ok(records.len() as isize);
+		}
+	}
+
+	fn write(&self, _file: &File, _buf: UserSlicePtr, _count: usize) -> Result<isize> {
+		Err(Error::EINVAL)
+	}
+
+	fn seek(&self, _file: &File, _offset: i64, _whence: i32) -> Result<i64> {
+		Err(Error::ESPIPE)
+	}
+
+	fn ioctl(&self, _file: &File, _cmd: u32, _arg: usize) -> Result<isize> {
+		Err(Error::ENOTTY)
+	}
+
+	fn mmap(&self, _file: &File, _vma: &mut crate::memory::VmaArea) -> Result<()> {
+		Err(Error::ENODEV)
+	}
+
+	fn fsync(&self, _file: &File, _datasync: bool) -> Result<()> {
+		Err(Error::EINVAL)
+	}
+
+	fn poll(&self, _file: &File, wait: &mut PollWait) -> Result<u32> {
+		if let Some(queue) = crate::process::with_current(|p| p.signal_queue.clone()) {
+			wait.add(&queue);
+		}
+		Ok(if self.ready() { POLLIN | POLLRDNORM } else { 0 })
+	}
+
+	fn as_any(&self) -> Option<&dyn core::any::Any> {
+		Some(self)
+	}
+}
+
+/// Create a signalfd reading the signals in `mask`. `sfd_flags` takes
+/// SFD_NONBLOCK.
+pub fn create_signalfd(mask: SigSet, sfd_flags: u32) -> Result<Arc<File>> {
+	let open_flags = flags::O_RDONLY | (sfd_flags & SFD_NONBLOCK);
+	let mut file = File::new("anon_inode:[signalfd]", open_flags, 0o600)?;
+	let signalfd = SignalFd {
+		mask: Spinlock::new(SigSet::empty()),
+	};
+	signalfd.set_mask(mask);
+	file.set_operations(Arc::new(signalfd));
+	Ok(Arc::new(file))
+}
@@ -8,7 +8,7 @@ use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 
 use crate::device::DeviceNumber;
 use crate::error::Result;
-use crate::sync::{Arc, Mutex};
+use crate::sync::{Arc, SpinMutex};
 
 /// Superblock structure - similar to Linux struct super_block
 #[derive(Debug)]
@@ -34,13 +34,13 @@ pub struct SuperBlock {
 	/// File system magic number
 	pub s_magic: u32,
 	/// List of inodes
-	pub s_inodes: Mutex<Vec<Arc<super::Inode>>>,
+	pub s_inodes: SpinMutex<Vec<Arc<super::Inode>>>,
 	/// Next inode number
 	pub s_next_ino: AtomicU64,
 	/// Private data
 	pub s_fs_info: Option<*mut u8>,
 	/// Dirty inodes
-	pub s_dirty: Mutex<Vec<Arc<super::Inode>>>,
+	pub s_dirty: SpinMutex<Vec<Arc<super::Inode>>>,
 	/// Reference count
 	pub s_count: AtomicU32,
 	/// File system name
@@ -61,10 +61,10 @@ impl SuperBlock {
 			s_mount: None,
 			s_flags: AtomicU32::new(0),
 			s_magic: 0,
-			s_inodes: Mutex::new(Vec::new()),
+			s_inodes: SpinMutex::new(Vec::new()),
 			s_next_ino: AtomicU64::new(1),
 			s_fs_info: None,
-			s_dirty: Mutex::new(Vec::new()),
+			s_dirty: SpinMutex::new(Vec::new()),
 			s_count: AtomicU32::new(1),
 			s_id: String::from(fstype),
 		})
@@ -0,0 +1,222 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! timerfd - timer expirations as a file
+//!
+//! A read returns how often the timer expired since the last read.
+//! Expirations are counted from the clock whenever the file is read or
+//! polled; a timer on the timer wheel wakes pollers when one is due.
+
+use alloc::sync::Weak;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::error::{Error, Result};
+use crate::fs::*;
+use crate::interrupt::without_interrupts;
+use crate::memory::UserSlicePtr;
+use crate::posix_timers::{forward_timer, TIMER_MAX_NS};
+use crate::sync::{Arc, Spinlock, WaitQueue};
+use crate::time::{HrTimer, HrTimerBase, TimeSpec};
+
+/// timerfd_create flags
+pub const TFD_CLOEXEC: u32 = flags::O_CLOEXEC;
+pub const TFD_NONBLOCK: u32 = flags::O_NONBLOCK;
+
+/// timerfd_settime flags
+pub const TFD_TIMER_ABSTIME: u32 = 1;
+pub const TFD_TIMER_CANCEL_ON_SET: u32 = 2;
+
+/// Every timerfd, for the timer wheel to find
+static TIMERFDS: Spinlock<Vec<Weak<TimerFd>>> = Spinlock::new(Vec::new());
+
+/// Expiry settings and the expirations not read yet
+#[derive(Debug, Default)]
+struct TimerState {
+	expires: u64,  // Next expiry in clock ns, 0 when disarmed
+	interval: u64, // Period, 0 for a one-shot timer
+	ticks: u64,    // Expirations since the last read
+	queued: u64,   // Expiry of the last timer put on the wheel
+}
+
+impl TimerState {
+	/// Count the expirations up to `now`. Returns whether there were any.
+	fn update(&mut self, now: u64) -> bool {
+		let (expired, next) = forward_timer(self.expires, self.interval, now);
+		self.ticks = self.ticks.saturating_add(expired);
+		self.expires = next;
+		expired != 0
+	}
+}
+
+/// A timerfd
+pub struct TimerFd {
+	clock: HrTimerBase,
+	state: Spinlock<TimerState>,
+	queue: Arc<WaitQueue>, // Woken on expiry and when the timer is set
+}
+
+impl fmt::Debug for TimerFd {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("TimerFd")
+			.field("clock", &self.clock)
+			.field("state", &*self.state.lock())
+			.finish()
+	}
+}
+
+impl TimerFd {
+	fn now(&self) -> u64 {
+		crate::time::clock_now(self.clock).to_ns()
+	}
+
+	/// Run `f` on the state, which the timer wheel also takes
+	fn with_state<T>(&self, f: impl FnOnce(&mut TimerState) -> T) -> T {
+		without_interrupts(|| f(&mut self.state.lock()))
+	}
+
+	/// Put a timer for the next expiry on the wheel unless one is there
+	fn queue_timer(&self) {
+		let expires = self.with_state(|state| {
+			if state.expires == 0 || state.expires == state.queued {
+				return None;
+			}
+			state.queued = state.expires;
+			Some(state.expires)
+		});
+		if let Some(expires) = expires {
+			let mut timer = HrTimer::new(self.clock);
+			timer.expires = TimeSpec::from_ns(expires);
+			timer.function = Some(timerfd_timer_fn);
+			crate::time::add_timer(timer);
+		}
+	}
+
+	/// Count expirations from the wheel and wake pollers
+	fn expire(&self) {
+		let now = self.now();
+		if self.with_state(|state| state.update(now)) {
+			self.queue.wake_up();
+		}
+		self.queue_timer();
+	}
+
+	/// Arm the timer to expire after `value` ns, or at `value` with
+	/// `absolute`, and then every `interval` ns. A zero `value` disarms
+	/// it. Times past TIMER_MAX_NS are clamped to it. Returns the old
+	/// (interval, time left).
+	pub fn settime(&self, absolute: bool, interval: u64, value: u64) -> (u64, u64) {
+		let now = self.now();
+		let old = self.with_state(|state| {
+			state.update(now);
+			let old = (state.interval, state.expires.saturating_sub(now));
+			state.ticks = 0;
+			state.queued = 0;
+			state.interval = interval.min(TIMER_MAX_NS);
+			state.expires = match value {
+				0 => 0,
+				_ if absolute => value,
+				_ => now.saturating_add(value),
+			}
+			.min(TIMER_MAX_NS);
+			old
+		});
+		self.queue.wake_up();
+		self.queue_timer();
+		old
+	}
+
+	/// The (interval, time left) of the timer, time left 0 if disarmed
+	pub fn gettime(&self) -> (u64, u64) {
+		let now = self.now();
+		self.with_state(|state| {
+			state.update(now);
+			(state.interval, state.expires.saturating_sub(now))
+		})
+	}
+
+	/// Whether an expiration is waiting to be read
+	fn ready(&self) -> bool {
+		let now = self.now();
+		self.with_state(|state| {
+			state.update(now);
+			state.ticks > 0
+		})
+	}
+}
+
+impl FileOperations for TimerFd {
+	fn read(&self, file: &File, buf: UserSlicePtr, count: usize) -> Result<isize> {
+		if count < 8 {
+			return Err(Error::EINVAL);
+		}
+		loop {
+			wait_event(file, || self.ready())?;
+			let ticks = self.with_state(|state| core::mem::take(&mut state.ticks));
+			if ticks == 0 {
+				continue;
+			}
+			// The wheel timer may have been used up by this read
+			self.queue_timer();
+			buf.copy_from_slice(&ticks.to_ne_bytes())?;
+			return Ok(8);
+		}
+	}
+
+	fn write(&self, _file: &File, _buf: UserSlicePtr, _count: usize) -> Result<isize> {
+		Err(Error::EINVAL)
+	}
+
+	fn seek(&self, _file: &File, _offset: i64, _whence: i32) -> Result<i64> {
+		Err(Error::ESPIPE)
+	}
+
+	fn ioctl(&self, _file: &File, _cmd: u32, _arg: usize) -> Result<isize> {
+		Err(Error::ENOTTY)
+	}
+
+	fn mmap(&self, _file: &File, _vma: &mut crate::memory::VmaArea) -> Result<()> {
+		Err(Error::ENODEV)
+	}
+
+	fn fsync(&self, _file: &File, _datasync: bool) -> Result<()> {
+		Err(Error::EINVAL)
+	}
+
+	fn poll(&self, _file: &File, wait: &mut PollWait) -> Result<u32> {
+		wait.add(&self.queue);
+		Ok(if self.ready() { POLLIN | POLLRDNORM } else { 0 })
+	}
+
+	fn as_any(&self) -> Option<&dyn core::any::Any> {
+		Some(self)
+	}
+}
+
+/// Timer wheel function of every timerfd timer
+fn timerfd_timer_fn() {
+	let timers: Vec<Arc<TimerFd>> = without_interrupts(|| {
+		let mut timers = TIMERFDS.lock();
+		timers.retain(|timer| timer.strong_count() > 0);
+		timers.iter().filter_map(Weak::upgrade).collect()
+	});
+	for timer in timers {
+		timer.expire();
+	}
+}
+
+/// Create a disarmed timerfd on `clock`, CLOCK_REALTIME, CLOCK_MONOTONIC or
+/// CLOCK_BOOTTIME. `tfd_flags` takes TFD_NONBLOCK.
+pub fn create_timerfd(clock: i32, tfd_flags: u32) -> Result<Arc<File>> {
+	let clock = crate::time::clock_base(clock).ok_or(Error::EINVAL)?;
+	let timerfd = Arc::new(TimerFd {
+		clock,
+		state: Spinlock::new(TimerState::default()),
+		queue: Arc::new(WaitQueue::new()),
+	});
+	without_interrupts(|| TIMERFDS.lock().push(Arc::downgrade(&timerfd)));
+
+	let open_flags = flags::O_RDONLY | (tfd_flags & TFD_NONBLOCK);
+	let mut file = File::new("anon_inode:[timerfd]", open_flags, 0o600)?;
+	file.set_operations(timerfd);
+	Ok(Arc::new(file))
+}
@@ -0,0 +1,445 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Fast user-space mutexes (futexes) - Linux compatible
+
+use alloc::{collections::VecDeque, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+
+use crate::error::{Error, Result};
+use crate::memory::{copy_from_user, virt_to_phys, UserPtr};
+use crate::rtmutex::RawRtMutex;
+use crate::sync::{Arc, Spinlock, WaitQueue};
+use crate::time::{self, TimeSpec};
+use crate::types::{Tid, VirtAddr};
+
+/// Futex operations
+pub const FUTEX_WAIT: i32 = 0;
+pub const FUTEX_WAKE: i32 = 1;
+pub const FUTEX_REQUEUE: i32 = 3;
+pub const FUTEX_CMP_REQUEUE: i32 = 4;
+pub const FUTEX_LOCK_PI: i32 = 6;
+pub const FUTEX_UNLOCK_PI: i32 = 7;
+pub const FUTEX_TRYLOCK_PI: i32 = 8;
+pub const FUTEX_WAIT_BITSET: i32 = 9;
+pub const FUTEX_WAKE_BITSET: i32 = 10;
+
+/// Futex operation modifiers
+pub const FUTEX_PRIVATE_FLAG: i32 = 128;
+pub const FUTEX_CLOCK_REALTIME: i32 = 256;
+const FUTEX_CMD_MASK: i32 = !(FUTEX_PRIVATE_FLAG | FUTEX_CLOCK_REALTIME);
+
+/// Bitset matching every waiter
+pub const FUTEX_BITSET_MATCH_ANY: u32 = 0xFFFF_FFFF;
+
+/// PI futex word layout
+pub const FUTEX_WAITERS: u32 = 0x8000_0000;
+pub const FUTEX_OWNER_DIED: u32 = 0x4000_0000;
+pub const FUTEX_TID_MASK: u32 = 0x3FFF_FFFF;
+
+/// Keeps PI futex rt-mutex IDs apart from kernel rt-mutexes
+const PI_FUTEX_ID: u64 = 1 << 63;
+
+const FUTEX_HASH_SIZE: usize = 256;
+
+/// A thread blocked on a futex
+#[derive(Debug)]
+pub(crate) struct FutexWaiter {
+	key: AtomicU64, // Physical address of the futex word
+	bitset: u32,
+	woken: AtomicBool,
+	queue: WaitQueue, // The waiter sleeps here until woken
+}
+
+impl FutexWaiter {
+	pub(crate) fn new(key: u64, bitset: u32) -> Arc<Self> {
+		Arc::new(Self {
+			key: AtomicU64::new(key),
+			bitset,
+			woken: AtomicBool::new(false),
+			queue: WaitQueue::new(),
+		})
+	}
+
+	/// Key of the futex the waiter is queued on
+	pub(crate) fn key(&self) -> u64 {
+		self.key.load(Ordering::Relaxed)
+	}
+
+	pub(crate) fn is_woken(&self) -> bool {
+		self.woken.load(Ordering::Acquire)
+	}
+
+	/// Sleep until woken, a signal is pending, or `deadline` (ns since
+	/// boot) passes. Returns false if the deadline passed.
+	pub(crate) fn sleep(&self, deadline: Option<u64>) -> bool {
+		crate::sync::might_sleep();
+		let ready = || self.is_woken() || crate::process::signal_pending();
+		self.queue.wait_event_deadline(ready, deadline)
+	}
+}
+
+/// Futex wait queues, hashed on the physical address of the futex word
+pub(crate) struct FutexHash {
+	buckets: Vec<VecDeque<Arc<FutexWaiter>>>,
+}
+
+impl FutexHash {
+	pub(crate) const fn new() -> Self {
+		Self {
+			buckets: Vec::new(),
+		}
+	}
+
+	fn bucket(&mut self, key: u64) -> &mut VecDeque<Arc<FutexWaiter>> {
+		if self.buckets.is_empty() {
+			self.buckets.resize_with(FUTEX_HASH_SIZE, VecDeque::new);
+		}
+		// Futex words are 4-byte aligned, so skip the low bits
+		let hash = ((key >> 2).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 56) as usize;
+		&mut self.buckets[hash % FUTEX_HASH_SIZE]
+	}
+
+	/// Queue a waiter behind the others on its futex
+	pub(crate) fn queue(&mut self, waiter: Arc<FutexWaiter>) {
+		self.bucket(waiter.key()).push_back(waiter);
+	}
+
+	/// Wake up to `nr` waiters on `key` whose bitset matches
+	pub(crate) fn wake(&mut self, key: u64, nr: usize, bitset: u32) -> usize {
+		let bucket = self.bucket(key);
+		let mut woken = 0;
+		bucket.retain(|waiter| {
+			if woken < nr
+				&& waiter.key.load(Ordering::Relaxed) == key
+				&& waiter.bitset & bitset != 0
+			{
+				waiter.woken.store(true, Ordering::Release);
+				waiter.queue.wake_up();
+				woken += 1;
+				false
+			} else {
+				true
+			}
+		});
+		woken
+	}
+
+	/// Remove a waiter that gave up (timeout or signal)
+	fn remove(&mut self, waiter: &Arc<FutexWaiter>) -> bool {
+		let key = waiter.key.load(Ordering::Relaxed);
+		let bucket = self.bucket(key);
+		let before = bucket.len();
+		bucket.retain(|w| !Arc::ptr_eq(w, waiter));
+		bucket.len() != before
+	}
+
+	/// Wake `nr_wake` waiters on `key` and move up to `nr_requeue` of the
+	/// rest, in queue order, to `key2`
+	pub(crate) fn requeue(
+		&mut self,
+		key: u64,
+		key2: u64,
+		nr_wake: usize,
+		nr_requeue: usize,
+	) -> usize {
+		let woken = self.wake(key, nr_wake, FUTEX_BITSET_MATCH_ANY);
+
+		let mut moved = Vec::new();
+		self.bucket(key).retain(|waiter| {
+			if moved.len() < nr_requeue && waiter.key() == key {
+				moved.push(waiter.clone());
+				false
+			} else {
+				true
+			}
+		});
+
+		let requeued = moved.len();
+		for waiter in moved {
+			waiter.key.store(key2, Ordering::Relaxed);
+			self.queue(waiter);
+		}
+		woken + requeued
+	}
+}
+
+static FUTEX_HASH: Spinlock<FutexHash> = Spinlock::new(FutexHash::new());
+
+/// Resolve a user futex address to its hash key
+fn futex_key(uaddr: u64) -> Result<u64> {
+	if uaddr == 0 || uaddr % 4 != 0 {
+		return Err(Error::EINVAL);
+	}
+	Ok(virt_to_phys(VirtAddr::new(uaddr as usize))?.as_u64())
+}
+
+/// Read the current value of a futex word
+fn read_futex_word(uaddr: u64) -> Result<u32> {
+	let mut bytes = [0u8; 4];
+	copy_from_user(&mut bytes, UserPtr::from_const(uaddr as *const u8)?)?;
+	Ok(u32::from_ne_bytes(bytes))
+}
+
+/// Access a futex word for atomic updates
+fn futex_word(uaddr: u64) -> Result<&'static AtomicU32> {
+	let ptr = UserPtr::new(uaddr as *mut u32)?;
+	Ok(unsafe { &*(ptr.as_ptr() as *const AtomicU32) })
+}
+
+/// Read a timespec argument from user space
+fn read_timespec(uaddr: u64) -> Result<TimeSpec> {
+	let mut bytes = [0u8; 16];
+	copy_from_user(&mut bytes, UserPtr::from_const(uaddr as *const u8)?)?;
+	let mut sec = [0u8; 8];
+	let mut nsec = [0u8; 8];
+	sec.copy_from_slice(&bytes[..8]);
+	nsec.copy_from_slice(&bytes[8..]);
+
+	let ts = TimeSpec::new(i64::from_ne_bytes(sec), i64::from_ne_bytes(nsec));
+	if ts.tv_sec < 0 || !(0..time::NSEC_PER_SEC as i64).contains(&ts.tv_nsec) {
+		return Err(Error::EINVAL);
+	}
+	Ok(ts)
+}
+
+/// Block until woken, the deadline (in ns since boot) passes, or a signal
+/// arrives. The value check and enqueue happen under the hash lock so a
+/// concurrent wake can't be missed.
+pub fn futex_wait(uaddr: u64, val: u32, deadline: Option<u64>, bitset: u32) -> Result<u64> {
+	if bitset == 0 {
+		return Err(Error::EINVAL);
+	}
+	let key = futex_key(uaddr)?;
+	let waiter = FutexWaiter::new(key, bitset);
+
+	{
+		let mut hash = FUTEX_HASH.lock();
+		if read_futex_word(uaddr)? != val {
+			return Err(Error::WouldBlock);
+		}
+		hash.queue(waiter.clone());
+	}
+
+	let woken = waiter.sleep(deadline);
+	if waiter.is_woken() {
+		return Ok(0);
+	}
+
+	// A racing wake wins if it already dequeued us
+	if !FUTEX_HASH.lock().remove(&waiter) {
+		return Ok(0);
+	}
+	Err(if woken {
+		Error::Interrupted
+	} else {
+		Error::Timeout
+	})
+}
+
+/// Wake up to `nr` threads waiting on a futex
+pub fn futex_wake(uaddr: u64, nr: u32, bitset: u32) -> Result<u64> {
+	if bitset == 0 {
+		return Err(Error::EINVAL);
+	}
+	let key = futex_key(uaddr)?;
+	let woken = FUTEX_HASH.lock().wake(key, nr as usize, bitset);
+	Ok(woken as u64)
+}
+
+/// Wake `nr_wake` waiters on `uaddr` and move up to `nr_requeue` of the
+/// rest to `uaddr2`. With `cmpval`, fail with EAGAIN if the futex word
+/// changed.
+pub fn futex_requeue(
+	uaddr: u64,
+	nr_wake: u32,
+	nr_requeue: u32,
+	uaddr2: u64,
+	cmpval: Option<u32>,
+) -> Result<u64> {
+	let key = futex_key(uaddr)?;
+	let key2 = futex_key(uaddr2)?;
+
+	let mut hash = FUTEX_HASH.lock();
+	if let Some(expected) = cmpval {
+		if read_futex_word(uaddr)? != expected {
+			return Err(Error::WouldBlock);
+		}
+	}
+
+	Ok(hash.requeue(key, key2, nr_wake as usize, nr_requeue as usize) as u64)
+}
+
+/// The rt-mutex backing the PI futex at `key`
+fn pi_state(key: u64) -> RawRtMutex {
+	RawRtMutex::with_id(key | PI_FUTEX_ID)
+}
+
+/// Publish the owner of a PI futex in the user word
+fn set_pi_owner(word: &AtomicU32, state: &RawRtMutex, owner: Option<Tid>) {
+	let value = match owner {
+		Some(tid) if state.has_waiters() => tid.0 | FUTEX_WAITERS,
+		Some(tid) => tid.0,
+		None => 0,
+	};
+	word.store(value, Ordering::Release);
+}
+
+/// Take a PI futex, lending our priority to its owner while blocked.
+/// With `try_only`, fail with EAGAIN instead of blocking.
+pub fn futex_lock_pi(uaddr: u64, deadline: Option<u64>, try_only: bool) -> Result<u64> {
+	let tid = crate::process::current_thread_tid().ok_or(Error::ESRCH)?;
+	let key = futex_key(uaddr)?;
+	let word = futex_word(uaddr)?;
+	let state = pi_state(key);
+
+	{
+		// The hash lock serializes kernel updates of the word
+		let _hash = FUTEX_HASH.lock();
+		let mut val = word.load(Ordering::Acquire);
+		loop {
+			let owner = val & FUTEX_TID_MASK;
+			if owner == tid.0 {
+				return Err(Error::EDEADLK);
+			}
+			if owner == 0 {
+				// Free or its owner died, so take it over
+				let new = tid.0 | (val & FUTEX_WAITERS);
+				match word.compare_exchange(
+					val,
+					new,
+					Ordering::AcqRel,
+					Ordering::Acquire,
+				) {
+					Ok(_) => {
+						state.set_owner(tid);
+						return Ok(0);
+					}
+					Err(current) => {
+						val = current;
+						continue;
+					}
+				}
+			}
+			// An owner that doesn't exist can't hand the lock on
+			if !crate::process::thread_exists(Tid(owner)) {
+				return Err(Error::ESRCH);
+			}
+			if try_only {
+				return Err(Error::WouldBlock);
+			}
+
+			// Make the owner's unlock enter the kernel
+			match word.compare_exchange(
+				val,
+				val | FUTEX_WAITERS,
+				Ordering::AcqRel,
+				Ordering::Acquire,
+			) {
+				Ok(_) => {
+					state.set_owner(Tid(owner));
+					break;
+				}
+				Err(current) => val = current,
+			}
+		}
+	}
+
+	state.lock(tid, deadline)?;
+
+	let _hash = FUTEX_HASH.lock();
+	set_pi_owner(word, &state, Some(tid));
+	Ok(0)
+}
+
+/// Release a PI futex and hand it to the highest priority waiter
+pub fn futex_unlock_pi(uaddr: u64) -> Result<u64> {
+	let tid = crate::process::current_thread_tid().ok_or(Error::ESRCH)?;
+	let key = futex_key(uaddr)?;
+	let word = futex_word(uaddr)?;
+	let state = pi_state(key);
+
+	let _hash = FUTEX_HASH.lock();
+	let val = word.load(Ordering::Acquire);
+	if val & FUTEX_TID_MASK != tid.0 {
+		return Err(Error::EPERM);
+	}
+
+	if state.owner().is_none() {
+		// Taken in user space without contention
+		word.store(0, Ordering::Release);
+		return Ok(0);
+	}
+	let next = state.unlock(tid)?;
+	set_pi_owner(word, &state, next);
+	Ok(0)
+}
+
+/// Futex syscall entry point
+pub fn do_futex(
+	uaddr: u64,
+	op: i32,
+	val: u32,
+	timeout: u64,
+	uaddr2: u64,
+	val3: u32,
+) -> Result<u64> {
+	let cmd = op & FUTEX_CMD_MASK;
+	let realtime = op & FUTEX_CLOCK_REALTIME != 0;
+
+	match cmd {
+		FUTEX_WAIT | FUTEX_WAIT_BITSET => {
+			let deadline = if timeout != 0 {
+				let ts = read_timespec(timeout)?;
+				Some(if cmd == FUTEX_WAIT {
+					// FUTEX_WAIT takes a relative timeout
+					time::get_time_ns().saturating_add(ts.to_ns())
+				} else if realtime {
+					// Wall-clock deadline to boot time
+					let offset =
+						time::get_realtime().to_ns() - time::get_time_ns();
+					ts.to_ns().saturating_sub(offset)
+				} else {
+					ts.to_ns()
+				})
+			} else {
+				None
+			};
+			let bitset = if cmd == FUTEX_WAIT {
+				FUTEX_BITSET_MATCH_ANY
+			} else {
+				val3
+			};
+			futex_wait(uaddr, val, deadline, bitset)
+		}
+		FUTEX_WAKE => futex_wake(uaddr, val, FUTEX_BITSET_MATCH_ANY),
+		FUTEX_WAKE_BITSET => futex_wake(uaddr, val, val3),
+		// For requeue operations the timeout argument carries nr_requeue
+		FUTEX_REQUEUE => futex_requeue(uaddr, val, timeout as u32, uaddr2, None),
+		FUTEX_CMP_REQUEUE => futex_requeue(uaddr, val, timeout as u32, uaddr2, Some(val3)),
+		FUTEX_LOCK_PI => {
+			// The timeout is an absolute CLOCK_REALTIME deadline
+			let deadline = if timeout != 0 {
+				let offset = time::get_realtime().to_ns() - time::get_time_ns();
+				Some(read_timespec(timeout)?.to_ns().saturating_sub(offset))
+			} else {
+				None
+			};
+			futex_lock_pi(uaddr, deadline, false)
+		}
+		FUTEX_UNLOCK_PI => futex_unlock_pi(uaddr),
+		FUTEX_TRYLOCK_PI => futex_lock_pi(uaddr, None, true),
+		_ => Err(Error::ENOSYS),
+	}
+}
+
+/// Clear a thread's clear_child_tid word and wake one waiter on it
+pub fn clear_child_tid(uaddr: u64) {
+	if uaddr == 0 {
+		return;
+	}
+	if let Ok(ptr) = UserPtr::new(uaddr as *mut u8) {
+		if crate::memory::copy_to_user(ptr, &0u32.to_ne_bytes()).is_ok() {
+			let _ = futex_wake(uaddr, 1, FUTEX_BITSET_MATCH_ANY);
+		}
+	}
+}
@@ -37,6 +37,12 @@ fn init_subsystems() {
 		crate::console::write_str("      [!] IPC init failed (non-fatal)\n");
 	}
 
+	// Initialize terminals
+	crate::console::write_str("    - TTY layer\n");
+	if let Err(_e) = crate::tty::init() {
+		crate::console::write_str("      [!] TTY init failed (non-fatal)\n");
+	}
+
 	// Initialize performance monitoring
 	crate::console::write_str("    - Performance monitoring\n");
 	if let Err(_e) = crate::advanced_perf::init_performance_monitoring() {
@@ -55,6 +61,27 @@ fn init_subsystems() {
 		crate::console::write_str("      [!] Task mgmt init failed (non-fatal)\n");
 	}
 
+	// Initialize deferred interrupt work
+	crate::console::write_str("    - Softirqs and workqueues\n");
+	if let Err(_e) = crate::softirq::init() {
+		crate::console::write_str("      [!] Softirq init failed (non-fatal)\n");
+	}
+	if let Err(_e) = crate::workqueue::init() {
+		crate::console::write_str("      [!] Workqueue init failed (non-fatal)\n");
+	}
+
+	// Seed the random number generator
+	crate::console::write_str("    - Random number generator\n");
+	if let Err(_e) = crate::random::init() {
+		crate::console::write_str("      [!] Random init failed (non-fatal)\n");
+	}
+
+	// Initialize the vDSO
+	crate::console::write_str("    - vDSO\n");
+	if let Err(_e) = crate::vdso::init() {
+		crate::console::write_str("      [!] vDSO init failed (non-fatal)\n");
+	}
+
 	crate::console::write_str("[+] Subsystems initialized\n");
 }
 
@@ -79,11 +106,53 @@ pub fn main_init() -> ! {
 	crate::console::write_str("\n");
 	crate::console::write_str("[+] Kernel initialization complete\n");
 	crate::console::write_str("\n");
+	crate::sync::mark_system_running();
+
+	// Mount the root file system and hand over to user space
+	if let Err(e) = crate::memfs::init_memfs() {
+		crate::warn!("Failed to mount root file system: {:?}", e);
+	}
+	if !run_init_process() {
+		crate::warn!("No init found, falling back to the kernel shell");
+		if let Err(e) = crate::shell::init_shell() {
+			crate::warn!("Failed to start kernel shell: {:?}", e);
+		}
+	}
 
 	// Enter main kernel loop
 	main_kernel_loop()
 }
 
+/// Init binaries tried in order when no `init=` is given
+const DEFAULT_INITS: [&str; 4] = ["/sbin/init", "/etc/init", "/bin/init", "/bin/sh"];
+
+/// Start the first user process as PID 1. Returns false if no init binary
+/// exists; one that exists but fails to start is fatal.
+fn run_init_process() -> bool {
+	let requested = crate::boot::cmdline_param("init");
+	let candidates = requested
+		.iter()
+		.map(|path| path.as_str())
+		.chain(DEFAULT_INITS);
+
+	for path in candidates {
+		if crate::memfs::fs_stat(path).is_err() {
+			continue;
+		}
+		if let Err(e) = crate::fs::console_on_rootfs() {
+			crate::warn!("Unable to open an initial console: {:?}", e);
+		}
+		match crate::usermode::exec_init(path) {
+			Ok(pid) => {
+				crate::info!("Started init {} as PID {}", path, pid.0);
+				return true;
+			}
+			Err(e) => panic!("Failed to execute init {}: {:?}", path, e),
+		}
+	}
+	false
+}
+
 /// Main kernel loop with task scheduling
 fn main_kernel_loop() -> ! {
 	crate::console::write_str("Entering kernel main loop...\n");
@@ -111,12 +180,18 @@ fn main_kernel_loop() -> ! {
 			crate::working_task::cleanup_tasks();
 		}
 
+		// Run softirqs raised while they couldn't run
+		crate::softirq::do_softirq();
+
+		// Free memory retired by RCU updaters
+		crate::rcu::rcu_process_callbacks();
+
 		// Heartbeat indicator
 		if tick_count % 5_000_000 == 0 {
 			crate::console::write_str(".");
 		}
 
 		// Halt CPU to save power
-		unsafe { core::arch::asm!("hlt"); }
+		crate::timer::cpu_idle();
 	}
 }
@@ -5,7 +5,7 @@
 use alloc::{boxed::Box, collections::BTreeMap}; // Add Box import
 use core::arch::asm;
 use core::fmt;
-use core::sync::atomic::{AtomicU64, Ordering};
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 
 use crate::error::{Error, Result};
 use crate::sync::Spinlock;
@@ -356,6 +356,59 @@ pub fn disable() {
 	}
 }
 
+/// Check whether interrupts are enabled on this CPU
+pub fn are_enabled() -> bool {
+	#[cfg(target_arch = "x86_64")]
+	{
+		let rflags: u64;
+		unsafe {
+			core::arch::asm!("pushfq; pop {}", out(reg) rflags);
+		}
+		rflags & 0x200 != 0
+	}
+	#[cfg(not(target_arch = "x86_64"))]
+	{
+		true
+	}
+}
+
+/// Run `f` with interrupts disabled, restoring the previous state after -
+/// like Linux local_irq_save/local_irq_restore
+pub fn without_interrupts<R>(f: impl FnOnce() -> R) -> R {
+	let enabled = are_enabled();
+	disable();
+	let result = f();
+	if enabled {
+		enable();
+	}
+	result
+}
+
+/// Hardware interrupt handlers currently running
+static HARDIRQ_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Mark the start of a hardware interrupt handler
+pub fn irq_enter() {
+	crate::random::add_interrupt_randomness();
+	if HARDIRQ_DEPTH.fetch_add(1, Ordering::Relaxed) == 0 {
+		// Bring jiffies up to date if the CPU slept tickless
+		crate::timer::tick_irq_enter();
+	}
+}
+
+/// Mark the end of a hardware interrupt handler
+pub fn irq_exit() {
+	if HARDIRQ_DEPTH.fetch_sub(1, Ordering::Relaxed) == 1 {
+		// Leaving the outermost handler, run deferred work
+		crate::softirq::invoke_softirq();
+	}
+}
+
+/// Check whether we are running in a hardware interrupt handler
+pub fn in_irq() -> bool {
+	HARDIRQ_DEPTH.load(Ordering::Relaxed) != 0
+}
+
 /// Enable a specific interrupt line
 pub fn enable_irq(irq: u32) -> Result<()> {
 	let mut subsystem = INTERRUPT_SUBSYSTEM.lock();
@@ -27,8 +27,11 @@ pub mod arch;
 pub mod arp;
 pub mod benchmark; // Performance benchmarking
 pub mod boot;
+pub mod capability; // POSIX capabilities
 pub mod console;
+pub mod coredump; // ELF core dumps
 pub mod cpu;
+pub mod cred; // Process credentials
 pub mod device;
 pub mod device_advanced;
 pub mod diagnostics; // System diagnostics and health monitoring
@@ -37,24 +40,38 @@ pub mod drivers_init; // Driver initialization
 pub mod enhanced_scheduler; // Enhanced preemptive scheduler
 pub mod error;
 pub mod fs;
+pub mod futex; // Fast user-space mutexes
 pub mod hardware; // Hardware detection and initialization
 pub mod icmp;
 pub mod init;
 pub mod interrupt;
 pub mod ipc; // Inter-process communication
 pub mod kthread; // Kernel thread management
+pub mod loadavg; // System load average
+#[cfg(feature = "debug")]
+pub mod lockdep; // Lock dependency validator
 pub mod logging; // Kernel logging and debugging
 pub mod memfs; // In-memory file system
 pub mod memory;
 pub mod module;
 pub mod module_loader; // Dynamic module loading
+pub mod namespace; // Mount and PID namespaces
 pub mod network;
 pub mod panic;
 pub mod perf; // Performance monitoring
+pub mod posix_timers; // POSIX interval timers
 pub mod prelude;
 pub mod process;
+pub mod ptrace; // Process tracing
+pub mod rcu; // Read-copy-update
+pub mod random; // Entropy pool and CSPRNG
+pub mod resource; // Resource limits and usage accounting
+pub mod rtmutex; // Priority-inheritance mutexes
 pub mod scheduler;
+pub mod seccomp; // Syscall filtering
 pub mod shell; // Kernel shell interface
+pub mod signal;
+pub mod softirq; // Softirqs and tasklets
 pub mod stress_test; // System stress testing
 pub mod sync;
 pub mod syscall;
@@ -65,9 +82,12 @@ pub mod test_init; // Kernel initialization testing
 pub mod test_suite; // Comprehensive kernel test suite
 pub mod time;
 pub mod timer; // Timer interrupt and preemptive scheduling
+pub mod tty; // Terminals and job control
 pub mod types;
 pub mod usermode;
+pub mod vdso; // Virtual dynamic shared object
 pub mod working_task; // Working kernel task implementation // User mode program support
+pub mod workqueue; // Deferred work in kernel threads
 
 /// Kernel version information
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
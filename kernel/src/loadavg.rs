@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! System load average - Linux compatible fixed-point calculation
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::time::NSEC_PER_SEC;
+
+/// Bits of fractional precision
+pub const FSHIFT: u32 = 11;
+/// 1.0 in fixed point
+pub const FIXED_1: u64 = 1 << FSHIFT;
+
+/// Sampling interval
+pub const LOAD_FREQ_NS: u64 = 5 * NSEC_PER_SEC;
+
+/// Decay factors 1/exp(5s/1min), 1/exp(5s/5min) and 1/exp(5s/15min)
+pub(crate) const EXP_1: u64 = 1884;
+const EXP_5: u64 = 2014;
+const EXP_15: u64 = 2037;
+
+/// 1, 5 and 15 minute averages in fixed point
+static AVENRUN: [AtomicU64; 3] = [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)];
+
+/// Time of the next sample in ns since boot
+static NEXT_SAMPLE: AtomicU64 = AtomicU64::new(LOAD_FREQ_NS);
+
+/// Decay `load` towards `active`, both in fixed point
+pub(crate) fn calc_load(load: u64, exp: u64, active: u64) -> u64 {
+	let mut newload = load * exp + active * (FIXED_1 - exp);
+	if active >= load {
+		newload += FIXED_1 - 1;
+	}
+	newload / FIXED_1
+}
+
+/// Fold the number of runnable tasks into the averages. Called on every
+/// timer tick, samples once per LOAD_FREQ_NS.
+pub fn calc_global_load() {
+	let now = crate::time::get_time_ns();
+	let next = NEXT_SAMPLE.load(Ordering::Relaxed);
+	if now < next {
+		return;
+	}
+
+	// Count the run queue once and fold it in for every missed interval
+	let missed = (now - next) / LOAD_FREQ_NS + 1;
+	if NEXT_SAMPLE
+		.compare_exchange(
+			next,
+			next + missed * LOAD_FREQ_NS,
+			Ordering::Relaxed,
+			Ordering::Relaxed,
+		)
+		.is_err()
+	{
+		return;
+	}
+
+	let active = crate::scheduler::nr_running() as u64 * FIXED_1;
+	for (avg, exp) in AVENRUN.iter().zip([EXP_1, EXP_5, EXP_15]) {
+		let mut load = avg.load(Ordering::Relaxed);
+		for _ in 0..missed.min(64) {
+			load = calc_load(load, exp, active);
+		}
+		avg.store(load, Ordering::Relaxed);
+	}
+}
+
+/// Get the 1, 5 and 15 minute load averages in fixed point
+pub fn get_avenrun() -> [u64; 3] {
+	[
+		AVENRUN[0].load(Ordering::Relaxed),
+		AVENRUN[1].load(Ordering::Relaxed),
+		AVENRUN[2].load(Ordering::Relaxed),
+	]
+}
+
+/// Integer part of a fixed-point load
+pub fn load_int(load: u64) -> u64 {
+	load >> FSHIFT
+}
+
+/// Hundredths of a fixed-point load
+pub fn load_frac(load: u64) -> u64 {
+	load_int((load & (FIXED_1 - 1)) * 100)
+}
+
+/// Get the load averages as floating point numbers
+pub fn get_loadavg() -> (f32, f32, f32) {
+	let [one, five, fifteen] = get_avenrun().map(|load| load as f32 / FIXED_1 as f32);
+	(one, five, fifteen)
+}
@@ -0,0 +1,340 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Lock dependency validator - similar to Linux lockdep
+//!
+//! Every `Spinlock` belongs to a lock class, the place it was created.
+//! Taking lock B while holding lock A records the dependency A -> B. A new
+//! dependency that closes a cycle is a possible deadlock and is reported
+//! the first time the ordering is seen, whether or not it deadlocks.
+//! Classes taken both in interrupt handlers and with interrupts enabled
+//! are reported too.
+//!
+//! Lockdep turns itself off after the first report, like Linux.
+
+use alloc::{
+	collections::{BTreeMap, BTreeSet},
+	vec::Vec,
+};
+use core::panic::Location;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::sync::SpinMutex;
+
+/// Lock class - where the lock was created
+pub type LockClass = &'static Location<'static>;
+
+/// Longest dependency path searched for cycles
+const MAX_CHAIN_DEPTH: usize = 48;
+
+/// Held-lock stack of interrupt handlers
+const IRQ_CONTEXT: u32 = u32::MAX;
+
+/// Class identity, stable across duplicated Location statics
+type ClassKey = (&'static str, u32, u32);
+
+fn key(class: LockClass) -> ClassKey {
+	(class.file(), class.line(), class.column())
+}
+
+/// A lock on a held-lock stack
+#[derive(Clone, Copy)]
+struct HeldLock {
+	addr: usize,
+	class: LockClass,
+	site: &'static Location<'static>, // Where it was taken
+}
+
+/// First observation of a dependency
+struct Dependency {
+	chain: Vec<HeldLock>, // Held stack ending with the new lock
+	context: u32,
+}
+
+/// Interrupt usage of a class, with the first site of each
+#[derive(Default)]
+struct ClassUsage {
+	in_irq: Option<&'static Location<'static>>,
+	irqs_enabled: Option<&'static Location<'static>>,
+}
+
+struct LockdepState {
+	held: BTreeMap<u32, Vec<HeldLock>>,
+	deps: BTreeMap<(ClassKey, ClassKey), Dependency>,
+	forward: BTreeMap<ClassKey, BTreeSet<ClassKey>>,
+	usage: BTreeMap<ClassKey, ClassUsage>,
+}
+
+impl LockdepState {
+	const fn new() -> Self {
+		Self {
+			held: BTreeMap::new(),
+			deps: BTreeMap::new(),
+			forward: BTreeMap::new(),
+			usage: BTreeMap::new(),
+		}
+	}
+
+	/// Find a dependency path from `from` to `to`
+	fn find_path(&self, from: ClassKey, to: ClassKey) -> Option<Vec<ClassKey>> {
+		let mut path = Vec::new();
+		let mut visited = BTreeSet::new();
+		if self.search(from, to, &mut path, &mut visited) {
+			Some(path)
+		} else {
+			None
+		}
+	}
+
+	fn search(
+		&self,
+		node: ClassKey,
+		to: ClassKey,
+		path: &mut Vec<ClassKey>,
+		visited: &mut BTreeSet<ClassKey>,
+	) -> bool {
+		path.push(node);
+		if node == to {
+			return true;
+		}
+		if path.len() <= MAX_CHAIN_DEPTH && visited.insert(node) {
+			if let Some(next) = self.forward.get(&node) {
+				for &n in next {
+					if self.search(n, to, path, visited) {
+						return true;
+					}
+				}
+			}
+		}
+		path.pop();
+		false
+	}
+
+	/// Record `prev -> next`, reporting it if it closes a cycle
+	fn add_dependency(&mut self, context: u32, chain: &[HeldLock]) -> bool {
+		let (prev, next) = match chain {
+			[.., prev, next] => (key(prev.class), key(next.class)),
+			_ => return true,
+		};
+		if self.deps.contains_key(&(prev, next)) {
+			return true;
+		}
+
+		if prev == next {
+			report_header("possible recursive locking detected");
+			print_chain("taking", context, chain);
+			return false;
+		}
+
+		if let Some(path) = self.find_path(next, prev) {
+			report_header("possible circular locking dependency detected");
+			print_chain("new dependency", context, chain);
+			crate::warn!("which closes this existing dependency chain:");
+			for pair in path.windows(2) {
+				if let Some(dep) = self.deps.get(&(pair[0], pair[1])) {
+					print_chain("recorded", dep.context, &dep.chain);
+				}
+			}
+			print_scenario(chain[chain.len() - 2].class, chain[chain.len() - 1].class);
+			return false;
+		}
+
+		self.deps.insert(
+			(prev, next),
+			Dependency {
+				chain: chain.to_vec(),
+				context,
+			},
+		);
+		self.forward.entry(prev).or_default().insert(next);
+		true
+	}
+
+	/// Track interrupt usage of a class, reporting inconsistent usage
+	fn mark_usage(&mut self, lock: &HeldLock) -> bool {
+		let in_irq = crate::interrupt::in_irq();
+		let irqs_enabled = !in_irq && crate::interrupt::are_enabled();
+		let usage = self.usage.entry(key(lock.class)).or_default();
+		if in_irq && usage.in_irq.is_none() {
+			usage.in_irq = Some(lock.site);
+		} else if irqs_enabled && usage.irqs_enabled.is_none() {
+			usage.irqs_enabled = Some(lock.site);
+		} else {
+			return true;
+		}
+
+		match (usage.in_irq, usage.irqs_enabled) {
+			(Some(irq_site), Some(enabled_site)) => {
+				report_header(
+					"inconsistent {IN-HARDIRQ} -> {HARDIRQ-ON} lock usage",
+				);
+				crate::warn!("lock class created at {}", lock.class);
+				crate::warn!("  taken in an interrupt handler at {}", irq_site);
+				crate::warn!("  taken with interrupts enabled at {}", enabled_site);
+				crate::warn!("an interrupt taking it while it is held deadlocks");
+				false
+			}
+			_ => true,
+		}
+	}
+}
+
+static STATE: SpinMutex<LockdepState> = SpinMutex::new(LockdepState::new());
+
+/// Cleared after the first report
+static DEBUG_LOCKS: AtomicBool = AtomicBool::new(true);
+
+static ACQUISITIONS: AtomicU64 = AtomicU64::new(0);
+
+fn report_header(what: &str) {
+	crate::warn!("======================================================");
+	crate::warn!("WARNING: {}", what);
+	crate::warn!("------------------------------------------------------");
+}
+
+fn print_chain(title: &str, context: u32, chain: &[HeldLock]) {
+	match context {
+		IRQ_CONTEXT => crate::warn!("{} (interrupt context):", title),
+		0 => crate::warn!("{} (boot context):", title),
+		tid => crate::warn!("{} (task {}):", title, tid),
+	}
+	for (i, held) in chain.iter().enumerate() {
+		crate::warn!(
+			"  #{}: lock of class {} taken at {}",
+			i,
+			held.class,
+			held.site
+		);
+	}
+}
+
+fn print_scenario(a: LockClass, b: LockClass) {
+	crate::warn!("possible unsafe locking scenario:");
+	crate::warn!("  CPU0: lock({}) ; CPU1: lock({})", b, a);
+	crate::warn!("  CPU0: lock({}) ; CPU1: lock({})", a, b);
+	crate::warn!("  *** DEADLOCK ***");
+}
+
+/// Held-lock stack the current code runs with
+fn current_context() -> u32 {
+	if crate::interrupt::in_irq() {
+		IRQ_CONTEXT
+	} else {
+		crate::scheduler::current_tid_lockless().map_or(0, |tid| tid.0)
+	}
+}
+
+/// Check whether lockdep is still validating
+pub fn debug_locks() -> bool {
+	DEBUG_LOCKS.load(Ordering::Relaxed)
+}
+
+/// Record that the lock at `addr` is about to be taken. Called before
+/// spinning so a deadlock is reported before it hangs. A trylock can't
+/// deadlock and only joins the held stack.
+pub fn acquire(addr: usize, class: LockClass, site: &'static Location<'static>, trylock: bool) {
+	if !debug_locks() {
+		return;
+	}
+	// Locks taken by lockdep itself, e.g. for printing, aren't tracked
+	let mut state = match STATE.try_lock() {
+		Some(state) => state,
+		None => return,
+	};
+	ACQUISITIONS.fetch_add(1, Ordering::Relaxed);
+
+	let context = current_context();
+	let lock = HeldLock { addr, class, site };
+	let mut ok = state.mark_usage(&lock);
+
+	let mut chain = state.held.get(&context).cloned().unwrap_or_default();
+	chain.push(lock);
+	if ok && !trylock {
+		// Each held lock now precedes the new one
+		for i in 0..chain.len() - 1 {
+			let mut pair = Vec::with_capacity(i + 2);
+			pair.extend_from_slice(&chain[..=i]);
+			pair.push(lock);
+			if !state.add_dependency(context, &pair) {
+				ok = false;
+				break;
+			}
+		}
+	}
+	state.held.insert(context, chain);
+
+	if !ok {
+		DEBUG_LOCKS.store(false, Ordering::Relaxed);
+		crate::warn!("lockdep: turning off the locking correctness validator");
+	}
+}
+
+/// Record that the lock at `addr` was released
+pub fn release(addr: usize) {
+	if !debug_locks() {
+		return;
+	}
+	let mut state = match STATE.try_lock() {
+		Some(state) => state,
+		None => return,
+	};
+
+	// A lock can be released by another task than took it, as with the
+	// scheduler lock across a context switch
+	let context = current_context();
+	let contexts =
+		core::iter::once(context).chain(state.held.keys().copied().collect::<Vec<_>>());
+	for ctx in contexts {
+		if let Some(stack) = state.held.get_mut(&ctx) {
+			if let Some(pos) = stack.iter().rposition(|held| held.addr == addr) {
+				stack.remove(pos);
+				if stack.is_empty() {
+					state.held.remove(&ctx);
+				}
+				return;
+			}
+		}
+	}
+}
+
+/// Forget the classes and turn validation back on, so the self tests can
+/// provoke a report. Returns how many dependencies were dropped.
+pub(crate) fn reset_classes(classes: &[LockClass]) -> usize {
+	let keys: BTreeSet<ClassKey> = classes.iter().map(|&class| key(class)).collect();
+	let mut state = STATE.lock();
+	let before = state.deps.len();
+	state.deps
+		.retain(|(prev, next), _| !keys.contains(prev) && !keys.contains(next));
+	let dropped = before - state.deps.len();
+	state.forward.retain(|prev, _| !keys.contains(prev));
+	for next in state.forward.values_mut() {
+		next.retain(|class| !keys.contains(class));
+	}
+	for class in &keys {
+		state.usage.remove(class);
+	}
+	for stack in state.held.values_mut() {
+		stack.retain(|held| !keys.contains(&key(held.class)));
+	}
+	state.held.retain(|_, stack| !stack.is_empty());
+	DEBUG_LOCKS.store(true, Ordering::Relaxed);
+	dropped
+}
+
+/// Lockdep statistics
+#[derive(Debug, Clone, Copy)]
+pub struct LockdepStats {
+	pub classes: usize,
+	pub dependencies: usize,
+	pub acquisitions: u64,
+	pub enabled: bool,
+}
+
+pub fn stats() -> LockdepStats {
+	let state = STATE.lock();
+	LockdepStats {
+		classes: state.usage.len(),
+		dependencies: state.deps.len(),
+		acquisitions: ACQUISITIONS.load(Ordering::Relaxed),
+		enabled: debug_locks(),
+	}
+}
@@ -51,12 +51,18 @@ impl FileMode {
 	}
 }
 
+/// Largest file memfs holds. Files live in the kernel heap, so this is
+/// kept well below its size.
+pub const MAX_FILE_SIZE: usize = 16 * 1024 * 1024;
+
 /// In-memory file node
 #[derive(Debug)]
 pub struct MemFile {
 	pub name: String,
 	pub file_type: FileType,
 	pub mode: FileMode,
+	pub uid: u32, // Owner, root for the default files
+	pub gid: u32,
 	pub size: usize,
 	pub data: Vec<u8>,
 	pub children: BTreeMap<String, Box<MemFile>>,
@@ -69,6 +75,8 @@ impl MemFile {
 			name,
 			file_type: FileType::RegularFile,
 			mode,
+			uid: 0,
+			gid: 0,
 			size: 0,
 			data: Vec::new(),
 			children: BTreeMap::new(),
@@ -81,6 +89,8 @@ impl MemFile {
 			name,
 			file_type: FileType::Directory,
 			mode,
+			uid: 0,
+			gid: 0,
 			size: 0,
 			data: Vec::new(),
 			children: BTreeMap::new(),
@@ -111,6 +121,39 @@ impl MemFile {
 		Ok(data.len())
 	}
 
+	/// Write data to file at `offset`, filling a gap with zeros. Access is
+	/// checked when the file is opened.
+	pub fn write_at(&mut self, offset: usize, data: &[u8]) -> Result<usize> {
+		if !self.is_file() {
+			return Err(crate::error::Error::InvalidOperation);
+		}
+
+		let end = offset
+			.checked_add(data.len())
+			.filter(|end| *end <= MAX_FILE_SIZE)
+			.ok_or(crate::error::Error::EFBIG)?;
+		if end > self.data.len() {
+			self.data.resize(end, 0);
+		}
+		self.data[offset..end].copy_from_slice(data);
+		self.size = self.data.len();
+		Ok(data.len())
+	}
+
+	/// Truncate or extend file to `len` bytes
+	pub fn truncate(&mut self, len: usize) -> Result<()> {
+		if !self.is_file() {
+			return Err(crate::error::Error::InvalidOperation);
+		}
+		if len > MAX_FILE_SIZE {
+			return Err(crate::error::Error::EFBIG);
+		}
+
+		self.data.resize(len, 0);
+		self.size = len;
+		Ok(())
+	}
+
 	/// Read data from file
 	pub fn read(&self, offset: usize, buffer: &mut [u8]) -> Result<usize> {
 		if !self.mode.can_read() {
@@ -202,7 +245,8 @@ impl MemFileSystem {
 		self.root.add_child(proc_dir)?;
 
 		// Create /tmp directory
-		let tmp_dir = MemFile::new_dir("tmp".to_string(), FileMode::new(FileMode::ALL));
+		let tmp_dir =
+			MemFile::new_dir("tmp".to_string(), FileMode::new(FileMode::ALL | 0o1000));
 		self.root.add_child(tmp_dir)?;
 
 		// Create /dev directory
@@ -266,32 +310,26 @@ impl MemFileSystem {
 
 	/// Create a file
 	pub fn create_file(&mut self, path: &str, mode: FileMode) -> Result<()> {
-		let (dir_path, filename) = self.split_path(path);
-
-		if let Some(dir) = self.resolve_path_mut(&dir_path) {
-			if !dir.is_dir() {
-				return Err(crate::error::Error::InvalidOperation);
-			}
-
-			let file = MemFile::new_file(filename, mode);
-			dir.add_child(file)?;
-			Ok(())
-		} else {
-			Err(crate::error::Error::NotFound)
-		}
+		let (_, filename) = self.split_path(path);
+		self.add_node(path, MemFile::new_file(filename, mode))
 	}
 
 	/// Create a directory
 	pub fn create_dir(&mut self, path: &str, mode: FileMode) -> Result<()> {
-		let (dir_path, dirname) = self.split_path(path);
+		let (_, dirname) = self.split_path(path);
+		self.add_node(path, MemFile::new_dir(dirname, mode))
+	}
+
+	/// Put a new node at `path`
+	fn add_node(&mut self, path: &str, node: MemFile) -> Result<()> {
+		let (dir_path, _) = self.split_path(path);
 
 		if let Some(dir) = self.resolve_path_mut(&dir_path) {
 			if !dir.is_dir() {
 				return Err(crate::error::Error::InvalidOperation);
 			}
 
-			let new_dir = MemFile::new_dir(dirname, mode);
-			dir.add_child(new_dir)?;
+			dir.add_child(node)?;
 			Ok(())
 		} else {
 			Err(crate::error::Error::NotFound)
@@ -344,6 +382,28 @@ impl MemFileSystem {
 		}
 	}
 
+	/// Move a file or directory to a new path, replacing what is there
+	pub fn rename(&mut self, old_path: &str, new_path: &str) -> Result<()> {
+		let (old_dir, old_name) = self.split_path(old_path);
+		let (new_dir, new_name) = self.split_path(new_path);
+
+		match self.resolve_path(&new_dir) {
+			Some(dir) if dir.is_dir() => {}
+			Some(_) => return Err(crate::error::Error::InvalidOperation),
+			None => return Err(crate::error::Error::NotFound),
+		}
+
+		let mut file = self
+			.resolve_path_mut(&old_dir)
+			.and_then(|dir| dir.children.remove(&old_name))
+			.ok_or(crate::error::Error::NotFound)?;
+		file.name = new_name.clone();
+		if let Some(dir) = self.resolve_path_mut(&new_dir) {
+			dir.children.insert(new_name, file);
+		}
+		Ok(())
+	}
+
 	/// Get file info
 	pub fn stat(&self, path: &str) -> Result<(FileType, FileMode, usize)> {
 		if let Some(file) = self.resolve_path(path) {
@@ -353,6 +413,15 @@ impl MemFileSystem {
 		}
 	}
 
+	/// Get the owner of a file as (uid, gid)
+	pub fn owner(&self, path: &str) -> Result<(u32, u32)> {
+		if let Some(file) = self.resolve_path(path) {
+			Ok((file.uid, file.gid))
+		} else {
+			Err(crate::error::Error::NotFound)
+		}
+	}
+
 	/// Split path into directory and filename
 	fn split_path(&self, path: &str) -> (String, String) {
 		if let Some(pos) = path.rfind('/') {
@@ -404,6 +473,63 @@ pub fn fs_read(path: &str) -> Result<Vec<u8>> {
 	}
 }
 
+/// Read a whole file
+pub fn fs_read_all(path: &str) -> Result<Vec<u8>> {
+	let filesystem = FILESYSTEM.lock();
+	if let Some(ref fs) = *filesystem {
+		let (_, _, size) = fs.stat(path)?;
+		let mut buffer = vec![0u8; size];
+		let bytes_read = fs.read_file(path, 0, &mut buffer)?;
+		buffer.truncate(bytes_read);
+		Ok(buffer)
+	} else {
+		Err(crate::error::Error::NotInitialized)
+	}
+}
+
+/// Read from a file at `offset`
+pub fn fs_read_at(path: &str, offset: usize, buffer: &mut [u8]) -> Result<usize> {
+	let filesystem = FILESYSTEM.lock();
+	if let Some(ref fs) = *filesystem {
+		fs.read_file(path, offset, buffer)
+	} else {
+		Err(crate::error::Error::NotInitialized)
+	}
+}
+
+/// Write to a file at `offset`
+pub fn fs_write_at(path: &str, offset: usize, data: &[u8]) -> Result<usize> {
+	let mut filesystem = FILESYSTEM.lock();
+	if let Some(ref mut fs) = *filesystem {
+		fs.resolve_path_mut(path)
+			.ok_or(crate::error::Error::NotFound)?
+			.write_at(offset, data)
+	} else {
+		Err(crate::error::Error::NotInitialized)
+	}
+}
+
+/// Set the size of a file
+pub fn fs_truncate(path: &str, len: usize) -> Result<()> {
+	let mut filesystem = FILESYSTEM.lock();
+	if let Some(ref mut fs) = *filesystem {
+		fs.resolve_path_mut(path)
+			.ok_or(crate::error::Error::NotFound)?
+			.truncate(len)
+	} else {
+		Err(crate::error::Error::NotInitialized)
+	}
+}
+
+pub fn fs_rename(old_path: &str, new_path: &str) -> Result<()> {
+	let mut filesystem = FILESYSTEM.lock();
+	if let Some(ref mut fs) = *filesystem {
+		fs.rename(old_path, new_path)
+	} else {
+		Err(crate::error::Error::NotInitialized)
+	}
+}
+
 pub fn fs_write(path: &str, data: &[u8]) -> Result<usize> {
 	let mut filesystem = FILESYSTEM.lock();
 	if let Some(ref mut fs) = *filesystem {
@@ -422,6 +548,32 @@ pub fn fs_create_file(path: &str) -> Result<()> {
 	}
 }
 
+/// Create a file with the given permission bits and owner
+pub fn fs_create_file_mode(path: &str, mode: u32, uid: u32, gid: u32) -> Result<()> {
+	let mut filesystem = FILESYSTEM.lock();
+	if let Some(ref mut fs) = *filesystem {
+		let (_, filename) = fs.split_path(path);
+		let mut file = MemFile::new_file(filename, FileMode::new(mode));
+		(file.uid, file.gid) = (uid, gid);
+		fs.add_node(path, file)
+	} else {
+		Err(crate::error::Error::NotInitialized)
+	}
+}
+
+/// Create a directory with the given permission bits and owner
+pub fn fs_create_dir_mode(path: &str, mode: u32, uid: u32, gid: u32) -> Result<()> {
+	let mut filesystem = FILESYSTEM.lock();
+	if let Some(ref mut fs) = *filesystem {
+		let (_, dirname) = fs.split_path(path);
+		let mut dir = MemFile::new_dir(dirname, FileMode::new(mode));
+		(dir.uid, dir.gid) = (uid, gid);
+		fs.add_node(path, dir)
+	} else {
+		Err(crate::error::Error::NotInitialized)
+	}
+}
+
 pub fn fs_create_dir(path: &str) -> Result<()> {
 	let mut filesystem = FILESYSTEM.lock();
 	if let Some(ref mut fs) = *filesystem {
@@ -449,6 +601,16 @@ pub fn fs_stat(path: &str) -> Result<(FileType, FileMode, usize)> {
 	}
 }
 
+/// Get the owner of a file as (uid, gid)
+pub fn fs_owner(path: &str) -> Result<(u32, u32)> {
+	let filesystem = FILESYSTEM.lock();
+	if let Some(ref fs) = *filesystem {
+		fs.owner(path)
+	} else {
+		Err(crate::error::Error::NotInitialized)
+	}
+}
+
 /// File system statistics for diagnostics
 #[derive(Debug, Clone)]
 pub struct FileSystemStats {
@@ -214,6 +214,49 @@ pub fn virt_to_phys(virt: VirtAddr) -> Result<PhysAddr> {
 	Ok(PhysAddr::new(virt.as_usize()))
 }
 
+/// Physical memory mapped one to one by the boot page tables
+const IDENTITY_MAPPED: usize = 1 << 30;
+
+/// Copy between `buf` and the user memory of a process at `addr`, which
+/// must lie within one of its `mappings`
+pub fn access_process_vm(
+	mappings: &[VmaArea],
+	addr: VirtAddr,
+	buf: &mut [u8],
+	write: bool,
+) -> Result<()> {
+	let end = addr
+		.as_usize()
+		.checked_add(buf.len())
+		.ok_or(Error::EFAULT)?;
+	let mapped = mappings
+		.iter()
+		.any(|vma| vma.vm_start <= addr && end <= vma.vm_end.as_usize());
+	if !mapped {
+		return Err(Error::EFAULT);
+	}
+
+	let mut done = 0;
+	while done < buf.len() {
+		let vaddr = addr + done;
+		let len = (4096 - vaddr.as_usize() % 4096).min(buf.len() - done);
+		let paddr = virt_to_phys(vaddr)?.as_usize();
+		if paddr + len > IDENTITY_MAPPED {
+			return Err(Error::EFAULT);
+		}
+		let chunk = buf[done..done + len].as_mut_ptr();
+		unsafe {
+			if write {
+				core::ptr::copy_nonoverlapping(chunk, paddr as *mut u8, len);
+			} else {
+				core::ptr::copy_nonoverlapping(paddr as *const u8, chunk, len);
+			}
+		}
+		done += len;
+	}
+	Ok(())
+}
+
 /// Convert physical address to virtual address
 pub fn phys_to_virt(phys: PhysAddr) -> Result<VirtAddr> {
 	// TODO: implement address translation
@@ -488,6 +531,11 @@ pub fn copy_string_from_user(user_ptr: UserPtr<u8>, max_len: usize) -> Result<St
 	String::from_utf8(buffer).map_err(|_| Error::InvalidArgument)
 }
 
+/// Memory protection bits of a mapping, as passed to mmap
+pub const PROT_READ: u32 = 0x1;
+pub const PROT_WRITE: u32 = 0x2;
+pub const PROT_EXEC: u32 = 0x4;
+
 /// Memory mapping area structure
 #[derive(Debug, Clone)]
 pub struct VmaArea {
@@ -183,12 +183,14 @@ pub fn init_modules() -> Result<()> {
 
 /// Register a module
 pub fn register_module(module: Module) -> Result<()> {
+	crate::capability::require(crate::capability::CAP_SYS_MODULE)?;
 	let mut subsystem = MODULE_SUBSYSTEM.lock();
 	subsystem.register_module(module)
 }
 
 /// Unload a module
 pub fn unload_module(name: &str) -> Result<()> {
+	crate::capability::require(crate::capability::CAP_SYS_MODULE)?;
 	let mut subsystem = MODULE_SUBSYSTEM.lock();
 	subsystem.unload_module(name)
 }
@@ -0,0 +1,143 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Mount and PID namespaces - Linux compatible
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+
+use crate::error::{Error, Result};
+use crate::fs::mount::MountNamespace;
+use crate::sync::{Arc, SpinMutex};
+use crate::types::Pid;
+
+/// Maximum nesting depth of PID namespaces
+pub const MAX_PID_NS_LEVEL: usize = 32;
+
+/// Namespace IDs start where Linux starts its namespace inode numbers
+static NEXT_NS_ID: AtomicU64 = AtomicU64::new(0xF000_0000);
+
+/// Allocate a unique namespace ID
+pub fn alloc_ns_id() -> u64 {
+	NEXT_NS_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// PID namespace - similar to Linux struct pid_namespace
+#[derive(Debug)]
+pub struct PidNamespace {
+	pub id: u64,
+	pub level: usize,
+	pub parent: Option<Arc<PidNamespace>>,
+	next_nr: AtomicU32,
+	reaper: SpinMutex<Option<Pid>>, // Global PID of the namespace's init
+	dead: AtomicBool,               // Init has exited
+}
+
+impl PidNamespace {
+	fn new_root() -> Self {
+		Self {
+			id: alloc_ns_id(),
+			level: 0,
+			parent: None,
+			next_nr: AtomicU32::new(1),
+			reaper: SpinMutex::new(Some(Pid(1))),
+			dead: AtomicBool::new(false),
+		}
+	}
+
+	/// Create a namespace nested inside this one
+	pub fn new_child(self: &Arc<Self>) -> Result<Arc<Self>> {
+		if self.level + 1 >= MAX_PID_NS_LEVEL {
+			return Err(Error::ENOSPC);
+		}
+		Ok(Arc::new(Self {
+			id: alloc_ns_id(),
+			level: self.level + 1,
+			parent: Some(self.clone()),
+			next_nr: AtomicU32::new(1),
+			reaper: SpinMutex::new(None),
+			dead: AtomicBool::new(false),
+		}))
+	}
+
+	/// Get the ancestor at `level` (or self)
+	pub fn ancestor(self: &Arc<Self>, level: usize) -> Option<Arc<Self>> {
+		let mut ns = self.clone();
+		while ns.level > level {
+			ns = ns.parent.clone()?;
+		}
+		(ns.level == level).then_some(ns)
+	}
+
+	/// Check whether this namespace is `other` or one of its descendants
+	pub fn is_within(self: &Arc<Self>, other: &PidNamespace) -> bool {
+		self.ancestor(other.level)
+			.map_or(false, |ns| ns.id == other.id)
+	}
+
+	/// Global PID of the namespace's init process
+	pub fn reaper(&self) -> Option<Pid> {
+		*self.reaper.lock()
+	}
+
+	/// Check whether the namespace's init has exited
+	pub fn is_dead(&self) -> bool {
+		self.dead.load(Ordering::Acquire)
+	}
+
+	/// Mark the namespace dead after its init exited
+	pub fn set_dead(&self) {
+		self.dead.store(true, Ordering::Release);
+	}
+
+	/// Allocate the PID numbers of a new process, one per level from the
+	/// root namespace down to this one. Level 0 uses the global PID. The
+	/// first process of a namespace gets number 1 and becomes its init.
+	pub fn alloc_numbers(self: &Arc<Self>, global: Pid) -> Result<Vec<u32>> {
+		if self.is_dead() {
+			return Err(Error::ENOMEM);
+		}
+
+		let mut numbers = alloc::vec![0; self.level + 1];
+		let mut ns = Some(self.clone());
+		while let Some(current) = ns {
+			numbers[current.level] = if current.level == 0 {
+				global.0
+			} else {
+				let nr = current.next_nr.fetch_add(1, Ordering::Relaxed);
+				if nr == 1 {
+					*current.reaper.lock() = Some(global);
+				}
+				nr
+			};
+			ns = current.parent.clone();
+		}
+		Ok(numbers)
+	}
+}
+
+/// Global initial PID namespace
+static INIT_PID_NS: spin::once::Once<Arc<PidNamespace>> = spin::once::Once::new();
+
+/// Get the initial PID namespace
+pub fn init_pid_ns() -> Arc<PidNamespace> {
+	INIT_PID_NS
+		.call_once(|| Arc::new(PidNamespace::new_root()))
+		.clone()
+}
+
+/// Namespaces of a process - similar to Linux struct nsproxy
+#[derive(Debug, Clone)]
+pub struct NsProxy {
+	pub mnt_ns: Arc<SpinMutex<MountNamespace>>,
+	pub pid_ns_for_children: Arc<PidNamespace>, // Used by fork and clone
+}
+
+impl NsProxy {
+	/// Namespaces of processes started by the kernel
+	pub fn init() -> Self {
+		Self {
+			mnt_ns: crate::fs::mount::get_init_ns(),
+			pid_ns_for_children: init_pid_ns(),
+		}
+	}
+}
@@ -12,6 +12,7 @@ use alloc::{
 use core::fmt;
 
 use crate::error::{Error, Result};
+use crate::rcu::RcuCell;
 use crate::sync::Spinlock;
 
 /// Network protocol types
@@ -321,7 +322,6 @@ struct PendingArpRequest {
 pub struct NetworkStack {
 	interfaces: BTreeMap<String, Box<dyn NetworkInterface>>,
 	interface_stats: BTreeMap<String, InterfaceStats>,
-	routing_table: Vec<RouteEntry>,
 	arp_table: BTreeMap<Ipv4Address, MacAddress>,
 	pending_arp_requests: Vec<PendingArpRequest>,
 }
@@ -341,7 +341,6 @@ impl NetworkStack {
 		Self {
 			interfaces: BTreeMap::new(),
 			interface_stats: BTreeMap::new(),
-			routing_table: Vec::new(),
 			arp_table: BTreeMap::new(),
 			pending_arp_requests: Vec::new(),
 		}
@@ -378,22 +377,15 @@ impl NetworkStack {
 	}
 
 	pub fn add_route(&mut self, route: RouteEntry) {
-		self.routing_table.push(route);
-		// Sort by metric (lower is better)
-		self.routing_table.sort_by_key(|r| r.metric);
+		routing_table().update(|table| {
+			table.push(route);
+			// Sort by metric (lower is better)
+			table.sort_by_key(|r| r.metric);
+		});
 	}
 
-	pub fn find_route(&self, dest: Ipv4Address) -> Option<&RouteEntry> {
-		for route in &self.routing_table {
-			let dest_u32 = dest.to_u32();
-			let route_dest = route.destination.to_u32();
-			let netmask = route.netmask.to_u32();
-
-			if (dest_u32 & netmask) == (route_dest & netmask) {
-				return Some(route);
-			}
-		}
-		None
+	pub fn find_route(&self, dest: Ipv4Address) -> Option<RouteEntry> {
+		find_route(dest)
 	}
 
 	pub fn add_arp_entry(&mut self, ip: Ipv4Address, mac: MacAddress) {
@@ -415,11 +407,8 @@ impl NetworkStack {
 		self.pending_arp_requests
 			.retain(|req| now - req.timestamp < 10_000_000_000); // 10 seconds
 
-		// Find route (borrow self immutably)
-		let route = {
-			let route = self.find_route(dest).ok_or(Error::NetworkUnreachable)?;
-			route.clone() // Clone to avoid borrowing issues
-		};
+		// Find route
+		let route = self.find_route(dest).ok_or(Error::NetworkUnreachable)?;
 
 		// Look up MAC address first (borrow self immutably)
 		let dest_mac = if let Some(gateway) = route.gateway {
@@ -507,6 +496,7 @@ impl NetworkStack {
 					stats.packets_received += 1;
 					stats.bytes_received += packet.len() as u64;
 				}
+				crate::random::add_network_randomness(packet.data());
 				received_packets.push((name.clone(), packet));
 			}
 		}
@@ -621,6 +611,26 @@ impl NetworkStack {
 /// Global network stack
 pub static NETWORK_STACK: Spinlock<Option<NetworkStack>> = Spinlock::new(None);
 
+/// Routing table, read without locking
+static ROUTING_TABLE: spin::once::Once<RcuCell<Vec<RouteEntry>>> = spin::once::Once::new();
+
+fn routing_table() -> &'static RcuCell<Vec<RouteEntry>> {
+	ROUTING_TABLE.call_once(|| RcuCell::new(Vec::new()))
+}
+
+/// Find the route with the lowest metric for a destination
+pub fn find_route(dest: Ipv4Address) -> Option<RouteEntry> {
+	let dest_u32 = dest.to_u32();
+	routing_table().read_with(|table| {
+		table.iter()
+			.find(|route| {
+				let netmask = route.netmask.to_u32();
+				(dest_u32 & netmask) == (route.destination.to_u32() & netmask)
+			})
+			.cloned()
+	})
+}
+
 /// Initialize network stack
 pub fn init() -> Result<()> {
 	let mut stack = NETWORK_STACK.lock();
@@ -684,6 +694,11 @@ pub mod utils {
 
 /// Send a packet
 pub fn send_packet(dest: Ipv4Address, data: &[u8], protocol: ProtocolType) -> Result<()> {
+	// Anything below the transport layer is raw network access
+	if !matches!(protocol, ProtocolType::TCP | ProtocolType::UDP) {
+		crate::capability::require(crate::capability::CAP_NET_RAW)?;
+	}
+
 	let mut stack_opt = NETWORK_STACK.lock();
 	if let Some(ref mut stack) = *stack_opt {
 		stack.send_packet(dest, data, protocol)
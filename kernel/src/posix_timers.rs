@@ -0,0 +1,337 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! POSIX interval timers - timer_create, setitimer and alarm
+//!
+//! Timers on the realtime and monotonic clocks go on the timer wheel and
+//! send their signal when it expires them. Timers on CPU-time clocks are
+//! checked on every tick charged to their process.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::error::{Error, Result};
+use crate::interrupt::without_interrupts;
+use crate::resource::Rusage;
+use crate::signal;
+use crate::sync::Spinlock;
+use crate::time::{HrTimer, HrTimerBase, TimeSpec};
+use crate::types::{Pid, Tid};
+
+/// Interval timers of setitimer
+pub const ITIMER_REAL: i32 = 0;
+pub const ITIMER_VIRTUAL: i32 = 1;
+pub const ITIMER_PROF: i32 = 2;
+
+/// How a timer notifies its process (sigev_notify)
+pub const SIGEV_SIGNAL: i32 = 0;
+pub const SIGEV_NONE: i32 = 1;
+pub const SIGEV_THREAD_ID: i32 = 4;
+
+/// Timers of a process: the three interval timers and the ones made by
+/// timer_create, by ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TimerKey {
+	Itimer(i32),
+	Posix(i32),
+}
+
+/// What a timer measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimerClock {
+	Wall(HrTimerBase),
+	ProcessCpu,  // User and system time of the process
+	ProcessUser, // User time of the process
+	ThreadCpu(Tid),
+}
+
+/// An interval timer
+#[derive(Debug, Clone)]
+struct PosixTimer {
+	clock: TimerClock,
+	signal: i32,   // Sent on expiry, 0 for none
+	expires: u64,  // Next expiry in clock ns, 0 when disarmed
+	interval: u64, // Period, 0 for a one-shot timer
+	overrun: u64,  // Expirations missed when the last signal was sent
+}
+
+impl PosixTimer {
+	/// Count the expirations up to `now`. Returns whether there were any.
+	fn update(&mut self, now: u64) -> bool {
+		let (expired, next) = forward_timer(self.expires, self.interval, now);
+		if expired == 0 {
+			return false;
+		}
+		self.overrun = expired - 1;
+		self.expires = next;
+		true
+	}
+}
+
+/// Largest expiry and interval of a timer, like Linux's KTIME_MAX
+pub const TIMER_MAX_NS: u64 = i64::MAX as u64;
+
+/// Expirations of a timer due at `expires` and then every `interval` ns,
+/// up to `now`. Returns how many there were and the next expiry, which is
+/// 0 once the timer is disarmed: after a one-shot expiry, or when the next
+/// one would be past TIMER_MAX_NS.
+pub fn forward_timer(expires: u64, interval: u64, now: u64) -> (u64, u64) {
+	if expires == 0 || now < expires {
+		return (0, expires);
+	}
+	let periods = match (now - expires).checked_div(interval) {
+		Some(elapsed) => elapsed + 1,
+		None => return (1, 0),
+	};
+	let next = periods
+		.checked_mul(interval)
+		.and_then(|delta| expires.checked_add(delta))
+		.filter(|&next| next <= TIMER_MAX_NS)
+		.unwrap_or(0);
+	(periods, next)
+}
+
+/// Every armed or created timer. The timer interrupt takes this lock, so
+/// other code holds it with interrupts off.
+static TIMERS: Spinlock<BTreeMap<(Pid, TimerKey), PosixTimer>> = Spinlock::new(BTreeMap::new());
+
+/// CPU time of a usage, user and system
+fn cpu_ns(usage: &Rusage) -> u64 {
+	usage.utime + usage.stime
+}
+
+/// Current time of `clock` for the process `pid`
+fn clock_now(pid: Pid, clock: TimerClock) -> Result<u64> {
+	match clock {
+		TimerClock::Wall(base) => Ok(crate::time::clock_now(base).to_ns()),
+		TimerClock::ThreadCpu(tid) => {
+			crate::process::with_thread(pid, Some(tid), |t| cpu_ns(&t.usage()))
+		}
+		TimerClock::ProcessCpu | TimerClock::ProcessUser => {
+			let usage = crate::process::find_process(pid)
+				.ok_or(Error::ESRCH)?
+				.usage();
+			Ok(match clock {
+				TimerClock::ProcessUser => usage.utime,
+				_ => cpu_ns(&usage),
+			})
+		}
+	}
+}
+
+/// Put a timer wheel timer for a wall-clock expiry
+fn queue_timer(base: HrTimerBase, expires: u64) {
+	let mut timer = HrTimer::new(base);
+	timer.expires = TimeSpec::from_ns(expires);
+	timer.function = Some(posix_timer_fn);
+	crate::time::add_timer(timer);
+}
+
+/// Timer wheel function of every wall-clock timer
+fn posix_timer_fn() {
+	let mut signals = Vec::new();
+	let mut requeue = Vec::new();
+	without_interrupts(|| {
+		for (&(pid, _), timer) in TIMERS.lock().iter_mut() {
+			let base = match timer.clock {
+				TimerClock::Wall(base) => base,
+				_ => continue,
+			};
+			if !timer.update(crate::time::clock_now(base).to_ns()) {
+				continue;
+			}
+			if timer.signal != 0 {
+				signals.push((pid, timer.signal));
+			}
+			if timer.expires != 0 {
+				requeue.push((base, timer.expires));
+			}
+		}
+	});
+
+	for (pid, signal) in signals {
+		let _ = crate::process::send_signal(pid, signal);
+	}
+	for (base, expires) in requeue {
+		queue_timer(base, expires);
+	}
+}
+
+/// Expire the CPU-time timers of `pid` on a timer tick. `tid` is the
+/// thread that ran, with `thread` its usage. Called from the timer
+/// interrupt.
+pub fn cpu_tick(pid: Pid, process: &Rusage, tid: Tid, thread: &Rusage) {
+	let mut signals = Vec::new();
+	{
+		let mut timers = TIMERS.lock();
+		let range = (pid, TimerKey::Itimer(i32::MIN))..=(pid, TimerKey::Posix(i32::MAX));
+		for (_, timer) in timers.range_mut(range) {
+			let now = match timer.clock {
+				TimerClock::Wall(_) => continue,
+				TimerClock::ProcessCpu => cpu_ns(process),
+				TimerClock::ProcessUser => process.utime,
+				TimerClock::ThreadCpu(id) if id == tid => cpu_ns(thread),
+				TimerClock::ThreadCpu(_) => continue,
+			};
+			if timer.update(now) && timer.signal != 0 {
+				signals.push(timer.signal);
+			}
+		}
+	}
+	for signal in signals {
+		let _ = crate::process::send_signal(pid, signal);
+	}
+}
+
+/// Create a timer for the process `pid` on `clock` that sends `signal`,
+/// or nothing if that is 0. `tid` is the creating thread, whose CPU time
+/// CLOCK_THREAD_CPUTIME_ID measures. Returns the timer ID.
+pub fn timer_create(pid: Pid, tid: Tid, clock: i32, signal: i32) -> Result<i32> {
+	use crate::time::{CLOCK_PROCESS_CPUTIME_ID, CLOCK_THREAD_CPUTIME_ID};
+
+	let clock = match clock {
+		CLOCK_PROCESS_CPUTIME_ID => TimerClock::ProcessCpu,
+		CLOCK_THREAD_CPUTIME_ID => TimerClock::ThreadCpu(tid),
+		_ => TimerClock::Wall(crate::time::clock_base(clock).ok_or(Error::EINVAL)?),
+	};
+	if signal != 0 {
+		signal::validate(signal)?;
+	}
+	let limit = crate::resource::rlimit(crate::resource::RLIMIT_SIGPENDING);
+
+	without_interrupts(|| {
+		let mut timers = TIMERS.lock();
+		let ids = (pid, TimerKey::Posix(0))..=(pid, TimerKey::Posix(i32::MAX));
+		if timers.range(ids.clone()).count() as u64 >= limit.min(i32::MAX as u64) {
+			return Err(Error::WouldBlock);
+		}
+		// The lowest free ID
+		let mut id = 0;
+		for (&(_, key), _) in timers.range(ids) {
+			if key != TimerKey::Posix(id) {
+				break;
+			}
+			id += 1;
+		}
+		let timer = PosixTimer {
+			clock,
+			signal,
+			expires: 0,
+			interval: 0,
+			overrun: 0,
+		};
+		timers.insert((pid, TimerKey::Posix(id)), timer);
+		Ok(id)
+	})
+}
+
+/// The interval timer `which` of setitimer, as a disarmed timer
+fn itimer(which: i32) -> Result<PosixTimer> {
+	let (clock, signal) = match which {
+		ITIMER_REAL => (TimerClock::Wall(HrTimerBase::Monotonic), signal::SIGALRM),
+		ITIMER_VIRTUAL => (TimerClock::ProcessUser, signal::SIGVTALRM),
+		ITIMER_PROF => (TimerClock::ProcessCpu, signal::SIGPROF),
+		_ => return Err(Error::EINVAL),
+	};
+	Ok(PosixTimer {
+		clock,
+		signal,
+		expires: 0,
+		interval: 0,
+		overrun: 0,
+	})
+}
+
+/// The clock of a timer. Interval timers exist before they are first set.
+fn timer_clock(pid: Pid, key: TimerKey) -> Result<TimerClock> {
+	let timer = without_interrupts(|| TIMERS.lock().get(&(pid, key)).map(|t| t.clock));
+	match (timer, key) {
+		(Some(clock), _) => Ok(clock),
+		(None, TimerKey::Itimer(which)) => Ok(itimer(which)?.clock),
+		(None, TimerKey::Posix(_)) => Err(Error::EINVAL),
+	}
+}
+
+/// Arm a timer to expire after `value` ns, or at `value` on its clock
+/// with `absolute`, and then every `interval` ns. A zero `value` disarms
+/// it. Times past TIMER_MAX_NS are clamped to it. Returns the old
+/// (interval, time left).
+pub fn timer_settime(
+	pid: Pid,
+	key: TimerKey,
+	absolute: bool,
+	interval: u64,
+	value: u64,
+) -> Result<(u64, u64)> {
+	let clock = timer_clock(pid, key)?;
+	let now = clock_now(pid, clock)?;
+
+	let (old, expires) = without_interrupts(|| -> Result<_> {
+		let mut timers = TIMERS.lock();
+		if let TimerKey::Itimer(which) = key {
+			timers.entry((pid, key)).or_insert(itimer(which)?);
+		}
+		let timer = timers.get_mut(&(pid, key)).ok_or(Error::EINVAL)?;
+		let old = (timer.interval, timer.expires.saturating_sub(now));
+		timer.interval = interval.min(TIMER_MAX_NS);
+		timer.overrun = 0;
+		timer.expires = match value {
+			0 => 0,
+			_ if absolute => value.max(1),
+			_ => now.saturating_add(value),
+		}
+		.min(TIMER_MAX_NS);
+		Ok((old, timer.expires))
+	})?;
+
+	if let TimerClock::Wall(base) = clock {
+		if expires != 0 {
+			queue_timer(base, expires);
+		}
+	}
+	Ok(old)
+}
+
+/// The (interval, time left) of a timer, time left 0 if disarmed
+pub fn timer_gettime(pid: Pid, key: TimerKey) -> Result<(u64, u64)> {
+	let clock = timer_clock(pid, key)?;
+	let now = clock_now(pid, clock)?;
+	without_interrupts(|| match TIMERS.lock().get(&(pid, key)) {
+		Some(timer) => Ok((timer.interval, timer.expires.saturating_sub(now))),
+		None if matches!(key, TimerKey::Itimer(_)) => Ok((0, 0)),
+		None => Err(Error::EINVAL),
+	})
+}
+
+/// Expirations that the last signal of a timer stood for, beyond the
+/// first
+pub fn timer_getoverrun(pid: Pid, id: i32) -> Result<u64> {
+	without_interrupts(|| {
+		let timers = TIMERS.lock();
+		let timer = timers
+			.get(&(pid, TimerKey::Posix(id)))
+			.ok_or(Error::EINVAL)?;
+		Ok(timer.overrun.min(i32::MAX as u64))
+	})
+}
+
+/// Delete a timer made by timer_create
+pub fn timer_delete(pid: Pid, id: i32) -> Result<()> {
+	without_interrupts(|| TIMERS.lock().remove(&(pid, TimerKey::Posix(id))))
+		.map(|_| ())
+		.ok_or(Error::EINVAL)
+}
+
+/// Drop the timers of a process that exited
+pub fn exit_timers(pid: Pid) {
+	without_interrupts(|| TIMERS.lock().retain(|&(owner, _), _| owner != pid));
+}
+
+/// Drop the timer_create timers of a process on exec. Interval timers
+/// carry over.
+pub fn exec_timers(pid: Pid) {
+	without_interrupts(|| {
+		TIMERS.lock().retain(|&(owner, key), _| {
+			owner != pid || matches!(key, TimerKey::Itimer(_))
+		})
+	});
+}
@@ -7,12 +7,18 @@ use alloc::{
 	string::{String, ToString},
 	vec::Vec,
 };
-use core::sync::atomic::{AtomicU32, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
 use crate::arch::x86_64::context::Context;
+use crate::cred::Credentials;
 use crate::error::{Error, Result};
-use crate::memory::VirtAddr;
-use crate::sync::Spinlock;
+use crate::memory::{VirtAddr, VmaArea};
+use crate::namespace::{NsProxy, PidNamespace};
+use crate::rcu::RcuCell;
+use crate::resource::{RLimits, Rusage, RLIMIT_AS, RLIMIT_CPU, RLIMIT_NPROC, RLIM_INFINITY};
+use crate::seccomp::Seccomp;
+use crate::signal::{self, DefaultAction, SigAction, SigSet};
+use crate::sync::{Arc, SpinMutex, Spinlock, WaitQueue};
 use crate::types::{Gid, Pid, Tid, Uid};
 
 /// Process state - compatible with Linux kernel
@@ -25,20 +31,75 @@ pub enum ProcessState {
 	Dead,
 }
 
+/// Flags for clone() - Linux compatible
+pub mod clone_flags {
+	pub const CLONE_VM: u64 = 0x00000100;
+	pub const CLONE_FS: u64 = 0x00000200;
+	pub const CLONE_FILES: u64 = 0x00000400;
+	pub const CLONE_SIGHAND: u64 = 0x00000800;
+	pub const CLONE_VFORK: u64 = 0x00004000;
+	pub const CLONE_THREAD: u64 = 0x00010000;
+	pub const CLONE_NEWNS: u64 = 0x00020000;
+	pub const CLONE_SETTLS: u64 = 0x00080000;
+	pub const CLONE_PARENT_SETTID: u64 = 0x00100000;
+	pub const CLONE_CHILD_CLEARTID: u64 = 0x00200000;
+	pub const CLONE_CHILD_SETTID: u64 = 0x01000000;
+	pub const CLONE_NEWPID: u64 = 0x20000000;
+}
+
+/// Filesystem context (root and working directory), shared with CLONE_FS
+#[derive(Debug, Clone)]
+pub struct FsContext {
+	pub root: String,
+	pub cwd: String,
+}
+
+impl FsContext {
+	pub fn new() -> Self {
+		Self {
+			root: "/".to_string(),
+			cwd: "/".to_string(),
+		}
+	}
+}
+
 /// Process structure - similar to Linux task_struct
 #[derive(Debug, Clone)]
 pub struct Process {
 	pub pid: Pid,
 	pub parent: Option<Pid>,
 	pub state: ProcessState,
-	pub uid: Uid,
-	pub gid: Gid,
+	pub cred: Credentials,
+	pub pgid: Pid,           // Process group ID
+	pub sid: Pid,            // Session ID
+	pub ctty: Option<usize>, // Controlling terminal (index into tty table)
 	pub name: String,
 	pub threads: Vec<Thread>,
 	pub memory_map: Option<VirtAddr>, // Points to mm_struct equivalent
-	pub files: Vec<u32>,              // File descriptor table
+	pub files: Arc<SpinMutex<Vec<u32>>>, // File descriptor table
+	pub fs: Arc<SpinMutex<FsContext>>, // Root and working directory
 	pub signal_pending: bool,
+	pub pending_signals: SigSet,
+	pub blocked_signals: SigSet, // Held pending instead of acted on
+	pub sigactions: Vec<SigAction>, // Disposition of signal N at N-1
+	pub signal_queue: Arc<WaitQueue>, // Woken when a signal is queued
 	pub exit_code: i32,
+	pub wait_status: i32, // Exit status reported to wait()
+	pub nsproxy: NsProxy,
+	pub pid_ns: Arc<PidNamespace>, // Namespace the process is in
+	pub ns_pids: Vec<u32>,         // PID numbers, indexed by namespace level
+	pub rlimits: RLimits,
+	pub exited_usage: Rusage,   // Usage of threads that already exited
+	pub children_usage: Rusage, // Usage of exited children
+	pub total_vm: u64,          // Mapped address space in bytes
+	pub hiwater_vm: u64,        // Peak of total_vm
+	pub xcpu_sent: Option<u64>, // CPU second of the last SIGXCPU
+	pub mappings: Vec<VmaArea>, // Mapped regions of the address space
+	pub no_new_privs: bool,     // Exec can't gain privileges
+	pub seccomp: Seccomp,       // Syscall filters
+	pub vdso_base: Option<VirtAddr>, // Where the vDSO is mapped
+	pub auxv: Vec<(u64, u64)>,  // Auxiliary vector given at exec
+	pub dumpable: bool,         // Cleared when credentials change
 }
 
 impl Process {
@@ -47,14 +108,37 @@ impl Process {
 			pid,
 			parent: None,
 			state: ProcessState::Running,
-			uid,
-			gid,
+			cred: Credentials::new(uid, gid),
+			pgid: pid,
+			sid: pid,
+			ctty: None,
 			name,
 			threads: Vec::new(),
 			memory_map: None,
-			files: Vec::new(),
+			files: Arc::new(SpinMutex::new(Vec::new())),
+			fs: Arc::new(SpinMutex::new(FsContext::new())),
 			signal_pending: false,
+			pending_signals: SigSet::empty(),
+			blocked_signals: SigSet::empty(),
+			sigactions: alloc::vec![SigAction::default(); signal::NSIG as usize],
+			signal_queue: Arc::new(WaitQueue::new()),
 			exit_code: 0,
+			wait_status: 0,
+			nsproxy: NsProxy::init(),
+			pid_ns: crate::namespace::init_pid_ns(),
+			ns_pids: alloc::vec![pid.0],
+			rlimits: RLimits::new(),
+			exited_usage: Rusage::default(),
+			children_usage: Rusage::default(),
+			total_vm: 0,
+			hiwater_vm: 0,
+			xcpu_sent: None,
+			mappings: Vec::new(),
+			no_new_privs: false,
+			seccomp: Seccomp::default(),
+			vdso_base: None,
+			auxv: Vec::new(),
+			dumpable: true,
 		}
 	}
 
@@ -78,16 +162,63 @@ impl Process {
 		self.state == ProcessState::Running
 	}
 
+	/// PID number of this process in its own namespace
+	pub fn vnr(&self) -> u32 {
+		self.ns_pids[self.pid_ns.level]
+	}
+
+	/// PID number of this process as seen from `ns`, if visible there
+	pub fn pid_nr_ns(&self, ns: &PidNamespace) -> Option<u32> {
+		if self.pid_ns.is_within(ns) {
+			Some(self.ns_pids[ns.level])
+		} else {
+			None
+		}
+	}
+
+	/// Resource usage of all live and exited threads
+	pub fn usage(&self) -> Rusage {
+		let mut usage = self.exited_usage;
+		for thread in &self.threads {
+			usage.add(&thread.usage());
+		}
+		usage.maxrss = usage.maxrss.max(self.hiwater_vm / 1024);
+		usage
+	}
+
 	/// Fork the current process (create a copy)
 	pub fn fork(&self) -> Result<Process> {
+		self.fork_with(self.nsproxy.clone())
+	}
+
+	/// Fork into the given namespaces. The child joins the PID namespace
+	/// `nsproxy.pid_ns_for_children`.
+	pub fn fork_with(&self, nsproxy: NsProxy) -> Result<Process> {
 		let new_pid = allocate_pid();
+		let pid_ns = nsproxy.pid_ns_for_children.clone();
 		let mut child = self.clone();
+		child.ns_pids = pid_ns.alloc_numbers(new_pid)?;
+		child.pid_ns = pid_ns;
+		child.nsproxy = nsproxy;
 		child.pid = new_pid;
 		child.parent = Some(self.pid);
 		child.state = ProcessState::Running;
+		// Process group, session and controlling terminal are inherited
+		child.pending_signals = SigSet::empty();
+		child.signal_pending = false;
+		child.signal_queue = Arc::new(WaitQueue::new());
+		child.files = Arc::new(SpinMutex::new(self.files.lock().clone()));
+		child.fs = Arc::new(SpinMutex::new(self.fs.lock().clone()));
+		// Resource limits are inherited, usage starts from zero
+		child.exited_usage = Rusage::default();
+		child.children_usage = Rusage::default();
+		child.xcpu_sent = None;
+		let now = crate::time::get_time_ns();
+		for thread in &mut child.threads {
+			thread.reset_usage(now);
+		}
 
 		// TODO: Copy memory space (copy-on-write)
-		// TODO: Copy file descriptor table
 		// TODO: Set up new page tables
 
 		Ok(child)
@@ -115,25 +246,83 @@ impl Process {
 		// TODO: Reparent children to init
 	}
 
-	/// Send a signal to the process
+	/// Send a signal to the process, applying its default action
 	pub fn send_signal(&mut self, signal: i32) -> Result<()> {
-		match signal {
-			9 => {
-				// SIGKILL
-				self.state = ProcessState::Dead;
+		signal::validate(signal)?;
+
+		// Signal 0 only checks that the process exists
+		if signal == 0 || matches!(self.state, ProcessState::Zombie | ProcessState::Dead) {
+			return Ok(());
+		}
+
+		// Blocked signals wait to be unblocked or read from a signalfd
+		if self.blocked_signals.contains(signal) {
+			self.pending_signals.add(signal);
+			self.signal_queue.wake_up();
+			return Ok(());
+		}
+
+		if self.sigactions[signal as usize - 1].handler == signal::SIG_IGN {
+			return Ok(());
+		}
+
+		match signal::default_action(signal) {
+			// SIGCHLD stays pending for reapers like init to see, without
+			// interrupting their sleeps
+			DefaultAction::Ignore if signal == signal::SIGCHLD => {
+				self.pending_signals.add(signal);
+				self.signal_queue.wake_up();
 			}
-			15 => {
-				// SIGTERM
-				self.signal_pending = true;
-				// TODO: Add to signal queue
+			DefaultAction::Ignore => {}
+			DefaultAction::Continue => {
+				for stop in [
+					signal::SIGSTOP,
+					signal::SIGTSTP,
+					signal::SIGTTIN,
+					signal::SIGTTOU,
+				] {
+					self.pending_signals.remove(stop);
+				}
+				if self.state == ProcessState::Stopped {
+					self.state = ProcessState::Running;
+				}
+			}
+			DefaultAction::Stop => {
+				self.pending_signals.remove(signal::SIGCONT);
+				self.state = ProcessState::Stopped;
 			}
-			_ => {
-				// TODO: Handle other signals
+			DefaultAction::Terminate | DefaultAction::CoreDump => {
+				// No user handlers yet, use the default
+				self.pending_signals.add(signal);
+				self.signal_pending = true;
+				if signal == signal::SIGKILL {
+					self.state = ProcessState::Dead;
+					self.exit_code = signal;
+				} else {
+					self.exit(signal);
+				}
 			}
 		}
 		Ok(())
 	}
 
+	/// Make `signal` impossible to block or ignore before it is sent, like
+	/// Linux force_sig: if it is either, it gets its default action back
+	/// and is unblocked
+	pub fn force_deliverable(&mut self, signal: i32) {
+		if self.ignores_signal(signal) {
+			self.sigactions[signal as usize - 1].handler = signal::SIG_DFL;
+			self.blocked_signals.remove(signal);
+		}
+	}
+
+	/// Whether `signal` is blocked or ignored, so sending it would do
+	/// nothing for now
+	pub fn ignores_signal(&self, signal: i32) -> bool {
+		self.blocked_signals.contains(signal)
+			|| self.sigactions[signal as usize - 1].handler == signal::SIG_IGN
+	}
+
 	/// Wait for child processes
 	pub fn wait(&self) -> Result<(Pid, i32)> {
 		// TODO: Block until child exits
@@ -154,6 +343,12 @@ pub struct Thread {
 	pub nice: i32,     // Nice value (-20 to 19)
 	pub cpu_time: u64, // Nanoseconds
 	pub context: Context,
+	pub fs_base: u64,         // Thread-local storage base
+	pub clear_child_tid: u64, // User address cleared and woken on exit
+	pub utime: u64,           // Nanoseconds spent in user mode
+	pub stime: u64,           // Nanoseconds spent in the kernel
+	pub in_kernel: bool,      // Inside a system call
+	acct_start: u64,          // Time of the last accounting update
 }
 
 impl Thread {
@@ -168,6 +363,12 @@ impl Thread {
 			nice: 0,
 			cpu_time: 0,
 			context: Context::new(),
+			fs_base: 0,
+			clear_child_tid: 0,
+			utime: 0,
+			stime: 0,
+			in_kernel: false,
+			acct_start: crate::time::get_time_ns(),
 		}
 	}
 
@@ -180,6 +381,37 @@ impl Thread {
 	pub fn add_cpu_time(&mut self, time: u64) {
 		self.cpu_time += time;
 	}
+
+	/// Charge the time since the last update to user or system time
+	pub fn account(&mut self, now: u64) {
+		let delta = now.saturating_sub(self.acct_start);
+		self.acct_start = now;
+		if self.in_kernel {
+			self.stime += delta;
+			crate::resource::account_cpu(0, delta);
+		} else {
+			self.utime += delta;
+			crate::resource::account_cpu(delta, 0);
+		}
+		self.add_cpu_time(delta);
+	}
+
+	/// Clear CPU times, as for a new child
+	pub fn reset_usage(&mut self, now: u64) {
+		self.cpu_time = 0;
+		self.utime = 0;
+		self.stime = 0;
+		self.acct_start = now;
+	}
+
+	/// CPU usage of this thread
+	pub fn usage(&self) -> Rusage {
+		Rusage {
+			utime: self.utime,
+			stime: self.stime,
+			maxrss: 0,
+		}
+	}
 }
 
 /// Global process table
@@ -187,6 +419,16 @@ pub static PROCESS_TABLE: Spinlock<ProcessTable> = Spinlock::new(ProcessTable::n
 static NEXT_PID: AtomicU32 = AtomicU32::new(1);
 static NEXT_TID: AtomicU32 = AtomicU32::new(1);
 
+/// Copy of the process table for lock-free lookups, republished by the
+/// first lookup after a change
+static PROCESS_SNAPSHOT: spin::once::Once<RcuCell<BTreeMap<Pid, Process>>> =
+	spin::once::Once::new();
+static SNAPSHOT_STALE: AtomicBool = AtomicBool::new(true);
+
+fn process_snapshot() -> &'static RcuCell<BTreeMap<Pid, Process>> {
+	PROCESS_SNAPSHOT.call_once(|| RcuCell::new(BTreeMap::new()))
+}
+
 /// Process table implementation
 pub struct ProcessTable {
 	processes: BTreeMap<Pid, Process>,
@@ -201,9 +443,15 @@ impl ProcessTable {
 		}
 	}
 
+	/// Get the process map for changing, invalidating the snapshot
+	fn processes_mut(&mut self) -> &mut BTreeMap<Pid, Process> {
+		SNAPSHOT_STALE.store(true, Ordering::Release);
+		&mut self.processes
+	}
+
 	pub fn add_process(&mut self, process: Process) {
 		let pid = process.pid;
-		self.processes.insert(pid, process);
+		self.processes_mut().insert(pid, process);
 		if self.current_process.is_none() {
 			self.current_process = Some(pid);
 		}
@@ -214,12 +462,11 @@ impl ProcessTable {
 	}
 
 	fn get_process_mut(&mut self, pid: Pid) -> Option<&mut Process> {
-		self.processes.get_mut(&pid)
+		self.processes_mut().get_mut(&pid)
 	}
 
-	#[allow(dead_code)]
 	fn remove_process(&mut self, pid: Pid) -> Option<Process> {
-		let process = self.processes.remove(&pid);
+		let process = self.processes_mut().remove(&pid);
 		if self.current_process == Some(pid) {
 			self.current_process = self.processes.keys().next().copied();
 		}
@@ -230,6 +477,64 @@ impl ProcessTable {
 		self.processes.keys().copied().collect()
 	}
 
+	/// Get the live members of a process group
+	fn group_members(&self, pgid: Pid) -> Vec<Pid> {
+		self.processes
+			.values()
+			.filter(|p| p.pgid == pgid)
+			.filter(|p| !matches!(p.state, ProcessState::Zombie | ProcessState::Dead))
+			.map(|p| p.pid)
+			.collect()
+	}
+
+	/// Find the process with PID number `nr` in a PID namespace
+	fn find_by_nr(&self, ns: &PidNamespace, nr: u32) -> Option<Pid> {
+		self.processes
+			.values()
+			.find(|p| p.pid_nr_ns(ns) == Some(nr))
+			.map(|p| p.pid)
+	}
+
+	/// Check whether a process group exists within a session
+	fn group_in_session(&self, pgid: Pid, sid: Pid) -> bool {
+		self.processes
+			.values()
+			.any(|p| p.pgid == pgid && p.sid == sid)
+	}
+
+	/// Get the running thread of the current process, falling back to
+	/// its main thread like current_thread_tid()
+	fn current_thread_mut(&mut self, running: Option<Tid>) -> Option<&mut Thread> {
+		let pid = self.current_process?;
+		let process = self.processes_mut().get_mut(&pid)?;
+		let index = running
+			.and_then(|tid| process.threads.iter().position(|t| t.tid == tid))
+			.unwrap_or(0);
+		process.threads.get_mut(index)
+	}
+
+	/// Enforce RLIMIT_NPROC for a new process created by `parent`
+	fn check_nproc(&self, parent: &Process) -> Result<()> {
+		let cred = &parent.cred;
+		if cred.capable(crate::capability::CAP_SYS_RESOURCE)
+			|| cred.capable(crate::capability::CAP_SYS_ADMIN)
+		{
+			return Ok(());
+		}
+
+		let count = self
+			.processes
+			.values()
+			.filter(|p| p.cred.uid == cred.uid)
+			.filter(|p| !matches!(p.state, ProcessState::Zombie | ProcessState::Dead))
+			.count();
+		let limit = parent.rlimits.get(RLIMIT_NPROC)?;
+		if limit.exceeded_by(count as u64 + 1) {
+			return Err(Error::WouldBlock);
+		}
+		Ok(())
+	}
+
 	pub fn find_thread(&self, tid: Tid) -> Option<&Thread> {
 		for process in self.processes.values() {
 			for thread in &process.threads {
@@ -242,6 +547,13 @@ impl ProcessTable {
 	}
 
 	pub fn find_thread_mut(&mut self, tid: Tid) -> Option<&mut Thread> {
+		SNAPSHOT_STALE.store(true, Ordering::Release);
+		self.thread_mut(tid)
+	}
+
+	/// Like find_thread_mut, but keeps the snapshot. For register state
+	/// saved on context switches, which lookups don't care about.
+	fn thread_mut(&mut self, tid: Tid) -> Option<&mut Thread> {
 		for process in self.processes.values_mut() {
 			for thread in &mut process.threads {
 				if thread.tid == tid {
@@ -258,7 +570,7 @@ impl ProcessTable {
 		tid2: Tid,
 	) -> (Option<&mut Thread>, Option<&mut Thread>) {
 		if tid1 == tid2 {
-			let t = self.find_thread_mut(tid1);
+			let t = self.thread_mut(tid1);
 			return (t, None);
 		}
 
@@ -269,8 +581,8 @@ impl ProcessTable {
 
 		let ptr = self as *mut ProcessTable;
 		unsafe {
-			let t1 = (*ptr).find_thread_mut(tid1);
-			let t2 = (*ptr).find_thread_mut(tid2);
+			let t1 = (*ptr).thread_mut(tid1);
+			let t2 = (*ptr).thread_mut(tid2);
 			(t1, t2)
 		}
 	}
@@ -281,6 +593,30 @@ pub fn allocate_pid() -> Pid {
 	Pid(NEXT_PID.fetch_add(1, Ordering::SeqCst))
 }
 
+/// PID of the init process
+pub const INIT_PID: Pid = Pid(1);
+
+/// Set once init runs as PID 1; the kernel can't go on without it
+static INIT_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Claim PID 1 for the init process. Fails once any process has a PID.
+pub fn allocate_init_pid() -> Result<Pid> {
+	NEXT_PID.compare_exchange(
+		INIT_PID.0,
+		INIT_PID.0 + 1,
+		Ordering::SeqCst,
+		Ordering::SeqCst,
+	)
+	.map_err(|_| Error::Busy)?;
+	INIT_STARTED.store(true, Ordering::Release);
+	Ok(INIT_PID)
+}
+
+/// Get the most recently allocated PID
+pub fn last_pid() -> Pid {
+	Pid(NEXT_PID.load(Ordering::Relaxed).saturating_sub(1))
+}
+
 /// Allocate a new TID
 pub fn allocate_tid() -> Tid {
 	Tid(NEXT_TID.fetch_add(1, Ordering::SeqCst))
@@ -322,6 +658,12 @@ pub fn current_process_pid() -> Option<Pid> {
 	table.current_process
 }
 
+/// Switch the current process, returning the one it replaces
+pub fn set_current_process(pid: Option<Pid>) -> Option<Pid> {
+	let mut table = PROCESS_TABLE.lock();
+	core::mem::replace(&mut table.current_process, pid)
+}
+
 /// Get current process object
 pub fn current_process() -> Option<Process> {
 	let table = PROCESS_TABLE.lock();
@@ -334,7 +676,14 @@ pub fn current_process() -> Option<Process> {
 
 /// Get process by PID
 pub fn find_process(pid: Pid) -> Option<Process> {
+	if !SNAPSHOT_STALE.load(Ordering::Acquire) {
+		return process_snapshot().read_with(|processes| processes.get(&pid).cloned());
+	}
+
+	// Republish under the table lock so no change can slip in between
 	let table = PROCESS_TABLE.lock();
+	SNAPSHOT_STALE.store(false, Ordering::Release);
+	process_snapshot().replace(table.processes.clone());
 	table.get_process(pid).cloned()
 }
 
@@ -350,12 +699,1100 @@ pub fn kill_process(pid: Pid, signal: i32) -> Result<()> {
 	}
 }
 
+/// Get the thread currently running in the current process
+pub fn current_thread_tid() -> Option<Tid> {
+	let running = crate::scheduler::current_tid();
+	let table = PROCESS_TABLE.lock();
+	let process = table.get_process(table.current_process?)?;
+	match running {
+		Some(tid) if process.threads.iter().any(|t| t.tid == tid) => Some(tid),
+		_ => process.main_thread().map(|t| t.tid),
+	}
+}
+
+/// Check whether a thread with `tid` exists in any process
+pub fn thread_exists(tid: Tid) -> bool {
+	PROCESS_TABLE.lock().find_thread(tid).is_some()
+}
+
+/// Arguments of a clone() request
+#[derive(Debug, Clone, Copy)]
+pub struct CloneArgs {
+	pub flags: u64,
+	pub stack: u64,
+	pub parent_tid: u64,
+	pub child_tid: u64,
+	pub tls: u64,
+}
+
+/// Create a new thread or process from the calling thread.
+///
+/// Returns the PID and TID of the new task. With CLONE_THREAD the new
+/// thread joins the caller's process, otherwise a new process is created
+/// that shares the resources selected by the other CLONE_* flags.
+pub fn clone_task(pid: Pid, tid: Tid, args: &CloneArgs) -> Result<(Pid, Tid)> {
+	use clone_flags::*;
+
+	// Threads share signal handlers, and shared handlers need a shared VM
+	if args.flags & CLONE_THREAD != 0 && args.flags & CLONE_SIGHAND == 0 {
+		return Err(Error::EINVAL);
+	}
+	if args.flags & CLONE_SIGHAND != 0 && args.flags & CLONE_VM == 0 {
+		return Err(Error::EINVAL);
+	}
+
+	// Every task runs in the one address space, so a child with a copy of
+	// its own can't be made
+	if args.flags & CLONE_VM == 0 {
+		return Err(Error::ENOSYS);
+	}
+
+	let namespace_flags = CLONE_NEWNS | CLONE_NEWPID;
+	if args.flags & CLONE_THREAD != 0 && args.flags & namespace_flags != 0 {
+		return Err(Error::EINVAL);
+	}
+
+	let mut table = PROCESS_TABLE.lock();
+	let parent = table.get_process(pid).ok_or(Error::ESRCH)?;
+	let nsproxy = if args.flags & namespace_flags != 0 {
+		if !parent.cred.capable(crate::capability::CAP_SYS_ADMIN) {
+			return Err(Error::EPERM);
+		}
+		copy_namespaces(&parent.nsproxy, args.flags)?
+	} else {
+		parent.nsproxy.clone()
+	};
+	let caller = parent
+		.threads
+		.iter()
+		.find(|t| t.tid == tid)
+		.or(parent.main_thread())
+		.ok_or(Error::ESRCH)?;
+
+	let new_tid = allocate_tid();
+	let mut thread = Thread::new(new_tid, pid, caller.priority);
+	thread.nice = caller.nice;
+	thread.context = caller.context.clone();
+	thread.context.rax = 0; // clone() returns 0 in the child
+	if args.stack != 0 {
+		thread.context.rsp = args.stack;
+		thread.stack_pointer = VirtAddr::new(args.stack as usize);
+	}
+	thread.instruction_pointer = caller.instruction_pointer;
+	thread.fs_base = if args.flags & CLONE_SETTLS != 0 {
+		args.tls
+	} else {
+		caller.fs_base
+	};
+	if args.flags & CLONE_CHILD_CLEARTID != 0 {
+		thread.clear_child_tid = args.child_tid;
+	}
+
+	let new_pid = if args.flags & CLONE_THREAD != 0 {
+		if let Some(process) = table.get_process_mut(pid) {
+			process.add_thread(thread);
+		}
+		pid
+	} else {
+		table.check_nproc(parent)?;
+		let mut child = parent.fork_with(nsproxy)?;
+		thread.process_pid = child.pid;
+		child.threads = alloc::vec![thread];
+		if args.flags & CLONE_FILES != 0 {
+			child.files = parent.files.clone();
+		}
+		if args.flags & CLONE_FS != 0 {
+			child.fs = parent.fs.clone();
+		}
+		let child_pid = child.pid;
+		table.add_process(child);
+		child_pid
+	};
+	drop(table);
+
+	// The child shares the address space, so both writes land there
+	let tid_bytes = new_tid.0.to_ne_bytes();
+	if args.flags & CLONE_PARENT_SETTID != 0 {
+		let ptr = crate::memory::UserPtr::new(args.parent_tid as *mut u8)?;
+		crate::memory::copy_to_user(ptr, &tid_bytes)?;
+	}
+	if args.flags & CLONE_CHILD_SETTID != 0 {
+		let ptr = crate::memory::UserPtr::new(args.child_tid as *mut u8)?;
+		crate::memory::copy_to_user(ptr, &tid_bytes)?;
+	}
+
+	Ok((new_pid, new_tid))
+}
+
+/// Remove a thread from its process.
+///
+/// Returns the thread's clear_child_tid address and whether it was the
+/// last thread of the process.
+pub fn exit_thread(pid: Pid, tid: Tid) -> Result<(u64, bool)> {
+	let mut table = PROCESS_TABLE.lock();
+	let process = table.get_process_mut(pid).ok_or(Error::ESRCH)?;
+	let index = process
+		.threads
+		.iter()
+		.position(|t| t.tid == tid)
+		.ok_or(Error::ESRCH)?;
+
+	if process.threads.len() == 1 {
+		return Ok((process.threads[0].clear_child_tid, true));
+	}
+
+	let mut thread = process.threads.remove(index);
+	thread.account(crate::time::get_time_ns());
+	process.exited_usage.add(&thread.usage());
+	Ok((thread.clear_child_tid, false))
+}
+
+/// Remove every thread of a process except `tid`, as exit_group does,
+/// and return the removed thread IDs
+pub fn exit_other_threads(pid: Pid, tid: Tid) -> Result<Vec<Tid>> {
+	let mut table = PROCESS_TABLE.lock();
+	let process = table.get_process_mut(pid).ok_or(Error::ESRCH)?;
+	let now = crate::time::get_time_ns();
+
+	let mut removed = Vec::new();
+	for mut thread in core::mem::take(&mut process.threads) {
+		if thread.tid == tid {
+			process.threads.push(thread);
+			continue;
+		}
+		thread.account(now);
+		process.exited_usage.add(&thread.usage());
+		removed.push(thread.tid);
+	}
+	Ok(removed)
+}
+
+/// Replace the program of a process after exec has dropped its other
+/// threads: name, credentials and address space come from `image`, and
+/// the remaining thread starts over with `context`
+pub fn replace_image(pid: Pid, image: Process, context: Context) -> Result<()> {
+	let mut table = PROCESS_TABLE.lock();
+	let process = table.get_process_mut(pid).ok_or(Error::ESRCH)?;
+	process.name = image.name;
+	// Exec makes the process dumpable again unless it gains privileges
+	process.dumpable = !process.cred.changes_identity(&image.cred);
+	process.cred = image.cred;
+	// Handlers are gone with the old image, ignored signals stay ignored
+	for action in &mut process.sigactions {
+		if action.handler != signal::SIG_IGN {
+			*action = SigAction::default();
+		}
+	}
+	process.mappings = image.mappings;
+	process.total_vm = image.total_vm;
+	process.hiwater_vm = image.total_vm;
+	process.vdso_base = image.vdso_base;
+	process.auxv = image.auxv;
+
+	let thread = process.threads.first_mut().ok_or(Error::ESRCH)?;
+	thread.instruction_pointer = VirtAddr::new(context.rip as usize);
+	thread.stack_pointer = VirtAddr::new(context.rsp as usize);
+	thread.context = context;
+	thread.fs_base = 0;
+	thread.clear_child_tid = 0;
+	Ok(())
+}
+
+/// Terminate a process with the given exit code
+pub fn exit_process(pid: Pid, exit_code: i32) -> Result<()> {
+	{
+		let mut table = PROCESS_TABLE.lock();
+		let process = table.get_process_mut(pid).ok_or(Error::ESRCH)?;
+		process.exit(exit_code);
+	}
+	let status = (exit_code & 0xff) << 8;
+	crate::ptrace::exit_notify(pid, status);
+	exit_notify(pid, status);
+	Ok(())
+}
+
+/// Record the wait status of an exited process, reparent its children to
+/// the nearest namespace init and send SIGCHLD to the parents. If it was
+/// itself the init of a PID namespace, kill everything left in that
+/// namespace; the death of the system's init is fatal.
+fn exit_notify(pid: Pid, status: i32) {
+	crate::posix_timers::exit_timers(pid);
+
+	let mut notify = Vec::new();
+	let victims: Vec<Pid> = {
+		let mut table = PROCESS_TABLE.lock();
+		let (pid_ns, parent, usage) = match table.get_process_mut(pid) {
+			Some(process) => {
+				process.wait_status = status;
+				let mut usage = process.usage();
+				usage.add(&process.children_usage);
+				(process.pid_ns.clone(), process.parent, usage)
+			}
+			None => return,
+		};
+		if pid == INIT_PID && INIT_STARTED.load(Ordering::Acquire) {
+			drop(table);
+			panic!("Attempted to kill init! exitcode=0x{:08x}", status);
+		}
+		notify.extend(parent);
+
+		// Hand the usage of the process and its children to the parent
+		if let Some(parent) = parent.and_then(|p| table.get_process_mut(p)) {
+			parent.children_usage.add(&usage);
+		}
+
+		let mut reaper = None;
+		let mut ns = Some(pid_ns.clone());
+		while let Some(current) = ns {
+			match current.reaper() {
+				Some(r) if r != pid => {
+					reaper = Some(r);
+					break;
+				}
+				_ => ns = current.parent.clone(),
+			}
+		}
+		for child in table.processes_mut().values_mut() {
+			if child.parent == Some(pid) {
+				child.parent = reaper;
+				// Already exited, the reaper has to collect it
+				if child.state == ProcessState::Zombie
+					|| child.state == ProcessState::Dead
+				{
+					notify.extend(reaper);
+				}
+			}
+		}
+
+		if pid_ns.level == 0 || pid_ns.reaper() != Some(pid) {
+			Vec::new()
+		} else {
+			pid_ns.set_dead();
+			table.processes
+				.values()
+				.filter(|p| p.pid != pid && p.pid_ns.is_within(&pid_ns))
+				.filter(|p| {
+					!matches!(
+						p.state,
+						ProcessState::Zombie | ProcessState::Dead
+					)
+				})
+				.map(|p| p.pid)
+				.collect()
+		}
+	};
+
+	notify.dedup();
+	for parent in notify {
+		let _ = send_signal(parent, signal::SIGCHLD);
+	}
+	for victim in victims {
+		let _ = send_signal(victim, signal::SIGKILL);
+	}
+}
+
+/// Wait for a child of `parent` to exit, `target` or any, and reap it.
+/// Returns its PID and wait status, or PID 0 with `nohang` if none has
+/// exited yet.
+pub fn wait_child(parent: Pid, target: Option<Pid>, nohang: bool) -> Result<(Pid, i32)> {
+	if let Some(found) = reap_child(parent, target)? {
+		return Ok(found);
+	}
+	if nohang {
+		return Ok((Pid(0), 0));
+	}
+
+	crate::sync::might_sleep();
+	let mut result = Err(Error::ECHILD);
+	crate::sync::wait_until(|| match reap_child(parent, target) {
+		Ok(None) => false,
+		found => {
+			result = found.map(|found| found.unwrap_or((Pid(0), 0)));
+			true
+		}
+	});
+	result
+}
+
+/// Remove an exited child of `parent` from the process table. Fails with
+/// ECHILD if there is no child to wait for.
+fn reap_child(parent: Pid, target: Option<Pid>) -> Result<Option<(Pid, i32)>> {
+	let mut table = PROCESS_TABLE.lock();
+	let mut children = table
+		.processes
+		.values()
+		.filter(|p| p.parent == Some(parent) && target.map_or(true, |t| t == p.pid))
+		.peekable();
+	if children.peek().is_none() {
+		return Err(Error::ECHILD);
+	}
+	let exited = children
+		.find(|p| matches!(p.state, ProcessState::Zombie | ProcessState::Dead))
+		.map(|p| (p.pid, p.wait_status));
+	if let Some((pid, _)) = exited {
+		table.remove_process(pid);
+	}
+	Ok(exited)
+}
+
+/// Send a signal to a process and update its scheduling state
+pub fn send_signal(pid: Pid, signal: i32) -> Result<()> {
+	deliver_signal(pid, signal, false)
+}
+
+/// Send a signal that the process can't block or ignore, for faults and
+/// seccomp violations
+pub fn force_sig(pid: Pid, signal: i32) -> Result<()> {
+	deliver_signal(pid, signal, true)
+}
+
+fn deliver_signal(pid: Pid, signal: i32, force: bool) -> Result<()> {
+	let (before, after, core) = {
+		let mut table = PROCESS_TABLE.lock();
+		let process = table.get_process_mut(pid).ok_or(Error::ESRCH)?;
+		let before = process.state;
+		if force {
+			signal::validate(signal)?;
+			process.force_deliverable(signal);
+		}
+		process.send_signal(signal)?;
+		// Snapshot the process for its core dump, written unlocked
+		let dumps = signal::default_action(signal) == DefaultAction::CoreDump
+			&& process.state == ProcessState::Zombie
+			&& before != ProcessState::Zombie
+			&& crate::coredump::wants_dump(process);
+		(before, process.state, dumps.then(|| process.clone()))
+	};
+
+	// This can run in softirq context, so the dump is written later
+	let dumped = core.is_some();
+	if let Some(process) = core {
+		crate::coredump::queue_coredump(process, signal);
+	}
+
+	// The scheduler takes PROCESS_TABLE on switch, so only call it unlocked
+	if before != after {
+		match after {
+			ProcessState::Running => crate::scheduler::wake_task(pid)?,
+			_ => crate::scheduler::remove_task(pid)?,
+		}
+		if matches!(after, ProcessState::Zombie | ProcessState::Dead) {
+			// Wait status of a signal death, 0x80 flagging a core
+			// dump
+			let status = signal | if dumped { 0x80 } else { 0 };
+			crate::ptrace::exit_notify(pid, status);
+			exit_notify(pid, status);
+		}
+	}
+	Ok(())
+}
+
+/// Force `signal` on the current process for a fault in user mode, saving
+/// the registers at the fault for its core dump
+pub fn force_sig_fault(signal: i32, regs: Context) -> Result<()> {
+	let running = crate::scheduler::current_tid();
+	let pid = {
+		let mut table = PROCESS_TABLE.lock();
+		let pid = table.current_process.ok_or(Error::ESRCH)?;
+		if let Some(thread) = table.current_thread_mut(running) {
+			let (cr3, fpu_state) = (thread.context.cr3, thread.context.fpu_state);
+			thread.context = Context {
+				cr3,
+				fpu_state,
+				..regs
+			};
+		}
+		pid
+	};
+	force_sig(pid, signal)
+}
+
+/// Send a signal to every process in a process group. Like Linux
+/// kill_pgrp, it succeeds if any member got the signal, and otherwise
+/// fails with the last member's error.
+pub fn signal_process_group(pgid: Pid, signal: i32) -> Result<()> {
+	signal::validate(signal)?;
+	let members = PROCESS_TABLE.lock().group_members(pgid);
+
+	let mut result = Err(Error::ESRCH);
+	for pid in members {
+		match send_signal(pid, signal) {
+			Ok(()) => result = Ok(()),
+			Err(error) if result.is_err() => result = Err(error),
+			Err(_) => {}
+		}
+	}
+	result
+}
+
+/// Check whether `sender` may signal `target`: it needs CAP_KILL or a
+/// matching real or effective user ID. SIGCONT is allowed within a session.
+fn may_signal(sender: &Process, target: &Process, signal: i32) -> bool {
+	let (from, to) = (&sender.cred, &target.cred);
+	from.capable(crate::capability::CAP_KILL)
+		|| from.euid == to.uid
+		|| from.euid == to.suid
+		|| from.uid == to.uid
+		|| from.uid == to.suid
+		|| (signal == signal::SIGCONT && sender.sid == target.sid)
+}
+
+/// Send a signal on behalf of a process (kill(2)).
+///
+/// `pid` is a number in the sender's PID namespace and selects the
+/// targets: a single process if positive, the sender's process group if 0,
+/// every process except init and the sender if -1, and the process group
+/// -pid otherwise. Targets the sender may not signal are skipped; EPERM is
+/// returned if that leaves none. A namespace init has no signal handlers,
+/// so signals sent to it from inside its namespace are dropped.
+pub fn kill(sender: &Process, pid: i32, signal: i32) -> Result<()> {
+	signal::validate(signal)?;
+	let ns = &sender.pid_ns;
+
+	let targets: Vec<Pid> = {
+		let table = PROCESS_TABLE.lock();
+		let group = |nr: u32| {
+			table.find_by_nr(ns, nr)
+				.map(|leader| table.group_members(leader))
+				.unwrap_or_default()
+		};
+		let candidates: Vec<Pid> = match pid {
+			p if p > 0 => table.find_by_nr(ns, p as u32).into_iter().collect(),
+			0 => table.group_members(sender.pgid),
+			-1 => table
+				.processes
+				.values()
+				.filter(|p| {
+					p.pid != sender.pid
+						&& p.pid_nr_ns(ns).map_or(false, |nr| nr != 1)
+				})
+				.map(|p| p.pid)
+				.collect(),
+			p => group(p.unsigned_abs()),
+		};
+		if candidates.is_empty() {
+			return Err(Error::ESRCH);
+		}
+
+		let permitted: Vec<&Process> = candidates
+			.iter()
+			.filter_map(|&p| table.get_process(p))
+			.filter(|target| may_signal(sender, target, signal))
+			.collect();
+		if permitted.is_empty() {
+			return Err(Error::EPERM);
+		}
+		permitted
+			.into_iter()
+			.filter(|target| !(target.pid_ns.id == ns.id && target.vnr() == 1))
+			.map(|target| target.pid)
+			.collect()
+	};
+
+	for target in targets {
+		let _ = send_signal(target, signal);
+	}
+	Ok(())
+}
+
+/// Modify the credentials of a process
+pub fn update_cred<T>(pid: Pid, f: impl FnOnce(&mut Credentials) -> Result<T>) -> Result<T> {
+	let mut table = PROCESS_TABLE.lock();
+	let process = table.get_process_mut(pid).ok_or(Error::ESRCH)?;
+
+	// Work on a copy so a failed update leaves the credentials untouched
+	let mut cred = process.cred.clone();
+	let result = f(&mut cred)?;
+	if process.cred.changes_identity(&cred) {
+		process.dumpable = false;
+	}
+	process.cred = cred;
+	Ok(result)
+}
+
+/// Modify the seccomp state of a process
+pub fn update_seccomp<T>(pid: Pid, f: impl FnOnce(&mut Seccomp) -> Result<T>) -> Result<T> {
+	let mut table = PROCESS_TABLE.lock();
+	let process = table.get_process_mut(pid).ok_or(Error::ESRCH)?;
+	f(&mut process.seccomp)
+}
+
+/// Set no_new_privs on a process. It can't be cleared again.
+pub fn set_no_new_privs(pid: Pid) -> Result<()> {
+	let mut table = PROCESS_TABLE.lock();
+	table.get_process_mut(pid).ok_or(Error::ESRCH)?.no_new_privs = true;
+	Ok(())
+}
+
+/// Look at the current process without cloning it
+pub fn with_current<T>(f: impl FnOnce(&Process) -> T) -> Option<T> {
+	let table = PROCESS_TABLE.lock();
+	table.get_process(table.current_process?).map(f)
+}
+
+/// Whether the current process has a signal that interrupts sleeps
+pub fn signal_pending() -> bool {
+	with_current(|p| p.signal_pending).unwrap_or(false)
+}
+
+/// Change the blocked signals of the current process as rt_sigprocmask
+/// does with `how` and return the old mask. Pending signals that become
+/// unblocked are delivered.
+pub fn sigprocmask(how: i32, set: Option<SigSet>) -> Result<SigSet> {
+	let (pid, old, unblocked) = {
+		let mut table = PROCESS_TABLE.lock();
+		let pid = table.current_process.ok_or(Error::ESRCH)?;
+		let process = table.get_process_mut(pid).ok_or(Error::ESRCH)?;
+		let old = process.blocked_signals;
+		let mut blocked = match (how, set) {
+			(_, None) => return Ok(old),
+			(signal::SIG_BLOCK, Some(set)) => SigSet(old.0 | set.0),
+			(signal::SIG_UNBLOCK, Some(set)) => SigSet(old.0 & !set.0),
+			(signal::SIG_SETMASK, Some(set)) => set,
+			_ => return Err(Error::EINVAL),
+		};
+		// SIGKILL and SIGSTOP can't be blocked
+		blocked.remove(signal::SIGKILL);
+		blocked.remove(signal::SIGSTOP);
+		process.blocked_signals = blocked;
+
+		let unblocked = SigSet(process.pending_signals.0 & old.0 & !blocked.0);
+		process.pending_signals.0 &= !unblocked.0;
+		(pid, old, unblocked)
+	};
+
+	let mut unblocked = unblocked;
+	while let Some(signal) = unblocked.take_next() {
+		send_signal(pid, signal)?;
+	}
+	Ok(old)
+}
+
+/// Change the disposition of `signal` for the current process as
+/// rt_sigaction does and return the old one. User handlers are recorded,
+/// but until signal frames are set up the default action is taken in
+/// their place.
+pub fn sigaction(signal: i32, action: Option<SigAction>) -> Result<SigAction> {
+	if !(1..=signal::NSIG).contains(&signal) {
+		return Err(Error::EINVAL);
+	}
+	let mut table = PROCESS_TABLE.lock();
+	let pid = table.current_process.ok_or(Error::ESRCH)?;
+	let process = table.get_process_mut(pid).ok_or(Error::ESRCH)?;
+	let slot = &mut process.sigactions[signal as usize - 1];
+	let old = *slot;
+	if let Some(action) = action {
+		// SIGKILL and SIGSTOP can't be caught or ignored
+		if signal == signal::SIGKILL || signal == signal::SIGSTOP {
+			return Err(Error::EINVAL);
+		}
+		*slot = action;
+		// Ignoring a signal discards it if it is pending
+		if action.handler == signal::SIG_IGN {
+			process.pending_signals.remove(signal);
+		}
+	}
+	Ok(old)
+}
+
+/// Remove up to `max` pending signals in `mask` from the current process,
+/// lowest numbered first
+pub fn dequeue_signals(mask: SigSet, max: usize) -> Result<Vec<i32>> {
+	let mut table = PROCESS_TABLE.lock();
+	let pid = table.current_process.ok_or(Error::ESRCH)?;
+	let process = table.get_process_mut(pid).ok_or(Error::ESRCH)?;
+
+	let mut ready = SigSet(process.pending_signals.0 & mask.0);
+	let mut signals = Vec::new();
+	while signals.len() < max {
+		match ready.take_next() {
+			Some(signal) => {
+				process.pending_signals.remove(signal);
+				signals.push(signal);
+			}
+			None => break,
+		}
+	}
+	Ok(signals)
+}
+
+/// Run `f` on a thread of a process: `tid`, or the main thread if that is
+/// None or gone
+pub fn with_thread<T>(pid: Pid, tid: Option<Tid>, f: impl FnOnce(&mut Thread) -> T) -> Result<T> {
+	let mut table = PROCESS_TABLE.lock();
+	let process = table.get_process_mut(pid).ok_or(Error::ESRCH)?;
+	let index = tid
+		.and_then(|tid| process.threads.iter().position(|t| t.tid == tid))
+		.unwrap_or(0);
+	process.threads.get_mut(index).map(f).ok_or(Error::ESRCH)
+}
+
+/// Check that `parent` may create another process under RLIMIT_NPROC
+pub fn check_nproc(parent: &Process) -> Result<()> {
+	PROCESS_TABLE.lock().check_nproc(parent)
+}
+
+/// Get or replace a resource limit of `target` on behalf of `caller`
+/// (prlimit64 semantics) and return the old limit. Another process's
+/// limits need matching user and group IDs or CAP_SYS_RESOURCE.
+pub fn prlimit(
+	caller: &Process,
+	target: Pid,
+	resource: usize,
+	new: Option<crate::resource::Rlimit>,
+) -> Result<crate::resource::Rlimit> {
+	let mut table = PROCESS_TABLE.lock();
+	let process = table.get_process_mut(target).ok_or(Error::ESRCH)?;
+
+	let (from, to) = (&caller.cred, &process.cred);
+	let same_ids = [to.uid, to.euid, to.suid].iter().all(|&id| id == from.uid)
+		&& [to.gid, to.egid, to.sgid].iter().all(|&id| id == from.gid);
+	if target != caller.pid && !same_ids && !from.capable(crate::capability::CAP_SYS_RESOURCE) {
+		return Err(Error::EPERM);
+	}
+
+	match new {
+		Some(limit) => process.rlimits.set(resource, limit, &caller.cred),
+		None => process.rlimits.get(resource),
+	}
+}
+
+/// Account `bytes` of new address space, enforcing RLIMIT_AS
+pub fn expand_vm(pid: Pid, bytes: u64) -> Result<()> {
+	let mut table = PROCESS_TABLE.lock();
+	let process = table.get_process_mut(pid).ok_or(Error::ESRCH)?;
+	let total = process.total_vm.saturating_add(bytes);
+	if process.rlimits.get(RLIMIT_AS)?.exceeded_by(total) {
+		return Err(Error::ENOMEM);
+	}
+	process.total_vm = total;
+	process.hiwater_vm = process.hiwater_vm.max(total);
+	Ok(())
+}
+
+/// Account `bytes` of address space released by a process
+pub fn shrink_vm(pid: Pid, bytes: u64) {
+	if let Some(process) = PROCESS_TABLE.lock().get_process_mut(pid) {
+		process.total_vm = process.total_vm.saturating_sub(bytes);
+	}
+}
+
+/// Record a region mapped into a process's address space
+pub fn add_mapping(pid: Pid, vma: VmaArea) {
+	if let Some(process) = PROCESS_TABLE.lock().get_process_mut(pid) {
+		process.mappings.push(vma);
+		process.mappings.sort_by_key(|vma| vma.vm_start);
+	}
+}
+
+/// Forget the mapped regions of a process within [start, end), splitting
+/// regions that only partly overlap
+pub fn remove_mapping(pid: Pid, start: VirtAddr, end: VirtAddr) {
+	let mut table = PROCESS_TABLE.lock();
+	let process = match table.get_process_mut(pid) {
+		Some(process) => process,
+		None => return,
+	};
+	let mut kept = Vec::new();
+	for vma in core::mem::take(&mut process.mappings) {
+		if vma.vm_end <= start || vma.vm_start >= end {
+			kept.push(vma);
+			continue;
+		}
+		if vma.vm_start < start {
+			let mut below = vma.clone();
+			below.vm_end = start;
+			kept.push(below);
+		}
+		if vma.vm_end > end {
+			let mut above = vma;
+			above.vm_start = end;
+			kept.push(above);
+		}
+	}
+	process.mappings = kept;
+}
+
+/// Change the protection of the mapped regions of a process within
+/// [start, end), splitting regions that only partly overlap. Fails with
+/// ENOMEM if part of the range is not mapped.
+pub fn protect_mapping(pid: Pid, start: VirtAddr, end: VirtAddr, prot: u32) -> Result<()> {
+	let mut table = PROCESS_TABLE.lock();
+	let process = table.get_process_mut(pid).ok_or(Error::ESRCH)?;
+
+	// The regions are sorted, so the range is covered if they chain up
+	let mut covered = start;
+	for vma in process.mappings.iter().filter(|vma| vma.vm_end > start) {
+		if vma.vm_start > covered || covered >= end {
+			break;
+		}
+		covered = vma.vm_end;
+	}
+	if covered < end {
+		return Err(Error::ENOMEM);
+	}
+
+	let mut regions = Vec::new();
+	for vma in core::mem::take(&mut process.mappings) {
+		if vma.vm_end <= start || vma.vm_start >= end {
+			regions.push(vma);
+			continue;
+		}
+		if vma.vm_start < start {
+			let mut below = vma.clone();
+			below.vm_end = start;
+			regions.push(below);
+		}
+		if vma.vm_end > end {
+			let mut above = vma.clone();
+			above.vm_start = end;
+			regions.push(above);
+		}
+		let mut inside = vma;
+		inside.vm_start = inside.vm_start.max(start);
+		inside.vm_end = inside.vm_end.min(end);
+		inside.vm_prot = prot;
+		regions.push(inside);
+	}
+	regions.sort_by_key(|vma| vma.vm_start);
+	process.mappings = regions;
+	Ok(())
+}
+
+/// Extend the region of a process ending at `end` down to `start`, as the
+/// stack grows
+pub fn grow_mapping_down(pid: Pid, end: VirtAddr, start: VirtAddr) {
+	if let Some(process) = PROCESS_TABLE.lock().get_process_mut(pid) {
+		if let Some(vma) = process.mappings.iter_mut().find(|vma| vma.vm_end == end) {
+			vma.vm_start = vma.vm_start.min(start);
+		}
+	}
+}
+
+/// Charge the running thread and switch its accounting between user and
+/// system time
+fn account_transition(in_kernel: bool) {
+	let running = crate::scheduler::current_tid();
+	let now = crate::time::get_time_ns();
+	if let Some(thread) = PROCESS_TABLE.lock().current_thread_mut(running) {
+		thread.account(now);
+		thread.in_kernel = in_kernel;
+	}
+}
+
+/// Start charging system time on kernel entry
+pub fn account_kernel_entry() {
+	account_transition(true);
+}
+
+/// Go back to charging user time on kernel exit
+pub fn account_kernel_exit() {
+	account_transition(false);
+}
+
+/// Signal to send when a process ran past RLIMIT_CPU: SIGXCPU once per
+/// CPU second over the soft limit, SIGKILL at the hard limit
+fn cpu_limit_signal(process: &mut Process) -> Option<i32> {
+	let limit = process.rlimits.get(RLIMIT_CPU).ok()?;
+	let usage = process.usage();
+	let seconds = (usage.utime + usage.stime) / crate::time::NSEC_PER_SEC;
+
+	if limit.max != RLIM_INFINITY && seconds >= limit.max {
+		return Some(signal::SIGKILL);
+	}
+	let over_soft = limit.cur != RLIM_INFINITY && seconds >= limit.cur;
+	if over_soft && process.xcpu_sent != Some(seconds) {
+		process.xcpu_sent = Some(seconds);
+		return Some(signal::SIGXCPU);
+	}
+	None
+}
+
+/// Charge the running thread on a timer tick and enforce RLIMIT_CPU
+pub fn account_tick() {
+	let running = crate::scheduler::current_tid();
+	let now = crate::time::get_time_ns();
+	let target = {
+		// Skip the tick rather than spin on a table held by the
+		// interrupted code
+		let mut table = match PROCESS_TABLE.try_lock() {
+			Some(table) => table,
+			None => return,
+		};
+		let thread = match table.current_thread_mut(running) {
+			Some(thread) => {
+				thread.account(now);
+				(thread.tid, thread.usage())
+			}
+			None => return,
+		};
+		let pid = table.current_process;
+		pid.and_then(|pid| table.get_process_mut(pid))
+			.map(|process| {
+				let sig = cpu_limit_signal(process);
+				(process.pid, process.usage(), thread, sig)
+			})
+	};
+
+	if let Some((pid, usage, (tid, thread_usage), sig)) = target {
+		crate::posix_timers::cpu_tick(pid, &usage, tid, &thread_usage);
+		if let Some(sig) = sig {
+			let _ = send_signal(pid, sig);
+		}
+	}
+}
+
+/// Find the global PID of the process numbered `nr` in a PID namespace
+pub fn find_pid_in_ns(ns: &PidNamespace, nr: u32) -> Option<Pid> {
+	PROCESS_TABLE.lock().find_by_nr(ns, nr)
+}
+
+/// PID number of a process as seen from `ns`, or 0 if it isn't visible
+pub fn pid_nr_ns(pid: Pid, ns: &PidNamespace) -> u32 {
+	let table = PROCESS_TABLE.lock();
+	table.get_process(pid)
+		.and_then(|p| p.pid_nr_ns(ns))
+		.unwrap_or(0)
+}
+
+/// Build the namespaces for a new process or an unshare() caller. The
+/// mount tree is copied for CLONE_NEWNS, and CLONE_NEWPID creates a PID
+/// namespace that the next child will be init of.
+fn copy_namespaces(old: &NsProxy, flags: u64) -> Result<NsProxy> {
+	use clone_flags::{CLONE_NEWNS, CLONE_NEWPID};
+
+	let mut nsproxy = old.clone();
+	if flags & CLONE_NEWNS != 0 {
+		let copy = old
+			.mnt_ns
+			.lock()
+			.copy_tree(crate::namespace::alloc_ns_id())?;
+		nsproxy.mnt_ns = Arc::new(SpinMutex::new(copy));
+	}
+	if flags & CLONE_NEWPID != 0 {
+		nsproxy.pid_ns_for_children = old.pid_ns_for_children.new_child()?;
+	}
+	Ok(nsproxy)
+}
+
+/// Move a process into new namespaces or private copies of shared
+/// resources (unshare(2))
+pub fn unshare(pid: Pid, flags: u64) -> Result<()> {
+	use clone_flags::*;
+
+	let supported = CLONE_NEWNS | CLONE_NEWPID | CLONE_FS | CLONE_FILES;
+	if flags & !supported != 0 {
+		return Err(Error::EINVAL);
+	}
+	// The root and cwd belong to a mount namespace, so a new one needs
+	// them unshared too, as on Linux
+	let flags = if flags & CLONE_NEWNS != 0 {
+		flags | CLONE_FS
+	} else {
+		flags
+	};
+
+	let mut table = PROCESS_TABLE.lock();
+	let process = table.get_process_mut(pid).ok_or(Error::ESRCH)?;
+	if flags & (CLONE_NEWNS | CLONE_NEWPID) != 0 {
+		if !process.cred.capable(crate::capability::CAP_SYS_ADMIN) {
+			return Err(Error::EPERM);
+		}
+		// Children can only be placed in one new PID namespace
+		if flags & CLONE_NEWPID != 0
+			&& process.nsproxy.pid_ns_for_children.id != process.pid_ns.id
+		{
+			return Err(Error::EINVAL);
+		}
+		process.nsproxy = copy_namespaces(&process.nsproxy, flags)?;
+	}
+	if flags & CLONE_FS != 0 {
+		let fs = process.fs.lock().clone();
+		process.fs = Arc::new(SpinMutex::new(fs));
+	}
+	if flags & CLONE_FILES != 0 {
+		let files = process.files.lock().clone();
+		process.files = Arc::new(SpinMutex::new(files));
+	}
+	Ok(())
+}
+
+/// Namespace types for setns(2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NsType {
+	Mount,
+	Pid,
+}
+
+impl NsType {
+	/// The CLONE_NEW* flag of this namespace type
+	pub fn clone_flag(self) -> u64 {
+		match self {
+			NsType::Mount => clone_flags::CLONE_NEWNS,
+			NsType::Pid => clone_flags::CLONE_NEWPID,
+		}
+	}
+}
+
+/// Join a namespace of another process (setns(2)). Joining a PID
+/// namespace only affects children created afterwards, and is limited to
+/// the caller's own PID namespace and its descendants. Joining a mount
+/// namespace fails with EINVAL while the root and cwd are shared.
+pub fn setns(pid: Pid, target: Pid, ns_type: NsType) -> Result<()> {
+	let mut table = PROCESS_TABLE.lock();
+	let target = table.get_process(target).ok_or(Error::ESRCH)?;
+	let mnt_ns = target.nsproxy.mnt_ns.clone();
+	let pid_ns = target.pid_ns.clone();
+
+	// The root and cwd are reset below, which must not reach other
+	// threads or processes sharing them through CLONE_FS
+	let process = table.get_process(pid).ok_or(Error::ESRCH)?;
+	let fs_shared = process.threads.len() > 1
+		|| table.processes
+			.values()
+			.any(|p| p.pid != pid && Arc::ptr_eq(&p.fs, &process.fs));
+
+	let process = table.get_process_mut(pid).ok_or(Error::ESRCH)?;
+	if !process.cred.capable(crate::capability::CAP_SYS_ADMIN) {
+		return Err(Error::EPERM);
+	}
+	match ns_type {
+		NsType::Mount => {
+			if fs_shared {
+				return Err(Error::EINVAL);
+			}
+			process.nsproxy.mnt_ns = mnt_ns;
+			// Reset root and cwd, they may not exist in the new
+			// tree
+			*process.fs.lock() = FsContext::new();
+		}
+		NsType::Pid => {
+			if !pid_ns.is_within(&process.pid_ns) {
+				return Err(Error::EINVAL);
+			}
+			process.nsproxy.pid_ns_for_children = pid_ns;
+		}
+	}
+	Ok(())
+}
+
+/// Get the process group of a process
+pub fn get_pgid(pid: Pid) -> Result<Pid> {
+	let table = PROCESS_TABLE.lock();
+	table.get_process(pid).map(|p| p.pgid).ok_or(Error::ESRCH)
+}
+
+/// Get the session of a process
+pub fn get_sid(pid: Pid) -> Result<Pid> {
+	let table = PROCESS_TABLE.lock();
+	table.get_process(pid).map(|p| p.sid).ok_or(Error::ESRCH)
+}
+
+/// Whether a process group is orphaned: no live member has a parent other
+/// than init in another group of the same session, so no shell is left to
+/// continue it after a stop
+pub fn is_orphaned_pgrp(pgid: Pid) -> bool {
+	let table = PROCESS_TABLE.lock();
+	let members = table.processes.values().filter(|p| {
+		p.pgid == pgid && !matches!(p.state, ProcessState::Zombie | ProcessState::Dead)
+	});
+	for member in members {
+		let parent = match member.parent.and_then(|pid| table.get_process(pid)) {
+			Some(parent) if parent.pid != INIT_PID => parent,
+			_ => continue,
+		};
+		if parent.pgid != pgid && parent.sid == member.sid {
+			return false;
+		}
+	}
+	true
+}
+
+/// Move a process into a process group (setpgid semantics)
+pub fn set_pgid(caller: Pid, pid: Pid, pgid: Pid) -> Result<()> {
+	let mut table = PROCESS_TABLE.lock();
+	let caller_sid = table.get_process(caller).ok_or(Error::ESRCH)?.sid;
+	let target = table.get_process(pid).ok_or(Error::ESRCH)?;
+
+	// Only the caller itself or one of its children may be moved
+	if pid != caller && target.parent != Some(caller) {
+		return Err(Error::ESRCH);
+	}
+	// Session leaders can't change group, and groups can't span sessions
+	if target.sid == pid || target.sid != caller_sid {
+		return Err(Error::EPERM);
+	}
+	if pgid != pid && !table.group_in_session(pgid, caller_sid) {
+		return Err(Error::EPERM);
+	}
+
+	if let Some(process) = table.get_process_mut(pid) {
+		process.pgid = pgid;
+	}
+	Ok(())
+}
+
+/// Make a process the leader of a new session and process group
+pub fn create_session(pid: Pid) -> Result<Pid> {
+	let mut table = PROCESS_TABLE.lock();
+
+	// A process group leader can't start a new session
+	if table.processes.values().any(|p| p.pgid == pid) {
+		return Err(Error::EPERM);
+	}
+
+	let process = table.get_process_mut(pid).ok_or(Error::ESRCH)?;
+	process.sid = pid;
+	process.pgid = pid;
+	process.ctty = None;
+	Ok(pid)
+}
+
+/// Set the controlling terminal of every process in a session
+pub fn set_session_ctty(sid: Pid, ctty: Option<usize>) {
+	let mut table = PROCESS_TABLE.lock();
+	for process in table.processes_mut().values_mut() {
+		if process.sid == sid {
+			process.ctty = ctty;
+		}
+	}
+}
+
 /// List all processes
 pub fn list_processes() -> Vec<Pid> {
 	let table = PROCESS_TABLE.lock();
 	table.list_processes()
 }
 
+/// Process and thread counts
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TaskCounts {
+	pub processes: usize,
+	pub threads: usize,
+	pub running: usize, // Runnable threads
+}
+
+/// Count the processes and threads in the process table
+pub fn task_counts() -> TaskCounts {
+	let table = PROCESS_TABLE.lock();
+	let mut counts = TaskCounts::default();
+	for process in table.processes.values() {
+		if process.state == ProcessState::Dead {
+			continue;
+		}
+		counts.processes += 1;
+		counts.threads += process.threads.len();
+		if process.state == ProcessState::Running {
+			counts.running += process
+				.threads
+				.iter()
+				.filter(|t| t.state == ProcessState::Running)
+				.count();
+		}
+	}
+	counts
+}
+
 /// Initialize process management
 pub fn init_process_management() -> Result<()> {
 	init()
@@ -0,0 +1,592 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Process tracing (ptrace)
+//!
+//! A tracer attaches to a process and learns through wait4 when it stops:
+//! at system call entry and exit after PTRACE_SYSCALL, after one
+//! instruction after PTRACE_SINGLESTEP, at breakpoints, and at the fork,
+//! exec and exit events enabled with PTRACE_SETOPTIONS. While the tracee
+//! is stopped the tracer can read and change its registers and memory.
+//!
+//! Tracing is per process: requests act on the thread that stopped, or on
+//! the main thread.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::arch::x86_64::context::{Context, UserRegs};
+use crate::capability::CAP_SYS_PTRACE;
+use crate::error::{Error, Result};
+use crate::memory::{copy_from_user, copy_to_user, UserPtr, VirtAddr};
+use crate::process::{Process, ProcessState};
+use crate::signal;
+use crate::sync::Spinlock;
+use crate::syscalls::SyscallArgs;
+use crate::types::{Pid, Tid};
+
+pub const PTRACE_TRACEME: u64 = 0;
+pub const PTRACE_PEEKTEXT: u64 = 1;
+pub const PTRACE_PEEKDATA: u64 = 2;
+pub const PTRACE_POKETEXT: u64 = 4;
+pub const PTRACE_POKEDATA: u64 = 5;
+pub const PTRACE_CONT: u64 = 7;
+pub const PTRACE_KILL: u64 = 8;
+pub const PTRACE_SINGLESTEP: u64 = 9;
+pub const PTRACE_GETREGS: u64 = 12;
+pub const PTRACE_SETREGS: u64 = 13;
+pub const PTRACE_ATTACH: u64 = 16;
+pub const PTRACE_DETACH: u64 = 17;
+pub const PTRACE_SYSCALL: u64 = 24;
+pub const PTRACE_SETOPTIONS: u64 = 0x4200;
+pub const PTRACE_GETEVENTMSG: u64 = 0x4201;
+
+/// Options set with PTRACE_SETOPTIONS
+pub const PTRACE_O_TRACESYSGOOD: u64 = 0x01;
+pub const PTRACE_O_TRACEFORK: u64 = 0x02;
+pub const PTRACE_O_TRACEVFORK: u64 = 0x04;
+pub const PTRACE_O_TRACECLONE: u64 = 0x08;
+pub const PTRACE_O_TRACEEXEC: u64 = 0x10;
+pub const PTRACE_O_TRACEEXIT: u64 = 0x40;
+const PTRACE_O_MASK: u64 = 0x5f;
+
+/// Events of event stops, in bits 16-23 of the wait status
+pub const PTRACE_EVENT_FORK: i32 = 1;
+pub const PTRACE_EVENT_VFORK: i32 = 2;
+pub const PTRACE_EVENT_CLONE: i32 = 3;
+pub const PTRACE_EVENT_EXEC: i32 = 4;
+pub const PTRACE_EVENT_EXIT: i32 = 6;
+
+/// Trap flag in RFLAGS, raising a debug exception after each instruction
+const X86_EFLAGS_TF: u64 = 1 << 8;
+
+/// How a tracee continues from a stop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resume {
+	Cont,
+	Syscall,    // Stop at the next system call entry or exit
+	SingleStep, // Stop after the next instruction
+}
+
+/// Tracing state of a process
+struct Tracee {
+	tracer: Pid,
+	options: u64,
+	resume: Resume,
+	stop: Option<i32>,   // Wait status while stopped
+	reported: bool,      // Stop already returned by wait4
+	exited: Option<i32>, // Wait status once exited, until the tracer waits
+	stop_tid: Option<Tid>,
+	event_msg: u64,
+	orig_rax: u64, // System call number at a syscall stop, -1 otherwise
+}
+
+static TRACEES: Spinlock<BTreeMap<Pid, Tracee>> = Spinlock::new(BTreeMap::new());
+
+/// Number of traced processes, to keep system calls fast without any
+static NR_TRACEES: AtomicUsize = AtomicUsize::new(0);
+
+fn stopped_status(signal: i32) -> i32 {
+	(signal << 8) | 0x7f
+}
+
+fn event_status(event: i32) -> i32 {
+	stopped_status(signal::SIGTRAP | (event << 8))
+}
+
+fn attach(pid: Pid, tracer: Pid, options: u64, stop: Option<i32>) -> Result<()> {
+	let mut tracees = TRACEES.lock();
+	if tracees.contains_key(&pid) {
+		return Err(Error::EPERM);
+	}
+	tracees.insert(
+		pid,
+		Tracee {
+			tracer,
+			options,
+			resume: Resume::Cont,
+			stop,
+			reported: false,
+			exited: None,
+			stop_tid: None,
+			event_msg: 0,
+			orig_rax: u64::MAX,
+		},
+	);
+	NR_TRACEES.fetch_add(1, Ordering::AcqRel);
+	Ok(())
+}
+
+fn remove(tracees: &mut BTreeMap<Pid, Tracee>, pid: Pid) -> Option<Tracee> {
+	let tracee = tracees.remove(&pid)?;
+	NR_TRACEES.fetch_sub(1, Ordering::AcqRel);
+	Some(tracee)
+}
+
+/// Get the tracer of a process
+pub fn tracer_of(pid: Pid) -> Option<Pid> {
+	TRACEES.lock().get(&pid).map(|tracee| tracee.tracer)
+}
+
+/// Whether `pid` is traced by a tracer without CAP_SYS_PTRACE, which
+/// could not attach to it once privileged. Exec then ignores set-user-ID
+/// and set-group-ID bits.
+pub fn unsafe_exec(pid: Pid) -> bool {
+	tracer_of(pid).is_some_and(|tracer| {
+		!crate::process::find_process(tracer)
+			.is_some_and(|tracer| tracer.cred.capable(CAP_SYS_PTRACE))
+	})
+}
+
+/// The current process if it is traced, with how it was resumed and its
+/// options
+fn current_tracee() -> Option<(Pid, Resume, u64)> {
+	if NR_TRACEES.load(Ordering::Acquire) == 0 {
+		return None;
+	}
+	let pid = crate::process::current_process_pid()?;
+	let tracees = TRACEES.lock();
+	let tracee = tracees.get(&pid)?;
+	Some((pid, tracee.resume, tracee.options))
+}
+
+/// Stop the current process, a tracee, until its tracer resumes or
+/// detaches it
+fn ptrace_stop(pid: Pid, status: i32, event_msg: Option<u64>) {
+	{
+		let mut tracees = TRACEES.lock();
+		let tracee = match tracees.get_mut(&pid) {
+			Some(tracee) => tracee,
+			None => return,
+		};
+		tracee.stop = Some(status);
+		tracee.reported = false;
+		tracee.stop_tid = crate::scheduler::current_tid();
+		if let Some(msg) = event_msg {
+			tracee.event_msg = msg;
+		}
+	}
+	crate::sync::might_sleep();
+	crate::sync::wait_until(|| {
+		TRACEES.lock()
+			.get(&pid)
+			.is_none_or(|tracee| tracee.stop.is_none())
+	});
+}
+
+/// Run `f` on the thread of a tracee that stopped
+fn with_stopped_thread<T>(pid: Pid, f: impl FnOnce(&mut crate::process::Thread) -> T) -> Result<T> {
+	let tid = TRACEES.lock().get(&pid).and_then(|tracee| tracee.stop_tid);
+	crate::process::with_thread(pid, tid, f)
+}
+
+/// Syscall-entry stop. Returns the result of the call if the tracer
+/// cancelled it by setting orig_rax to -1.
+pub fn syscall_enter(args: &mut SyscallArgs) -> Option<u64> {
+	let (pid, resume, options) = current_tracee()?;
+	if resume != Resume::Syscall {
+		return None;
+	}
+	let running = crate::scheduler::current_tid();
+
+	let _ = crate::process::with_thread(pid, running, |thread| {
+		let ctx = &mut thread.context;
+		ctx.rax = Error::ENOSYS.to_syscall_ret();
+		ctx.rdi = args.arg0;
+		ctx.rsi = args.arg1;
+		ctx.rdx = args.arg2;
+		ctx.r10 = args.arg3;
+		ctx.r8 = args.arg4;
+		ctx.r9 = args.arg5;
+	});
+	if let Some(tracee) = TRACEES.lock().get_mut(&pid) {
+		tracee.orig_rax = args.syscall_num;
+	}
+	ptrace_stop(pid, syscall_status(options), None);
+
+	// The tracer may have changed the call
+	let nr = TRACEES
+		.lock()
+		.get(&pid)
+		.map_or(args.syscall_num, |tracee| tracee.orig_rax);
+	let ctx = crate::process::with_thread(pid, running, |thread| thread.context).ok()?;
+	if nr == u64::MAX {
+		return Some(ctx.rax);
+	}
+	*args = SyscallArgs {
+		syscall_num: nr,
+		arg0: ctx.rdi,
+		arg1: ctx.rsi,
+		arg2: ctx.rdx,
+		arg3: ctx.r10,
+		arg4: ctx.r8,
+		arg5: ctx.r9,
+	};
+	None
+}
+
+/// Syscall-exit stop. Returns the result, which the tracer may change.
+pub fn syscall_exit(result: u64) -> u64 {
+	let (pid, resume, options) = match current_tracee() {
+		Some(tracee) => tracee,
+		None => return result,
+	};
+	if resume != Resume::Syscall {
+		return result;
+	}
+	let running = crate::scheduler::current_tid();
+
+	let _ = crate::process::with_thread(pid, running, |thread| thread.context.rax = result);
+	ptrace_stop(pid, syscall_status(options), None);
+	if let Some(tracee) = TRACEES.lock().get_mut(&pid) {
+		tracee.orig_rax = u64::MAX;
+	}
+	crate::process::with_thread(pid, running, |thread| thread.context.rax).unwrap_or(result)
+}
+
+fn syscall_status(options: u64) -> i32 {
+	if options & PTRACE_O_TRACESYSGOOD != 0 {
+		stopped_status(signal::SIGTRAP | 0x80)
+	} else {
+		stopped_status(signal::SIGTRAP)
+	}
+}
+
+/// Stop the current process at a debug exception or breakpoint in user
+/// mode. `regs` are the registers at the trap, replaced by those to resume
+/// with. Returns false if the process isn't traced, or for a single-step
+/// trap it didn't ask for.
+pub fn debug_trap(regs: &mut Context, single_step: bool) -> bool {
+	let (pid, resume, _) = match current_tracee() {
+		Some(tracee) => tracee,
+		None => return false,
+	};
+	if single_step && resume != Resume::SingleStep {
+		return false;
+	}
+
+	let running = crate::scheduler::current_tid();
+	let mut trapped = *regs;
+	trapped.rflags &= !X86_EFLAGS_TF;
+	if crate::process::with_thread(pid, running, |thread| {
+		thread.context = Context {
+			cr3: thread.context.cr3,
+			fpu_state: thread.context.fpu_state,
+			..trapped
+		}
+	})
+	.is_err()
+	{
+		return false;
+	}
+	ptrace_stop(pid, stopped_status(signal::SIGTRAP), None);
+
+	// Resuming sets the trap flag again for PTRACE_SINGLESTEP
+	if let Ok(ctx) = crate::process::with_thread(pid, running, |thread| thread.context) {
+		*regs = ctx;
+	}
+	true
+}
+
+/// Report a new process created by the current process. With the
+/// matching option the child is traced too, starting in a SIGSTOP stop,
+/// and the parent stops for the event.
+pub fn fork_event(parent: Pid, child: Pid, event: i32) {
+	if NR_TRACEES.load(Ordering::Acquire) == 0 {
+		return;
+	}
+	let (tracer, options) = match TRACEES.lock().get(&parent) {
+		Some(tracee) => (tracee.tracer, tracee.options),
+		None => return,
+	};
+	let option = match event {
+		PTRACE_EVENT_FORK => PTRACE_O_TRACEFORK,
+		PTRACE_EVENT_VFORK => PTRACE_O_TRACEVFORK,
+		_ => PTRACE_O_TRACECLONE,
+	};
+	if options & option == 0 {
+		return;
+	}
+
+	let stop = stopped_status(signal::SIGSTOP);
+	if attach(child, tracer, options, Some(stop)).is_ok() {
+		let _ = crate::process::send_signal(child, signal::SIGSTOP);
+	}
+	ptrace_stop(parent, event_status(event), Some(child.0 as u64));
+}
+
+/// Stop the current process after a successful exec: an event stop with
+/// PTRACE_O_TRACEEXEC, otherwise a SIGTRAP stop
+pub fn exec_event(pid: Pid) {
+	if NR_TRACEES.load(Ordering::Acquire) == 0 {
+		return;
+	}
+	let options = match TRACEES.lock().get(&pid) {
+		Some(tracee) => tracee.options,
+		None => return,
+	};
+	if options & PTRACE_O_TRACEEXEC != 0 {
+		ptrace_stop(pid, event_status(PTRACE_EVENT_EXEC), Some(pid.0 as u64));
+	} else {
+		ptrace_stop(pid, stopped_status(signal::SIGTRAP), None);
+	}
+}
+
+/// Stop the current process as it starts exiting, with
+/// PTRACE_O_TRACEEXIT
+pub fn exit_event(pid: Pid, exit_code: i32) {
+	if NR_TRACEES.load(Ordering::Acquire) == 0 {
+		return;
+	}
+	let options = TRACEES.lock().get(&pid).map_or(0, |tracee| tracee.options);
+	if options & PTRACE_O_TRACEEXIT != 0 {
+		let status = ((exit_code & 0xff) << 8) as u64;
+		ptrace_stop(pid, event_status(PTRACE_EVENT_EXIT), Some(status));
+	}
+}
+
+/// Handle the exit of a process with wait status `status`: keep the
+/// status of a tracee for its tracer, and detach the tracees of a tracer
+pub fn exit_notify(pid: Pid, status: i32) {
+	if NR_TRACEES.load(Ordering::Acquire) == 0 {
+		return;
+	}
+	let orphans: Vec<Pid> = {
+		let mut tracees = TRACEES.lock();
+		if let Some(tracee) = tracees.get_mut(&pid) {
+			tracee.exited = Some(status);
+			tracee.stop = None;
+		}
+		let orphans = tracees
+			.iter()
+			.filter(|(_, tracee)| tracee.tracer == pid)
+			.map(|(&tracee, _)| tracee)
+			.collect::<Vec<_>>();
+		for &orphan in &orphans {
+			remove(&mut tracees, orphan);
+		}
+		orphans
+	};
+	for orphan in orphans {
+		release(orphan);
+	}
+}
+
+/// Let a process that is no longer traced run freely
+fn release(pid: Pid) {
+	let _ = crate::process::with_thread(pid, None, |thread| {
+		thread.context.rflags &= !X86_EFLAGS_TF;
+	});
+	let stopped = crate::process::find_process(pid)
+		.is_some_and(|process| process.state == ProcessState::Stopped);
+	if stopped {
+		let _ = crate::process::send_signal(pid, signal::SIGCONT);
+	}
+}
+
+/// Wait for a tracee of `tracer` to stop or exit, as part of wait4.
+/// `target` selects one tracee, or any if None. Returns None if there is
+/// no such tracee, and PID 0 with `nohang` if none has changed state.
+#[track_caller]
+pub fn wait_tracee(tracer: Pid, target: Option<Pid>, nohang: bool) -> Option<(Pid, i32)> {
+	let (any, found) = poll_tracees(tracer, target);
+	if !any || found.is_some() {
+		return found;
+	}
+	if nohang {
+		return Some((Pid(0), 0));
+	}
+
+	crate::sync::might_sleep();
+	let mut result = None;
+	crate::sync::wait_until(|| {
+		let (any, found) = poll_tracees(tracer, target);
+		result = found;
+		!any || found.is_some()
+	});
+	result
+}
+
+/// Find a stop or exit of a tracee of `tracer` to report, and whether
+/// there is any tracee to wait for
+fn poll_tracees(tracer: Pid, target: Option<Pid>) -> (bool, Option<(Pid, i32)>) {
+	let mut tracees = TRACEES.lock();
+	let mut any = false;
+	let mut found = None;
+	for (&pid, tracee) in tracees.iter_mut() {
+		if tracee.tracer != tracer || target.is_some_and(|target| target != pid) {
+			continue;
+		}
+		any = true;
+		if let Some(status) = tracee.exited {
+			found = Some((pid, status));
+			break;
+		}
+		if let Some(status) = tracee.stop.filter(|_| !tracee.reported) {
+			tracee.reported = true;
+			found = Some((pid, status));
+			break;
+		}
+	}
+	// An exited tracee is gone once reported
+	if let Some((pid, _)) = found {
+		if tracees
+			.get(&pid)
+			.is_some_and(|tracee| tracee.exited.is_some())
+		{
+			remove(&mut tracees, pid);
+		}
+	}
+	(any, found)
+}
+
+/// Make the current process a tracee of its parent (PTRACE_TRACEME)
+pub fn traceme(current: &Process) -> Result<()> {
+	let parent = current.parent.ok_or(Error::EPERM)?;
+	attach(current.pid, parent, 0, None)
+}
+
+/// Attach `tracer` to `pid` and stop it with SIGSTOP (PTRACE_ATTACH).
+/// Needs matching user and group IDs and a dumpable target, or
+/// CAP_SYS_PTRACE.
+fn attach_to(tracer: &Process, pid: Pid) -> Result<()> {
+	if pid == tracer.pid {
+		return Err(Error::EPERM);
+	}
+	let target = crate::process::find_process(pid).ok_or(Error::ESRCH)?;
+	let (from, to) = (&tracer.cred, &target.cred);
+	let same_ids = [to.uid, to.euid, to.suid].iter().all(|&id| id == from.uid)
+		&& [to.gid, to.egid, to.sgid].iter().all(|&id| id == from.gid);
+	if !(same_ids && target.dumpable) && !from.capable(CAP_SYS_PTRACE) {
+		return Err(Error::EPERM);
+	}
+
+	attach(pid, tracer.pid, 0, Some(stopped_status(signal::SIGSTOP)))?;
+	crate::process::send_signal(pid, signal::SIGSTOP)
+}
+
+/// Check that `pid` is traced by `tracer` and, unless `any_state`,
+/// stopped
+fn check_tracee(tracer: Pid, pid: Pid, any_state: bool) -> Result<()> {
+	let tracees = TRACEES.lock();
+	match tracees.get(&pid) {
+		Some(tracee)
+			if tracee.tracer == tracer
+				&& tracee.exited.is_none()
+				&& (any_state || tracee.stop.is_some()) =>
+		{
+			Ok(())
+		}
+		_ => Err(Error::ESRCH),
+	}
+}
+
+/// Continue a stopped tracee, delivering `signal` if it isn't 0
+fn resume(pid: Pid, how: Resume, signal: u64) -> Result<()> {
+	if signal != 0 {
+		signal::validate(signal as i32)?;
+	}
+	crate::process::with_thread(pid, None, |thread| {
+		if how == Resume::SingleStep {
+			thread.context.rflags |= X86_EFLAGS_TF;
+		} else {
+			thread.context.rflags &= !X86_EFLAGS_TF;
+		}
+	})?;
+	if let Some(tracee) = TRACEES.lock().get_mut(&pid) {
+		tracee.resume = how;
+		tracee.stop = None;
+	}
+
+	// A tracee stopped by SIGSTOP is in a job control stop
+	let stopped = crate::process::find_process(pid)
+		.is_some_and(|process| process.state == ProcessState::Stopped);
+	if stopped {
+		crate::process::send_signal(pid, signal::SIGCONT)?;
+	}
+	if signal != 0 {
+		crate::process::send_signal(pid, signal as i32)?;
+	}
+	Ok(())
+}
+
+/// Copy between `buf` and the memory of a tracee
+fn access_tracee(pid: Pid, addr: u64, buf: &mut [u8], write: bool) -> Result<()> {
+	let process = crate::process::find_process(pid).ok_or(Error::ESRCH)?;
+	crate::memory::access_process_vm(
+		&process.mappings,
+		VirtAddr::new(addr as usize),
+		buf,
+		write,
+	)
+	.map_err(|_| Error::EIO)
+}
+
+/// Handle a ptrace request of `tracer` other than PTRACE_TRACEME
+pub fn ptrace(tracer: &Process, request: u64, pid: Pid, addr: u64, data: u64) -> Result<u64> {
+	if request == PTRACE_ATTACH {
+		attach_to(tracer, pid)?;
+		return Ok(0);
+	}
+	check_tracee(tracer.pid, pid, request == PTRACE_KILL)?;
+
+	match request {
+		PTRACE_PEEKTEXT | PTRACE_PEEKDATA => {
+			let mut word = [0u8; 8];
+			access_tracee(pid, addr, &mut word, false)?;
+			copy_to_user(UserPtr::new(data as *mut u8)?, &word)?;
+		}
+		PTRACE_POKETEXT | PTRACE_POKEDATA => {
+			access_tracee(pid, addr, &mut data.to_ne_bytes(), true)?;
+		}
+		PTRACE_GETREGS => {
+			let orig_rax = TRACEES
+				.lock()
+				.get(&pid)
+				.map_or(u64::MAX, |tracee| tracee.orig_rax);
+			let regs = with_stopped_thread(pid, |thread| {
+				thread.context.user_regs(orig_rax, thread.fs_base)
+			})?;
+			copy_to_user(UserPtr::new(data as *mut u8)?, regs.as_bytes())?;
+		}
+		PTRACE_SETREGS => {
+			let mut regs = UserRegs::default();
+			copy_from_user(regs.as_bytes_mut(), UserPtr::new(data as *mut u8)?)?;
+			with_stopped_thread(pid, |thread| -> Result<()> {
+				thread.context.set_user_regs(&regs)?;
+				thread.fs_base = regs.fs_base;
+				Ok(())
+			})??;
+			if let Some(tracee) = TRACEES.lock().get_mut(&pid) {
+				tracee.orig_rax = regs.orig_rax;
+			}
+		}
+		PTRACE_CONT => resume(pid, Resume::Cont, data)?,
+		PTRACE_SYSCALL => resume(pid, Resume::Syscall, data)?,
+		PTRACE_SINGLESTEP => resume(pid, Resume::SingleStep, data)?,
+		PTRACE_KILL => crate::process::send_signal(pid, signal::SIGKILL)?,
+		PTRACE_DETACH => {
+			remove(&mut TRACEES.lock(), pid);
+			release(pid);
+			if data != 0 {
+				crate::process::send_signal(pid, data as i32)?;
+			}
+		}
+		PTRACE_SETOPTIONS => {
+			if data & !PTRACE_O_MASK != 0 {
+				return Err(Error::EINVAL);
+			}
+			if let Some(tracee) = TRACEES.lock().get_mut(&pid) {
+				tracee.options = data;
+			}
+		}
+		PTRACE_GETEVENTMSG => {
+			let msg = TRACEES
+				.lock()
+				.get(&pid)
+				.map_or(0, |tracee| tracee.event_msg);
+			copy_to_user(UserPtr::new(data as *mut u8)?, &msg.to_ne_bytes())?;
+		}
+		_ => return Err(Error::EIO),
+	}
+	Ok(0)
+}
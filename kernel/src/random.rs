@@ -0,0 +1,556 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Random numbers - an entropy pool and a ChaCha20 CSPRNG, similar to
+//! Linux drivers/char/random.c
+//!
+//! Entropy sources feed an input pool: RDSEED and RDRAND when the CPU has
+//! them, TSC jitter, interrupt timing, keyboard input and network packets.
+//! The pool seeds a ChaCha20 generator once it holds 256 bits of entropy.
+//! The generator replaces its key after every request, so earlier output
+//! can't be recovered, and is reseeded from the pool every minute.
+//!
+//! Interrupt handlers can't take the pool lock, so they mix into a small
+//! lock-free fast pool that is folded into the input pool later.
+
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+
+use spin::once::Once;
+
+use crate::capability::CAP_SYS_ADMIN;
+use crate::error::{Error, Result};
+use crate::memory::{copy_from_user, copy_to_user, UserPtr};
+use crate::sync::{Arc, Spinlock, WaitQueue};
+use crate::time::NSEC_PER_SEC;
+
+/// Entropy the input pool holds at most, and needs to seed the generator
+const POOL_BITS: u32 = 256;
+
+/// Time between reseeds of a seeded generator
+const RESEED_INTERVAL_NS: u64 = 60 * NSEC_PER_SEC;
+
+/// Fast pool events that make up one bit of entropy
+const FAST_EVENTS_PER_BIT: u32 = 64;
+
+/// Changed jitter samples that make up one bit of entropy, and the most
+/// jitter_entropy credits. Jitter alone cannot seed the generator; the CPU
+/// or interrupts have to provide the rest.
+const JITTER_SAMPLES_PER_BIT: u32 = 256;
+const JITTER_MAX_BITS: u32 = 64;
+
+/// Bytes the generator hands out per lock hold
+const CHUNK_SIZE: usize = 256;
+
+/// Bytes between signal checks of a long read
+const PAGE_SIZE: usize = 4096;
+
+/// ioctls of /dev/random and /dev/urandom
+pub const RNDGETENTCNT: u32 = 0x80045200;
+pub const RNDADDTOENTCNT: u32 = 0x40045201;
+pub const RNDADDENTROPY: u32 = 0x40085203;
+pub const RNDZAPENTCNT: u32 = 0x5204;
+pub const RNDCLEARPOOL: u32 = 0x5206;
+pub const RNDRESEEDCRNG: u32 = 0x5207;
+
+/// "expand 32-byte k"
+const CHACHA_CONSTANTS: [u32; 4] = [0x61707865, 0x3320646e, 0x79622d32, 0x6b206574];
+
+pub(crate) fn quarter_round(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+	s[a] = s[a].wrapping_add(s[b]);
+	s[d] = (s[d] ^ s[a]).rotate_left(16);
+	s[c] = s[c].wrapping_add(s[d]);
+	s[b] = (s[b] ^ s[c]).rotate_left(12);
+	s[a] = s[a].wrapping_add(s[b]);
+	s[d] = (s[d] ^ s[a]).rotate_left(8);
+	s[c] = s[c].wrapping_add(s[d]);
+	s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+/// The 20 rounds of the ChaCha permutation
+fn chacha_permute(state: &mut [u32; 16]) {
+	for _ in 0..10 {
+		quarter_round(state, 0, 4, 8, 12);
+		quarter_round(state, 1, 5, 9, 13);
+		quarter_round(state, 2, 6, 10, 14);
+		quarter_round(state, 3, 7, 11, 15);
+		quarter_round(state, 0, 5, 10, 15);
+		quarter_round(state, 1, 6, 11, 12);
+		quarter_round(state, 2, 7, 8, 13);
+		quarter_round(state, 3, 4, 9, 14);
+	}
+}
+
+/// ChaCha20 block `counter` of the stream of `key`, with a zero nonce
+fn chacha20_block(key: &[u32; 8], counter: u64) -> [u8; 64] {
+	let mut input = [0u32; 16];
+	input[..4].copy_from_slice(&CHACHA_CONSTANTS);
+	input[4..12].copy_from_slice(key);
+	input[12] = counter as u32;
+	input[13] = (counter >> 32) as u32;
+	chacha20_core(&input)
+}
+
+/// ChaCha20 block function over a full input state
+pub(crate) fn chacha20_core(input: &[u32; 16]) -> [u8; 64] {
+	let mut state = *input;
+	chacha_permute(&mut state);
+	let mut block = [0u8; 64];
+	for (i, word) in state.iter().enumerate() {
+		let word = word.wrapping_add(input[i]);
+		block[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+	}
+	block
+}
+
+/// Words of a 32-byte key
+fn key_words(bytes: &[u8]) -> [u32; 8] {
+	let mut key = [0u32; 8];
+	for (word, chunk) in key.iter_mut().zip(bytes.as_chunks::<4>().0) {
+		*word = u32::from_le_bytes(*chunk);
+	}
+	key
+}
+
+/// The input pool - a sponge over the ChaCha permutation. Input is
+/// absorbed into the first half of the state, the second half is never
+/// output.
+struct InputPool {
+	state: [u32; 16],
+	entropy_bits: u32, // Entropy credited since the last reseed
+}
+
+impl InputPool {
+	const fn new() -> Self {
+		Self {
+			state: [0; 16],
+			entropy_bits: 0,
+		}
+	}
+
+	fn mix(&mut self, data: &[u8]) {
+		for chunk in data.chunks(32) {
+			for (i, byte) in chunk.iter().enumerate() {
+				self.state[i / 4] ^= (*byte as u32) << (8 * (i % 4));
+			}
+			chacha_permute(&mut self.state);
+		}
+	}
+
+	fn credit(&mut self, bits: u32) {
+		self.entropy_bits = (self.entropy_bits + bits).min(POOL_BITS);
+	}
+
+	/// Squeeze a 32-byte seed out of the pool. The state moves on, so
+	/// the seed can't be worked back from what remains.
+	fn extract(&mut self) -> [u32; 8] {
+		chacha_permute(&mut self.state);
+		let mut seed = [0u32; 8];
+		seed.copy_from_slice(&self.state[..8]);
+		self.state[..8].fill(0);
+		chacha_permute(&mut self.state);
+		self.entropy_bits = 0;
+		seed
+	}
+}
+
+/// The ChaCha20 generator
+struct Crng {
+	key: [u32; 8],
+	seeded: bool,
+	last_reseed: u64, // Boot time in ns
+}
+
+static INPUT_POOL: Spinlock<InputPool> = Spinlock::new(InputPool::new());
+
+static CRNG: Spinlock<Crng> = Spinlock::new(Crng {
+	key: [0; 8],
+	seeded: false,
+	last_reseed: 0,
+});
+
+/// Set once the generator was seeded with a full pool
+static CRNG_READY: AtomicBool = AtomicBool::new(false);
+
+/// Woken when the generator becomes ready
+static READY_QUEUE: Once<Arc<WaitQueue>> = Once::new();
+
+/// The fast pool, mixed into from interrupt context
+static FAST_POOL: [AtomicU64; 4] = [
+	AtomicU64::new(0),
+	AtomicU64::new(0),
+	AtomicU64::new(0),
+	AtomicU64::new(0),
+];
+static FAST_EVENTS: AtomicU32 = AtomicU32::new(0);
+static FAST_CREDIT: AtomicU32 = AtomicU32::new(0); // In 1/64 bits
+
+/// Hardware random number instructions, as sysinfo reports them
+static CPU_RNG: Once<(bool, bool)> = Once::new();
+
+fn read_tsc() -> u64 {
+	unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// (RDRAND, RDSEED) support of the CPU
+fn cpu_rng() -> (bool, bool) {
+	*CPU_RNG.call_once(|| {
+		let cpu = crate::sysinfo::CpuInfo::detect();
+		let has = |name: &str| cpu.features.iter().any(|f| f == name);
+		(has("RDRAND"), has("RDSEED"))
+	})
+}
+
+/// A value from RDRAND, or RDSEED with `seed`, retrying a few times as
+/// both can run dry
+fn cpu_random(seed: bool) -> Option<u64> {
+	for _ in 0..10 {
+		let value: u64;
+		let ok: u8;
+		unsafe {
+			if seed {
+				core::arch::asm!("rdseed {}", "setc {}", out(reg) value, out(reg_byte) ok,
+					options(nomem, nostack));
+			} else {
+				core::arch::asm!("rdrand {}", "setc {}", out(reg) value, out(reg_byte) ok,
+					options(nomem, nostack));
+			}
+		}
+		if ok == 1 {
+			return Some(value);
+		}
+		core::hint::spin_loop();
+	}
+	None
+}
+
+/// 32 bytes from the CPU's generator: RDSEED if present, else RDRAND
+fn cpu_seed() -> Option<[u8; 32]> {
+	let (rdrand, rdseed) = cpu_rng();
+	if !rdrand && !rdseed {
+		return None;
+	}
+	let mut seed = [0u8; 32];
+	for chunk in seed.as_chunks_mut::<8>().0 {
+		let value = rdseed
+			.then(|| cpu_random(true))
+			.flatten()
+			.or_else(|| rdrand.then(|| cpu_random(false)).flatten())?;
+		*chunk = value.to_ne_bytes();
+	}
+	Some(seed)
+}
+
+/// Mix `value` and the time stamp counter into the fast pool, crediting
+/// `credit` 1/64 bits of entropy. Safe in interrupt context.
+fn fast_mix(value: u64, credit: u32) {
+	let events = FAST_EVENTS.fetch_add(1, Ordering::Relaxed);
+	let slot = &FAST_POOL[events as usize % FAST_POOL.len()];
+	let mixed = (slot.load(Ordering::Relaxed) ^ value ^ read_tsc().rotate_left(events % 64))
+		.rotate_left(23)
+		.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+	slot.store(mixed, Ordering::Relaxed);
+	if credit != 0 {
+		FAST_CREDIT.fetch_add(credit, Ordering::Relaxed);
+	}
+}
+
+/// Fold the fast pool into the input pool
+fn drain_fast_pool() {
+	if FAST_EVENTS.swap(0, Ordering::Relaxed) == 0 {
+		return;
+	}
+	let mut data = [0u8; 32];
+	for (chunk, slot) in data.as_chunks_mut::<8>().0.iter_mut().zip(&FAST_POOL) {
+		*chunk = slot.swap(0, Ordering::Relaxed).to_ne_bytes();
+	}
+	let credit = FAST_CREDIT.swap(0, Ordering::Relaxed) / FAST_EVENTS_PER_BIT;
+	let mut pool = INPUT_POOL.lock();
+	pool.mix(&data);
+	pool.credit(credit);
+}
+
+/// Timing of a hardware interrupt. Each interrupt counts for 1/64 bit.
+pub fn add_interrupt_randomness() {
+	fast_mix(0, 1);
+}
+
+/// A keyboard event. Each counts for one bit.
+pub fn add_input_randomness(value: u64) {
+	fast_mix(value, FAST_EVENTS_PER_BIT);
+}
+
+/// A received network packet, mixed in without credit since its timing
+/// and contents can be seen from outside
+pub fn add_network_randomness(data: &[u8]) {
+	let mut value = data.len() as u64;
+	for chunk in data.chunks(8).take(4) {
+		let mut bytes = [0u8; 8];
+		bytes[..chunk.len()].copy_from_slice(chunk);
+		value = value.rotate_left(13) ^ u64::from_ne_bytes(bytes);
+	}
+	fast_mix(value, 0);
+}
+
+/// Mix data into the input pool, crediting `bits` of entropy
+pub fn add_entropy(data: &[u8], bits: u32) {
+	let mut pool = INPUT_POOL.lock();
+	pool.mix(data);
+	pool.credit(bits);
+}
+
+/// Entropy in the input pool, in bits
+pub fn entropy_count() -> u32 {
+	drain_fast_pool();
+	INPUT_POOL.lock().entropy_bits
+}
+
+/// Forget the entropy credited to the input pool
+pub fn clear_entropy_count() {
+	INPUT_POOL.lock().entropy_bits = 0;
+}
+
+/// Seed the generator from the input pool, and the CPU if it can
+fn reseed() {
+	let mut seed = INPUT_POOL.lock().extract();
+	if let Some(cpu) = cpu_seed() {
+		for (word, extra) in seed.iter_mut().zip(key_words(&cpu)) {
+			*word ^= extra;
+		}
+	}
+
+	let mut crng = CRNG.lock();
+	for (word, extra) in crng.key.iter_mut().zip(seed) {
+		*word ^= extra;
+	}
+	crng.seeded = true;
+	crng.last_reseed = crate::time::get_time_ns();
+	drop(crng);
+
+	if !CRNG_READY.swap(true, Ordering::AcqRel) {
+		crate::info!("random: crng init done");
+		ready_queue().wake_up();
+	}
+}
+
+/// Reseed the generator when due: first once the pool is full, then
+/// every RESEED_INTERVAL_NS
+fn maybe_reseed() {
+	drain_fast_pool();
+	let due = if CRNG_READY.load(Ordering::Acquire) {
+		let last = CRNG.lock().last_reseed;
+		crate::time::get_time_ns().saturating_sub(last) >= RESEED_INTERVAL_NS
+	} else {
+		INPUT_POOL.lock().entropy_bits >= POOL_BITS
+	};
+	if due {
+		reseed();
+	}
+}
+
+/// Reseed the generator now, if it was seeded before
+pub fn force_reseed() {
+	drain_fast_pool();
+	if CRNG_READY.load(Ordering::Acquire) {
+		reseed();
+	}
+}
+
+fn ready_queue() -> &'static Arc<WaitQueue> {
+	READY_QUEUE.call_once(|| Arc::new(WaitQueue::new()))
+}
+
+/// Whether the generator was seeded with a full pool
+pub fn crng_ready() -> bool {
+	if !CRNG_READY.load(Ordering::Acquire) {
+		maybe_reseed();
+	}
+	CRNG_READY.load(Ordering::Acquire)
+}
+
+/// Wait queue woken when the generator becomes ready
+pub fn ready_wait_queue() -> Arc<WaitQueue> {
+	ready_queue().clone()
+}
+
+/// Sleep until the generator is ready. Fails with EINTR if a signal
+/// arrives first.
+pub fn wait_for_random_bytes() -> Result<()> {
+	if crng_ready() {
+		return Ok(());
+	}
+	crate::sync::might_sleep();
+	crate::sync::wait_until(|| crng_ready() || crate::process::signal_pending());
+	if crng_ready() {
+		Ok(())
+	} else {
+		Err(Error::Interrupted)
+	}
+}
+
+/// Fill `buf` with random bytes from the generator, which may not be
+/// seeded yet early in boot
+pub fn get_random_bytes(buf: &mut [u8]) {
+	maybe_reseed();
+	for chunk in buf.chunks_mut(CHUNK_SIZE) {
+		// Fast key erasure: the first block replaces the key and keys a
+		// stream for this chunk
+		let first = {
+			let mut crng = CRNG.lock();
+			let first = chacha20_block(&crng.key, 0);
+			crng.key = key_words(&first[..32]);
+			first
+		};
+		let stream_key = key_words(&first[32..]);
+		for (counter, out) in chunk.chunks_mut(64).enumerate() {
+			let block = chacha20_block(&stream_key, counter as u64);
+			out.copy_from_slice(&block[..out.len()]);
+		}
+	}
+}
+
+/// Generate `len` random bytes for a reader, a chunk at a time in a stack
+/// buffer, handing `copy` each chunk and its offset. A pending signal ends
+/// it early after a page. Returns the bytes copied, failing only if the
+/// first copy does.
+pub fn get_random_bytes_chunked(
+	len: usize,
+	mut copy: impl FnMut(usize, &[u8]) -> Result<()>,
+) -> Result<usize> {
+	let mut chunk = [0u8; CHUNK_SIZE];
+	let mut done = 0;
+	while done < len {
+		if done % PAGE_SIZE == 0 && done > 0 && crate::process::signal_pending() {
+			break;
+		}
+		let n = (len - done).min(CHUNK_SIZE);
+		get_random_bytes(&mut chunk[..n]);
+		if let Err(err) = copy(done, &chunk[..n]) {
+			if done == 0 {
+				return Err(err);
+			}
+			break;
+		}
+		done += n;
+	}
+	chunk.fill(0);
+	Ok(done)
+}
+
+/// A random u64
+pub fn get_random_u64() -> u64 {
+	let mut bytes = [0u8; 8];
+	get_random_bytes(&mut bytes);
+	u64::from_ne_bytes(bytes)
+}
+
+/// Largest RNDADDENTROPY buffer
+const MAX_ADD_ENTROPY: usize = 4096;
+
+/// ioctl of /dev/random and /dev/urandom
+pub fn random_ioctl(cmd: u32, arg: usize) -> Result<isize> {
+	match cmd {
+		RNDGETENTCNT => {
+			let count = entropy_count() as i32;
+			copy_to_user(UserPtr::new(arg as *mut u8)?, &count.to_ne_bytes())?;
+			Ok(0)
+		}
+		RNDADDTOENTCNT => {
+			crate::capability::require(CAP_SYS_ADMIN)?;
+			let mut bytes = [0u8; 4];
+			copy_from_user(&mut bytes, UserPtr::from_const(arg as *const u8)?)?;
+			let bits = i32::from_ne_bytes(bytes);
+			if bits < 0 {
+				return Err(Error::EINVAL);
+			}
+			INPUT_POOL.lock().credit(bits as u32);
+			maybe_reseed();
+			Ok(0)
+		}
+		RNDADDENTROPY => {
+			// struct rand_pool_info { int entropy_count; int
+			// buf_size; __u32 buf[]; }
+			crate::capability::require(CAP_SYS_ADMIN)?;
+			let mut header = [0u8; 8];
+			copy_from_user(&mut header, UserPtr::from_const(arg as *const u8)?)?;
+			let bits = i32::from_ne_bytes(header[..4].try_into().unwrap());
+			let size = i32::from_ne_bytes(header[4..].try_into().unwrap());
+			if bits < 0 || size < 0 || size as usize > MAX_ADD_ENTROPY {
+				return Err(Error::EINVAL);
+			}
+			let mut data = alloc::vec![0u8; size as usize];
+			copy_from_user(&mut data, UserPtr::from_const((arg + 8) as *const u8)?)?;
+			add_entropy(&data, (bits as u32).min(size as u32 * 8));
+			maybe_reseed();
+			Ok(0)
+		}
+		RNDZAPENTCNT | RNDCLEARPOOL => {
+			crate::capability::require(CAP_SYS_ADMIN)?;
+			clear_entropy_count();
+			Ok(0)
+		}
+		RNDRESEEDCRNG => {
+			crate::capability::require(CAP_SYS_ADMIN)?;
+			if !crng_ready() {
+				return Err(Error::ENODATA);
+			}
+			force_reseed();
+			Ok(0)
+		}
+		_ => Err(Error::ENOTTY),
+	}
+}
+
+/// Gather entropy from the jitter of the time stamp counter across short
+/// busy loops, one bit per JITTER_SAMPLES_PER_BIT samples whose delta
+/// changed, up to JITTER_MAX_BITS
+fn jitter_entropy() {
+	let mut last = read_tsc();
+	let mut last_delta = 0;
+	let mut changed = 0;
+	for _ in 0..JITTER_MAX_BITS * JITTER_SAMPLES_PER_BIT {
+		for _ in 0..(last & 0xf) {
+			core::hint::spin_loop();
+		}
+		let now = read_tsc();
+		let delta = now.wrapping_sub(last);
+		fast_mix(delta, 0);
+		changed += (delta != last_delta) as u32;
+		last = now;
+		last_delta = delta;
+	}
+	drain_fast_pool();
+	INPUT_POOL
+		.lock()
+		.credit((changed / JITTER_SAMPLES_PER_BIT).min(JITTER_MAX_BITS));
+}
+
+/// Seed the generator at boot
+pub fn init() -> Result<()> {
+	let boot = [crate::time::get_realtime().to_ns(), read_tsc()];
+	let mut data = [0u8; 16];
+	data[..8].copy_from_slice(&boot[0].to_ne_bytes());
+	data[8..].copy_from_slice(&boot[1].to_ne_bytes());
+	add_entropy(&data, 0);
+
+	// The CPU's generator is trusted to seed us fully, as Linux does by
+	// default with random.trust_cpu
+	let (rdrand, rdseed) = cpu_rng();
+	if let Some(seed) = cpu_seed() {
+		add_entropy(&seed, POOL_BITS);
+	}
+	if entropy_count() < POOL_BITS {
+		jitter_entropy();
+	}
+	maybe_reseed();
+
+	crate::info!(
+		"random: RDRAND {}, RDSEED {}, crng {}",
+		if rdrand { "yes" } else { "no" },
+		if rdseed { "yes" } else { "no" },
+		if crng_ready() {
+			"ready"
+		} else {
+			"waiting for entropy"
+		}
+	);
+	Ok(())
+}
@@ -0,0 +1,278 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Read-copy-update - lock-free readers for read-mostly data
+//!
+//! Readers mark their critical sections with rcu_read_lock. Updaters
+//! publish a new copy and free the old one only after a grace period, once
+//! every reader that could still see it has left its critical section.
+//!
+//! Readers are counted in one of two phases. A grace period flips the
+//! phase and ends when the old phase has no readers left. Context switches
+//! and timer ticks are quiescent states that drive grace periods forward
+//! for call_rcu, and synchronize_rcu drives them itself.
+
+use alloc::{boxed::Box, collections::VecDeque, sync::Arc, vec::Vec};
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+
+use crate::sync::Spinlock;
+
+/// Readers in each phase
+static READERS: [AtomicUsize; 2] = [AtomicUsize::new(0), AtomicUsize::new(0)];
+
+/// Phase new readers join
+static PHASE: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of grace periods completed
+static GP_COMPLETED: AtomicU64 = AtomicU64::new(0);
+
+type RcuCallback = Box<dyn FnOnce() + Send>;
+
+/// Grace period and callback state
+struct RcuState {
+	gp_in_progress: bool,
+	callbacks: VecDeque<(u64, RcuCallback)>, // Grace period each waits for
+	ready: Vec<RcuCallback>,                 // Past their grace period
+}
+
+impl RcuState {
+	const fn new() -> Self {
+		Self {
+			gp_in_progress: false,
+			callbacks: VecDeque::new(),
+			ready: Vec::new(),
+		}
+	}
+
+	/// Grace period that must complete before something retired now can
+	/// be freed. A grace period already running may have missed readers
+	/// that started before the retirement, so wait for the next one.
+	fn gp_target(&self) -> u64 {
+		let completed = GP_COMPLETED.load(Ordering::Acquire);
+		if self.gp_in_progress {
+			completed + 2
+		} else {
+			completed + 1
+		}
+	}
+
+	/// End the running grace period if the old phase has no readers left.
+	/// Returns whether no grace period is running afterwards.
+	fn try_end_gp(&mut self) -> bool {
+		if self.gp_in_progress {
+			let old = (PHASE.load(Ordering::SeqCst) + 1) % 2;
+			if READERS[old].load(Ordering::SeqCst) != 0 {
+				return false;
+			}
+			self.gp_in_progress = false;
+			GP_COMPLETED.fetch_add(1, Ordering::AcqRel);
+		}
+		true
+	}
+
+	/// Start a grace period if a callback or `wanted` still waits for one
+	fn start_gp_if_needed(&mut self, wanted: Option<u64>) {
+		let completed = GP_COMPLETED.load(Ordering::Acquire);
+		let needed = self
+			.callbacks
+			.back()
+			.map_or(false, |(gp, _)| *gp > completed)
+			|| wanted.map_or(false, |gp| gp > completed);
+		if needed {
+			PHASE.fetch_add(1, Ordering::SeqCst);
+			self.gp_in_progress = true;
+		}
+	}
+
+	/// Advance the grace period machine as far as it can go without
+	/// waiting, collecting callbacks that are ready to run
+	fn advance(&mut self, wanted: Option<u64>) {
+		if !self.try_end_gp() {
+			return;
+		}
+
+		let completed = GP_COMPLETED.load(Ordering::Acquire);
+		while let Some((target, _)) = self.callbacks.front() {
+			if *target > completed {
+				break;
+			}
+			if let Some((_, callback)) = self.callbacks.pop_front() {
+				self.ready.push(callback);
+			}
+		}
+
+		self.start_gp_if_needed(wanted);
+	}
+}
+
+static RCU_STATE: Spinlock<RcuState> = Spinlock::new(RcuState::new());
+
+/// Read-side critical section, ended when dropped
+pub struct RcuReadGuard {
+	phase: usize,
+	_not_send: PhantomData<*const ()>, // Must be released where taken
+}
+
+impl Drop for RcuReadGuard {
+	fn drop(&mut self) {
+		READERS[self.phase].fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
+/// Enter a read-side critical section. Sections nest and may be
+/// preempted, but must not sleep.
+pub fn rcu_read_lock() -> RcuReadGuard {
+	loop {
+		let phase = PHASE.load(Ordering::SeqCst) % 2;
+		READERS[phase].fetch_add(1, Ordering::SeqCst);
+		// Retry if a grace period flipped the phase under us, so it
+		// can't end without seeing this reader
+		if PHASE.load(Ordering::SeqCst) % 2 == phase {
+			return RcuReadGuard {
+				phase,
+				_not_send: PhantomData,
+			};
+		}
+		READERS[phase].fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
+/// Check whether any read-side critical section is active
+pub fn rcu_read_lock_held() -> bool {
+	READERS.iter().any(|r| r.load(Ordering::Relaxed) != 0)
+}
+
+/// Report a quiescent state. Called on context switches and timer ticks,
+/// which may interrupt an update, so this never spins on the state lock
+/// and leaves callbacks to rcu_process_callbacks.
+pub fn rcu_note_quiescent_state() {
+	if let Some(mut state) = RCU_STATE.try_lock() {
+		if state.try_end_gp() {
+			state.start_gp_if_needed(None);
+		}
+	}
+}
+
+/// Run callbacks whose grace period has ended. Must be called from
+/// process context, as callbacks may free memory.
+pub fn rcu_process_callbacks() {
+	let ready = {
+		let mut state = RCU_STATE.lock();
+		state.advance(None);
+		core::mem::take(&mut state.ready)
+	};
+	for callback in ready {
+		callback();
+	}
+}
+
+/// Run `func` after a grace period
+pub fn call_rcu(func: impl FnOnce() + Send + 'static) {
+	let mut state = RCU_STATE.lock();
+	let target = state.gp_target();
+	state.callbacks.push_back((target, Box::new(func)));
+	state.advance(None);
+}
+
+/// Wait until every read-side critical section that started before the
+/// call has ended
+#[track_caller]
+pub fn synchronize_rcu() {
+	crate::sync::might_sleep();
+	let target = RCU_STATE.lock().gp_target();
+	crate::sync::wait_until(|| {
+		RCU_STATE.lock().advance(Some(target));
+		GP_COMPLETED.load(Ordering::Acquire) >= target
+	});
+	rcu_process_callbacks();
+}
+
+/// Wait for every callback queued so far to run
+#[track_caller]
+pub fn rcu_barrier() {
+	crate::sync::might_sleep();
+	let target = RCU_STATE.lock().gp_target();
+	crate::sync::wait_until(|| {
+		rcu_process_callbacks();
+		GP_COMPLETED.load(Ordering::Acquire) >= target
+	});
+	rcu_process_callbacks();
+}
+
+/// Number of completed grace periods
+pub fn gp_completed() -> u64 {
+	GP_COMPLETED.load(Ordering::Acquire)
+}
+
+/// Pointer to RCU-protected shared data. Readers get the current version
+/// without locking; updaters copy it, change the copy and publish it.
+pub struct RcuCell<T: Send + Sync + 'static> {
+	ptr: AtomicPtr<T>, // From Arc::into_raw
+	update_lock: Spinlock<()>,
+}
+
+unsafe impl<T: Send + Sync + 'static> Send for RcuCell<T> {}
+unsafe impl<T: Send + Sync + 'static> Sync for RcuCell<T> {}
+
+impl<T: Send + Sync + 'static> RcuCell<T> {
+	pub fn new(value: T) -> Self {
+		Self {
+			ptr: AtomicPtr::new(Arc::into_raw(Arc::new(value)) as *mut T),
+			update_lock: Spinlock::new(()),
+		}
+	}
+
+	/// Get a reference to the current version
+	pub fn read(&self) -> Arc<T> {
+		let _guard = rcu_read_lock();
+		let ptr = self.ptr.load(Ordering::Acquire);
+		// The version can't be freed before the guard is dropped
+		unsafe {
+			Arc::increment_strong_count(ptr);
+			Arc::from_raw(ptr)
+		}
+	}
+
+	/// Run `f` on the current version without taking a reference
+	pub fn read_with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+		let _guard = rcu_read_lock();
+		let ptr = self.ptr.load(Ordering::Acquire);
+		f(unsafe { &*ptr })
+	}
+
+	/// Publish a new version and free the old one after a grace period
+	pub fn replace(&self, value: T) {
+		let _lock = self.update_lock.lock();
+		self.publish(value);
+	}
+
+	/// Copy the current version, let `f` change the copy and publish it.
+	/// Updaters are serialized, readers never wait.
+	pub fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> R
+	where T: Clone {
+		let _lock = self.update_lock.lock();
+		let mut copy = unsafe { (*self.ptr.load(Ordering::Acquire)).clone() };
+		let result = f(&mut copy);
+		self.publish(copy);
+		result
+	}
+
+	fn publish(&self, value: T) {
+		let new = Arc::into_raw(Arc::new(value)) as *mut T;
+		let old = self.ptr.swap(new, Ordering::AcqRel) as usize;
+		call_rcu(move || unsafe { drop(Arc::from_raw(old as *const T)) });
+	}
+}
+
+impl<T: Send + Sync + 'static> Drop for RcuCell<T> {
+	fn drop(&mut self) {
+		// Readers hold their own references, so this can go at once
+		unsafe { drop(Arc::from_raw(*self.ptr.get_mut())) };
+	}
+}
+
+impl<T: Send + Sync + Default + 'static> Default for RcuCell<T> {
+	fn default() -> Self {
+		Self::new(T::default())
+	}
+}
@@ -0,0 +1,197 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Resource limits and usage accounting - Linux compatible
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::capability::CAP_SYS_RESOURCE;
+use crate::cred::Credentials;
+use crate::error::{Error, Result};
+use crate::time::NSEC_PER_SEC;
+
+pub const RLIMIT_CPU: usize = 0; // CPU time in seconds
+pub const RLIMIT_FSIZE: usize = 1;
+pub const RLIMIT_DATA: usize = 2;
+pub const RLIMIT_STACK: usize = 3; // Stack size in bytes
+pub const RLIMIT_CORE: usize = 4; // Core file size in bytes
+pub const RLIMIT_RSS: usize = 5;
+pub const RLIMIT_NPROC: usize = 6; // Processes per real user ID
+pub const RLIMIT_NOFILE: usize = 7; // Open file descriptors
+pub const RLIMIT_MEMLOCK: usize = 8;
+pub const RLIMIT_AS: usize = 9; // Address space size in bytes
+pub const RLIMIT_LOCKS: usize = 10;
+pub const RLIMIT_SIGPENDING: usize = 11;
+pub const RLIMIT_MSGQUEUE: usize = 12;
+pub const RLIMIT_NICE: usize = 13;
+pub const RLIMIT_RTPRIO: usize = 14;
+pub const RLIMIT_RTTIME: usize = 15;
+
+/// Number of resource limits
+pub const RLIM_NLIMITS: usize = 16;
+
+/// No limit
+pub const RLIM_INFINITY: u64 = u64::MAX;
+
+/// Hard ceiling for RLIMIT_NOFILE
+pub const NR_OPEN: u64 = 1 << 20;
+
+/// getrusage() targets
+pub const RUSAGE_SELF: i32 = 0;
+pub const RUSAGE_CHILDREN: i32 = -1;
+pub const RUSAGE_THREAD: i32 = 1;
+
+/// Clock ticks per second reported by times()
+pub const USER_HZ: u64 = 100;
+
+/// A soft and hard limit pair - same layout as Linux struct rlimit64
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rlimit {
+	pub cur: u64, // Soft limit, enforced
+	pub max: u64, // Hard limit, ceiling for the soft limit
+}
+
+impl Rlimit {
+	pub const fn new(cur: u64, max: u64) -> Self {
+		Self { cur, max }
+	}
+
+	pub const fn unlimited() -> Self {
+		Self::new(RLIM_INFINITY, RLIM_INFINITY)
+	}
+
+	/// Check whether `value` goes over the soft limit
+	pub fn exceeded_by(&self, value: u64) -> bool {
+		self.cur != RLIM_INFINITY && value > self.cur
+	}
+
+	pub fn to_bytes(&self) -> [u8; 16] {
+		let mut bytes = [0u8; 16];
+		bytes[..8].copy_from_slice(&self.cur.to_ne_bytes());
+		bytes[8..].copy_from_slice(&self.max.to_ne_bytes());
+		bytes
+	}
+
+	pub fn from_bytes(bytes: &[u8; 16]) -> Self {
+		let mut cur = [0u8; 8];
+		let mut max = [0u8; 8];
+		cur.copy_from_slice(&bytes[..8]);
+		max.copy_from_slice(&bytes[8..]);
+		Self::new(u64::from_ne_bytes(cur), u64::from_ne_bytes(max))
+	}
+}
+
+/// Resource limits of a process, inherited across fork and exec
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RLimits {
+	limits: [Rlimit; RLIM_NLIMITS],
+}
+
+impl RLimits {
+	/// Limits of the first process, like Linux INIT_RLIMITS
+	pub fn new() -> Self {
+		let mut limits = [Rlimit::unlimited(); RLIM_NLIMITS];
+		limits[RLIMIT_STACK] = Rlimit::new(8 * 1024 * 1024, RLIM_INFINITY);
+		limits[RLIMIT_CORE] = Rlimit::new(0, RLIM_INFINITY);
+		limits[RLIMIT_NPROC] = Rlimit::new(4096, 4096);
+		limits[RLIMIT_NOFILE] = Rlimit::new(1024, 4096);
+		limits[RLIMIT_MEMLOCK] = Rlimit::new(8 * 1024 * 1024, 8 * 1024 * 1024);
+		limits[RLIMIT_SIGPENDING] = Rlimit::new(4096, 4096);
+		limits[RLIMIT_MSGQUEUE] = Rlimit::new(819_200, 819_200);
+		limits[RLIMIT_NICE] = Rlimit::new(0, 0);
+		limits[RLIMIT_RTPRIO] = Rlimit::new(0, 0);
+		Self { limits }
+	}
+
+	pub fn get(&self, resource: usize) -> Result<Rlimit> {
+		self.limits.get(resource).copied().ok_or(Error::EINVAL)
+	}
+
+	/// Soft limit of a resource
+	pub fn cur(&self, resource: usize) -> u64 {
+		self.limits.get(resource).map_or(RLIM_INFINITY, |l| l.cur)
+	}
+
+	/// Replace a limit (setrlimit semantics) and return the old one.
+	/// Raising the hard limit needs CAP_SYS_RESOURCE in `cred`.
+	pub fn set(&mut self, resource: usize, new: Rlimit, cred: &Credentials) -> Result<Rlimit> {
+		let old = self.get(resource)?;
+		if new.cur > new.max {
+			return Err(Error::EINVAL);
+		}
+		if resource == RLIMIT_NOFILE && new.max > NR_OPEN {
+			return Err(Error::EPERM);
+		}
+		if new.max > old.max && !cred.capable(CAP_SYS_RESOURCE) {
+			return Err(Error::EPERM);
+		}
+		self.limits[resource] = new;
+		Ok(old)
+	}
+}
+
+impl Default for RLimits {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Resource usage - the fields of Linux struct rusage we track
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rusage {
+	pub utime: u64,  // User time in nanoseconds
+	pub stime: u64,  // System time in nanoseconds
+	pub maxrss: u64, // Peak memory use in KiB
+}
+
+impl Rusage {
+	/// Add the usage of a thread or a waited-for child
+	pub fn add(&mut self, other: &Rusage) {
+		self.utime += other.utime;
+		self.stime += other.stime;
+		self.maxrss = self.maxrss.max(other.maxrss);
+	}
+
+	/// Encode as a user space struct rusage
+	pub fn to_bytes(&self) -> [u8; 144] {
+		let mut bytes = [0u8; 144];
+		let words = [
+			self.utime / NSEC_PER_SEC,
+			self.utime % NSEC_PER_SEC / 1000,
+			self.stime / NSEC_PER_SEC,
+			self.stime % NSEC_PER_SEC / 1000,
+			self.maxrss,
+		];
+		for (chunk, word) in bytes.chunks_exact_mut(8).zip(words) {
+			chunk.copy_from_slice(&word.to_ne_bytes());
+		}
+		bytes
+	}
+}
+
+/// CPU time charged to all threads since boot
+static CPU_USER_NS: AtomicU64 = AtomicU64::new(0);
+static CPU_SYSTEM_NS: AtomicU64 = AtomicU64::new(0);
+
+/// Add thread CPU time to the system-wide totals
+pub fn account_cpu(user: u64, system: u64) {
+	CPU_USER_NS.fetch_add(user, Ordering::Relaxed);
+	CPU_SYSTEM_NS.fetch_add(system, Ordering::Relaxed);
+}
+
+/// System-wide user and system time in nanoseconds
+pub fn cpu_times() -> (u64, u64) {
+	(
+		CPU_USER_NS.load(Ordering::Relaxed),
+		CPU_SYSTEM_NS.load(Ordering::Relaxed),
+	)
+}
+
+/// Convert nanoseconds to clock ticks as reported by times()
+pub fn ns_to_clock_t(ns: u64) -> u64 {
+	ns / (NSEC_PER_SEC / USER_HZ)
+}
+
+/// Soft limit of a resource for the current process
+pub fn rlimit(resource: usize) -> u64 {
+	crate::process::current_process().map_or(RLIM_INFINITY, |p| p.rlimits.cur(resource))
+}
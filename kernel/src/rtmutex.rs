@@ -0,0 +1,370 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Priority-inheritance mutexes - similar to Linux rtmutex
+//!
+//! A task blocked on an rt-mutex lends its priority to the owner, and on
+//! to the owner of any lock the owner itself is blocked on. All PI state
+//! lives in one table so the lock chain can be walked under one lock.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::error::{Error, Result};
+use crate::sync::Spinlock;
+use crate::types::Tid;
+
+/// Longest lock chain walked for boosting and deadlock detection
+pub const MAX_LOCK_DEPTH: usize = 1024;
+
+static NEXT_LOCK_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A task blocked on an rt-mutex
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Waiter {
+	pub(crate) tid: Tid,
+	pub(crate) prio: i32,
+}
+
+/// Owner and waiters of one rt-mutex
+#[derive(Debug, Default)]
+pub(crate) struct LockState {
+	pub(crate) owner: Option<Tid>,
+	waiters: Vec<Waiter>, // Highest priority first, FIFO among equals
+}
+
+impl LockState {
+	pub(crate) fn enqueue(&mut self, waiter: Waiter) {
+		let pos = self
+			.waiters
+			.iter()
+			.position(|w| w.prio > waiter.prio)
+			.unwrap_or(self.waiters.len());
+		self.waiters.insert(pos, waiter);
+	}
+
+	fn remove(&mut self, tid: Tid) -> Option<Waiter> {
+		let pos = self.waiters.iter().position(|w| w.tid == tid)?;
+		Some(self.waiters.remove(pos))
+	}
+
+	fn top_prio(&self) -> Option<i32> {
+		self.waiters.first().map(|w| w.prio)
+	}
+
+	/// Pass ownership to the highest priority waiter, or free the lock
+	pub(crate) fn hand_off(&mut self) -> Option<Tid> {
+		let next = (!self.waiters.is_empty()).then(|| self.waiters.remove(0).tid);
+		self.owner = next;
+		next
+	}
+}
+
+/// PI state of all contended or owned rt-mutexes
+struct PiTable {
+	locks: BTreeMap<u64, LockState>,
+	blocked_on: BTreeMap<Tid, u64>, // Lock each waiting task is blocked on
+}
+
+impl PiTable {
+	const fn new() -> Self {
+		Self {
+			locks: BTreeMap::new(),
+			blocked_on: BTreeMap::new(),
+		}
+	}
+
+	fn owner(&self, id: u64) -> Option<Tid> {
+		self.locks.get(&id).and_then(|lock| lock.owner)
+	}
+
+	/// Drop the state of a lock nobody owns or waits for
+	fn release_state(&mut self, id: u64) {
+		if let Some(lock) = self.locks.get(&id) {
+			if lock.owner.is_none() && lock.waiters.is_empty() {
+				self.locks.remove(&id);
+			}
+		}
+	}
+
+	/// Highest priority waiting on any lock owned by `tid`
+	fn top_pi_waiter(&self, tid: Tid) -> Option<i32> {
+		self.locks
+			.values()
+			.filter(|lock| lock.owner == Some(tid))
+			.filter_map(|lock| lock.top_prio())
+			.min()
+	}
+
+	/// Check whether `tid` blocking on lock `id` would close a cycle
+	fn would_deadlock(&self, id: u64, tid: Tid) -> bool {
+		let mut next = Some(id);
+		for _ in 0..MAX_LOCK_DEPTH {
+			let owner = match next.and_then(|id| self.owner(id)) {
+				Some(owner) => owner,
+				None => return false,
+			};
+			if owner == tid {
+				return true;
+			}
+			next = self.blocked_on.get(&owner).copied();
+		}
+		// Treat an overlong chain like a cycle
+		true
+	}
+
+	/// Re-evaluate the priority of the owner of lock `id` and propagate
+	/// the change along the chain of locks it is blocked on
+	fn adjust_prio_chain(&mut self, id: u64) {
+		let mut next = Some(id);
+		for _ in 0..MAX_LOCK_DEPTH {
+			let owner = match next.and_then(|id| self.owner(id)) {
+				Some(owner) => owner,
+				None => return,
+			};
+
+			let old_prio = crate::scheduler::task_prio(owner);
+			crate::scheduler::rt_mutex_setprio(owner, self.top_pi_waiter(owner));
+			let new_prio = crate::scheduler::task_prio(owner);
+			if new_prio == old_prio {
+				return;
+			}
+
+			// Requeue the owner on the lock it is blocked on
+			next = self.blocked_on.get(&owner).copied();
+			if let Some(lock) = next.and_then(|id| self.locks.get_mut(&id)) {
+				if let Some(mut waiter) = lock.remove(owner) {
+					waiter.prio = new_prio;
+					lock.enqueue(waiter);
+				}
+			}
+		}
+	}
+}
+
+static PI_TABLE: Spinlock<PiTable> = Spinlock::new(PiTable::new());
+
+/// Lock word of an rt-mutex, without data.
+///
+/// Callers pass the task ID explicitly so PI futexes can act on behalf of
+/// user threads.
+#[derive(Debug)]
+pub struct RawRtMutex {
+	id: AtomicU64, // Allocated on first use so statics can be const
+}
+
+impl RawRtMutex {
+	pub const fn new() -> Self {
+		Self {
+			id: AtomicU64::new(0),
+		}
+	}
+
+	/// Create a handle for the PI state with the given ID
+	pub const fn with_id(id: u64) -> Self {
+		Self {
+			id: AtomicU64::new(id),
+		}
+	}
+
+	fn id(&self) -> u64 {
+		let id = self.id.load(Ordering::Acquire);
+		if id != 0 {
+			return id;
+		}
+		let new = NEXT_LOCK_ID.fetch_add(1, Ordering::Relaxed);
+		match self
+			.id
+			.compare_exchange(0, new, Ordering::AcqRel, Ordering::Acquire)
+		{
+			Ok(_) => new,
+			Err(existing) => existing,
+		}
+	}
+
+	/// Get the owner of the lock
+	pub fn owner(&self) -> Option<Tid> {
+		PI_TABLE.lock().owner(self.id())
+	}
+
+	/// Check whether any task waits for the lock
+	pub fn has_waiters(&self) -> bool {
+		PI_TABLE.lock()
+			.locks
+			.get(&self.id())
+			.map_or(false, |lock| !lock.waiters.is_empty())
+	}
+
+	/// Record `tid` as owner of a free lock, as when user space took a PI
+	/// futex without entering the kernel
+	pub fn set_owner(&self, tid: Tid) {
+		let mut table = PI_TABLE.lock();
+		let lock = table.locks.entry(self.id()).or_default();
+		if lock.owner.is_none() {
+			lock.owner = Some(tid);
+		}
+	}
+
+	/// Take the lock if it is free
+	pub fn try_lock(&self, tid: Tid) -> bool {
+		let mut table = PI_TABLE.lock();
+		let lock = table.locks.entry(self.id()).or_default();
+		if lock.owner.is_some() {
+			return false;
+		}
+		lock.owner = Some(tid);
+		true
+	}
+
+	/// Take the lock, boosting the owner chain while blocked.
+	///
+	/// Fails with EDEADLK if waiting would deadlock, and with Timeout if
+	/// `deadline` (ns since boot) passes first.
+	pub fn lock(&self, tid: Tid, deadline: Option<u64>) -> Result<()> {
+		let id = self.id();
+		{
+			let mut table = PI_TABLE.lock();
+			let lock = table.locks.entry(id).or_default();
+			if lock.owner.is_none() {
+				lock.owner = Some(tid);
+				return Ok(());
+			}
+			if table.would_deadlock(id, tid) {
+				return Err(Error::EDEADLK);
+			}
+
+			let prio = crate::scheduler::task_prio(tid);
+			if let Some(lock) = table.locks.get_mut(&id) {
+				lock.enqueue(Waiter { tid, prio });
+			}
+			table.blocked_on.insert(tid, id);
+			table.adjust_prio_chain(id);
+		}
+
+		// Unlock hands the lock over, so just wait to become the owner
+		loop {
+			if self.owner() == Some(tid) {
+				return Ok(());
+			}
+
+			if deadline.map_or(false, |d| crate::time::get_time_ns() >= d) {
+				let mut table = PI_TABLE.lock();
+				if table.owner(id) == Some(tid) {
+					return Ok(());
+				}
+				table.blocked_on.remove(&tid);
+				if let Some(lock) = table.locks.get_mut(&id) {
+					lock.remove(tid);
+				}
+				table.adjust_prio_chain(id);
+				return Err(Error::Timeout);
+			}
+
+			crate::scheduler::yield_now();
+		}
+	}
+
+	/// Release the lock held by `tid` and hand it to the highest priority
+	/// waiter. Returns the new owner, if any.
+	pub fn unlock(&self, tid: Tid) -> Result<Option<Tid>> {
+		let id = self.id();
+		let mut table = PI_TABLE.lock();
+		let lock = table.locks.get_mut(&id).ok_or(Error::EPERM)?;
+		if lock.owner != Some(tid) {
+			return Err(Error::EPERM);
+		}
+
+		let next = lock.hand_off();
+		if let Some(next) = next {
+			table.blocked_on.remove(&next);
+		}
+
+		// The old owner drops what it inherited through this lock,
+		// the new owner inherits from the remaining waiters
+		crate::scheduler::rt_mutex_setprio(tid, table.top_pi_waiter(tid));
+		table.adjust_prio_chain(id);
+		table.release_state(id);
+		Ok(next)
+	}
+}
+
+impl Default for RawRtMutex {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// The task ID rt-mutexes use for the running kernel context
+fn current_tid() -> Tid {
+	crate::scheduler::current_tid().unwrap_or(Tid(0))
+}
+
+/// Mutex with priority inheritance for data shared with real-time tasks
+pub struct RtMutex<T> {
+	raw: RawRtMutex,
+	data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for RtMutex<T> {}
+unsafe impl<T: Send> Send for RtMutex<T> {}
+
+impl<T> RtMutex<T> {
+	pub const fn new(data: T) -> Self {
+		Self {
+			raw: RawRtMutex::new(),
+			data: UnsafeCell::new(data),
+		}
+	}
+
+	/// Lock the mutex, failing with EDEADLK instead of deadlocking
+	pub fn lock(&self) -> Result<RtMutexGuard<'_, T>> {
+		self.lock_timeout(None)
+	}
+
+	/// Lock the mutex, giving up at `deadline` (ns since boot)
+	pub fn lock_timeout(&self, deadline: Option<u64>) -> Result<RtMutexGuard<'_, T>> {
+		let tid = current_tid();
+		self.raw.lock(tid, deadline)?;
+		Ok(RtMutexGuard { mutex: self, tid })
+	}
+
+	pub fn try_lock(&self) -> Option<RtMutexGuard<'_, T>> {
+		let tid = current_tid();
+		if self.raw.try_lock(tid) {
+			Some(RtMutexGuard { mutex: self, tid })
+		} else {
+			None
+		}
+	}
+
+	/// Check whether the mutex is held
+	pub fn is_locked(&self) -> bool {
+		self.raw.owner().is_some()
+	}
+}
+
+pub struct RtMutexGuard<'a, T> {
+	mutex: &'a RtMutex<T>,
+	tid: Tid,
+}
+
+impl<T> Deref for RtMutexGuard<'_, T> {
+	type Target = T;
+
+	fn deref(&self) -> &Self::Target {
+		unsafe { &*self.mutex.data.get() }
+	}
+}
+
+impl<T> DerefMut for RtMutexGuard<'_, T> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		unsafe { &mut *self.mutex.data.get() }
+	}
+}
+
+impl<T> Drop for RtMutexGuard<'_, T> {
+	fn drop(&mut self) {
+		let _ = self.mutex.raw.unlock(self.tid);
+	}
+}
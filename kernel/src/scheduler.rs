@@ -3,12 +3,13 @@
 //! Task scheduler compatible with Linux kernel CFS (Completely Fair Scheduler)
 
 use alloc::{
-	collections::{BTreeMap, VecDeque},
+	collections::{BTreeMap, BTreeSet, VecDeque},
+	string::String,
 	vec::Vec,
 };
-use core::sync::atomic::{AtomicU64, Ordering};
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 
-use crate::arch::x86_64::context::{switch_context, Context};
+use crate::arch::x86_64::context::{set_fs_base, switch_context, Context};
 use crate::error::{Error, Result};
 use crate::process::{Thread, PROCESS_TABLE};
 use crate::sync::Spinlock;
@@ -49,7 +50,9 @@ pub fn prio_to_nice(prio: i32) -> i32 {
 pub struct SchedEntity {
 	pub tid: Tid,
 	pub policy: SchedulerPolicy,
-	pub priority: i32,
+	pub priority: i32,        // Effective priority, lower runs first
+	pub normal_prio: i32,     // Priority without inheritance
+	pub pi_prio: Option<i32>, // Priority inherited through rt-mutexes
 	pub nice: i32,
 	pub vruntime: u64,         // Virtual runtime for CFS
 	pub exec_start: u64,       // Last execution start time
@@ -57,7 +60,9 @@ pub struct SchedEntity {
 	pub prev_sum_exec_runtime: u64,
 	pub load_weight: u32, // Load weight for this entity
 	pub runnable_weight: u32,
-	pub on_rq: bool, // On run queue?
+	pub on_rq: bool,           // On run queue?
+	pub group: GroupId,        // Task group whose run queue it is on
+	pub my_q: Option<GroupId>, // Group this entity stands for
 }
 
 impl SchedEntity {
@@ -67,6 +72,8 @@ impl SchedEntity {
 			tid,
 			policy,
 			priority,
+			normal_prio: priority,
+			pi_prio: None,
 			nice,
 			vruntime: 0,
 			exec_start: 0,
@@ -75,15 +82,42 @@ impl SchedEntity {
 			load_weight: nice_to_weight(nice),
 			runnable_weight: nice_to_weight(nice),
 			on_rq: false,
+			group: ROOT_TASK_GROUP,
+			my_q: None,
 		}
 	}
 
+	/// Position in a CFS timeline, unique among tasks and groups
+	fn rq_key(&self) -> (u64, u64) {
+		let id = match self.my_q {
+			Some(group) => GROUP_ENTITY_KEY | group as u64,
+			None => self.tid.0 as u64,
+		};
+		(self.vruntime, id)
+	}
+
 	/// Update virtual runtime
 	pub fn update_vruntime(&mut self, delta: u64) {
 		// Virtual runtime is weighted by load
 		let weighted_delta = delta * 1024 / self.load_weight as u64;
 		self.vruntime += weighted_delta;
 	}
+
+	/// Check whether the entity belongs on the real-time run queue
+	pub fn is_rt(&self) -> bool {
+		self.priority < MAX_RT_PRIO
+			|| matches!(
+				self.policy,
+				SchedulerPolicy::Fifo | SchedulerPolicy::RoundRobin
+			)
+	}
+
+	/// Recompute the effective priority from the normal and inherited ones
+	fn update_prio(&mut self) {
+		self.priority = self
+			.pi_prio
+			.map_or(self.normal_prio, |prio| prio.min(self.normal_prio));
+	}
 }
 
 /// Convert nice value to load weight (Linux compatible)
@@ -137,7 +171,7 @@ fn nice_to_weight(nice: i32) -> u32 {
 /// CFS (Completely Fair Scheduler) run queue
 #[derive(Debug)]
 pub struct CfsRunQueue {
-	tasks_timeline: BTreeMap<u64, SchedEntity>, // Red-black tree equivalent
+	tasks_timeline: BTreeMap<(u64, u64), SchedEntity>, // Red-black tree equivalent
 	min_vruntime: u64,
 	nr_running: u32,
 	load_weight: u64,
@@ -166,7 +200,7 @@ impl CfsRunQueue {
 				se.vruntime = self.min_vruntime;
 			}
 
-			self.tasks_timeline.insert(se.vruntime, se.clone());
+			self.tasks_timeline.insert(se.rq_key(), se.clone());
 			self.nr_running += 1;
 			self.load_weight += se.load_weight as u64;
 			self.runnable_weight += se.runnable_weight as u64;
@@ -176,7 +210,7 @@ impl CfsRunQueue {
 	/// Remove task from run queue
 	pub fn dequeue_task(&mut self, se: &SchedEntity) -> bool {
 		if se.on_rq {
-			self.tasks_timeline.remove(&se.vruntime);
+			self.tasks_timeline.remove(&se.rq_key());
 			self.nr_running -= 1;
 			self.load_weight -= se.load_weight as u64;
 			self.runnable_weight -= se.runnable_weight as u64;
@@ -189,16 +223,16 @@ impl CfsRunQueue {
 	/// Pick next task to run
 	pub fn pick_next_task(&mut self) -> Option<SchedEntity> {
 		// Pick leftmost task (smallest vruntime)
-		if let Some((vruntime, se)) = self.tasks_timeline.iter().next() {
+		if let Some((key, se)) = self.tasks_timeline.iter().next() {
 			let se = se.clone();
-			let vruntime = *vruntime;
-			self.tasks_timeline.remove(&vruntime);
+			let key = *key;
+			self.tasks_timeline.remove(&key);
 			self.nr_running -= 1;
 			self.load_weight -= se.load_weight as u64;
 			self.runnable_weight -= se.runnable_weight as u64;
 
 			// Update min_vruntime
-			if let Some((next_vruntime, _)) = self.tasks_timeline.iter().next() {
+			if let Some(((next_vruntime, _), _)) = self.tasks_timeline.iter().next() {
 				self.min_vruntime =
 					core::cmp::max(self.min_vruntime, *next_vruntime);
 			} else {
@@ -211,9 +245,32 @@ impl CfsRunQueue {
 		}
 	}
 
+	/// Remove the entity of a task, wherever it is in the timeline
+	pub fn remove_tid(&mut self, tid: Tid) -> Option<SchedEntity> {
+		let key = self
+			.tasks_timeline
+			.iter()
+			.find(|(_, se)| se.my_q.is_none() && se.tid == tid)
+			.map(|(&key, _)| key)?;
+		let mut se = self.tasks_timeline.remove(&key)?;
+		self.nr_running -= 1;
+		self.load_weight -= se.load_weight as u64;
+		self.runnable_weight -= se.runnable_weight as u64;
+		se.on_rq = false;
+		Some(se)
+	}
+
+	/// Number of queued tasks, not counting group entities
+	pub fn nr_tasks(&self) -> usize {
+		self.tasks_timeline
+			.values()
+			.filter(|se| se.my_q.is_none())
+			.count()
+	}
+
 	/// Update minimum virtual runtime
 	pub fn update_min_vruntime(&mut self) {
-		if let Some((&next_vruntime, _)) = self.tasks_timeline.iter().next() {
+		if let Some((&(next_vruntime, _), _)) = self.tasks_timeline.iter().next() {
 			self.min_vruntime = core::cmp::max(self.min_vruntime, next_vruntime);
 		}
 	}
@@ -234,19 +291,26 @@ impl RtRunQueue {
 		}
 	}
 
+	/// Queue a task behind every task of equal or higher priority
 	pub fn enqueue_task(&mut self, se: SchedEntity) {
-		self.runqueue.push_back(se);
+		let pos = self
+			.runqueue
+			.iter()
+			.position(|task| task.priority > se.priority)
+			.unwrap_or(self.runqueue.len());
+		self.runqueue.insert(pos, se);
 		self.nr_running += 1;
 	}
 
 	pub fn dequeue_task(&mut self, se: &SchedEntity) -> bool {
-		if let Some(pos) = self.runqueue.iter().position(|task| task.tid == se.tid) {
-			self.nr_running -= 1;
-			self.runqueue.remove(pos);
-			true
-		} else {
-			false
-		}
+		self.dequeue_tid(se.tid).is_some()
+	}
+
+	/// Remove the entity of a task from the queue
+	pub fn dequeue_tid(&mut self, tid: Tid) -> Option<SchedEntity> {
+		let pos = self.runqueue.iter().position(|task| task.tid == tid)?;
+		self.nr_running -= 1;
+		self.runqueue.remove(pos)
 	}
 
 	pub fn pick_next_task(&mut self) -> Option<SchedEntity> {
@@ -346,10 +410,102 @@ impl RunQueue {
 	}
 }
 
+/// Task group identifier
+pub type GroupId = u32;
+
+/// Group every task starts in; its run queue is the top-level one
+pub const ROOT_TASK_GROUP: GroupId = 0;
+
+/// Marks group entities in timeline keys, apart from task IDs
+const GROUP_ENTITY_KEY: u64 = 1 << 63;
+
+/// Deepest nesting of task groups
+const MAX_GROUP_DEPTH: usize = 16;
+
+/// Share limits and default - like Linux cpu.shares
+pub const MIN_SHARES: u32 = 2;
+pub const MAX_SHARES: u32 = 1 << 18;
+pub const DEFAULT_SHARES: u32 = 1024;
+
+/// Bandwidth period limits and default, in microseconds
+pub const MIN_CFS_PERIOD_US: u64 = 1_000;
+pub const MAX_CFS_PERIOD_US: u64 = 1_000_000;
+pub const DEFAULT_CFS_PERIOD_US: u64 = 100_000;
+
+/// Smallest quota per period, in microseconds
+pub const MIN_CFS_QUOTA_US: u64 = 1_000;
+
+/// CPU bandwidth limit of a task group - like Linux cfs_bandwidth
+#[derive(Debug, Clone)]
+struct CfsBandwidth {
+	quota: Option<u64>, // Runtime per period in ns, None for no limit
+	period: u64,        // ns
+	runtime: u64,       // Left in the current period
+	period_start: u64,
+	throttled_at: Option<u64>,
+	nr_periods: u64,
+	nr_throttled: u64,
+	throttled_time: u64,
+}
+
+impl CfsBandwidth {
+	fn new() -> Self {
+		Self {
+			quota: None,
+			period: DEFAULT_CFS_PERIOD_US * 1000,
+			runtime: 0,
+			period_start: time::get_time_ns(),
+			throttled_at: None,
+			nr_periods: 0,
+			nr_throttled: 0,
+			throttled_time: 0,
+		}
+	}
+}
+
+/// Tasks sharing CPU time as one entity - like Linux task_group
+#[derive(Debug)]
+struct TaskGroup {
+	name: String,
+	parent: GroupId,
+	se: SchedEntity,     // Entity on the parent's run queue
+	cfs_rq: CfsRunQueue, // Entities of the group's tasks and child groups
+	bandwidth: CfsBandwidth,
+	usage: u64, // CPU time used in ns
+}
+
+impl TaskGroup {
+	/// Whether the group's entity belongs on its parent's run queue
+	fn runnable(&self) -> bool {
+		self.cfs_rq.nr_running > 0 && self.bandwidth.throttled_at.is_none()
+	}
+}
+
+/// Task group settings and statistics
+#[derive(Debug, Clone)]
+pub struct TaskGroupStats {
+	pub id: GroupId,
+	pub name: String,
+	pub parent: Option<GroupId>,
+	pub shares: u32,
+	pub quota_us: Option<u64>,
+	pub period_us: u64,
+	pub usage_ns: u64,
+	pub nr_running: usize,
+	pub nr_periods: u64,
+	pub nr_throttled: u64,
+	pub throttled_ns: u64,
+	pub throttled: bool,
+}
+
 /// Global scheduler state
 static SCHEDULER: Spinlock<Scheduler> = Spinlock::new(Scheduler::new());
 static SCHEDULE_CLOCK: AtomicU64 = AtomicU64::new(0);
 
+/// Running task, readable without the scheduler lock. 0 before the first
+/// task runs.
+static CURRENT_TID: AtomicU32 = AtomicU32::new(0);
+
 /// Main scheduler structure
 struct Scheduler {
 	run_queues: Vec<RunQueue>,
@@ -359,7 +515,11 @@ struct Scheduler {
 	cfs: CfsRunQueue,
 	rt: RtRunQueue,
 	current: Option<Tid>,
+	sleeping: BTreeSet<Tid>, // Blocked on a wait queue, off the run queues
+	timeouts: BTreeMap<Tid, u64>, // Sleepers woken at a deadline (ns since boot)
 	nr_switches: u64,
+	groups: BTreeMap<GroupId, TaskGroup>, // All but the root group
+	next_group_id: GroupId,
 }
 
 impl Scheduler {
@@ -381,7 +541,36 @@ impl Scheduler {
 				nr_running: 0,
 			},
 			current: None,
+			sleeping: BTreeSet::new(),
+			timeouts: BTreeMap::new(),
 			nr_switches: 0,
+			groups: BTreeMap::new(),
+			next_group_id: ROOT_TASK_GROUP + 1,
+		}
+	}
+
+	/// Make a sleeping task runnable again
+	fn wake_sleeper(&mut self, tid: Tid) {
+		self.timeouts.remove(&tid);
+		if !self.sleeping.remove(&tid) || self.current == Some(tid) {
+			return;
+		}
+		if let Some(se) = self.entities.get(&tid).cloned() {
+			self.enqueue_entity(se);
+			self.set_need_resched();
+		}
+	}
+
+	/// Wake the sleepers whose deadline has passed
+	fn expire_timeouts(&mut self, now: u64) {
+		let expired: Vec<Tid> = self
+			.timeouts
+			.iter()
+			.filter(|&(_, &deadline)| deadline <= now)
+			.map(|(&tid, _)| tid)
+			.collect();
+		for tid in expired {
+			self.wake_sleeper(tid);
 		}
 	}
 
@@ -437,21 +626,62 @@ impl Scheduler {
 
 	/// Pick next task to run
 	fn pick_next_task(&mut self) -> Option<Tid> {
-		// Try CFS first
-		if let Some(se) = self.cfs.pick_next_task() {
-			self.current = Some(se.tid);
+		// Real-time and priority-boosted tasks run first
+		if let Some(se) = self.rt.pick_next_task() {
+			self.set_current(se.tid);
 			return Some(se.tid);
 		}
 
-		// Then try RT
-		if let Some(se) = self.rt.pick_next_task() {
-			self.current = Some(se.tid);
+		// Then CFS, descending through task groups
+		if let Some(se) = self.pick_next_fair() {
+			self.set_current(se.tid);
 			return Some(se.tid);
 		}
 
 		None
 	}
 
+	/// Queue a new entity on the run queue matching its priority
+	fn enqueue_entity(&mut self, se: SchedEntity) {
+		self.entities.insert(se.tid, se.clone());
+		if se.is_rt() {
+			self.rt.enqueue_task(se);
+		} else {
+			self.enqueue_fair(se);
+		}
+	}
+
+	/// Change a task's priority and move it to the matching run queue.
+	/// Preempts the running task if a queued task now outranks it.
+	fn change_prio(&mut self, tid: Tid, f: impl FnOnce(&mut SchedEntity)) {
+		let se = match self.entities.get_mut(&tid) {
+			Some(se) => se,
+			None => return,
+		};
+		f(se);
+		se.update_prio();
+		let (priority, updated) = (se.priority, se.clone());
+
+		let queued = self.dequeue_fair(tid).or_else(|| self.rt.dequeue_tid(tid));
+		if queued.is_some() {
+			let mut se = updated;
+			se.on_rq = false;
+			if se.is_rt() {
+				self.rt.enqueue_task(se);
+			} else {
+				self.enqueue_fair(se);
+			}
+		}
+
+		let current_prio = self
+			.current
+			.and_then(|current| self.entities.get(&current))
+			.map_or(MAX_PRIO, |se| se.priority);
+		if queued.is_some() && priority < current_prio {
+			self.set_need_resched();
+		}
+	}
+
 	/// Switch to a task
 	fn switch_to(&mut self, tid: Tid) {
 		// Save current task's context
@@ -459,7 +689,7 @@ impl Scheduler {
 			if current_tid != tid {
 				// Look up current and next threads
 				// We need to use a scope to ensure the lock is dropped before switching
-				let (current_ctx_ptr, next_ctx_ptr) = {
+				let (current_ctx_ptr, next_ctx_ptr, next_fs_base) = {
 					let mut process_table = PROCESS_TABLE.lock();
 
 					let (current_thread, next_thread) = process_table
@@ -471,18 +701,22 @@ impl Scheduler {
 						return; // Current thread not found?
 					};
 
-					let next_ptr = if let Some(t) = next_thread {
-						&t.context as *const Context
+					let (next_ptr, fs_base) = if let Some(t) = next_thread {
+						(&t.context as *const Context, t.fs_base)
 					} else {
 						return; // Next thread not found
 					};
 
-					(current_ptr, next_ptr)
+					(current_ptr, next_ptr, fs_base)
 				};
 
 				// Update scheduler state
-				self.current = Some(tid);
+				self.set_current(tid);
 				self.nr_switches += 1;
+				crate::rcu::rcu_note_quiescent_state();
+
+				// Install the next thread's TLS base
+				set_fs_base(next_fs_base);
 
 				// Perform the context switch
 				// SAFETY: We have valid pointers to the contexts and we've dropped the lock
@@ -495,16 +729,363 @@ impl Scheduler {
 		}
 
 		// First task or same task
-		self.current = Some(tid);
+		self.set_current(tid);
 		self.nr_switches += 1;
 	}
 
+	/// Record the running task
+	fn set_current(&mut self, tid: Tid) {
+		self.current = Some(tid);
+		CURRENT_TID.store(tid.0, Ordering::Relaxed);
+	}
+
 	/// Set need resched flag
 	fn set_need_resched(&mut self) {
 		self.need_resched = true;
 	}
 }
 
+/// Group scheduling: each task group has its own CFS run queue, queued on
+/// its parent's run queue as one entity weighted by the group's shares
+impl Scheduler {
+	fn group_rq_mut(&mut self, group: GroupId) -> Option<&mut CfsRunQueue> {
+		if group == ROOT_TASK_GROUP {
+			Some(&mut self.cfs)
+		} else {
+			self.groups.get_mut(&group).map(|g| &mut g.cfs_rq)
+		}
+	}
+
+	/// Queued fair tasks in all groups
+	fn nr_fair_tasks(&self) -> usize {
+		self.cfs.nr_tasks()
+			+ self.groups
+				.values()
+				.map(|g| g.cfs_rq.nr_tasks())
+				.sum::<usize>()
+	}
+
+	/// Put a group's entity on its parent's run queue if the group became
+	/// runnable. Returns whether it was queued.
+	fn enqueue_group_se(&mut self, group: GroupId) -> bool {
+		let (mut se, parent) = match self.groups.get(&group) {
+			Some(g) if !g.se.on_rq && g.runnable() => (g.se.clone(), g.parent),
+			_ => return false,
+		};
+		let rq = match self.group_rq_mut(parent) {
+			Some(rq) => rq,
+			None => return false,
+		};
+		se.vruntime = se.vruntime.max(rq.min_vruntime);
+		rq.enqueue_task(se.clone());
+		se.on_rq = true;
+		if let Some(g) = self.groups.get_mut(&group) {
+			g.se = se;
+		}
+		true
+	}
+
+	/// Take a group's entity off its parent's run queue if the group is
+	/// empty or throttled. Returns whether it was dequeued.
+	fn dequeue_group_se(&mut self, group: GroupId) -> bool {
+		let (se, parent) = match self.groups.get_mut(&group) {
+			Some(g) if g.se.on_rq && !g.runnable() => {
+				let se = g.se.clone();
+				g.se.on_rq = false;
+				(se, g.parent)
+			}
+			_ => return false,
+		};
+		if let Some(rq) = self.group_rq_mut(parent) {
+			rq.dequeue_task(&se);
+		}
+		true
+	}
+
+	/// Queue or dequeue group entities from `group` up to the root as
+	/// their groups became runnable or not
+	fn update_group_chain(&mut self, mut group: GroupId) {
+		for _ in 0..MAX_GROUP_DEPTH {
+			if group == ROOT_TASK_GROUP {
+				return;
+			}
+			let changed = self.enqueue_group_se(group) || self.dequeue_group_se(group);
+			if !changed {
+				return;
+			}
+			group = match self.groups.get(&group) {
+				Some(g) => g.parent,
+				None => return,
+			};
+		}
+	}
+
+	/// Change a group's entity, keeping its place on the parent's run
+	/// queue in order
+	fn update_group_se(&mut self, group: GroupId, f: impl FnOnce(&mut SchedEntity)) {
+		let (mut se, parent) = match self.groups.get(&group) {
+			Some(g) => (g.se.clone(), g.parent),
+			None => return,
+		};
+		let queued = se.on_rq;
+		if queued {
+			if let Some(rq) = self.group_rq_mut(parent) {
+				rq.dequeue_task(&se);
+			}
+		}
+		f(&mut se);
+		if queued {
+			se.on_rq = false;
+			if let Some(rq) = self.group_rq_mut(parent) {
+				rq.enqueue_task(se.clone());
+			}
+			se.on_rq = true;
+		}
+		if let Some(g) = self.groups.get_mut(&group) {
+			g.se = se;
+		}
+	}
+
+	/// Queue a fair task on its group's run queue
+	fn enqueue_fair(&mut self, mut se: SchedEntity) {
+		if !self.groups.contains_key(&se.group) {
+			se.group = ROOT_TASK_GROUP;
+		}
+		let group = se.group;
+		if let Some(rq) = self.group_rq_mut(group) {
+			rq.enqueue_task(se);
+		}
+		self.update_group_chain(group);
+	}
+
+	/// Remove a fair task from its group's run queue
+	fn dequeue_fair(&mut self, tid: Tid) -> Option<SchedEntity> {
+		let group = self
+			.entities
+			.get(&tid)
+			.map_or(ROOT_TASK_GROUP, |se| se.group);
+		let se = self.group_rq_mut(group)?.remove_tid(tid)?;
+		self.update_group_chain(group);
+		Some(se)
+	}
+
+	/// Pick the leftmost entity from the root run queue, descending into
+	/// groups until a task is found
+	fn pick_next_fair(&mut self) -> Option<SchedEntity> {
+		let mut path = Vec::new();
+		let mut group = ROOT_TASK_GROUP;
+		let mut picked = None;
+		for _ in 0..=MAX_GROUP_DEPTH {
+			let se = match self.group_rq_mut(group).and_then(|rq| rq.pick_next_task()) {
+				Some(se) => se,
+				None => break,
+			};
+			match se.my_q {
+				Some(child) => {
+					if let Some(g) = self.groups.get_mut(&child) {
+						g.se.on_rq = false;
+					}
+					path.push(child);
+					group = child;
+				}
+				None => {
+					picked = Some(se);
+					break;
+				}
+			}
+		}
+
+		// Groups stay queued while they have other runnable entities
+		for &group in path.iter().rev() {
+			self.enqueue_group_se(group);
+		}
+		picked
+	}
+
+	/// Charge `delta` ns of CPU time to the running task and its groups,
+	/// throttling groups that ran out of bandwidth
+	fn update_curr_fair(&mut self, delta: u64, now: u64) {
+		let tid = match self.current {
+			Some(tid) => tid,
+			None => return,
+		};
+		let mut group = match self.entities.get_mut(&tid) {
+			Some(se) if !se.is_rt() => {
+				se.sum_exec_runtime += delta;
+				se.update_vruntime(delta);
+				se.group
+			}
+			_ => return,
+		};
+
+		for _ in 0..MAX_GROUP_DEPTH {
+			if group == ROOT_TASK_GROUP {
+				return;
+			}
+			self.update_group_se(group, |se| {
+				se.sum_exec_runtime += delta;
+				se.update_vruntime(delta);
+			});
+			let (parent, throttle) = match self.groups.get_mut(&group) {
+				Some(g) => {
+					g.usage += delta;
+					let bw = &mut g.bandwidth;
+					let mut throttle = false;
+					if bw.quota.is_some() {
+						bw.runtime = bw.runtime.saturating_sub(delta);
+						if bw.runtime == 0 && bw.throttled_at.is_none() {
+							bw.throttled_at = Some(now);
+							bw.nr_throttled += 1;
+							throttle = true;
+						}
+					}
+					(g.parent, throttle)
+				}
+				None => return,
+			};
+			if throttle {
+				self.update_group_chain(group);
+				self.set_need_resched();
+			}
+			group = parent;
+		}
+	}
+
+	/// Refill the bandwidth of groups whose period ended, unthrottling them
+	fn refresh_bandwidth(&mut self, now: u64) {
+		let mut unthrottled = Vec::new();
+		for (&id, g) in self.groups.iter_mut() {
+			let bw = &mut g.bandwidth;
+			let quota = match bw.quota {
+				Some(quota) => quota,
+				None => continue,
+			};
+			if now.saturating_sub(bw.period_start) < bw.period {
+				continue;
+			}
+			bw.period_start = now;
+			bw.nr_periods += 1;
+			bw.runtime = quota;
+			if let Some(at) = bw.throttled_at.take() {
+				bw.throttled_time += now.saturating_sub(at);
+				unthrottled.push(id);
+			}
+		}
+		for group in unthrottled {
+			self.update_group_chain(group);
+		}
+	}
+
+	fn create_group(&mut self, name: &str, parent: GroupId) -> Result<GroupId> {
+		if name.is_empty() || name == "/" {
+			return Err(Error::EINVAL);
+		}
+		if self.groups.values().any(|g| g.name == name) {
+			return Err(Error::EEXIST);
+		}
+		if parent != ROOT_TASK_GROUP && !self.groups.contains_key(&parent) {
+			return Err(Error::ENOENT);
+		}
+		if self.group_depth(parent) >= MAX_GROUP_DEPTH {
+			return Err(Error::EINVAL);
+		}
+
+		let id = self.next_group_id;
+		self.next_group_id += 1;
+		let mut se = SchedEntity::new(Tid(0), SchedulerPolicy::Normal, 0);
+		se.group = parent;
+		se.my_q = Some(id);
+		self.groups.insert(
+			id,
+			TaskGroup {
+				name: String::from(name),
+				parent,
+				se,
+				cfs_rq: CfsRunQueue::new(),
+				bandwidth: CfsBandwidth::new(),
+				usage: 0,
+			},
+		);
+		Ok(id)
+	}
+
+	fn group_depth(&self, mut group: GroupId) -> usize {
+		let mut depth = 0;
+		while let Some(g) = self.groups.get(&group) {
+			depth += 1;
+			group = g.parent;
+		}
+		depth
+	}
+
+	fn destroy_group(&mut self, group: GroupId) -> Result<()> {
+		if !self.groups.contains_key(&group) {
+			return Err(Error::ENOENT);
+		}
+		let in_use = self.entities.values().any(|se| se.group == group)
+			|| self.groups.values().any(|g| g.parent == group);
+		if in_use {
+			return Err(Error::Busy);
+		}
+		self.groups.remove(&group);
+		Ok(())
+	}
+
+	fn move_task(&mut self, tid: Tid, group: GroupId) -> Result<()> {
+		if group != ROOT_TASK_GROUP && !self.groups.contains_key(&group) {
+			return Err(Error::ENOENT);
+		}
+		if !self.entities.contains_key(&tid) {
+			return Err(Error::ESRCH);
+		}
+		let queued = self.dequeue_fair(tid);
+		if let Some(se) = self.entities.get_mut(&tid) {
+			se.group = group;
+		}
+		if let Some(mut se) = queued {
+			se.group = group;
+			se.on_rq = false;
+			self.enqueue_fair(se);
+		}
+		Ok(())
+	}
+
+	fn group_stats(&self, id: GroupId) -> Option<TaskGroupStats> {
+		if id == ROOT_TASK_GROUP {
+			return Some(TaskGroupStats {
+				id,
+				name: String::from("/"),
+				parent: None,
+				shares: DEFAULT_SHARES,
+				quota_us: None,
+				period_us: DEFAULT_CFS_PERIOD_US,
+				usage_ns: 0,
+				nr_running: self.cfs.nr_tasks(),
+				nr_periods: 0,
+				nr_throttled: 0,
+				throttled_ns: 0,
+				throttled: false,
+			});
+		}
+		let g = self.groups.get(&id)?;
+		let bw = &g.bandwidth;
+		Some(TaskGroupStats {
+			id,
+			name: g.name.clone(),
+			parent: Some(g.parent),
+			shares: g.se.load_weight,
+			quota_us: bw.quota.map(|quota| quota / 1000),
+			period_us: bw.period / 1000,
+			usage_ns: g.usage,
+			nr_running: g.cfs_rq.nr_tasks(),
+			nr_periods: bw.nr_periods,
+			nr_throttled: bw.nr_throttled,
+			throttled_ns: bw.throttled_time,
+			throttled: bw.throttled_at.is_some(),
+		})
+	}
+}
+
 /// Initialize the scheduler
 pub fn init() -> Result<()> {
 	let mut scheduler = SCHEDULER.lock();
@@ -520,11 +1101,30 @@ pub fn add_task(pid: crate::types::Pid) -> Result<()> {
 
 	// Create a scheduler entity for the process
 	let tid = crate::types::Tid(pid.0); // Simple mapping for now
-	let se = SchedEntity::new(tid, SchedulerPolicy::Normal, DEFAULT_PRIO);
+	let se = SchedEntity::new(tid, SchedulerPolicy::Normal, 0);
 
 	// Add to CFS runqueue
-	scheduler.cfs.enqueue_task(se);
+	scheduler.enqueue_entity(se);
+
+	Ok(())
+}
+
+/// Add a thread of a multi-threaded process to the scheduler
+pub fn add_thread(tid: Tid) -> Result<()> {
+	let mut scheduler = SCHEDULER.lock();
+	let se = SchedEntity::new(tid, SchedulerPolicy::Normal, 0);
+	scheduler.enqueue_entity(se);
+	Ok(())
+}
 
+/// Remove a thread from the scheduler
+pub fn remove_thread(tid: Tid) -> Result<()> {
+	let mut scheduler = SCHEDULER.lock();
+	scheduler.dequeue_fair(tid);
+	scheduler.rt.dequeue_tid(tid);
+	scheduler.sleeping.remove(&tid);
+	scheduler.timeouts.remove(&tid);
+	scheduler.entities.remove(&tid);
 	Ok(())
 }
 
@@ -535,10 +1135,11 @@ pub fn remove_task(pid: crate::types::Pid) -> Result<()> {
 	// Remove from all runqueues
 	let tid = crate::types::Tid(pid.0);
 
-	// Create a minimal SchedEntity for removal
-	let se = SchedEntity::new(tid, SchedulerPolicy::Normal, DEFAULT_PRIO);
-	scheduler.cfs.dequeue_task(&se);
-	scheduler.rt.dequeue_task(&se);
+	scheduler.dequeue_fair(tid);
+	scheduler.rt.dequeue_tid(tid);
+	scheduler.sleeping.remove(&tid);
+	scheduler.timeouts.remove(&tid);
+	scheduler.entities.remove(&tid);
 
 	Ok(())
 }
@@ -546,10 +1147,15 @@ pub fn remove_task(pid: crate::types::Pid) -> Result<()> {
 /// Schedule next task (called from syscall exit or timer interrupt)
 pub fn schedule() {
 	let mut scheduler = SCHEDULER.lock();
+	let prev = scheduler.current;
 
 	// Pick next task to run
 	if let Some(next) = scheduler.pick_next_task() {
-		// Switch to next task
+		// Picking marks `next` running, but switch_to has to save the
+		// registers of the task that still is
+		if let Some(prev) = prev {
+			scheduler.set_current(prev);
+		}
 		scheduler.switch_to(next);
 	}
 }
@@ -560,6 +1166,27 @@ pub fn current_task() -> Option<crate::types::Pid> {
 	scheduler.current.map(|tid| crate::types::Pid(tid.0))
 }
 
+/// Get the thread ID of the running task
+pub fn current_tid() -> Option<Tid> {
+	SCHEDULER.lock().current
+}
+
+/// Get the running task without taking the scheduler lock, for code
+/// that runs while it may be held
+pub fn current_tid_lockless() -> Option<Tid> {
+	match CURRENT_TID.load(Ordering::Relaxed) {
+		0 => None,
+		tid => Some(Tid(tid)),
+	}
+}
+
+/// Number of runnable tasks, including the running one
+pub fn nr_running() -> usize {
+	let scheduler = SCHEDULER.lock();
+	let queued = scheduler.nr_fair_tasks() + scheduler.rt.nr_running as usize;
+	queued + scheduler.current.is_some() as usize
+}
+
 /// Yield current task (alias for yield_task)
 pub fn yield_now() {
 	yield_task();
@@ -578,35 +1205,217 @@ pub fn sleep_task(duration_ms: u64) {
 	yield_task();
 }
 
+/// Take the running task off the run queue and run another one until
+/// wake_up_thread() is called for it. The CPU halts if nothing else is
+/// runnable. Returns at once if `woken` holds: it is checked under the
+/// scheduler lock, which wake_up_thread also takes, so a wake-up just
+/// before going to sleep isn't lost. Returns false if no task is running.
+pub fn sleep_current(woken: impl FnOnce() -> bool) -> bool {
+	sleep_current_until(woken, None)
+}
+
+/// Like sleep_current, also waking up on the first tick at or after
+/// `deadline` (ns since boot)
+pub fn sleep_current_until(woken: impl FnOnce() -> bool, deadline: Option<u64>) -> bool {
+	let asleep = crate::interrupt::without_interrupts(|| {
+		let mut scheduler = SCHEDULER.lock();
+		let tid = scheduler.current?;
+		if woken() {
+			return Some(None);
+		}
+		scheduler.dequeue_fair(tid);
+		scheduler.rt.dequeue_tid(tid);
+		scheduler.sleeping.insert(tid);
+		if let Some(deadline) = deadline {
+			scheduler.timeouts.insert(tid, deadline);
+		}
+		Some(Some(tid))
+	});
+	let tid = match asleep {
+		Some(Some(tid)) => tid,
+		Some(None) => return true,
+		None => return false,
+	};
+
+	schedule();
+	// Back without a switch, or switched to before the wake-up
+	while crate::interrupt::without_interrupts(|| SCHEDULER.lock().sleeping.contains(&tid)) {
+		crate::timer::cpu_idle();
+	}
+	true
+}
+
+/// Make a task put to sleep by sleep_current() runnable again
+pub fn wake_up_thread(tid: Tid) {
+	crate::interrupt::without_interrupts(|| SCHEDULER.lock().wake_sleeper(tid));
+}
+
+/// Nanoseconds until the earliest sleeper deadline
+pub fn next_timeout_ns() -> Option<u64> {
+	let now = time::get_time_ns();
+	let earliest = SCHEDULER.lock().timeouts.values().min().copied();
+	earliest.map(|deadline| deadline.saturating_sub(now))
+}
+
 /// Wake up a task
 pub fn wake_task(pid: crate::types::Pid) -> Result<()> {
 	let mut scheduler = SCHEDULER.lock();
 	let tid = crate::types::Tid(pid.0);
 
 	// TODO: Move from wait queue to runqueue
-	// For now, just ensure it's in the runqueue
-	let se = SchedEntity::new(tid, SchedulerPolicy::Normal, DEFAULT_PRIO);
-	scheduler.cfs.enqueue_task(se);
+	// For now, just ensure it's in the runqueue once, keeping any
+	// inherited priority
+	let se = scheduler
+		.entities
+		.get(&tid)
+		.cloned()
+		.unwrap_or_else(|| SchedEntity::new(tid, SchedulerPolicy::Normal, 0));
+	scheduler.dequeue_fair(tid);
+	scheduler.rt.dequeue_tid(tid);
+	scheduler.enqueue_entity(se);
 
 	Ok(())
 }
 
-/// Set task priority
+/// Set task priority. Priorities below MAX_RT_PRIO are real-time.
 pub fn set_task_priority(pid: crate::types::Pid, priority: i32) -> Result<()> {
+	if !(0..MAX_PRIO).contains(&priority) {
+		return Err(Error::EINVAL);
+	}
 	let mut scheduler = SCHEDULER.lock();
 	let tid = crate::types::Tid(pid.0);
+	if !scheduler.entities.contains_key(&tid) {
+		return Err(Error::ESRCH);
+	}
+	scheduler.change_prio(tid, |se| se.normal_prio = priority);
+	Ok(())
+}
 
-	// TODO: Update priority in runqueue
-	// This would require finding the task and updating its priority
+/// Get the effective priority of a task
+pub fn task_prio(tid: Tid) -> i32 {
+	SCHEDULER
+		.lock()
+		.entities
+		.get(&tid)
+		.map_or(DEFAULT_PRIO, |se| se.priority)
+}
 
+/// Set or clear the priority a task inherits from rt-mutex waiters
+pub fn rt_mutex_setprio(tid: Tid, pi_prio: Option<i32>) {
+	SCHEDULER.lock().change_prio(tid, |se| se.pi_prio = pi_prio);
+}
+
+/// Charge a timer tick of `delta` ns to the running task's groups, refill
+/// group bandwidth and wake sleepers whose deadline passed. Called from the
+/// timer interrupt.
+pub fn task_tick_fair(delta: u64) {
+	// Skip the tick rather than spin on a lock the interrupted code holds
+	let mut scheduler = match SCHEDULER.try_lock() {
+		Some(scheduler) => scheduler,
+		None => return,
+	};
+	let now = time::get_time_ns();
+	scheduler.update_curr_fair(delta, now);
+	scheduler.refresh_bandwidth(now);
+	scheduler.expire_timeouts(now);
+}
+
+/// Create a task group under `parent`
+pub fn create_task_group(name: &str, parent: GroupId) -> Result<GroupId> {
+	SCHEDULER.lock().create_group(name, parent)
+}
+
+/// Remove a task group that has no tasks or child groups
+pub fn destroy_task_group(group: GroupId) -> Result<()> {
+	SCHEDULER.lock().destroy_group(group)
+}
+
+/// Find a task group by name, "/" being the root group
+pub fn find_task_group(name: &str) -> Option<GroupId> {
+	if name == "/" {
+		return Some(ROOT_TASK_GROUP);
+	}
+	SCHEDULER
+		.lock()
+		.groups
+		.iter()
+		.find(|(_, g)| g.name == name)
+		.map(|(&id, _)| id)
+}
+
+/// Set the CPU weight of a group relative to its siblings
+pub fn set_group_shares(group: GroupId, shares: u32) -> Result<()> {
+	if !(MIN_SHARES..=MAX_SHARES).contains(&shares) {
+		return Err(Error::EINVAL);
+	}
+	let mut scheduler = SCHEDULER.lock();
+	if !scheduler.groups.contains_key(&group) {
+		return Err(Error::ENOENT);
+	}
+	scheduler.update_group_se(group, |se| {
+		se.load_weight = shares;
+		se.runnable_weight = shares;
+	});
+	Ok(())
+}
+
+/// Limit a group to `quota_us` of CPU time every `period_us`, or lift
+/// the limit with None
+pub fn set_group_bandwidth(group: GroupId, quota_us: Option<u64>, period_us: u64) -> Result<()> {
+	if !(MIN_CFS_PERIOD_US..=MAX_CFS_PERIOD_US).contains(&period_us) {
+		return Err(Error::EINVAL);
+	}
+	if quota_us.map_or(false, |quota| quota < MIN_CFS_QUOTA_US) {
+		return Err(Error::EINVAL);
+	}
+
+	let mut scheduler = SCHEDULER.lock();
+	let now = time::get_time_ns();
+	let bw = &mut scheduler
+		.groups
+		.get_mut(&group)
+		.ok_or(Error::ENOENT)?
+		.bandwidth;
+	bw.quota = quota_us.map(|quota| quota * 1000);
+	bw.period = period_us * 1000;
+	bw.runtime = bw.quota.unwrap_or(0);
+	bw.period_start = now;
+	if let Some(at) = bw.throttled_at.take() {
+		bw.throttled_time += now.saturating_sub(at);
+	}
+	scheduler.update_group_chain(group);
 	Ok(())
 }
 
+/// Move a task to another group
+pub fn move_task_to_group(tid: Tid, group: GroupId) -> Result<()> {
+	SCHEDULER.lock().move_task(tid, group)
+}
+
+/// Get the group a task belongs to
+pub fn task_group_of(tid: Tid) -> Option<GroupId> {
+	SCHEDULER.lock().entities.get(&tid).map(|se| se.group)
+}
+
+/// Get the settings and statistics of a group
+pub fn task_group_stats(group: GroupId) -> Option<TaskGroupStats> {
+	SCHEDULER.lock().group_stats(group)
+}
+
+/// Get the settings and statistics of all groups, the root group first
+pub fn task_groups() -> Vec<TaskGroupStats> {
+	let scheduler = SCHEDULER.lock();
+	core::iter::once(ROOT_TASK_GROUP)
+		.chain(scheduler.groups.keys().copied())
+		.filter_map(|id| scheduler.group_stats(id))
+		.collect()
+}
+
 /// Get scheduler statistics
 pub fn get_scheduler_stats() -> SchedulerStats {
 	let scheduler = SCHEDULER.lock();
 	SchedulerStats {
-		total_tasks: (scheduler.cfs.nr_running + scheduler.rt.nr_running) as usize,
+		total_tasks: scheduler.nr_fair_tasks() + scheduler.rt.nr_running as usize,
 		running_tasks: if scheduler.current.is_some() { 1 } else { 0 },
 		context_switches: scheduler.nr_switches,
 		load_average: scheduler.cfs.load_weight as f64 / 1024.0,
@@ -0,0 +1,359 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Secure computing - per-process system call filters
+//!
+//! A process restricts the system calls it and its descendants may make,
+//! with seccomp(2) or prctl(PR_SET_SECCOMP). Strict mode allows only read,
+//! write, exit and rt_sigreturn. Filter mode installs a list of rules, each
+//! matching a syscall number and optionally comparing one argument, with
+//! an action for matching calls and a default action for the rest.
+//!
+//! Filters stack and are kept across fork and exec. Every installed
+//! filter is checked and the most restrictive action wins. Installing a
+//! filter needs no_new_privs or CAP_SYS_ADMIN, so it can't be used to
+//! confuse set-user-ID programs.
+
+use alloc::vec::Vec;
+
+use crate::error::{Error, Result};
+use crate::memory::{copy_from_user, UserPtr};
+use crate::sync::Arc;
+use crate::syscalls::SyscallArgs;
+use crate::types::Pid;
+
+/// Seccomp modes, as reported by PR_GET_SECCOMP
+pub const SECCOMP_MODE_DISABLED: u32 = 0;
+pub const SECCOMP_MODE_STRICT: u32 = 1;
+pub const SECCOMP_MODE_FILTER: u32 = 2;
+
+/// Operations of seccomp(2)
+pub const SECCOMP_SET_MODE_STRICT: u64 = 0;
+pub const SECCOMP_SET_MODE_FILTER: u64 = 1;
+pub const SECCOMP_GET_ACTION_AVAIL: u64 = 2;
+
+/// Filter return values: the action in the high 16 bits, data below
+pub const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+pub const SECCOMP_RET_KILL_THREAD: u32 = 0x0000_0000;
+pub const SECCOMP_RET_TRAP: u32 = 0x0003_0000;
+pub const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+pub const SECCOMP_RET_LOG: u32 = 0x7ffc_0000;
+pub const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+const SECCOMP_RET_ACTION_FULL: u32 = 0xffff_0000;
+const SECCOMP_RET_DATA: u32 = 0x0000_ffff;
+
+/// Argument comparisons of a rule
+pub const SECCOMP_CMP_NONE: u32 = 0;
+pub const SECCOMP_CMP_NE: u32 = 1;
+pub const SECCOMP_CMP_LT: u32 = 2;
+pub const SECCOMP_CMP_LE: u32 = 3;
+pub const SECCOMP_CMP_EQ: u32 = 4;
+pub const SECCOMP_CMP_GE: u32 = 5;
+pub const SECCOMP_CMP_GT: u32 = 6;
+pub const SECCOMP_CMP_MASKED_EQ: u32 = 7;
+
+/// Rules in all filters of a process, like Linux's limit on BPF
+/// instructions
+const MAX_RULES: usize = 32768;
+
+/// Syscalls allowed in strict mode: read, write, exit and rt_sigreturn
+const STRICT_SYSCALLS: [u64; 4] = [0, 1, 60, 15];
+
+/// What to do with a system call. Earlier variants are more restrictive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Action {
+	KillProcess,
+	KillThread,
+	Trap,       // Send SIGSYS
+	Errno(u16), // Fail with this errno
+	Log,        // Allow and log
+	Allow,
+}
+
+impl Action {
+	/// Decode a SECCOMP_RET_* value
+	pub fn from_ret(ret: u32) -> Result<Self> {
+		match ret & SECCOMP_RET_ACTION_FULL {
+			SECCOMP_RET_KILL_PROCESS => Ok(Action::KillProcess),
+			SECCOMP_RET_KILL_THREAD => Ok(Action::KillThread),
+			SECCOMP_RET_TRAP => Ok(Action::Trap),
+			SECCOMP_RET_ERRNO => {
+				Ok(Action::Errno((ret & SECCOMP_RET_DATA).min(4095) as u16))
+			}
+			SECCOMP_RET_LOG => Ok(Action::Log),
+			SECCOMP_RET_ALLOW => Ok(Action::Allow),
+			_ => Err(Error::EINVAL),
+		}
+	}
+}
+
+/// Comparison of one syscall argument
+#[derive(Debug, Clone, Copy)]
+pub struct ArgCmp {
+	pub arg: usize,
+	pub op: u32,
+	pub value: u64,
+	pub mask: u64, // For SECCOMP_CMP_MASKED_EQ
+}
+
+impl ArgCmp {
+	fn matches(&self, args: &[u64; 6]) -> bool {
+		let arg = args[self.arg];
+		match self.op {
+			SECCOMP_CMP_NE => arg != self.value,
+			SECCOMP_CMP_LT => arg < self.value,
+			SECCOMP_CMP_LE => arg <= self.value,
+			SECCOMP_CMP_EQ => arg == self.value,
+			SECCOMP_CMP_GE => arg >= self.value,
+			SECCOMP_CMP_GT => arg > self.value,
+			SECCOMP_CMP_MASKED_EQ => arg & self.mask == self.value,
+			_ => true,
+		}
+	}
+}
+
+/// A syscall rule of a filter
+#[derive(Debug, Clone)]
+pub struct Rule {
+	pub nr: u64,
+	pub cmp: Option<ArgCmp>,
+	pub action: Action,
+}
+
+/// A filter: the action of the first matching rule, or the default
+#[derive(Debug, Clone)]
+pub struct Filter {
+	pub default: Action,
+	pub rules: Vec<Rule>,
+}
+
+impl Filter {
+	/// The filter strict mode behaves like
+	fn strict() -> Self {
+		Self {
+			default: Action::KillProcess,
+			rules: STRICT_SYSCALLS
+				.iter()
+				.map(|&nr| Rule {
+					nr,
+					cmp: None,
+					action: Action::Allow,
+				})
+				.collect(),
+		}
+	}
+
+	fn evaluate(&self, nr: u64, args: &[u64; 6]) -> Action {
+		self.rules
+			.iter()
+			.find(|rule| {
+				rule.nr == nr && rule.cmp.map_or(true, |cmp| cmp.matches(args))
+			})
+			.map_or(self.default, |rule| rule.action)
+	}
+}
+
+/// Seccomp state of a process
+#[derive(Debug, Clone, Default)]
+pub struct Seccomp {
+	pub mode: u32,
+	pub filters: Vec<Arc<Filter>>,
+}
+
+impl Seccomp {
+	/// Decide on a system call, the most restrictive action of all filters
+	pub fn evaluate(&self, args: &SyscallArgs) -> Action {
+		let argv = [
+			args.arg0, args.arg1, args.arg2, args.arg3, args.arg4, args.arg5,
+		];
+		self.filters
+			.iter()
+			.map(|filter| filter.evaluate(args.syscall_num, &argv))
+			.min()
+			.unwrap_or(Action::Allow)
+	}
+
+	fn nr_rules(&self) -> usize {
+		self.filters.iter().map(|filter| filter.rules.len()).sum()
+	}
+}
+
+/// Filter program passed to SECCOMP_SET_MODE_FILTER
+#[repr(C)]
+#[derive(Default)]
+struct SeccompProg {
+	default_action: u32,
+	len: u32,
+	rules: u64, // Pointer to `len` SeccompRule
+}
+
+/// Rule of a filter program
+#[repr(C)]
+#[derive(Default)]
+struct SeccompRule {
+	nr: u32,
+	action: u32,
+	arg: u32, // Argument index compared, with op
+	op: u32,  // SECCOMP_CMP_*
+	value: u64,
+	mask: u64,
+}
+
+fn copy_struct_from_user<T: Default>(addr: u64) -> Result<T> {
+	let mut value = T::default();
+	let bytes = unsafe {
+		core::slice::from_raw_parts_mut(
+			&mut value as *mut T as *mut u8,
+			core::mem::size_of::<T>(),
+		)
+	};
+	copy_from_user(
+		bytes,
+		UserPtr::new(addr as *mut u8).map_err(|_| Error::EFAULT)?,
+	)?;
+	Ok(value)
+}
+
+/// Read a filter program from user memory
+fn filter_from_user(addr: u64) -> Result<Filter> {
+	let prog: SeccompProg = copy_struct_from_user(addr)?;
+	if prog.len as usize > MAX_RULES {
+		return Err(Error::EINVAL);
+	}
+
+	let mut rules = Vec::with_capacity(prog.len as usize);
+	for i in 0..prog.len as u64 {
+		let rule: SeccompRule = copy_struct_from_user(
+			prog.rules + i * core::mem::size_of::<SeccompRule>() as u64,
+		)?;
+		let cmp = match rule.op {
+			SECCOMP_CMP_NONE => None,
+			SECCOMP_CMP_NE..=SECCOMP_CMP_MASKED_EQ if rule.arg < 6 => Some(ArgCmp {
+				arg: rule.arg as usize,
+				op: rule.op,
+				value: rule.value,
+				mask: rule.mask,
+			}),
+			_ => return Err(Error::EINVAL),
+		};
+		rules.push(Rule {
+			nr: rule.nr as u64,
+			cmp,
+			action: Action::from_ret(rule.action)?,
+		});
+	}
+	Ok(Filter {
+		default: Action::from_ret(prog.default_action)?,
+		rules,
+	})
+}
+
+/// Enter strict mode or add a filter to the current process
+fn install(mode: u32, filter: Option<Filter>) -> Result<()> {
+	let current = crate::process::current_process().ok_or(Error::ESRCH)?;
+	if mode == SECCOMP_MODE_FILTER
+		&& !current.no_new_privs
+		&& !current.cred.capable(crate::capability::CAP_SYS_ADMIN)
+	{
+		return Err(Error::EACCES);
+	}
+
+	crate::process::update_seccomp(current.pid, |seccomp| {
+		// Strict mode is final, and filters can only be added to
+		if seccomp.mode != SECCOMP_MODE_DISABLED && seccomp.mode != mode
+			|| seccomp.mode == SECCOMP_MODE_STRICT
+		{
+			return Err(Error::EINVAL);
+		}
+		let filter = filter.unwrap_or_else(Filter::strict);
+		if seccomp.nr_rules() + filter.rules.len() > MAX_RULES {
+			return Err(Error::ENOMEM);
+		}
+		seccomp.mode = mode;
+		seccomp.filters.push(Arc::new(filter));
+		Ok(())
+	})
+}
+
+/// seccomp(2)
+pub fn do_seccomp(op: u64, flags: u64, uargs: u64) -> Result<u64> {
+	if flags != 0 {
+		return Err(Error::EINVAL);
+	}
+	match op {
+		SECCOMP_SET_MODE_STRICT if uargs == 0 => install(SECCOMP_MODE_STRICT, None)?,
+		SECCOMP_SET_MODE_FILTER => {
+			install(SECCOMP_MODE_FILTER, Some(filter_from_user(uargs)?))?
+		}
+		SECCOMP_GET_ACTION_AVAIL => {
+			let ret: u32 = copy_struct_from_user(uargs)?;
+			Action::from_ret(ret & SECCOMP_RET_ACTION_FULL)
+				.map_err(|_| Error::ENOSYS)?;
+		}
+		_ => return Err(Error::EINVAL),
+	}
+	Ok(0)
+}
+
+/// prctl(PR_SET_SECCOMP)
+pub fn prctl_set_seccomp(mode: u64, filter: u64) -> Result<u64> {
+	match mode as u32 {
+		SECCOMP_MODE_STRICT => do_seccomp(SECCOMP_SET_MODE_STRICT, 0, 0),
+		SECCOMP_MODE_FILTER => do_seccomp(SECCOMP_SET_MODE_FILTER, 0, filter),
+		_ => Err(Error::EINVAL),
+	}
+}
+
+/// Check a system call of the current process against its filters before
+/// it runs. Returns the result to fail it with, or None to run it.
+pub fn secure_computing(args: &SyscallArgs) -> Option<u64> {
+	let (pid, action) = crate::process::with_current(|p| {
+		let action = match p.seccomp.mode {
+			SECCOMP_MODE_DISABLED => Action::Allow,
+			_ => p.seccomp.evaluate(args),
+		};
+		(p.pid, action)
+	})?;
+
+	match action {
+		Action::Allow => None,
+		Action::Log => {
+			crate::info!("seccomp: process {} syscall {}", pid, args.syscall_num);
+			None
+		}
+		Action::Errno(errno) => Some((-(errno as i64)) as u64),
+		Action::KillThread => {
+			kill_thread(pid);
+			Some(Error::ENOSYS.to_syscall_ret())
+		}
+		// Without signal handlers SIGSYS kills the process too
+		Action::Trap | Action::KillProcess => {
+			kill_process(pid);
+			Some(Error::ENOSYS.to_syscall_ret())
+		}
+	}
+}
+
+/// Kill the calling thread, or the process if it is the last one
+fn kill_thread(pid: Pid) {
+	let tid = match crate::process::current_thread_tid() {
+		Some(tid) => tid,
+		None => return kill_process(pid),
+	};
+	match crate::process::exit_thread(pid, tid) {
+		Ok((clear_tid, false)) => {
+			crate::futex::clear_child_tid(clear_tid);
+			let _ = crate::scheduler::remove_thread(tid);
+			crate::scheduler::schedule();
+		}
+		_ => kill_process(pid),
+	}
+}
+
+/// Kill the current process with SIGSYS, dumping core. Blocking or
+/// ignoring SIGSYS doesn't stop it.
+fn kill_process(pid: Pid) {
+	crate::warn!("seccomp: killing process {}", pid);
+	if crate::process::force_sig(pid, crate::signal::SIGSYS).is_ok() {
+		crate::scheduler::schedule();
+	}
+}
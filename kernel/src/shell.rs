@@ -79,6 +79,7 @@ impl KernelShell {
 				"info" => self.cmd_info(),
 				"mem" => self.cmd_memory(),
 				"ps" => self.cmd_processes(),
+				"caps" => self.cmd_caps(&parts[1..]),
 				"uptime" => self.cmd_uptime(),
 				"net" => self.cmd_network(&parts[1..]),
 				"mod" => self.cmd_modules(&parts[1..]),
@@ -103,6 +104,7 @@ impl KernelShell {
 				"ipc" => self.cmd_ipc(&parts[1..]),
 				"aperf" => self.cmd_advanced_perf(&parts[1..]),
 				"tasks" => self.cmd_tasks(&parts[1..]),
+				"cgroup" => self.cmd_cgroup(&parts[1..]),
 				"panic" => self.cmd_panic(),
 				"version" => {
 					info!("Rust Kernel v0.1.0 - Advanced Features Edition");
@@ -186,6 +188,7 @@ impl KernelShell {
 		info!("  info     - Show kernel information");
 		info!("  mem      - Show memory statistics");
 		info!("  ps       - Show process information");
+		info!("  caps     - Show process capabilities (caps [pid])");
 		info!("  uptime   - Show system uptime");
 		info!("  net      - Network commands (stats, test)");
 		info!("  mod      - Module commands (list, test, unload)");
@@ -210,6 +213,7 @@ impl KernelShell {
 		info!("  ipc      - Inter-process communication (stats, semaphore, pipe, shm)");
 		info!("  aperf    - Advanced performance monitoring (summary, counters, profilers)");
 		info!("  tasks    - Task management (list, spawn, status, cleanup)");
+		info!("  cgroup   - CPU task groups (list, create, shares, quota, attach)");
 		info!("  panic    - Trigger kernel panic (for testing)");
 		info!("  version   - Show kernel version and build information");
 		info!("  hwinfo    - Show hardware information");
@@ -257,10 +261,75 @@ impl KernelShell {
 	/// Process command
 	fn cmd_processes(&self) {
 		info!("Process information:");
-		info!("  Current PID: 0 (kernel)");
-		info!("  Total processes: 1");
-		// TODO: Show actual process list when process management is
-		// fully implemented
+		info!("  PID   PPID  PGID  SID   TTY    STATE     NAME");
+
+		let pids = crate::process::list_processes();
+		for pid in &pids {
+			if let Some(process) = crate::process::find_process(*pid) {
+				let tty = match process.ctty {
+					Some(crate::tty::CONSOLE_TTY) => "tty0",
+					Some(crate::tty::SERIAL_TTY) => "ttyS0",
+					_ => "?",
+				};
+				info!(
+					"  {:<5} {:<5} {:<5} {:<5} {:<6} {:<9} {}",
+					process.pid.0,
+					process.parent.map(|p| p.0).unwrap_or(0),
+					process.pgid.0,
+					process.sid.0,
+					tty,
+					alloc::format!("{:?}", process.state),
+					process.name
+				);
+			}
+		}
+		info!("  Total processes: {}", pids.len());
+	}
+
+	/// Capabilities command
+	fn cmd_caps(&self, args: &[&str]) {
+		let (label, cred) = match args.first() {
+			Some(arg) => {
+				let pid = match arg.parse::<u32>() {
+					Ok(pid) => crate::types::Pid(pid),
+					Err(_) => {
+						info!("Usage: caps [pid]");
+						return;
+					}
+				};
+				match crate::process::find_process(pid) {
+					Some(process) => {
+						(alloc::format!("PID {}", pid.0), process.cred)
+					}
+					None => {
+						info!("No such process: {}", pid.0);
+						return;
+					}
+				}
+			}
+			None => (String::from("current context"), crate::cred::current_cred()),
+		};
+
+		info!("Capabilities of {}:", label);
+		info!(
+			"  uid={} euid={} gid={} egid={}",
+			cred.uid.0, cred.euid.0, cred.gid.0, cred.egid.0
+		);
+		info!("  Effective:   {:#018x}", cred.cap_effective.0);
+		info!("  Permitted:   {:#018x}", cred.cap_permitted.0);
+		info!("  Inheritable: {:#018x}", cred.cap_inheritable.0);
+		info!("  Bounding:    {:#018x}", cred.cap_bset.0);
+
+		if cred.cap_effective == crate::capability::CapSet::full() {
+			info!("  Effective set: all capabilities");
+		} else if cred.cap_effective.is_empty() {
+			info!("  Effective set: none");
+		} else {
+			info!("  Effective set:");
+			for cap in cred.cap_effective.iter() {
+				info!("    {}", crate::capability::cap_name(cap).unwrap_or("?"));
+			}
+		}
 	}
 
 	/// Uptime command
@@ -694,6 +763,7 @@ impl KernelShell {
 			info!("  clear    - Clear performance events");
 			info!("  counters - Show performance counters");
 			info!("  reset    - Reset all counters");
+			info!("  idle     - Show idle residency and tickless stats");
 			return;
 		}
 
@@ -744,6 +814,31 @@ impl KernelShell {
 				}
 				info!("All performance counters reset");
 			}
+			"idle" => {
+				let stats = crate::timer::get_timer_stats();
+				let uptime = crate::time::get_time_ns();
+				let residency = if uptime > 0 {
+					stats.idle_time_ns * 100 / uptime
+				} else {
+					0
+				};
+				let nohz = if crate::timer::nohz_enabled() {
+					"on"
+				} else {
+					"off"
+				};
+				info!("Idle Statistics:");
+				info!("  NO_HZ: {}", nohz);
+				info!("  Idle calls: {}", stats.idle_calls);
+				info!("  Tickless sleeps: {}", stats.idle_sleeps);
+				info!(
+					"  Idle time: {} ms ({}%)",
+					stats.idle_time_ns / 1_000_000,
+					residency
+				);
+				info!("  Ticks skipped: {}", stats.ticks_skipped);
+				info!("  Timer interrupts: {}", stats.total_interrupts);
+			}
 			_ => {
 				info!("Unknown perf command: {}", args[0]);
 			}
@@ -1555,6 +1650,120 @@ impl KernelShell {
 		}
 	}
 
+	/// CPU task group command
+	fn cmd_cgroup(&self, args: &[&str]) {
+		use crate::scheduler;
+
+		if args.is_empty() {
+			info!("CPU Task Group Commands:");
+			info!("  list                          - List groups and statistics");
+			info!("  create <name> [parent]        - Create a group");
+			info!("  remove <name>                 - Remove an empty group");
+			info!("  shares <name> <shares>        - Set CPU weight");
+			info!("  quota <name> <us|max> [period] - Limit CPU bandwidth");
+			info!("  attach <name> <tid>           - Move a task to a group");
+			return;
+		}
+
+		let group = |name: &str| match scheduler::find_task_group(name) {
+			Some(group) => Some(group),
+			None => {
+				info!("No such group: {}", name);
+				None
+			}
+		};
+
+		let result = match args[0] {
+			"list" => {
+				info!("  Group        | Tasks | Shares | Quota/Period us | Usage ms | Throttled");
+				for stats in scheduler::task_groups() {
+					let quota = match stats.quota_us {
+						Some(quota) => {
+							format!("{}/{}", quota, stats.period_us)
+						}
+						None => format!("max/{}", stats.period_us),
+					};
+					info!(
+						"  {:12} | {:5} | {:6} | {:15} | {:8} | {}/{} ({} ms){}",
+						stats.name,
+						stats.nr_running,
+						stats.shares,
+						quota,
+						stats.usage_ns / 1_000_000,
+						stats.nr_throttled,
+						stats.nr_periods,
+						stats.throttled_ns / 1_000_000,
+						if stats.throttled { " *" } else { "" }
+					);
+				}
+				Ok(())
+			}
+			"create" if args.len() >= 2 => {
+				let parent = match args.get(2) {
+					Some(name) => match group(name) {
+						Some(parent) => parent,
+						None => return,
+					},
+					None => scheduler::ROOT_TASK_GROUP,
+				};
+				scheduler::create_task_group(args[1], parent).map(|id| {
+					info!("Created group {} ({})", args[1], id);
+				})
+			}
+			"remove" if args.len() >= 2 => match group(args[1]) {
+				Some(id) => scheduler::destroy_task_group(id),
+				None => return,
+			},
+			"shares" if args.len() >= 3 => {
+				match (group(args[1]), args[2].parse::<u32>()) {
+					(Some(id), Ok(shares)) => {
+						scheduler::set_group_shares(id, shares)
+					}
+					(Some(_), Err(_)) => Err(crate::error::Error::EINVAL),
+					(None, _) => return,
+				}
+			}
+			"quota" if args.len() >= 3 => {
+				let id = match group(args[1]) {
+					Some(id) => id,
+					None => return,
+				};
+				let quota = match args[2] {
+					"max" => Ok(None),
+					quota => quota.parse::<u64>().map(Some),
+				};
+				let period = match args.get(3) {
+					Some(period) => period.parse::<u64>(),
+					None => Ok(scheduler::DEFAULT_CFS_PERIOD_US),
+				};
+				match (quota, period) {
+					(Ok(quota), Ok(period)) => {
+						scheduler::set_group_bandwidth(id, quota, period)
+					}
+					_ => Err(crate::error::Error::EINVAL),
+				}
+			}
+			"attach" if args.len() >= 3 => {
+				match (group(args[1]), args[2].parse::<u32>()) {
+					(Some(id), Ok(tid)) => scheduler::move_task_to_group(
+						crate::types::Tid(tid),
+						id,
+					),
+					(Some(_), Err(_)) => Err(crate::error::Error::EINVAL),
+					(None, _) => return,
+				}
+			}
+			_ => {
+				info!("Unknown or incomplete cgroup command: {}", args[0]);
+				return;
+			}
+		};
+
+		if let Err(e) = result {
+			info!("cgroup {}: {}", args[0], e);
+		}
+	}
+
 	/// Task management command
 	fn cmd_tasks(&self, args: &[&str]) {
 		if args.is_empty() {
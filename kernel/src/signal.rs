@@ -0,0 +1,160 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Signal numbers and default dispositions - Linux compatible
+
+use crate::error::{Error, Result};
+
+pub const SIGHUP: i32 = 1;
+pub const SIGINT: i32 = 2;
+pub const SIGQUIT: i32 = 3;
+pub const SIGILL: i32 = 4;
+pub const SIGTRAP: i32 = 5;
+pub const SIGABRT: i32 = 6;
+pub const SIGBUS: i32 = 7;
+pub const SIGFPE: i32 = 8;
+pub const SIGKILL: i32 = 9;
+pub const SIGUSR1: i32 = 10;
+pub const SIGSEGV: i32 = 11;
+pub const SIGUSR2: i32 = 12;
+pub const SIGPIPE: i32 = 13;
+pub const SIGALRM: i32 = 14;
+pub const SIGTERM: i32 = 15;
+pub const SIGSTKFLT: i32 = 16;
+pub const SIGCHLD: i32 = 17;
+pub const SIGCONT: i32 = 18;
+pub const SIGSTOP: i32 = 19;
+pub const SIGTSTP: i32 = 20;
+pub const SIGTTIN: i32 = 21;
+pub const SIGTTOU: i32 = 22;
+pub const SIGURG: i32 = 23;
+pub const SIGXCPU: i32 = 24;
+pub const SIGXFSZ: i32 = 25;
+pub const SIGVTALRM: i32 = 26;
+pub const SIGPROF: i32 = 27;
+pub const SIGWINCH: i32 = 28;
+pub const SIGIO: i32 = 29;
+pub const SIGPWR: i32 = 30;
+pub const SIGSYS: i32 = 31;
+
+/// Highest valid signal number (including real-time signals)
+pub const NSIG: i32 = 64;
+
+/// How rt_sigprocmask changes the blocked signals
+pub const SIG_BLOCK: i32 = 0;
+pub const SIG_UNBLOCK: i32 = 1;
+pub const SIG_SETMASK: i32 = 2;
+
+/// Special handlers of rt_sigaction
+pub const SIG_DFL: u64 = 0;
+pub const SIG_IGN: u64 = 1;
+
+/// Default action taken when a signal is delivered without a handler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefaultAction {
+	Terminate,
+	CoreDump,
+	Stop,
+	Continue,
+	Ignore,
+}
+
+/// Get the default action for a signal
+pub fn default_action(signal: i32) -> DefaultAction {
+	match signal {
+		SIGQUIT | SIGILL | SIGTRAP | SIGABRT | SIGBUS | SIGFPE | SIGSEGV | SIGXCPU
+		| SIGXFSZ | SIGSYS => DefaultAction::CoreDump,
+		SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => DefaultAction::Stop,
+		SIGCONT => DefaultAction::Continue,
+		SIGCHLD | SIGURG | SIGWINCH => DefaultAction::Ignore,
+		_ => DefaultAction::Terminate,
+	}
+}
+
+/// Check whether a signal number is valid (0 is the existence probe)
+pub fn validate(signal: i32) -> Result<()> {
+	if (0..=NSIG).contains(&signal) {
+		Ok(())
+	} else {
+		Err(Error::EINVAL)
+	}
+}
+
+/// Set of signals as a bitmask, bit N-1 for signal N
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SigSet(pub u64);
+
+impl SigSet {
+	pub const fn empty() -> Self {
+		Self(0)
+	}
+
+	fn bit(signal: i32) -> u64 {
+		1u64 << (signal - 1)
+	}
+
+	pub fn add(&mut self, signal: i32) {
+		self.0 |= Self::bit(signal);
+	}
+
+	pub fn remove(&mut self, signal: i32) {
+		self.0 &= !Self::bit(signal);
+	}
+
+	pub fn contains(&self, signal: i32) -> bool {
+		self.0 & Self::bit(signal) != 0
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.0 == 0
+	}
+
+	/// Remove and return the lowest numbered signal in the set
+	pub fn take_next(&mut self) -> Option<i32> {
+		if self.0 == 0 {
+			return None;
+		}
+		let signal = self.0.trailing_zeros() as i32 + 1;
+		self.remove(signal);
+		Some(signal)
+	}
+}
+
+/// Disposition of a signal, laid out as the struct sigaction rt_sigaction
+/// takes on x86_64
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SigAction {
+	pub handler: u64, // SIG_DFL, SIG_IGN or a user function
+	pub flags: u64,
+	pub restorer: u64,
+	pub mask: SigSet, // Blocked while the handler runs
+}
+
+impl SigAction {
+	/// Size of struct sigaction in user memory
+	pub const SIZE: usize = 32;
+
+	pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+		let word = |i: usize| {
+			let mut value = [0u8; 8];
+			value.copy_from_slice(&bytes[i * 8..i * 8 + 8]);
+			u64::from_ne_bytes(value)
+		};
+		Self {
+			handler: word(0),
+			flags: word(1),
+			restorer: word(2),
+			mask: SigSet(word(3)),
+		}
+	}
+
+	pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+		let mut bytes = [0u8; Self::SIZE];
+		for (i, word) in [self.handler, self.flags, self.restorer, self.mask.0]
+			.into_iter()
+			.enumerate()
+		{
+			bytes[i * 8..i * 8 + 8].copy_from_slice(&word.to_ne_bytes());
+		}
+		bytes
+	}
+}
@@ -0,0 +1,288 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Softirqs and tasklets - deferred interrupt work, similar to Linux
+//!
+//! Interrupt handlers do the minimum in hard-IRQ context and raise a
+//! softirq for the rest. Pending softirqs run when the outermost interrupt
+//! handler exits, or in ksoftirqd when they keep being raised or the
+//! interrupted code holds a spinlock a handler might need.
+//!
+//! Tasklets are deferred driver functions run from the HI and TASKLET
+//! softirqs. A tasklet never runs concurrently with itself.
+
+use alloc::collections::VecDeque;
+use core::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+
+use spin::once::Once;
+
+use crate::error::Result;
+use crate::sync::Spinlock;
+
+pub const HI_SOFTIRQ: usize = 0;
+pub const TIMER_SOFTIRQ: usize = 1;
+pub const NET_TX_SOFTIRQ: usize = 2;
+pub const NET_RX_SOFTIRQ: usize = 3;
+pub const BLOCK_SOFTIRQ: usize = 4;
+pub const IRQ_POLL_SOFTIRQ: usize = 5;
+pub const TASKLET_SOFTIRQ: usize = 6;
+pub const SCHED_SOFTIRQ: usize = 7;
+pub const HRTIMER_SOFTIRQ: usize = 8;
+pub const RCU_SOFTIRQ: usize = 9;
+pub const NR_SOFTIRQS: usize = 10;
+
+/// Softirq names, as shown in /proc/softirqs
+pub const SOFTIRQ_NAMES: [&str; NR_SOFTIRQS] = [
+	"HI", "TIMER", "NET_TX", "NET_RX", "BLOCK", "IRQ_POLL", "TASKLET", "SCHED", "HRTIMER",
+	"RCU",
+];
+
+/// Rounds of pending softirqs handled before deferring to ksoftirqd
+const MAX_SOFTIRQ_RESTART: usize = 10;
+
+const NO_HANDLER: Once<fn()> = Once::new();
+const ZERO: AtomicU64 = AtomicU64::new(0);
+
+/// Handler of each softirq, set once by open_softirq
+static HANDLERS: [Once<fn()>; NR_SOFTIRQS] = [NO_HANDLER; NR_SOFTIRQS];
+
+/// Times each softirq has run
+static COUNTS: [AtomicU64; NR_SOFTIRQS] = [ZERO; NR_SOFTIRQS];
+
+/// Bitmask of raised softirqs
+static PENDING: AtomicU32 = AtomicU32::new(0);
+
+/// Set while softirq handlers run
+static SERVING: AtomicUsize = AtomicUsize::new(0);
+
+/// Nesting of local_bh_disable sections
+static BH_DISABLED: AtomicUsize = AtomicUsize::new(0);
+
+/// Register the handler of softirq `nr`
+pub fn open_softirq(nr: usize, handler: fn()) {
+	if nr < NR_SOFTIRQS {
+		HANDLERS[nr].call_once(|| handler);
+	}
+}
+
+/// Mark softirq `nr` pending. It runs on the next interrupt exit, or in
+/// ksoftirqd.
+pub fn raise_softirq(nr: usize) {
+	if nr < NR_SOFTIRQS {
+		PENDING.fetch_or(1 << nr, Ordering::AcqRel);
+	}
+}
+
+/// Bitmask of pending softirqs
+pub fn local_softirq_pending() -> u32 {
+	PENDING.load(Ordering::Acquire)
+}
+
+/// Check whether softirq handlers are running
+pub fn in_serving_softirq() -> bool {
+	SERVING.load(Ordering::Relaxed) != 0
+}
+
+/// Check whether softirqs can't run here, because they are running or
+/// bottom halves are disabled
+pub fn in_softirq() -> bool {
+	in_serving_softirq() || BH_DISABLED.load(Ordering::Relaxed) != 0
+}
+
+/// Keep softirqs from running until local_bh_enable
+pub fn local_bh_disable() {
+	BH_DISABLED.fetch_add(1, Ordering::AcqRel);
+}
+
+/// End a local_bh_disable section, running softirqs raised meanwhile
+pub fn local_bh_enable() {
+	if BH_DISABLED.fetch_sub(1, Ordering::AcqRel) == 1 && !crate::interrupt::in_irq() {
+		do_softirq();
+	}
+}
+
+/// Times softirq `nr` has run
+pub fn softirq_count(nr: usize) -> u64 {
+	COUNTS.get(nr)
+		.map_or(0, |count| count.load(Ordering::Relaxed))
+}
+
+/// Run pending softirqs, unless they are already running, bottom halves
+/// are disabled or we are in an interrupt handler
+pub fn do_softirq() {
+	if local_softirq_pending() == 0 || in_softirq() || crate::interrupt::in_irq() {
+		return;
+	}
+	// A handler could need a lock the interrupted code holds
+	if crate::sync::spinlock_depth() != 0 {
+		return;
+	}
+	handle_softirqs();
+}
+
+/// Called when the outermost interrupt handler exits
+pub(crate) fn invoke_softirq() {
+	do_softirq();
+}
+
+fn handle_softirqs() {
+	SERVING.fetch_add(1, Ordering::AcqRel);
+	for _ in 0..MAX_SOFTIRQ_RESTART {
+		let pending = PENDING.swap(0, Ordering::AcqRel);
+		if pending == 0 {
+			break;
+		}
+		for nr in 0..NR_SOFTIRQS {
+			if pending & (1 << nr) == 0 {
+				continue;
+			}
+			COUNTS[nr].fetch_add(1, Ordering::Relaxed);
+			if let Some(handler) = HANDLERS[nr].get() {
+				handler();
+			}
+		}
+	}
+	// Whatever is still pending is left to ksoftirqd
+	SERVING.fetch_sub(1, Ordering::AcqRel);
+}
+
+/// Softirq daemon, running softirqs that were deferred
+fn ksoftirqd() {
+	loop {
+		crate::sync::wait_until(|| local_softirq_pending() != 0);
+		if crate::sync::spinlock_depth() == 0 && !in_softirq() {
+			crate::interrupt::without_interrupts(handle_softirqs);
+		}
+	}
+}
+
+/// Tasklet states
+const TASKLET_STATE_SCHED: u8 = 1 << 0; // Queued to run
+const TASKLET_STATE_RUN: u8 = 1 << 1; // Running
+
+/// Deferred function of a driver, run in softirq context
+pub struct Tasklet {
+	state: AtomicU8,
+	count: AtomicUsize, // Disable nesting
+	func: fn(usize),
+	data: usize,
+}
+
+impl Tasklet {
+	pub const fn new(func: fn(usize), data: usize) -> Self {
+		Self {
+			state: AtomicU8::new(0),
+			count: AtomicUsize::new(0),
+			func,
+			data,
+		}
+	}
+
+	/// Check whether the tasklet is queued to run
+	pub fn is_scheduled(&self) -> bool {
+		self.state.load(Ordering::Acquire) & TASKLET_STATE_SCHED != 0
+	}
+
+	fn is_running(&self) -> bool {
+		self.state.load(Ordering::Acquire) & TASKLET_STATE_RUN != 0
+	}
+}
+
+type TaskletList = Spinlock<VecDeque<&'static Tasklet>>;
+
+static TASKLET_VEC: TaskletList = Spinlock::new(VecDeque::new());
+static TASKLET_HI_VEC: TaskletList = Spinlock::new(VecDeque::new());
+
+fn tasklet_queue(list: &TaskletList, nr: usize, t: &'static Tasklet) {
+	if t.state.fetch_or(TASKLET_STATE_SCHED, Ordering::AcqRel) & TASKLET_STATE_SCHED != 0 {
+		return;
+	}
+	// Also called from interrupt handlers
+	crate::interrupt::without_interrupts(|| list.lock().push_back(t));
+	raise_softirq(nr);
+}
+
+/// Queue a tasklet to run once. Scheduling an already queued tasklet does
+/// nothing.
+pub fn tasklet_schedule(t: &'static Tasklet) {
+	tasklet_queue(&TASKLET_VEC, TASKLET_SOFTIRQ, t);
+}
+
+/// Queue a tasklet ahead of other softirq work
+pub fn tasklet_hi_schedule(t: &'static Tasklet) {
+	tasklet_queue(&TASKLET_HI_VEC, HI_SOFTIRQ, t);
+}
+
+/// Keep a tasklet from running, without waiting for it to finish
+pub fn tasklet_disable_nosync(t: &Tasklet) {
+	t.count.fetch_add(1, Ordering::AcqRel);
+}
+
+/// Keep a tasklet from running and wait for it to finish
+#[track_caller]
+pub fn tasklet_disable(t: &Tasklet) {
+	tasklet_disable_nosync(t);
+	if t.is_running() {
+		crate::sync::might_sleep();
+		crate::sync::wait_until(|| !t.is_running());
+	}
+}
+
+/// Allow a disabled tasklet to run again
+pub fn tasklet_enable(t: &Tasklet) {
+	t.count.fetch_sub(1, Ordering::AcqRel);
+}
+
+/// Wait for a tasklet to be neither queued nor running. The caller must
+/// keep it from being scheduled again.
+#[track_caller]
+pub fn tasklet_kill(t: &Tasklet) {
+	crate::sync::might_sleep();
+	crate::sync::wait_until(|| {
+		if t.is_scheduled() && !in_softirq() {
+			do_softirq();
+		}
+		!t.is_scheduled() && !t.is_running()
+	});
+}
+
+fn tasklet_action_common(list: &TaskletList, nr: usize) {
+	let queued = crate::interrupt::without_interrupts(|| core::mem::take(&mut *list.lock()));
+	for t in queued {
+		let state = t.state.fetch_or(TASKLET_STATE_RUN, Ordering::AcqRel);
+		if state & TASKLET_STATE_RUN == 0 {
+			if t.count.load(Ordering::Acquire) == 0 {
+				t.state.fetch_and(!TASKLET_STATE_SCHED, Ordering::AcqRel);
+				(t.func)(t.data);
+				t.state.fetch_and(!TASKLET_STATE_RUN, Ordering::AcqRel);
+				continue;
+			}
+			t.state.fetch_and(!TASKLET_STATE_RUN, Ordering::AcqRel);
+		}
+		// Disabled or running elsewhere, try again later
+		crate::interrupt::without_interrupts(|| list.lock().push_back(t));
+		raise_softirq(nr);
+	}
+}
+
+fn tasklet_action() {
+	tasklet_action_common(&TASKLET_VEC, TASKLET_SOFTIRQ);
+}
+
+fn tasklet_hi_action() {
+	tasklet_action_common(&TASKLET_HI_VEC, HI_SOFTIRQ);
+}
+
+fn run_timer_softirq() {
+	crate::time::run_timers();
+}
+
+/// Initialize softirqs and start ksoftirqd
+pub fn init() -> Result<()> {
+	open_softirq(HI_SOFTIRQ, tasklet_hi_action);
+	open_softirq(TIMER_SOFTIRQ, run_timer_softirq);
+	open_softirq(TASKLET_SOFTIRQ, tasklet_action);
+
+	crate::working_task::create_kernel_task("ksoftirqd/0", ksoftirqd)?;
+	crate::info!("Softirqs initialized");
+	Ok(())
+}
@@ -0,0 +1,101 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Completions - wait for another task to finish something, similar to
+//! Linux struct completion
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::WaitQueue;
+
+/// `done` value after complete_all, never consumed by waiters
+const COMPLETE_ALL: usize = usize::MAX;
+
+#[derive(Debug)]
+pub struct Completion {
+	done: AtomicUsize,
+	queue: WaitQueue, // Tasks in wait_for_completion
+}
+
+impl Completion {
+	pub const fn new() -> Self {
+		Self {
+			done: AtomicUsize::new(0),
+			queue: WaitQueue::new(),
+		}
+	}
+
+	/// Reset to not completed, for reuse
+	pub fn reinit(&self) {
+		self.done.store(0, Ordering::Release);
+	}
+
+	/// Wake one waiter, or let the next wait return at once
+	pub fn complete(&self) {
+		let mut done = self.done.load(Ordering::Relaxed);
+		while done != COMPLETE_ALL {
+			match self.done.compare_exchange_weak(
+				done,
+				done + 1,
+				Ordering::Release,
+				Ordering::Relaxed,
+			) {
+				Ok(_) => break,
+				Err(current) => done = current,
+			}
+		}
+		self.queue.wake_up();
+	}
+
+	/// Wake every current and future waiter
+	pub fn complete_all(&self) {
+		self.done.store(COMPLETE_ALL, Ordering::Release);
+		self.queue.wake_up();
+	}
+
+	/// Consume one completion if one is pending
+	pub fn try_wait_for_completion(&self) -> bool {
+		let mut done = self.done.load(Ordering::Acquire);
+		loop {
+			match done {
+				0 => return false,
+				COMPLETE_ALL => return true,
+				_ => match self.done.compare_exchange_weak(
+					done,
+					done - 1,
+					Ordering::Acquire,
+					Ordering::Relaxed,
+				) {
+					Ok(_) => return true,
+					Err(current) => done = current,
+				},
+			}
+		}
+	}
+
+	/// Sleep until completed
+	#[track_caller]
+	pub fn wait_for_completion(&self) {
+		super::might_sleep();
+		self.queue.wait_event(|| self.try_wait_for_completion());
+	}
+
+	/// Sleep until completed or `timeout_ns` passes. Returns whether it
+	/// completed.
+	#[track_caller]
+	pub fn wait_for_completion_timeout(&self, timeout_ns: u64) -> bool {
+		super::might_sleep();
+		let deadline = crate::time::get_time_ns().saturating_add(timeout_ns);
+		super::wait_until_deadline(|| self.try_wait_for_completion(), deadline)
+	}
+
+	/// Check whether a wait would return at once
+	pub fn completion_done(&self) -> bool {
+		self.done.load(Ordering::Acquire) != 0
+	}
+}
+
+impl Default for Completion {
+	fn default() -> Self {
+		Self::new()
+	}
+}
@@ -0,0 +1,210 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Synchronization primitives
+//!
+//! `Spinlock`, `SpinMutex` and `SpinRwLock` busy-wait and may be used
+//! anywhere. `Mutex`, `RwLock`, `Semaphore` and `Completion` block their
+//! waiters on a `WaitQueue`, so they must not be taken with interrupts
+//! disabled or a spinlock held.
+
+// Re-export common synchronization types
+pub use alloc::sync::Arc;
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::panic::Location;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+pub use spin::Mutex as SpinMutex;
+pub use spin::RwLock as SpinRwLock;
+
+use crate::cpu::NR_CPUS;
+
+pub mod completion;
+pub mod mutex;
+pub mod rwlock;
+pub mod semaphore;
+pub mod wait;
+
+pub use completion::Completion;
+pub use mutex::{Mutex, MutexGuard};
+pub use rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use semaphore::{Semaphore, SemaphoreGuard};
+pub use wait::WaitQueue;
+
+/// Spinlocks currently held, per CPU
+static SPINLOCK_DEPTH: [AtomicUsize; NR_CPUS] = [const { AtomicUsize::new(0) }; NR_CPUS];
+
+/// Set once boot is done; sleeping is not checked before that
+static SYSTEM_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Reports printed by might_sleep, bounded so a bad path can't flood the
+/// console
+static SLEEP_WARNINGS: AtomicUsize = AtomicUsize::new(0);
+const MAX_SLEEP_WARNINGS: usize = 16;
+
+/// Mark the end of boot, enabling might_sleep checks
+pub fn mark_system_running() {
+	SYSTEM_RUNNING.store(true, Ordering::Release);
+}
+
+/// Spinlock depth counter of the current CPU
+fn depth_counter() -> &'static AtomicUsize {
+	&SPINLOCK_DEPTH[crate::cpu::smp_processor_id()]
+}
+
+/// Number of spinlocks held on the current CPU
+pub fn spinlock_depth() -> usize {
+	depth_counter().load(Ordering::Relaxed)
+}
+
+/// Check whether the caller is allowed to sleep
+pub fn can_sleep() -> bool {
+	spinlock_depth() == 0 && crate::interrupt::are_enabled()
+}
+
+/// Complain if the caller may sleep in atomic context - like Linux
+/// might_sleep
+#[track_caller]
+pub fn might_sleep() {
+	if !SYSTEM_RUNNING.load(Ordering::Acquire) || can_sleep() {
+		return;
+	}
+	if SLEEP_WARNINGS.fetch_add(1, Ordering::Relaxed) >= MAX_SLEEP_WARNINGS {
+		return;
+	}
+	let location = Location::caller();
+	crate::warn!(
+		"BUG: sleeping function called from invalid context at {}:{}",
+		location.file(),
+		location.line()
+	);
+	crate::warn!(
+		"irqs_disabled: {}, spinlocks held: {}",
+		!crate::interrupt::are_enabled(),
+		spinlock_depth()
+	);
+	#[cfg(feature = "debug")]
+	panic!("sleeping in atomic context");
+}
+
+/// Give up the CPU until `cond` holds. Callers check might_sleep first.
+pub(crate) fn wait_until(mut cond: impl FnMut() -> bool) {
+	while !cond() {
+		crate::scheduler::yield_now();
+		core::hint::spin_loop();
+	}
+}
+
+/// Like wait_until, giving up at `deadline` (ns since boot). Returns
+/// whether `cond` was met.
+pub(crate) fn wait_until_deadline(mut cond: impl FnMut() -> bool, deadline: u64) -> bool {
+	loop {
+		if cond() {
+			return true;
+		}
+		if crate::time::get_time_ns() >= deadline {
+			return false;
+		}
+		crate::scheduler::yield_now();
+		core::hint::spin_loop();
+	}
+}
+
+/// Task ID recorded as owner of sleeping locks in debug builds. Boot
+/// code before the first task runs as u32::MAX.
+#[cfg(feature = "debug")]
+pub(crate) fn owner_id() -> u32 {
+	crate::scheduler::current_tid().map_or(u32::MAX, |tid| tid.0)
+}
+
+/// Spinlock implementation
+pub struct Spinlock<T> {
+	locked: AtomicBool,
+	#[cfg(feature = "debug")]
+	class: &'static Location<'static>, // Lockdep class, the creation site
+	data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for Spinlock<T> {}
+unsafe impl<T: Send> Send for Spinlock<T> {}
+
+impl<T> Spinlock<T> {
+	#[cfg_attr(feature = "debug", track_caller)]
+	pub const fn new(data: T) -> Self {
+		Self {
+			locked: AtomicBool::new(false),
+			#[cfg(feature = "debug")]
+			class: Location::caller(),
+			data: UnsafeCell::new(data),
+		}
+	}
+
+	#[cfg(feature = "debug")]
+	#[track_caller]
+	fn lockdep_acquire(&self, trylock: bool) {
+		let addr = self as *const Self as usize;
+		crate::lockdep::acquire(addr, self.class, Location::caller(), trylock);
+	}
+
+	#[cfg_attr(feature = "debug", track_caller)]
+	pub fn lock(&self) -> SpinlockGuard<'_, T> {
+		#[cfg(feature = "debug")]
+		self.lockdep_acquire(false);
+
+		while self
+			.locked
+			.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+			.is_err()
+		{
+			// Busy wait
+			while self.locked.load(Ordering::Relaxed) {
+				core::hint::spin_loop();
+			}
+		}
+
+		depth_counter().fetch_add(1, Ordering::Relaxed);
+		SpinlockGuard { lock: self }
+	}
+
+	#[cfg_attr(feature = "debug", track_caller)]
+	pub fn try_lock(&self) -> Option<SpinlockGuard<'_, T>> {
+		if self.locked
+			.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+			.is_ok()
+		{
+			#[cfg(feature = "debug")]
+			self.lockdep_acquire(true);
+			depth_counter().fetch_add(1, Ordering::Relaxed);
+			Some(SpinlockGuard { lock: self })
+		} else {
+			None
+		}
+	}
+}
+
+pub struct SpinlockGuard<'a, T> {
+	lock: &'a Spinlock<T>,
+}
+
+impl<T> Deref for SpinlockGuard<'_, T> {
+	type Target = T;
+
+	fn deref(&self) -> &Self::Target {
+		unsafe { &*self.lock.data.get() }
+	}
+}
+
+impl<T> DerefMut for SpinlockGuard<'_, T> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		unsafe { &mut *self.lock.data.get() }
+	}
+}
+
+impl<T> Drop for SpinlockGuard<'_, T> {
+	fn drop(&mut self) {
+		#[cfg(feature = "debug")]
+		crate::lockdep::release(self.lock as *const Spinlock<T> as usize);
+		self.lock.locked.store(false, Ordering::Release);
+		depth_counter().fetch_sub(1, Ordering::Relaxed);
+	}
+}
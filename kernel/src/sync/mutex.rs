@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Sleeping mutex - similar to Linux struct mutex
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "debug")]
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use super::WaitQueue;
+
+/// Mutual exclusion lock whose waiters sleep on a wait queue while it is
+/// contended
+pub struct Mutex<T: ?Sized> {
+	locked: AtomicBool,
+	#[cfg(feature = "debug")]
+	owner: AtomicU32, // Task holding the lock, 0 if free
+	queue: WaitQueue, // Tasks waiting for the lock
+	data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+
+impl<T> Mutex<T> {
+	pub const fn new(data: T) -> Self {
+		Self {
+			locked: AtomicBool::new(false),
+			#[cfg(feature = "debug")]
+			owner: AtomicU32::new(0),
+			queue: WaitQueue::new(),
+			data: UnsafeCell::new(data),
+		}
+	}
+
+	pub fn into_inner(self) -> T {
+		self.data.into_inner()
+	}
+}
+
+impl<T: ?Sized> Mutex<T> {
+	fn try_acquire(&self) -> bool {
+		self.locked
+			.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+			.is_ok()
+	}
+
+	fn guard(&self) -> MutexGuard<'_, T> {
+		#[cfg(feature = "debug")]
+		self.owner.store(super::owner_id(), Ordering::Relaxed);
+		MutexGuard { mutex: self }
+	}
+
+	/// Acquire the mutex, sleeping while it is held
+	#[track_caller]
+	pub fn lock(&self) -> MutexGuard<'_, T> {
+		super::might_sleep();
+		if !self.try_acquire() {
+			#[cfg(feature = "debug")]
+			if self.owner() == Some(super::owner_id()) && super::owner_id() != u32::MAX
+			{
+				panic!("mutex: recursive locking");
+			}
+			self.queue.wait_event(|| self.try_acquire());
+		}
+		self.guard()
+	}
+
+	pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+		if self.try_acquire() {
+			Some(self.guard())
+		} else {
+			None
+		}
+	}
+
+	pub fn is_locked(&self) -> bool {
+		self.locked.load(Ordering::Relaxed)
+	}
+
+	/// Task ID of the holder, tracked in debug builds only
+	#[cfg(feature = "debug")]
+	pub fn owner(&self) -> Option<u32> {
+		match self.owner.load(Ordering::Relaxed) {
+			0 => None,
+			tid => Some(tid),
+		}
+	}
+
+	pub fn get_mut(&mut self) -> &mut T {
+		self.data.get_mut()
+	}
+}
+
+impl<T: Default> Default for Mutex<T> {
+	fn default() -> Self {
+		Self::new(T::default())
+	}
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.try_lock() {
+			Some(guard) => f.debug_struct("Mutex").field("data", &&*guard).finish(),
+			None => f.write_str("Mutex { <locked> }"),
+		}
+	}
+}
+
+pub struct MutexGuard<'a, T: ?Sized> {
+	mutex: &'a Mutex<T>,
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+	type Target = T;
+
+	fn deref(&self) -> &Self::Target {
+		unsafe { &*self.mutex.data.get() }
+	}
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		unsafe { &mut *self.mutex.data.get() }
+	}
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+	fn drop(&mut self) {
+		#[cfg(feature = "debug")]
+		self.mutex.owner.store(0, Ordering::Relaxed);
+		self.mutex.locked.store(false, Ordering::Release);
+		self.mutex.queue.wake_up();
+	}
+}
@@ -0,0 +1,204 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Sleeping reader-writer lock with writer preference - similar to Linux
+//! struct rw_semaphore
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "debug")]
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::WaitQueue;
+
+/// State bit set while a writer holds the lock, the rest counts readers
+const WRITER: usize = 1 << (usize::BITS - 1);
+
+/// Reader-writer lock whose waiters sleep on a wait queue. New readers
+/// wait while a writer is queued, so writers can't starve.
+pub struct RwLock<T: ?Sized> {
+	state: AtomicUsize,
+	writers_waiting: AtomicUsize,
+	#[cfg(feature = "debug")]
+	owner: AtomicU32, // Writer holding the lock, 0 if none
+	queue: WaitQueue, // Readers and writers waiting for the lock
+	data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+impl<T> RwLock<T> {
+	pub const fn new(data: T) -> Self {
+		Self {
+			state: AtomicUsize::new(0),
+			writers_waiting: AtomicUsize::new(0),
+			#[cfg(feature = "debug")]
+			owner: AtomicU32::new(0),
+			queue: WaitQueue::new(),
+			data: UnsafeCell::new(data),
+		}
+	}
+
+	pub fn into_inner(self) -> T {
+		self.data.into_inner()
+	}
+}
+
+impl<T: ?Sized> RwLock<T> {
+	fn try_acquire_read(&self) -> bool {
+		if self.writers_waiting.load(Ordering::Relaxed) != 0 {
+			return false;
+		}
+		let state = self.state.load(Ordering::Relaxed);
+		state & WRITER == 0
+			&& self.state
+				.compare_exchange_weak(
+					state,
+					state + 1,
+					Ordering::Acquire,
+					Ordering::Relaxed,
+				)
+				.is_ok()
+	}
+
+	fn try_acquire_write(&self) -> bool {
+		self.state
+			.compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+			.is_ok()
+	}
+
+	fn write_guard(&self) -> RwLockWriteGuard<'_, T> {
+		#[cfg(feature = "debug")]
+		self.owner.store(super::owner_id(), Ordering::Relaxed);
+		RwLockWriteGuard { lock: self }
+	}
+
+	/// Acquire shared access, sleeping while a writer holds or waits for
+	/// the lock
+	#[track_caller]
+	pub fn read(&self) -> RwLockReadGuard<'_, T> {
+		super::might_sleep();
+		self.queue.wait_event(|| self.try_acquire_read());
+		RwLockReadGuard { lock: self }
+	}
+
+	/// Acquire exclusive access, sleeping until all holders are gone
+	#[track_caller]
+	pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+		super::might_sleep();
+		if !self.try_acquire_write() {
+			#[cfg(feature = "debug")]
+			if self.owner() == Some(super::owner_id()) && super::owner_id() != u32::MAX
+			{
+				panic!("rwlock: recursive write locking");
+			}
+			self.writers_waiting.fetch_add(1, Ordering::Relaxed);
+			self.queue.wait_event(|| self.try_acquire_write());
+			self.writers_waiting.fetch_sub(1, Ordering::Relaxed);
+		}
+		self.write_guard()
+	}
+
+	pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+		if self.try_acquire_read() {
+			Some(RwLockReadGuard { lock: self })
+		} else {
+			None
+		}
+	}
+
+	pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+		if self.try_acquire_write() {
+			Some(self.write_guard())
+		} else {
+			None
+		}
+	}
+
+	/// Number of readers holding the lock
+	pub fn reader_count(&self) -> usize {
+		self.state.load(Ordering::Relaxed) & !WRITER
+	}
+
+	/// Check whether a writer holds the lock
+	pub fn is_write_locked(&self) -> bool {
+		self.state.load(Ordering::Relaxed) & WRITER != 0
+	}
+
+	/// Task ID of the writer, tracked in debug builds only
+	#[cfg(feature = "debug")]
+	pub fn owner(&self) -> Option<u32> {
+		match self.owner.load(Ordering::Relaxed) {
+			0 => None,
+			tid => Some(tid),
+		}
+	}
+
+	pub fn get_mut(&mut self) -> &mut T {
+		self.data.get_mut()
+	}
+}
+
+impl<T: Default> Default for RwLock<T> {
+	fn default() -> Self {
+		Self::new(T::default())
+	}
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.try_read() {
+			Some(guard) => f.debug_struct("RwLock").field("data", &&*guard).finish(),
+			None => f.write_str("RwLock { <locked> }"),
+		}
+	}
+}
+
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+	lock: &'a RwLock<T>,
+}
+
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+	lock: &'a RwLock<T>,
+}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+	type Target = T;
+
+	fn deref(&self) -> &Self::Target {
+		unsafe { &*self.lock.data.get() }
+	}
+}
+
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+	fn drop(&mut self) {
+		if self.lock.state.fetch_sub(1, Ordering::Release) == 1 {
+			self.lock.queue.wake_up();
+		}
+	}
+}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+	type Target = T;
+
+	fn deref(&self) -> &Self::Target {
+		unsafe { &*self.lock.data.get() }
+	}
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		unsafe { &mut *self.lock.data.get() }
+	}
+}
+
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+	fn drop(&mut self) {
+		#[cfg(feature = "debug")]
+		self.lock.owner.store(0, Ordering::Relaxed);
+		self.lock.state.store(0, Ordering::Release);
+		self.lock.queue.wake_up();
+	}
+}
@@ -0,0 +1,116 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Counting semaphore - similar to Linux struct semaphore
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::WaitQueue;
+use crate::error::{Error, Result};
+
+/// Counting semaphore that yields the CPU while no count is available
+#[derive(Debug)]
+pub struct Semaphore {
+	count: AtomicUsize,
+	queue: WaitQueue, // Tasks waiting in down
+}
+
+impl Semaphore {
+	pub const fn new(count: usize) -> Self {
+		Self {
+			count: AtomicUsize::new(count),
+			queue: WaitQueue::new(),
+		}
+	}
+
+	/// Take one count if available
+	pub fn try_down(&self) -> bool {
+		let mut count = self.count.load(Ordering::Relaxed);
+		while count > 0 {
+			match self.count.compare_exchange_weak(
+				count,
+				count - 1,
+				Ordering::Acquire,
+				Ordering::Relaxed,
+			) {
+				Ok(_) => return true,
+				Err(current) => count = current,
+			}
+		}
+		false
+	}
+
+	/// Take one count, sleeping until one is available
+	#[track_caller]
+	pub fn down(&self) {
+		super::might_sleep();
+		self.queue.wait_event(|| self.try_down());
+	}
+
+	/// Like down, but give up with Interrupted when a signal arrives
+	#[track_caller]
+	pub fn down_interruptible(&self) -> Result<()> {
+		super::might_sleep();
+		let mut interrupted = false;
+		super::wait_until(|| {
+			if self.try_down() {
+				return true;
+			}
+			interrupted = crate::process::current_process()
+				.map_or(false, |p| p.signal_pending);
+			interrupted
+		});
+		if interrupted {
+			Err(Error::Interrupted)
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Like down, but give up with Timeout after `timeout_ns`
+	#[track_caller]
+	pub fn down_timeout(&self, timeout_ns: u64) -> Result<()> {
+		super::might_sleep();
+		let deadline = crate::time::get_time_ns().saturating_add(timeout_ns);
+		if super::wait_until_deadline(|| self.try_down(), deadline) {
+			Ok(())
+		} else {
+			Err(Error::Timeout)
+		}
+	}
+
+	/// Release one count
+	pub fn up(&self) {
+		self.count.fetch_add(1, Ordering::Release);
+		self.queue.wake_up();
+	}
+
+	/// Take one count, released when the guard is dropped
+	#[track_caller]
+	pub fn acquire(&self) -> SemaphoreGuard<'_> {
+		self.down();
+		SemaphoreGuard { sem: self }
+	}
+
+	pub fn try_acquire(&self) -> Option<SemaphoreGuard<'_>> {
+		if self.try_down() {
+			Some(SemaphoreGuard { sem: self })
+		} else {
+			None
+		}
+	}
+
+	/// Counts currently available
+	pub fn count(&self) -> usize {
+		self.count.load(Ordering::Relaxed)
+	}
+}
+
+pub struct SemaphoreGuard<'a> {
+	sem: &'a Semaphore,
+}
+
+impl Drop for SemaphoreGuard<'_> {
+	fn drop(&mut self) {
+		self.sem.up();
+	}
+}
@@ -0,0 +1,91 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Wait queues - tell sleepers that what they wait for may have changed,
+//! similar to Linux wait_queue_head
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use super::Spinlock;
+use crate::types::Tid;
+
+/// A wait queue counts its wake-ups. A sleeper notes the count before it
+/// checks its condition and sleeps until the count moves, then checks
+/// again. Tasks blocked in wait_event are off the run queue until
+/// wake_up makes them runnable.
+pub struct WaitQueue {
+	wakeups: AtomicU64,
+	waiters: Spinlock<Vec<Tid>>,
+}
+
+impl WaitQueue {
+	pub const fn new() -> Self {
+		Self {
+			wakeups: AtomicU64::new(0),
+			waiters: Spinlock::new(Vec::new()),
+		}
+	}
+
+	/// Wake every sleeper
+	pub fn wake_up(&self) {
+		self.wakeups.fetch_add(1, Ordering::Release);
+		let waiters = crate::interrupt::without_interrupts(|| {
+			core::mem::take(&mut *self.waiters.lock())
+		});
+		for tid in waiters {
+			crate::scheduler::wake_up_thread(tid);
+		}
+	}
+
+	/// Number of wake-ups so far, to compare with a later one
+	pub fn wakeups(&self) -> u64 {
+		self.wakeups.load(Ordering::Acquire)
+	}
+
+	/// Sleep until `cond` holds, blocked between wake-ups. Callers check
+	/// might_sleep first; where sleeping isn't possible this falls back
+	/// to polling `cond`.
+	pub fn wait_event(&self, cond: impl FnMut() -> bool) {
+		self.wait_event_deadline(cond, None);
+	}
+
+	/// Like wait_event, giving up at `deadline` (ns since boot). Returns
+	/// whether `cond` was met.
+	pub fn wait_event_deadline(
+		&self,
+		mut cond: impl FnMut() -> bool,
+		deadline: Option<u64>,
+	) -> bool {
+		loop {
+			let seen = self.wakeups();
+			if cond() {
+				return true;
+			}
+			if deadline.is_some_and(|d| crate::time::get_time_ns() >= d) {
+				return false;
+			}
+			let tid = match crate::scheduler::current_tid() {
+				Some(tid) if super::can_sleep() => tid,
+				_ => {
+					crate::scheduler::yield_now();
+					core::hint::spin_loop();
+					continue;
+				}
+			};
+			crate::interrupt::without_interrupts(|| self.waiters.lock().push(tid));
+			crate::scheduler::sleep_current_until(|| self.wakeups() != seen, deadline);
+			crate::interrupt::without_interrupts(|| {
+				self.waiters.lock().retain(|&waiter| waiter != tid)
+			});
+		}
+	}
+}
+
+impl fmt::Debug for WaitQueue {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("WaitQueue")
+			.field("wakeups", &self.wakeups())
+			.finish_non_exhaustive()
+	}
+}
@@ -3,8 +3,9 @@
 //! System call interface - Linux compatible
 
 use crate::error::{Error, Result};
-use crate::process::{allocate_pid, current_process, find_process};
-use crate::types::Pid;
+use crate::process::{allocate_pid, current_process};
+use crate::time::NSEC_PER_SEC;
+use crate::types::{Gid, Pid, Uid};
 
 /// System call numbers (Linux compatible subset)
 #[derive(Debug, Clone, Copy)]
@@ -38,9 +39,41 @@ pub enum SyscallNumber {
 	Setuid = 105,
 	Getgid = 104,
 	Setgid = 106,
+	Geteuid = 107,
+	Getegid = 108,
+	Setreuid = 113,
+	Setregid = 114,
+	Getgroups = 115,
+	Setgroups = 116,
+	Setresuid = 117,
+	Getresuid = 118,
+	Setresgid = 119,
+	Getresgid = 120,
+	Umask = 95,
+	Capget = 125,
+	Capset = 126,
+	Unshare = 272,
+	Setns = 308,
+	Setpgid = 109,
+	Getpgrp = 111,
+	Setsid = 112,
+	Getpgid = 121,
+	Getsid = 124,
 	Gettid = 186,
 	Clone = 56,
 	Futex = 202,
+	Getrlimit = 97,
+	Getrusage = 98,
+	Times = 100,
+	Setrlimit = 160,
+	Prlimit64 = 302,
+	Ptrace = 101,
+	Prctl = 157,
+	Seccomp = 317,
+	Gettimeofday = 96,
+	Time = 201,
+	ClockGettime = 228,
+	Getcpu = 309,
 }
 
 /// System call arguments structure
@@ -57,259 +90,2205 @@ pub struct SyscallArgs {
 
 /// System call dispatcher
 pub fn handle_syscall(args: SyscallArgs) -> u64 {
+	if let Some(ret) = crate::seccomp::secure_computing(&args) {
+		return ret;
+	}
+
 	let result = match args.syscall_num {
 		// Process management
 		57 => sys_fork(),                                            // fork
 		59 => sys_execve(args.arg0, args.arg1, args.arg2),           // execve
 		60 => sys_exit(args.arg0 as i32),                            // exit
+		231 => sys_exit_group(args.arg0 as i32),                     // exit_group
+		158 => sys_arch_prctl(args.arg0 as i32, args.arg1),          // arch_prctl
+		218 => sys_set_tid_address(args.arg0),                       // set_tid_address
 		61 => sys_wait4(args.arg0, args.arg1, args.arg2, args.arg3), // wait4
 		62 => sys_kill(args.arg0 as i32, args.arg1 as i32),          // kill
+		56 => sys_clone(args.arg0, args.arg1, args.arg2, args.arg3, args.arg4), // clone
+		101 => sys_ptrace(args.arg0, args.arg1 as i32, args.arg2, args.arg3), // ptrace
+		157 => sys_prctl(args.arg0 as i32, args.arg1, args.arg2),    // prctl
+		317 => crate::seccomp::do_seccomp(args.arg0, args.arg1, args.arg2), // seccomp
 
 		// Process info
 		39 => Ok(sys_getpid() as u64),   // getpid
 		110 => Ok(sys_getppid() as u64), // getppid
 		102 => Ok(sys_getuid() as u64),  // getuid
 		104 => Ok(sys_getgid() as u64),  // getgid
+		107 => Ok(sys_geteuid() as u64), // geteuid
+		108 => Ok(sys_getegid() as u64), // getegid
 		186 => Ok(sys_gettid() as u64),  // gettid
 
-		// File operations
-		0 => sys_read(args.arg0 as i32, args.arg1, args.arg2), // read
-		1 => sys_write(args.arg0 as i32, args.arg1, args.arg2), // write
-		2 => sys_open(args.arg0, args.arg1 as i32, args.arg2 as u32), // open
-		3 => sys_close(args.arg0 as i32),                      // close
+		// Namespaces
+		272 => sys_unshare(args.arg0), // unshare
+		308 => sys_setns(args.arg0 as i32, args.arg1 as i32), // setns
+
+		// Threads and synchronization
+		202 => crate::futex::do_futex(
+			args.arg0,
+			args.arg1 as i32,
+			args.arg2 as u32,
+			args.arg3,
+			args.arg4,
+			args.arg5 as u32,
+		), // futex
+
+		// Credentials
+		105 => sys_setuid(args.arg0 as u32), // setuid
+		106 => sys_setgid(args.arg0 as u32), // setgid
+		113 => sys_setreuid(args.arg0 as u32, args.arg1 as u32), // setreuid
+		114 => sys_setregid(args.arg0 as u32, args.arg1 as u32), // setregid
+		115 => sys_getgroups(args.arg0 as i32, args.arg1), // getgroups
+		116 => sys_setgroups(args.arg0 as usize, args.arg1), // setgroups
+		117 => sys_setresuid(args.arg0, args.arg1, args.arg2), // setresuid
+		118 => sys_getresuid(args.arg0, args.arg1, args.arg2), // getresuid
+		119 => sys_setresgid(args.arg0, args.arg1, args.arg2), // setresgid
+		120 => sys_getresgid(args.arg0, args.arg1, args.arg2), // getresgid
+		125 => sys_capget(args.arg0, args.arg1), // capget
+		126 => sys_capset(args.arg0, args.arg1), // capset
+		95 => sys_umask(args.arg0 as u32),   // umask
+
+		// Resource limits and usage
+		97 => sys_getrlimit(args.arg0 as u32, args.arg1), // getrlimit
+		160 => sys_setrlimit(args.arg0 as u32, args.arg1), // setrlimit
+		302 => sys_prlimit64(args.arg0, args.arg1, args.arg2, args.arg3), // prlimit64
+		98 => sys_getrusage(args.arg0 as i32, args.arg1), // getrusage
+		100 => sys_times(args.arg0),                      // times
+
+		// Time (the vDSO answers most of these without a syscall)
+		228 => sys_clock_gettime(args.arg0 as i32, args.arg1), // clock_gettime
+		96 => sys_gettimeofday(args.arg0, args.arg1),          // gettimeofday
+		201 => sys_time(args.arg0),                            // time
+		309 => sys_getcpu(args.arg0, args.arg1),               // getcpu
+		35 => sys_nanosleep(args.arg0, args.arg1),             // nanosleep
+		230 => sys_clock_nanosleep(
+			args.arg0 as i32,
+			args.arg1 as i32,
+			args.arg2,
+			args.arg3,
+		), // clock_nanosleep
+		229 => sys_clock_getres(args.arg0 as i32, args.arg1),  // clock_getres
+		222 => sys_timer_create(args.arg0 as i32, args.arg1, args.arg2), // timer_create
+		// timer_settime
+		223 => sys_timer_settime(args.arg0 as i32, args.arg1 as i32, args.arg2, args.arg3),
+		224 => sys_timer_gettime(args.arg0 as i32, args.arg1), // timer_gettime
+		225 => sys_timer_getoverrun(args.arg0 as i32),         // timer_getoverrun
+		226 => sys_timer_delete(args.arg0 as i32),             // timer_delete
+		36 => sys_getitimer(args.arg0 as i32, args.arg1),      // getitimer
+		38 => sys_setitimer(args.arg0 as i32, args.arg1, args.arg2), // setitimer
+		37 => sys_alarm(args.arg0 as u32),                     // alarm
+
+		// Process groups and sessions
+		109 => sys_setpgid(args.arg0 as i32, args.arg1 as i32), // setpgid
+		111 => sys_getpgrp(),                                   // getpgrp
+		112 => sys_setsid(),                                    // setsid
+		121 => sys_getpgid(args.arg0 as i32),                   // getpgid
+		124 => sys_getsid(args.arg0 as i32),                    // getsid
+
+		// File operations
+		0 => sys_read(args.arg0 as i32, args.arg1, args.arg2), // read
+		1 => sys_write(args.arg0 as i32, args.arg1, args.arg2), // write
+		2 => sys_open(args.arg0, args.arg1 as i32, args.arg2 as u32), // open
+		3 => sys_close(args.arg0 as i32),                      // close
+		4 => sys_newfstatat(AT_FDCWD, args.arg0, args.arg1, 0), // stat
+		5 => sys_fstat(args.arg0 as i32, args.arg1),           // fstat
+		6 => sys_newfstatat(AT_FDCWD, args.arg0, args.arg1, AT_SYMLINK_NOFOLLOW), // lstat
+		8 => sys_lseek(args.arg0 as i32, args.arg1 as i64, args.arg2 as i32), // lseek
+		16 => sys_ioctl(args.arg0 as i32, args.arg1 as u32, args.arg2), // ioctl
+		// pread64
+		17 => sys_pread64(args.arg0 as i32, args.arg1, args.arg2, args.arg3 as i64),
+		// pwrite64
+		18 => sys_pwrite64(args.arg0 as i32, args.arg1, args.arg2, args.arg3 as i64),
+		19 => sys_readv(args.arg0 as i32, args.arg1, args.arg2), // readv
+		20 => sys_writev(args.arg0 as i32, args.arg1, args.arg2), // writev
+		21 => sys_faccessat(AT_FDCWD, args.arg0, args.arg1 as u32, 0), // access
+		22 => sys_pipe2(args.arg0, 0),                           // pipe
+		32 => sys_dup(args.arg0 as i32),                         // dup
+		33 => sys_dup2(args.arg0 as i32, args.arg1 as i32),      // dup2
+		72 => sys_fcntl(args.arg0 as i32, args.arg1 as i32, args.arg2), // fcntl
+		79 => sys_getcwd(args.arg0, args.arg1),                  // getcwd
+		80 => sys_chdir(args.arg0),                              // chdir
+		81 => sys_fchdir(args.arg0 as i32),                      // fchdir
+		82 => sys_renameat2(AT_FDCWD, args.arg0, AT_FDCWD, args.arg1, 0), // rename
+		83 => sys_mkdirat(AT_FDCWD, args.arg0, args.arg1 as u32), // mkdir
+		84 => sys_unlinkat(AT_FDCWD, args.arg0, AT_REMOVEDIR),   // rmdir
+		87 => sys_unlinkat(AT_FDCWD, args.arg0, 0),              // unlink
+		89 => sys_readlinkat(AT_FDCWD, args.arg0, args.arg1, args.arg2), // readlink
+		217 => sys_getdents64(args.arg0 as i32, args.arg1, args.arg2), // getdents64
+		257 => sys_openat(
+			args.arg0 as i32,
+			args.arg1,
+			args.arg2 as i32,
+			args.arg3 as u32,
+		), // openat
+		258 => sys_mkdirat(args.arg0 as i32, args.arg1, args.arg2 as u32), // mkdirat
+		// newfstatat
+		262 => sys_newfstatat(args.arg0 as i32, args.arg1, args.arg2, args.arg3),
+		263 => sys_unlinkat(args.arg0 as i32, args.arg1, args.arg2), // unlinkat
+		// renameat
+		264 => sys_renameat2(args.arg0 as i32, args.arg1, args.arg2 as i32, args.arg3, 0),
+		// readlinkat
+		267 => sys_readlinkat(args.arg0 as i32, args.arg1, args.arg2, args.arg3),
+		// faccessat
+		269 => sys_faccessat(args.arg0 as i32, args.arg1, args.arg2 as u32, args.arg3),
+		292 => sys_dup3(args.arg0 as i32, args.arg1 as i32, args.arg2 as u32), // dup3
+		293 => sys_pipe2(args.arg0, args.arg1 as u32),                         // pipe2
+		316 => sys_renameat2(
+			args.arg0 as i32,
+			args.arg1,
+			args.arg2 as i32,
+			args.arg3,
+			args.arg4 as u32,
+		), // renameat2
+
+		// Polling
+		7 => sys_poll(args.arg0, args.arg1, args.arg2 as i32), // poll
+		// select
+		23 => sys_select(args.arg0 as i32, args.arg1, args.arg2, args.arg3, args.arg4),
+		// pselect6
+		270 => sys_pselect6(
+			args.arg0 as i32,
+			args.arg1,
+			args.arg2,
+			args.arg3,
+			args.arg4,
+			args.arg5,
+		),
+		271 => sys_ppoll(args.arg0, args.arg1, args.arg2, args.arg3, args.arg4), // ppoll
+		213 => sys_epoll_create(args.arg0 as i32), // epoll_create
+		291 => sys_epoll_create1(args.arg0 as u32), // epoll_create1
+		// epoll_ctl
+		233 => sys_epoll_ctl(
+			args.arg0 as i32,
+			args.arg1 as i32,
+			args.arg2 as i32,
+			args.arg3,
+		),
+		// epoll_wait
+		232 => sys_epoll_wait(
+			args.arg0 as i32,
+			args.arg1,
+			args.arg2 as i32,
+			args.arg3 as i32,
+		),
+		// epoll_pwait
+		281 => sys_epoll_pwait(
+			args.arg0 as i32,
+			args.arg1,
+			args.arg2 as i32,
+			args.arg3 as i32,
+			args.arg4,
+			args.arg5,
+		),
+
+		// Event, timer and signal files
+		284 => sys_eventfd2(args.arg0 as u32, 0), // eventfd
+		290 => sys_eventfd2(args.arg0 as u32, args.arg1 as u32), // eventfd2
+		283 => sys_timerfd_create(args.arg0 as i32, args.arg1 as u32), // timerfd_create
+		// timerfd_settime
+		286 => sys_timerfd_settime(
+			args.arg0 as i32,
+			args.arg1 as u32,
+			args.arg2,
+			args.arg3,
+		),
+		287 => sys_timerfd_gettime(args.arg0 as i32, args.arg1), // timerfd_gettime
+		282 => sys_signalfd4(args.arg0 as i32, args.arg1, args.arg2, 0), // signalfd
+		// signalfd4
+		289 => sys_signalfd4(args.arg0 as i32, args.arg1, args.arg2, args.arg3 as u32),
+		// rt_sigaction
+		13 => sys_rt_sigaction(args.arg0 as i32, args.arg1, args.arg2, args.arg3),
+		// rt_sigprocmask
+		14 => sys_rt_sigprocmask(args.arg0 as i32, args.arg1, args.arg2, args.arg3),
+
+		// System information and randomness
+		63 => sys_uname(args.arg0), // uname
+		318 => sys_getrandom(args.arg0, args.arg1, args.arg2 as u32), // getrandom
+
+		// Memory management
+		9 => sys_mmap(
+			args.arg0,
+			args.arg1,
+			args.arg2 as i32,
+			args.arg3 as i32,
+			args.arg4 as i32,
+			args.arg5 as i64,
+		), // mmap
+		10 => sys_mprotect(args.arg0, args.arg1, args.arg2), // mprotect
+		11 => sys_munmap(args.arg0, args.arg1),              // munmap
+		12 => sys_brk(args.arg0),                            // brk
+
+		// Unimplemented syscalls
+		_ => Err(Error::ENOSYS),
+	};
+
+	match result {
+		Ok(value) => value,
+		Err(error) => error.to_syscall_ret(),
+	}
+}
+
+/// Process management syscalls
+pub fn sys_fork() -> Result<u64> {
+	use crate::process::create_process;
+	use crate::scheduler::add_task;
+
+	// Get current process
+	let current = current_process().ok_or(Error::ESRCH)?;
+	crate::process::check_nproc(&current)?;
+
+	// Fork the process
+	let child = current.fork()?;
+	let child_pid = child.pid;
+
+	// Add child to process table and scheduler
+	let mut table = crate::process::PROCESS_TABLE.lock();
+	table.add_process(child.clone());
+	drop(table);
+
+	// Add to scheduler
+	add_task(child_pid)?;
+	crate::ptrace::fork_event(current.pid, child_pid, crate::ptrace::PTRACE_EVENT_FORK);
+
+	// Return child PID to parent (in child, this would return 0)
+	Ok(child.pid_nr_ns(&current.pid_ns).unwrap_or(0) as u64)
+}
+
+pub fn sys_clone(flags: u64, stack: u64, parent_tid: u64, child_tid: u64, tls: u64) -> Result<u64> {
+	use crate::process::{
+		clone_flags::{CLONE_THREAD, CLONE_VFORK},
+		clone_task, current_thread_tid, CloneArgs,
+	};
+	use crate::ptrace::{PTRACE_EVENT_CLONE, PTRACE_EVENT_FORK, PTRACE_EVENT_VFORK};
+
+	let current = current_process().ok_or(Error::ESRCH)?;
+	let tid = current_thread_tid().ok_or(Error::ESRCH)?;
+	let args = CloneArgs {
+		flags,
+		stack,
+		parent_tid,
+		child_tid,
+		tls,
+	};
+
+	let (pid, new_tid) = clone_task(current.pid, tid, &args)?;
+	if flags & CLONE_THREAD != 0 {
+		crate::scheduler::add_thread(new_tid)?;
+		Ok(new_tid.0 as u64)
+	} else {
+		crate::scheduler::add_task(pid)?;
+		// The low byte of the flags is the exit signal
+		let event = if flags & CLONE_VFORK != 0 {
+			PTRACE_EVENT_VFORK
+		} else if flags & 0xff == crate::signal::SIGCHLD as u64 {
+			PTRACE_EVENT_FORK
+		} else {
+			PTRACE_EVENT_CLONE
+		};
+		crate::ptrace::fork_event(current.pid, pid, event);
+		Ok(crate::process::pid_nr_ns(pid, &current.pid_ns) as u64)
+	}
+}
+
+/// Copy a NULL-terminated array of user strings, as execve takes for
+/// argv and envp. Each string with its NUL and pointer is taken from
+/// `room`, failing with E2BIG once that runs out.
+pub fn user_strings(array: u64, room: &mut u64) -> Result<alloc::vec::Vec<alloc::string::String>> {
+	use crate::memory::{copy_from_user, copy_string_from_user, UserPtr};
+	use crate::usermode::{MAX_ARG_STRINGS, MAX_ARG_STRLEN};
+
+	let mut strings = alloc::vec::Vec::new();
+	// Linux takes a NULL array as an empty one
+	if array == 0 {
+		return Ok(strings);
+	}
+	loop {
+		let mut bytes = [0u8; 8];
+		let slot = array + strings.len() as u64 * 8;
+		copy_from_user(&mut bytes, UserPtr::from_const(slot as *const u8)?)?;
+		let ptr = u64::from_ne_bytes(bytes);
+		if ptr == 0 {
+			return Ok(strings);
+		}
+		if strings.len() >= MAX_ARG_STRINGS {
+			return Err(Error::E2BIG);
+		}
+		let string = copy_string_from_user(
+			UserPtr::from_const(ptr as *const u8)?,
+			MAX_ARG_STRLEN,
+		)?;
+		if string.len() >= MAX_ARG_STRLEN {
+			return Err(Error::E2BIG);
+		}
+		*room = room
+			.checked_sub(string.len() as u64 + 1 + 8)
+			.ok_or(Error::E2BIG)?;
+		strings.push(string);
+	}
+}
+
+pub fn sys_execve(filename: u64, argv: u64, envp: u64) -> Result<u64> {
+	// Copy everything from the old image before replacing it
+	let path = resolve_at(AT_FDCWD, &user_path(filename)?)?;
+	// Stop copying as soon as the strings can't fit on the new stack
+	let stack_limit = crate::resource::rlimit(crate::resource::RLIMIT_STACK);
+	let mut room = crate::usermode::arg_max(stack_limit);
+	let argv = user_strings(argv, &mut room)?;
+	let envp = user_strings(envp, &mut room)?;
+
+	crate::usermode::init_usermode()?;
+	let program = crate::usermode::load_program_file(&path).map_err(|e| match e {
+		Error::NotFound | Error::NotInitialized => Error::ENOENT,
+		Error::InvalidOperation => Error::ENOTDIR,
+		e => e,
+	})?;
+	crate::usermode::get_user_mode_manager()?.exec_current(&program, &path, &argv, &envp)?;
+
+	let pid = crate::process::current_process_pid().ok_or(Error::ESRCH)?;
+	crate::ptrace::exec_event(pid);
+
+	// This doesn't return on success
+	Ok(0)
+}
+
+pub fn sys_exit(exit_code: i32) -> Result<u64> {
+	use crate::scheduler::remove_task;
+
+	// Get current process
+	if let Some(current) = current_process() {
+		crate::ptrace::exit_event(current.pid, exit_code);
+
+		// Only the calling thread exits while others remain
+		let tid = crate::process::current_thread_tid().ok_or(Error::ESRCH)?;
+		let (clear_tid, last_thread) = crate::process::exit_thread(current.pid, tid)?;
+		crate::futex::clear_child_tid(clear_tid);
+
+		if last_thread {
+			// Set exit code and mark as zombie
+			crate::process::exit_process(current.pid, exit_code)?;
+
+			// Remove from scheduler
+			let _ = remove_task(current.pid);
+		} else {
+			let _ = crate::scheduler::remove_thread(tid);
+		}
+
+		// In a real implementation, this would:
+		// 1. Free all process resources
+		// 2. Notify parent process
+		// 3. Reparent children to init
+		// 4. Schedule next process
+
+		// Signal scheduler to switch to next process
+		crate::scheduler::schedule();
+	}
+
+	// This syscall doesn't return
+	loop {
+		unsafe { core::arch::asm!("hlt") };
+	}
+}
+
+pub fn sys_exit_group(exit_code: i32) -> Result<u64> {
+	if let Some(current) = current_process() {
+		crate::ptrace::exit_event(current.pid, exit_code);
+
+		// Every other thread goes first, then the process
+		let tid = crate::process::current_thread_tid().ok_or(Error::ESRCH)?;
+		for other in crate::process::exit_other_threads(current.pid, tid)? {
+			let _ = crate::scheduler::remove_thread(other);
+		}
+		let (clear_tid, _) = crate::process::exit_thread(current.pid, tid)?;
+		crate::futex::clear_child_tid(clear_tid);
+		crate::process::exit_process(current.pid, exit_code)?;
+		let _ = crate::scheduler::remove_task(current.pid);
+
+		crate::scheduler::schedule();
+	}
+
+	// This syscall doesn't return
+	loop {
+		unsafe { core::arch::asm!("hlt") };
+	}
+}
+
+/// arch_prctl codes
+const ARCH_SET_FS: i32 = 0x1002;
+const ARCH_GET_FS: i32 = 0x1003;
+
+pub fn sys_arch_prctl(code: i32, addr: u64) -> Result<u64> {
+	use crate::memory::{copy_to_user, UserPtr};
+
+	let pid = crate::process::current_process_pid().ok_or(Error::ESRCH)?;
+	let tid = crate::process::current_thread_tid();
+	match code {
+		ARCH_SET_FS => {
+			// The base must be a canonical user address
+			if addr >= 0x0000_8000_0000_0000 {
+				return Err(Error::EPERM);
+			}
+			crate::process::with_thread(pid, tid, |t| t.fs_base = addr)?;
+			crate::arch::x86_64::context::set_fs_base(addr);
+			Ok(0)
+		}
+		ARCH_GET_FS => {
+			let base = crate::process::with_thread(pid, tid, |t| t.fs_base)?;
+			copy_to_user(UserPtr::new(addr as *mut u8)?, &base.to_ne_bytes())?;
+			Ok(0)
+		}
+		_ => Err(Error::EINVAL),
+	}
+}
+
+pub fn sys_set_tid_address(tidptr: u64) -> Result<u64> {
+	let pid = crate::process::current_process_pid().ok_or(Error::ESRCH)?;
+	let tid = crate::process::current_thread_tid();
+	crate::process::with_thread(pid, tid, |t| t.clear_child_tid = tidptr)?;
+	Ok(sys_gettid() as u64)
+}
+
+pub fn sys_uname(buf: u64) -> Result<u64> {
+	use crate::memory::{copy_to_user, UserPtr};
+
+	// struct utsname: six NUL-terminated 65-byte fields
+	let fields = ["Linux", "(none)", crate::VERSION, "#1", "x86_64", "(none)"];
+	let mut bytes = [0u8; 6 * 65];
+	for (field, value) in bytes.chunks_exact_mut(65).zip(fields) {
+		let len = value.len().min(64);
+		field[..len].copy_from_slice(&value.as_bytes()[..len]);
+	}
+	copy_to_user(UserPtr::new(buf as *mut u8)?, &bytes)?;
+	Ok(0)
+}
+
+/// wait4 option to return at once if no child changed state
+const WNOHANG: u64 = 1;
+
+pub fn sys_wait4(pid: u64, status: u64, options: u64, rusage: u64) -> Result<u64> {
+	use crate::memory::{copy_to_user, UserPtr};
+
+	// Get current process
+	let current = current_process().ok_or(Error::ESRCH)?;
+
+	// Stops and exits of tracees are reported to their tracer
+	let target = if (pid as i64) > 0 {
+		Some(pid_from_user(pid as i32)?)
+	} else {
+		None
+	};
+	let nohang = options & WNOHANG != 0;
+	let traced = crate::ptrace::wait_tracee(current.pid, target, nohang);
+
+	// Wait for child process
+	let (child_pid, exit_status) = match traced {
+		Some(traced) => traced,
+		None => crate::process::wait_child(current.pid, target, nohang)?,
+	};
+
+	// If status pointer is provided, write exit status
+	if status != 0 {
+		let status_ptr = UserPtr::new(status as *mut i32)?;
+		copy_to_user(status_ptr.cast(), &exit_status.to_ne_bytes())?;
+	}
+
+	if child_pid.0 == 0 {
+		return Ok(0);
+	}
+	Ok(pid_to_user(child_pid))
+}
+
+pub fn sys_ptrace(request: u64, pid: i32, addr: u64, data: u64) -> Result<u64> {
+	let current = current_process().ok_or(Error::ESRCH)?;
+	if request == crate::ptrace::PTRACE_TRACEME {
+		crate::ptrace::traceme(&current)?;
+		return Ok(0);
+	}
+	let pid = pid_from_user(pid)?;
+	crate::ptrace::ptrace(&current, request, pid, addr, data)
+}
+
+/// prctl() options
+pub const PR_GET_SECCOMP: i32 = 21;
+pub const PR_SET_SECCOMP: i32 = 22;
+pub const PR_SET_NO_NEW_PRIVS: i32 = 38;
+pub const PR_GET_NO_NEW_PRIVS: i32 = 39;
+
+pub fn sys_prctl(option: i32, arg2: u64, arg3: u64) -> Result<u64> {
+	let current = current_process().ok_or(Error::ESRCH)?;
+	match option {
+		PR_GET_SECCOMP => Ok(current.seccomp.mode as u64),
+		PR_SET_SECCOMP => crate::seccomp::prctl_set_seccomp(arg2, arg3),
+		PR_SET_NO_NEW_PRIVS if arg2 == 1 && arg3 == 0 => {
+			crate::process::set_no_new_privs(current.pid)?;
+			Ok(0)
+		}
+		PR_GET_NO_NEW_PRIVS => Ok(current.no_new_privs as u64),
+		_ => Err(Error::EINVAL),
+	}
+}
+
+pub fn sys_kill(pid: i32, signal: i32) -> Result<u64> {
+	let caller = current_process().ok_or(Error::ESRCH)?;
+	crate::process::kill(&caller, pid, signal)?;
+	Ok(0)
+}
+
+pub fn sys_rt_sigprocmask(how: i32, set: u64, oldset: u64, sigsetsize: u64) -> Result<u64> {
+	use crate::memory::{copy_from_user, copy_to_user, UserPtr};
+	use crate::signal::SigSet;
+
+	if sigsetsize != 8 {
+		return Err(Error::EINVAL);
+	}
+	let set = if set == 0 {
+		None
+	} else {
+		let mut bytes = [0u8; 8];
+		copy_from_user(&mut bytes, UserPtr::from_const(set as *const u8)?)?;
+		Some(SigSet(u64::from_ne_bytes(bytes)))
+	};
+	let old = crate::process::sigprocmask(how, set)?;
+	if oldset != 0 {
+		copy_to_user(UserPtr::new(oldset as *mut u8)?, &old.0.to_ne_bytes())?;
+	}
+	Ok(0)
+}
+
+pub fn sys_rt_sigaction(signal: i32, act: u64, oldact: u64, sigsetsize: u64) -> Result<u64> {
+	use crate::memory::{copy_from_user, copy_to_user, UserPtr};
+	use crate::signal::SigAction;
+
+	if sigsetsize != 8 {
+		return Err(Error::EINVAL);
+	}
+	let act = if act == 0 {
+		None
+	} else {
+		let mut bytes = [0u8; SigAction::SIZE];
+		copy_from_user(&mut bytes, UserPtr::from_const(act as *const u8)?)?;
+		Some(SigAction::from_bytes(&bytes))
+	};
+	let old = crate::process::sigaction(signal, act)?;
+	if oldact != 0 {
+		copy_to_user(UserPtr::new(oldact as *mut u8)?, &old.to_bytes())?;
+	}
+	Ok(0)
+}
+
+/// Translate a PID number in the caller's PID namespace to a global PID.
+/// 0 means the caller itself.
+fn pid_from_user(nr: i32) -> Result<Pid> {
+	let current = current_process().ok_or(Error::ESRCH)?;
+	if nr == 0 {
+		return Ok(current.pid);
+	}
+	crate::process::find_pid_in_ns(&current.pid_ns, nr as u32).ok_or(Error::ESRCH)
+}
+
+/// Translate a global PID to its number in the caller's PID namespace
+fn pid_to_user(pid: Pid) -> u64 {
+	match current_process() {
+		Some(current) => crate::process::pid_nr_ns(pid, &current.pid_ns) as u64,
+		None => pid.0 as u64,
+	}
+}
+
+/// Process group and session syscalls
+pub fn sys_setpgid(pid: i32, pgid: i32) -> Result<u64> {
+	if pid < 0 || pgid < 0 {
+		return Err(Error::EINVAL);
+	}
+
+	let caller = current_process().ok_or(Error::ESRCH)?.pid;
+	let pid = pid_from_user(pid)?;
+	let pgid = if pgid == 0 {
+		pid
+	} else {
+		pid_from_user(pgid).map_err(|_| Error::EPERM)?
+	};
+	crate::process::set_pgid(caller, pid, pgid)?;
+	Ok(0)
+}
+
+pub fn sys_getpgid(pid: i32) -> Result<u64> {
+	let pid = pid_from_user(pid)?;
+	Ok(pid_to_user(crate::process::get_pgid(pid)?))
+}
+
+pub fn sys_getpgrp() -> Result<u64> {
+	sys_getpgid(0)
+}
+
+pub fn sys_setsid() -> Result<u64> {
+	let caller = current_process().ok_or(Error::ESRCH)?.pid;
+	Ok(pid_to_user(crate::process::create_session(caller)?))
+}
+
+pub fn sys_getsid(pid: i32) -> Result<u64> {
+	let pid = pid_from_user(pid)?;
+	Ok(pid_to_user(crate::process::get_sid(pid)?))
+}
+
+/// Process info syscalls
+pub fn sys_getpid() -> u32 {
+	current_process().map(|p| p.vnr()).unwrap_or(0)
+}
+
+pub fn sys_getppid() -> u32 {
+	// A parent outside the caller's PID namespace shows up as 0
+	current_process()
+		.and_then(|p| {
+			p.parent.map(|parent| crate::process::pid_nr_ns(parent, &p.pid_ns))
+		})
+		.unwrap_or(0)
+}
+
+pub fn sys_getuid() -> u32 {
+	current_process().map(|p| p.cred.uid.0).unwrap_or(0)
+}
+
+pub fn sys_getgid() -> u32 {
+	current_process().map(|p| p.cred.gid.0).unwrap_or(0)
+}
+
+pub fn sys_geteuid() -> u32 {
+	current_process().map(|p| p.cred.euid.0).unwrap_or(0)
+}
+
+pub fn sys_getegid() -> u32 {
+	current_process().map(|p| p.cred.egid.0).unwrap_or(0)
+}
+
+/// Credential syscalls
+fn update_current_cred<T>(f: impl FnOnce(&mut crate::cred::Credentials) -> Result<T>) -> Result<T> {
+	let pid = current_process().ok_or(Error::ESRCH)?.pid;
+	crate::process::update_cred(pid, f)
+}
+
+/// Map a -1 "leave unchanged" ID argument to None
+fn optional_id(id: u32) -> Option<u32> {
+	if id == u32::MAX {
+		None
+	} else {
+		Some(id)
+	}
+}
+
+pub fn sys_setuid(uid: u32) -> Result<u64> {
+	update_current_cred(|cred| cred.set_uid(Uid(uid)))?;
+	Ok(0)
+}
+
+pub fn sys_setgid(gid: u32) -> Result<u64> {
+	update_current_cred(|cred| cred.set_gid(Gid(gid)))?;
+	Ok(0)
+}
+
+pub fn sys_setreuid(ruid: u32, euid: u32) -> Result<u64> {
+	let ruid = optional_id(ruid).map(Uid);
+	let euid = optional_id(euid).map(Uid);
+	update_current_cred(|cred| {
+		// The saved ID follows the new effective ID when the real ID
+		// is set or the effective ID moves away from the real one
+		let suid = match euid {
+			Some(e) if ruid.is_some() || e != cred.uid => Some(e),
+			_ => None,
+		};
+		cred.set_resuid(ruid, euid, suid)
+	})?;
+	Ok(0)
+}
+
+pub fn sys_setregid(rgid: u32, egid: u32) -> Result<u64> {
+	let rgid = optional_id(rgid).map(Gid);
+	let egid = optional_id(egid).map(Gid);
+	update_current_cred(|cred| {
+		let sgid = match egid {
+			Some(e) if rgid.is_some() || e != cred.gid => Some(e),
+			_ => None,
+		};
+		cred.set_resgid(rgid, egid, sgid)
+	})?;
+	Ok(0)
+}
+
+pub fn sys_setresuid(ruid: u64, euid: u64, suid: u64) -> Result<u64> {
+	update_current_cred(|cred| {
+		cred.set_resuid(
+			optional_id(ruid as u32).map(Uid),
+			optional_id(euid as u32).map(Uid),
+			optional_id(suid as u32).map(Uid),
+		)
+	})?;
+	Ok(0)
+}
+
+pub fn sys_setresgid(rgid: u64, egid: u64, sgid: u64) -> Result<u64> {
+	update_current_cred(|cred| {
+		cred.set_resgid(
+			optional_id(rgid as u32).map(Gid),
+			optional_id(egid as u32).map(Gid),
+			optional_id(sgid as u32).map(Gid),
+		)
+	})?;
+	Ok(0)
+}
+
+/// Write a u32 ID to user space
+fn put_user_id(addr: u64, id: u32) -> Result<()> {
+	use crate::memory::{copy_to_user, UserPtr};
+
+	copy_to_user(UserPtr::new(addr as *mut u8)?, &id.to_ne_bytes())
+}
+
+pub fn sys_getresuid(ruid: u64, euid: u64, suid: u64) -> Result<u64> {
+	let cred = crate::cred::current_cred();
+	put_user_id(ruid, cred.uid.0)?;
+	put_user_id(euid, cred.euid.0)?;
+	put_user_id(suid, cred.suid.0)?;
+	Ok(0)
+}
+
+pub fn sys_getresgid(rgid: u64, egid: u64, sgid: u64) -> Result<u64> {
+	let cred = crate::cred::current_cred();
+	put_user_id(rgid, cred.gid.0)?;
+	put_user_id(egid, cred.egid.0)?;
+	put_user_id(sgid, cred.sgid.0)?;
+	Ok(0)
+}
+
+pub fn sys_getgroups(size: i32, list: u64) -> Result<u64> {
+	let groups = crate::cred::current_cred().groups;
+	if size < 0 {
+		return Err(Error::EINVAL);
+	}
+	// A zero size only queries the number of groups
+	if size == 0 {
+		return Ok(groups.len() as u64);
+	}
+	if (size as usize) < groups.len() {
+		return Err(Error::EINVAL);
+	}
+
+	for (i, gid) in groups.iter().enumerate() {
+		put_user_id(list + (i * 4) as u64, gid.0)?;
+	}
+	Ok(groups.len() as u64)
+}
+
+pub fn sys_setgroups(size: usize, list: u64) -> Result<u64> {
+	use crate::memory::{copy_from_user, UserPtr};
+
+	if size > crate::cred::NGROUPS_MAX {
+		return Err(Error::EINVAL);
+	}
+
+	let mut bytes = alloc::vec![0u8; size * 4];
+	if size > 0 {
+		copy_from_user(&mut bytes, UserPtr::from_const(list as *const u8)?)?;
+	}
+	let groups = bytes
+		.chunks_exact(4)
+		.map(|b| Gid(u32::from_ne_bytes([b[0], b[1], b[2], b[3]])))
+		.collect();
+
+	update_current_cred(|cred| cred.set_groups(groups))?;
+	Ok(0)
+}
+
+pub fn sys_umask(mask: u32) -> Result<u64> {
+	let old = update_current_cred(|cred| {
+		let old = cred.umask;
+		cred.umask = mask & 0o777;
+		Ok(old)
+	})?;
+	Ok(old as u64)
+}
+
+/// Read a capget/capset header and return its pid. An unsupported
+/// version is reported back through the header.
+fn read_cap_header(hdrp: u64) -> Result<i32> {
+	use crate::capability::LINUX_CAPABILITY_VERSION_3;
+	use crate::memory::{copy_from_user, UserPtr};
+
+	let mut bytes = [0u8; 8];
+	copy_from_user(&mut bytes, UserPtr::from_const(hdrp as *const u8)?)?;
+	let version = u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+	let pid = i32::from_ne_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+	if version != LINUX_CAPABILITY_VERSION_3 {
+		put_user_id(hdrp, LINUX_CAPABILITY_VERSION_3)?;
+		return Err(Error::EINVAL);
+	}
+	if pid < 0 {
+		return Err(Error::EINVAL);
+	}
+	Ok(pid)
+}
+
+pub fn sys_capget(hdrp: u64, datap: u64) -> Result<u64> {
+	use crate::memory::{copy_to_user, UserPtr};
+
+	let pid = read_cap_header(hdrp)?;
+	let cred = if pid == 0 {
+		crate::cred::current_cred()
+	} else {
+		crate::process::find_process(Pid(pid as u32))
+			.ok_or(Error::ESRCH)?
+			.cred
+	};
+	// A null data pointer only probes the version
+	if datap == 0 {
+		return Ok(0);
+	}
+
+	// Two structs of {effective, permitted, inheritable}, low word first
+	let sets = [cred.cap_effective, cred.cap_permitted, cred.cap_inheritable];
+	let mut data = [0u8; 24];
+	for (half, chunk) in data.chunks_exact_mut(12).enumerate() {
+		for (set, word) in sets.iter().zip(chunk.chunks_exact_mut(4)) {
+			word.copy_from_slice(&((set.0 >> (32 * half)) as u32).to_ne_bytes());
+		}
+	}
+	copy_to_user(UserPtr::new(datap as *mut u8)?, &data)?;
+	Ok(0)
+}
+
+pub fn sys_capset(hdrp: u64, datap: u64) -> Result<u64> {
+	use crate::capability::CapSet;
+	use crate::memory::{copy_from_user, UserPtr};
+
+	// Only the caller's own capabilities can be changed
+	let pid = read_cap_header(hdrp)?;
+	if pid != 0 && pid as u32 != sys_getpid() {
+		return Err(Error::EPERM);
+	}
+
+	let mut data = [0u8; 24];
+	copy_from_user(&mut data, UserPtr::from_const(datap as *const u8)?)?;
+	let word = |offset: usize| {
+		let b = &data[offset..offset + 4];
+		u32::from_ne_bytes([b[0], b[1], b[2], b[3]]) as u64
+	};
+	let set = |index: usize| CapSet(word(index * 4) | word(12 + index * 4) << 32);
+
+	update_current_cred(|cred| cred.set_caps(set(0), set(1), set(2)))?;
+	Ok(0)
+}
+
+/// Resource limit syscalls
+pub fn sys_getrlimit(resource: u32, rlim: u64) -> Result<u64> {
+	sys_prlimit64(0, resource as u64, 0, rlim)
+}
+
+pub fn sys_setrlimit(resource: u32, rlim: u64) -> Result<u64> {
+	sys_prlimit64(0, resource as u64, rlim, 0)
+}
+
+pub fn sys_prlimit64(pid: u64, resource: u64, new_limit: u64, old_limit: u64) -> Result<u64> {
+	use crate::memory::{copy_from_user, copy_to_user, UserPtr};
+	use crate::resource::Rlimit;
+
+	let current = current_process().ok_or(Error::ESRCH)?;
+	let target = pid_from_user(pid as i32)?;
+	let new = if new_limit != 0 {
+		let mut bytes = [0u8; 16];
+		copy_from_user(&mut bytes, UserPtr::from_const(new_limit as *const u8)?)?;
+		Some(Rlimit::from_bytes(&bytes))
+	} else {
+		None
+	};
+
+	let old = crate::process::prlimit(&current, target, resource as usize, new)?;
+	if old_limit != 0 {
+		copy_to_user(UserPtr::new(old_limit as *mut u8)?, &old.to_bytes())?;
+	}
+	Ok(0)
+}
+
+pub fn sys_getrusage(who: i32, usage: u64) -> Result<u64> {
+	use crate::memory::{copy_to_user, UserPtr};
+	use crate::resource::{RUSAGE_CHILDREN, RUSAGE_SELF, RUSAGE_THREAD};
+
+	let current = current_process().ok_or(Error::ESRCH)?;
+	let rusage = match who {
+		RUSAGE_SELF => current.usage(),
+		RUSAGE_CHILDREN => current.children_usage,
+		RUSAGE_THREAD => {
+			let tid = crate::process::current_thread_tid().ok_or(Error::ESRCH)?;
+			let thread = current.threads.iter().find(|t| t.tid == tid);
+			let mut rusage = thread.ok_or(Error::ESRCH)?.usage();
+			rusage.maxrss = current.hiwater_vm / 1024;
+			rusage
+		}
+		_ => return Err(Error::EINVAL),
+	};
+	copy_to_user(UserPtr::new(usage as *mut u8)?, &rusage.to_bytes())?;
+	Ok(0)
+}
+
+pub fn sys_times(buf: u64) -> Result<u64> {
+	use crate::memory::{copy_to_user, UserPtr};
+	use crate::resource::ns_to_clock_t;
+
+	let current = current_process().ok_or(Error::ESRCH)?;
+	if buf != 0 {
+		// struct tms: tms_utime, tms_stime, tms_cutime, tms_cstime
+		let (usage, children) = (current.usage(), current.children_usage);
+		let ticks = [usage.utime, usage.stime, children.utime, children.stime];
+		let mut bytes = [0u8; 32];
+		for (chunk, ns) in bytes.chunks_exact_mut(8).zip(ticks) {
+			chunk.copy_from_slice(&ns_to_clock_t(ns).to_ne_bytes());
+		}
+		copy_to_user(UserPtr::new(buf as *mut u8)?, &bytes)?;
+	}
+	Ok(ns_to_clock_t(crate::time::get_time_ns()))
+}
+
+/// Read a POSIX clock in nanoseconds
+pub(crate) fn clock_ns(clock: i32) -> Result<u64> {
+	use crate::time::*;
+
+	match clock {
+		CLOCK_REALTIME | CLOCK_REALTIME_COARSE => Ok(get_realtime().to_ns()),
+		CLOCK_MONOTONIC | CLOCK_MONOTONIC_RAW | CLOCK_MONOTONIC_COARSE | CLOCK_BOOTTIME => {
+			Ok(get_time_ns())
+		}
+		CLOCK_PROCESS_CPUTIME_ID => {
+			let usage = current_process().ok_or(Error::ESRCH)?.usage();
+			Ok(usage.utime + usage.stime)
+		}
+		CLOCK_THREAD_CPUTIME_ID => {
+			let tid = crate::process::current_thread_tid().ok_or(Error::ESRCH)?;
+			let pid = crate::process::current_process_pid().ok_or(Error::ESRCH)?;
+			let usage = crate::process::with_thread(pid, Some(tid), |t| t.usage())?;
+			Ok(usage.utime + usage.stime)
+		}
+		_ => Err(Error::EINVAL),
+	}
+}
+
+pub fn sys_clock_gettime(clock: i32, tp: u64) -> Result<u64> {
+	use crate::memory::{copy_to_user, UserPtr};
+
+	let ns = clock_ns(clock)?;
+	let ts = crate::time::TimeSpec::from_ns(ns);
+	let mut bytes = [0u8; 16];
+	bytes[..8].copy_from_slice(&ts.tv_sec.to_ne_bytes());
+	bytes[8..].copy_from_slice(&ts.tv_nsec.to_ne_bytes());
+	copy_to_user(UserPtr::new(tp as *mut u8)?, &bytes)?;
+	Ok(0)
+}
+
+/// clock_nanosleep flag for an absolute deadline
+const TIMER_ABSTIME: i32 = 1;
+
+/// Read a struct timespec from user space as nanoseconds
+fn read_timespec(addr: u64) -> Result<u64> {
+	use crate::memory::{copy_from_user, UserPtr};
+
+	let mut bytes = [0u8; 16];
+	copy_from_user(&mut bytes, UserPtr::from_const(addr as *const u8)?)?;
+	let sec = i64::from_ne_bytes(bytes[..8].try_into().unwrap());
+	let nsec = i64::from_ne_bytes(bytes[8..].try_into().unwrap());
+	if sec < 0 || !(0..NSEC_PER_SEC as i64).contains(&nsec) {
+		return Err(Error::EINVAL);
+	}
+	Ok((sec as u64)
+		.saturating_mul(NSEC_PER_SEC)
+		.saturating_add(nsec as u64))
+}
+
+/// Read a struct timeval from user space as nanoseconds
+fn read_timeval(addr: u64) -> Result<u64> {
+	use crate::memory::{copy_from_user, UserPtr};
+
+	let mut bytes = [0u8; 16];
+	copy_from_user(&mut bytes, UserPtr::from_const(addr as *const u8)?)?;
+	let sec = i64::from_ne_bytes(bytes[..8].try_into().unwrap());
+	let usec = i64::from_ne_bytes(bytes[8..].try_into().unwrap());
+	if sec < 0 || !(0..1_000_000).contains(&usec) {
+		return Err(Error::EINVAL);
+	}
+	Ok((sec as u64)
+		.saturating_mul(NSEC_PER_SEC)
+		.saturating_add(usec as u64 * 1000))
+}
+
+/// Sleep until the monotonic time `deadline`. If a signal cuts the sleep
+/// short, the time left goes to `rem` and the call fails with EINTR.
+fn sleep_until(deadline: u64, rem: u64) -> Result<u64> {
+	use crate::memory::{copy_to_user, UserPtr};
+
+	crate::sync::might_sleep();
+	if crate::sync::wait_until_deadline(crate::process::signal_pending, deadline) {
+		if rem != 0 {
+			let left = deadline.saturating_sub(crate::time::get_time_ns());
+			let ts = crate::time::TimeSpec::from_ns(left);
+			let mut bytes = [0u8; 16];
+			bytes[..8].copy_from_slice(&ts.tv_sec.to_ne_bytes());
+			bytes[8..].copy_from_slice(&ts.tv_nsec.to_ne_bytes());
+			copy_to_user(UserPtr::new(rem as *mut u8)?, &bytes)?;
+		}
+		return Err(Error::Interrupted);
+	}
+	Ok(0)
+}
+
+pub fn sys_nanosleep(req: u64, rem: u64) -> Result<u64> {
+	let duration = read_timespec(req)?;
+	sleep_until(crate::time::get_time_ns().saturating_add(duration), rem)
+}
+
+pub fn sys_clock_nanosleep(clock: i32, flags: i32, req: u64, rem: u64) -> Result<u64> {
+	use crate::time::*;
+
+	if matches!(clock, CLOCK_PROCESS_CPUTIME_ID | CLOCK_THREAD_CPUTIME_ID) {
+		return Err(Error::EINVAL);
+	}
+	let now = clock_ns(clock)?;
+	let time = read_timespec(req)?;
+	if flags & TIMER_ABSTIME != 0 {
+		// Convert the deadline on `clock` into monotonic time; an
+		// absolute sleep leaves `rem` alone
+		let deadline = get_time_ns().saturating_add(time.saturating_sub(now));
+		sleep_until(deadline, 0)
+	} else {
+		sleep_until(get_time_ns().saturating_add(time), rem)
+	}
+}
+
+pub fn sys_clock_getres(clock: i32, res: u64) -> Result<u64> {
+	use crate::memory::{copy_to_user, UserPtr};
+	use crate::time::*;
+
+	clock_ns(clock)?;
+	// The coarse clocks move on timer ticks, the others read the TSC
+	let resolution = match clock {
+		CLOCK_REALTIME_COARSE | CLOCK_MONOTONIC_COARSE => NSEC_PER_JIFFY,
+		_ => 1,
+	};
+	if res != 0 {
+		let mut bytes = [0u8; 16];
+		bytes[8..].copy_from_slice(&resolution.to_ne_bytes());
+		copy_to_user(UserPtr::new(res as *mut u8)?, &bytes)?;
+	}
+	Ok(0)
+}
+
+pub fn sys_gettimeofday(tv: u64, tz: u64) -> Result<u64> {
+	use crate::memory::{copy_to_user, UserPtr};
+
+	if tv != 0 {
+		let ns = crate::time::get_realtime().to_ns();
+		let mut bytes = [0u8; 16];
+		bytes[..8].copy_from_slice(&(ns / NSEC_PER_SEC).to_ne_bytes());
+		bytes[8..].copy_from_slice(&(ns % NSEC_PER_SEC / 1000).to_ne_bytes());
+		copy_to_user(UserPtr::new(tv as *mut u8)?, &bytes)?;
+	}
+	if tz != 0 {
+		// struct timezone: always UTC without DST
+		copy_to_user(UserPtr::new(tz as *mut u8)?, &[0u8; 8])?;
+	}
+	Ok(0)
+}
+
+pub fn sys_time(tloc: u64) -> Result<u64> {
+	use crate::memory::{copy_to_user, UserPtr};
+
+	let secs = crate::time::get_realtime().to_ns() / NSEC_PER_SEC;
+	if tloc != 0 {
+		copy_to_user(UserPtr::new(tloc as *mut u8)?, &secs.to_ne_bytes())?;
+	}
+	Ok(secs)
+}
+
+pub fn sys_timer_create(clock: i32, sevp: u64, timerid: u64) -> Result<u64> {
+	use crate::memory::{copy_from_user, copy_to_user, UserPtr};
+	use crate::posix_timers::{SIGEV_NONE, SIGEV_SIGNAL, SIGEV_THREAD_ID};
+
+	// struct sigevent: sigev_value, sigev_signo, sigev_notify. Without
+	// one, the timer sends SIGALRM.
+	let signal = if sevp == 0 {
+		crate::signal::SIGALRM
+	} else {
+		let mut bytes = [0u8; 16];
+		copy_from_user(&mut bytes, UserPtr::from_const(sevp as *const u8)?)?;
+		let signo = i32::from_ne_bytes(bytes[8..12].try_into().unwrap());
+		match i32::from_ne_bytes(bytes[12..16].try_into().unwrap()) {
+			SIGEV_NONE => 0,
+			// Signals go to the whole process, including the targeted thread
+			SIGEV_SIGNAL | SIGEV_THREAD_ID if signo > 0 => signo,
+			_ => return Err(Error::EINVAL),
+		}
+	};
+
+	let pid = crate::process::current_process_pid().ok_or(Error::ESRCH)?;
+	let tid = crate::process::current_thread_tid().ok_or(Error::ESRCH)?;
+	let id = crate::posix_timers::timer_create(pid, tid, clock, signal)?;
+	if let Err(err) = copy_to_user(UserPtr::new(timerid as *mut u8)?, &id.to_ne_bytes()) {
+		let _ = crate::posix_timers::timer_delete(pid, id);
+		return Err(err);
+	}
+	Ok(0)
+}
+
+pub fn sys_timer_settime(id: i32, flags: i32, new: u64, old: u64) -> Result<u64> {
+	use crate::posix_timers::TimerKey;
+
+	let interval = read_timespec(new)?;
+	let value = read_timespec(new + 16)?;
+	let pid = crate::process::current_process_pid().ok_or(Error::ESRCH)?;
+	let (key, absolute) = (TimerKey::Posix(id), flags & TIMER_ABSTIME != 0);
+	let (old_interval, old_value) =
+		crate::posix_timers::timer_settime(pid, key, absolute, interval, value)?;
+	if old != 0 {
+		put_itimerspec(old, old_interval, old_value)?;
+	}
+	Ok(0)
+}
+
+pub fn sys_timer_gettime(id: i32, curr: u64) -> Result<u64> {
+	use crate::posix_timers::TimerKey;
+
+	let pid = crate::process::current_process_pid().ok_or(Error::ESRCH)?;
+	let (interval, value) = crate::posix_timers::timer_gettime(pid, TimerKey::Posix(id))?;
+	put_itimerspec(curr, interval, value)?;
+	Ok(0)
+}
+
+pub fn sys_timer_getoverrun(id: i32) -> Result<u64> {
+	let pid = crate::process::current_process_pid().ok_or(Error::ESRCH)?;
+	crate::posix_timers::timer_getoverrun(pid, id)
+}
+
+pub fn sys_timer_delete(id: i32) -> Result<u64> {
+	let pid = crate::process::current_process_pid().ok_or(Error::ESRCH)?;
+	crate::posix_timers::timer_delete(pid, id)?;
+	Ok(0)
+}
+
+/// Write a struct itimerval of (interval, value) in nanoseconds
+fn put_itimerval(addr: u64, interval: u64, value: u64) -> Result<()> {
+	use crate::memory::{copy_to_user, UserPtr};
+
+	let mut bytes = [0u8; 32];
+	for (chunk, ns) in bytes.chunks_exact_mut(16).zip([interval, value]) {
+		// A running timer never shows as 0, which would mean disarmed
+		let usec = ns.div_ceil(1000);
+		chunk[..8].copy_from_slice(&(usec / 1_000_000).to_ne_bytes());
+		chunk[8..].copy_from_slice(&(usec % 1_000_000).to_ne_bytes());
+	}
+	copy_to_user(UserPtr::new(addr as *mut u8)?, &bytes)
+}
+
+pub fn sys_getitimer(which: i32, curr: u64) -> Result<u64> {
+	use crate::posix_timers::TimerKey;
+
+	let pid = crate::process::current_process_pid().ok_or(Error::ESRCH)?;
+	let (interval, value) = crate::posix_timers::timer_gettime(pid, TimerKey::Itimer(which))?;
+	put_itimerval(curr, interval, value)?;
+	Ok(0)
+}
+
+pub fn sys_setitimer(which: i32, new: u64, old: u64) -> Result<u64> {
+	use crate::posix_timers::TimerKey;
+
+	// A NULL new value disarms the timer, as Linux allows
+	let (interval, value) = if new == 0 {
+		(0, 0)
+	} else {
+		(read_timeval(new)?, read_timeval(new + 16)?)
+	};
+	let pid = crate::process::current_process_pid().ok_or(Error::ESRCH)?;
+	let key = TimerKey::Itimer(which);
+	let (old_interval, old_value) =
+		crate::posix_timers::timer_settime(pid, key, false, interval, value)?;
+	if old != 0 {
+		put_itimerval(old, old_interval, old_value)?;
+	}
+	Ok(0)
+}
+
+pub fn sys_alarm(seconds: u32) -> Result<u64> {
+	use crate::posix_timers::{TimerKey, ITIMER_REAL};
+
+	let pid = crate::process::current_process_pid().ok_or(Error::ESRCH)?;
+	let key = TimerKey::Itimer(ITIMER_REAL);
+	let value = seconds as u64 * NSEC_PER_SEC;
+	let (_, left) = crate::posix_timers::timer_settime(pid, key, false, 0, value)?;
+	// Seconds left on the old alarm, rounded to nearest but never 0 while
+	// it was pending
+	let secs = (left + NSEC_PER_SEC / 2) / NSEC_PER_SEC;
+	Ok(if secs == 0 && left > 0 { 1 } else { secs })
+}
+
+pub fn sys_getcpu(cpu: u64, node: u64) -> Result<u64> {
+	use crate::memory::{copy_to_user, UserPtr};
+
+	// Uniprocessor: always CPU 0 on node 0
+	for ptr in [cpu, node] {
+		if ptr != 0 {
+			copy_to_user(UserPtr::new(ptr as *mut u8)?, &0u32.to_ne_bytes())?;
+		}
+	}
+	Ok(0)
+}
+
+pub fn sys_unshare(flags: u64) -> Result<u64> {
+	let caller = current_process().ok_or(Error::ESRCH)?.pid;
+	crate::process::unshare(caller, flags)?;
+	Ok(0)
+}
+
+pub fn sys_setns(fd: i32, nstype: i32) -> Result<u64> {
+	let current = current_process().ok_or(Error::ESRCH)?;
+	let file = crate::fs::get_file_descriptor(fd).ok_or(Error::EBADF)?;
+
+	// The descriptor must refer to a /proc/<pid>/ns/<type> file
+	let (nr, ns_type) = crate::fs::procfs::parse_ns_path(&file.path).ok_or(Error::EINVAL)?;
+	if nstype != 0 && nstype as u64 != ns_type.clone_flag() {
+		return Err(Error::EINVAL);
+	}
+	let target = match nr {
+		Some(nr) => {
+			crate::process::find_pid_in_ns(&current.pid_ns, nr).ok_or(Error::ESRCH)?
+		}
+		None => current.pid,
+	};
+
+	crate::process::setns(current.pid, target, ns_type)?;
+	Ok(0)
+}
+
+pub fn sys_gettid() -> u32 {
+	crate::process::current_thread_tid()
+		.map(|t| t.0)
+		.unwrap_or_else(sys_getpid)
+}
+
+/// Most bytes one read or write call transfers, larger requests come
+/// back short
+const MAX_RW_COUNT: u64 = 1024 * 1024;
+
+/// Bytes staged in the kernel at a time on their way to or from user space
+const RW_CHUNK: usize = 4096;
+
+/// Read up to `count` bytes into user memory at `buf` a chunk at a time,
+/// `read` filling each chunk from the given offset into the transfer.
+/// Stops at a short chunk; an error after some progress reports the bytes
+/// already read.
+fn read_chunks(
+	buf: u64,
+	count: u64,
+	mut read: impl FnMut(usize, &mut [u8]) -> Result<usize>,
+) -> Result<u64> {
+	use crate::memory::{copy_to_user, UserPtr};
+
+	let count = count.min(MAX_RW_COUNT) as usize;
+	let mut chunk = alloc::vec![0u8; count.min(RW_CHUNK)];
+	let mut done = 0;
+	while done < count {
+		let want = (count - done).min(chunk.len());
+		let copied = read(done, &mut chunk[..want]).and_then(|n| {
+			copy_to_user(UserPtr::new((buf + done as u64) as *mut u8)?, &chunk[..n])?;
+			Ok(n)
+		});
+		match copied {
+			Ok(n) => {
+				done += n;
+				if n < want {
+					break;
+				}
+			}
+			Err(_) if done > 0 => break,
+			Err(err) => return Err(err),
+		}
+	}
+	Ok(done as u64)
+}
+
+/// Write up to `count` bytes from user memory at `buf` a chunk at a time,
+/// `write` taking each chunk with its offset into the transfer. Stops at a
+/// short chunk; an error after some progress reports the bytes already
+/// written.
+fn write_chunks(
+	buf: u64,
+	count: u64,
+	mut write: impl FnMut(usize, &[u8]) -> Result<usize>,
+) -> Result<u64> {
+	use crate::memory::{copy_from_user, UserPtr};
+
+	let count = count.min(MAX_RW_COUNT) as usize;
+	let mut chunk = alloc::vec![0u8; count.min(RW_CHUNK)];
+	let mut done = 0;
+	while done < count {
+		let len = (count - done).min(chunk.len());
+		let written = UserPtr::from_const((buf + done as u64) as *const u8)
+			.and_then(|ptr| copy_from_user(&mut chunk[..len], ptr))
+			.and_then(|_| write(done, &chunk[..len]));
+		match written {
+			Ok(n) => {
+				done += n;
+				if n < len {
+					break;
+				}
+			}
+			Err(_) if done > 0 => break,
+			Err(err) => return Err(err),
+		}
+	}
+	Ok(done as u64)
+}
+
+/// File operation syscalls
+pub fn sys_read(fd: i32, buf: u64, count: u64) -> Result<u64> {
+	use crate::fs::{get_file_descriptor, read_file};
+
+	// Validate parameters
+	if count == 0 {
+		return Ok(0);
+	}
+
+	// Get file from file descriptor table
+	let file = get_file_descriptor(fd).ok_or(Error::EBADF)?;
+	if !file.is_readable() {
+		return Err(Error::EBADF);
+	}
+
+	// Only a regular file is sure to have more without blocking, anything
+	// else returns what one chunk got
+	let regular = crate::fs::mode::s_isreg(file.mode);
+	read_chunks(buf, count, |offset, chunk| {
+		if offset > 0 && !regular {
+			return Ok(0);
+		}
+		read_file(&file, chunk)
+	})
+}
+
+pub fn sys_write(fd: i32, buf: u64, count: u64) -> Result<u64> {
+	use crate::fs::{get_file_descriptor, write_file};
+
+	// Validate parameters
+	if count == 0 {
+		return Ok(0);
+	}
+
+	// Without a console open, stdout/stderr go to the kernel log
+	let file = get_file_descriptor(fd);
+	if file.is_none() && (fd == 1 || fd == 2) {
+		return write_chunks(buf, count, |_, chunk| {
+			// Write to console (for debugging)
+			let text = match core::str::from_utf8(chunk) {
+				Ok(s) => s,
+				Err(e) => core::str::from_utf8(&chunk[..e.valid_up_to()])
+					.unwrap_or_default(),
+			};
+			crate::print!("{}", text);
+			Ok(chunk.len())
+		});
+	}
+
+	// Get file from file descriptor table
+	let file = file.ok_or(Error::EBADF)?;
+	if !file.is_writable() {
+		return Err(Error::EBADF);
+	}
+
+	write_chunks(buf, count, |_, chunk| write_file(&file, chunk))
+}
+
+pub fn sys_open(filename: u64, flags: i32, mode: u32) -> Result<u64> {
+	sys_openat(AT_FDCWD, filename, flags, mode)
+}
+
+pub fn sys_openat(dirfd: i32, filename: u64, flags: i32, mode: u32) -> Result<u64> {
+	use crate::fs::{flags::O_CLOEXEC, install_file_descriptor, open_file};
+
+	// Copy filename from user space
+	let path = resolve_at(dirfd, &user_path(filename)?)?;
+
+	// Open file in VFS
+	let file = open_file(&path, flags, mode)?;
+
+	// Allocate file descriptor and add to process file table
+	let fd = install_file_descriptor(file, 0, flags as u32 & O_CLOEXEC != 0)?;
+
+	Ok(fd as u64)
+}
+
+pub fn sys_close(fd: i32) -> Result<u64> {
+	use crate::fs::close_file_descriptor;
+
+	// Close file descriptor
+	close_file_descriptor(fd)?;
+
+	Ok(0)
+}
+
+/// Directory file descriptor standing for the working directory
+const AT_FDCWD: i32 = -100;
+/// *at flags
+const AT_SYMLINK_NOFOLLOW: u64 = 0x100;
+const AT_REMOVEDIR: u64 = 0x200;
+const AT_EACCESS: u64 = 0x200;
+const AT_EMPTY_PATH: u64 = 0x1000;
+
+/// Copy a path argument from user space
+fn user_path(ptr: u64) -> Result<alloc::string::String> {
+	use crate::memory::{copy_string_from_user, UserPtr};
+
+	let path =
+		copy_string_from_user(UserPtr::from_const(ptr as *const u8)?, crate::fs::PATH_MAX)?;
+	if path.len() >= crate::fs::PATH_MAX {
+		return Err(Error::ENAMETOOLONG);
+	}
+	Ok(path)
+}
+
+/// Working directory of the current process
+fn current_cwd() -> alloc::string::String {
+	match crate::process::with_current(|p| p.fs.clone()) {
+		Some(fs) => fs.lock().cwd.clone(),
+		None => alloc::string::String::from("/"),
+	}
+}
+
+/// Resolve a path relative to directory `dirfd` into a normalized
+/// absolute path
+fn resolve_at(dirfd: i32, path: &str) -> Result<alloc::string::String> {
+	use crate::fs::{join_paths, normalize_path};
+
+	if path.is_empty() {
+		return Err(Error::ENOENT);
+	}
+	let base = if path.starts_with('/') {
+		alloc::string::String::from("/")
+	} else if dirfd == AT_FDCWD {
+		current_cwd()
+	} else {
+		let dir = crate::fs::get_file_descriptor(dirfd).ok_or(Error::EBADF)?;
+		if !crate::fs::mode::s_isdir(dir.mode) {
+			return Err(Error::ENOTDIR);
+		}
+		dir.path.clone()
+	};
+	Ok(normalize_path(&join_paths(&base, path)))
+}
+
+/// Write a struct stat to user space
+fn put_stat(addr: u64, st: &crate::fs::KStat) -> Result<()> {
+	use crate::memory::{copy_to_user, UserPtr};
+
+	let mut bytes = [0u8; 144];
+	let mut put = |offset: usize, value: &[u8]| {
+		bytes[offset..offset + value.len()].copy_from_slice(value);
+	};
+	put(0, &st.st_dev.to_ne_bytes());
+	put(8, &st.st_ino.to_ne_bytes());
+	put(16, &st.st_nlink.to_ne_bytes());
+	put(24, &st.st_mode.to_ne_bytes());
+	put(28, &st.st_uid.to_ne_bytes());
+	put(32, &st.st_gid.to_ne_bytes());
+	put(40, &st.st_rdev.to_ne_bytes());
+	put(48, &st.st_size.to_ne_bytes());
+	put(56, &st.st_blksize.to_ne_bytes());
+	put(64, &st.st_blocks.to_ne_bytes());
+	put(72, &st.st_atime.to_ne_bytes());
+	put(80, &st.st_atime_nsec.to_ne_bytes());
+	put(88, &st.st_mtime.to_ne_bytes());
+	put(96, &st.st_mtime_nsec.to_ne_bytes());
+	put(104, &st.st_ctime.to_ne_bytes());
+	put(112, &st.st_ctime_nsec.to_ne_bytes());
+	copy_to_user(UserPtr::new(addr as *mut u8)?, &bytes)
+}
+
+pub fn sys_fstat(fd: i32, statbuf: u64) -> Result<u64> {
+	let file = crate::fs::get_file_descriptor(fd).ok_or(Error::EBADF)?;
+	put_stat(statbuf, &crate::fs::file_stat(&file)?)?;
+	Ok(0)
+}
+
+pub fn sys_newfstatat(dirfd: i32, filename: u64, statbuf: u64, flags: u64) -> Result<u64> {
+	if flags & !(AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0 {
+		return Err(Error::EINVAL);
+	}
+	let path = user_path(filename)?;
+	if path.is_empty() && flags & AT_EMPTY_PATH != 0 {
+		return sys_fstat(dirfd, statbuf);
+	}
+	// No symbolic links yet, so AT_SYMLINK_NOFOLLOW changes nothing
+	let st = crate::fs::path_stat(&resolve_at(dirfd, &path)?)?;
+	put_stat(statbuf, &st)?;
+	Ok(0)
+}
+
+pub fn sys_lseek(fd: i32, offset: i64, whence: i32) -> Result<u64> {
+	let file = crate::fs::get_file_descriptor(fd).ok_or(Error::EBADF)?;
+	Ok(file.seek(offset, whence)? as u64)
+}
+
+/// Get an open regular file for positioned I/O
+fn positioned_file(fd: i32, offset: i64) -> Result<crate::sync::Arc<crate::fs::File>> {
+	let file = crate::fs::get_file_descriptor(fd).ok_or(Error::EBADF)?;
+	if !crate::fs::mode::s_isreg(file.mode) || file.inode.is_some() {
+		return Err(Error::ESPIPE);
+	}
+	if offset < 0 {
+		return Err(Error::EINVAL);
+	}
+	Ok(file)
+}
+
+pub fn sys_pread64(fd: i32, buf: u64, count: u64, offset: i64) -> Result<u64> {
+	let file = positioned_file(fd, offset)?;
+	if !file.is_readable() {
+		return Err(Error::EBADF);
+	}
+	read_chunks(buf, count, |done, chunk| {
+		crate::fs::memfile::read_at(&file, offset as usize + done, chunk)
+	})
+}
+
+pub fn sys_pwrite64(fd: i32, buf: u64, count: u64, offset: i64) -> Result<u64> {
+	let file = positioned_file(fd, offset)?;
+	if !file.is_writable() {
+		return Err(Error::EBADF);
+	}
+	write_chunks(buf, count, |done, chunk| {
+		crate::fs::memfile::write_at(&file, offset as usize + done, chunk)
+	})
+}
+
+/// Most iovecs readv and writev accept
+const IOV_MAX: u64 = 1024;
+
+/// Read the (base, len) pairs of a user iovec array
+fn read_iovecs(iov: u64, iovcnt: u64) -> Result<alloc::vec::Vec<(u64, u64)>> {
+	use crate::memory::{copy_from_user, UserPtr};
+
+	if iovcnt > IOV_MAX {
+		return Err(Error::EINVAL);
+	}
+	let mut bytes = alloc::vec![0u8; iovcnt as usize * 16];
+	if iovcnt > 0 {
+		copy_from_user(&mut bytes, UserPtr::from_const(iov as *const u8)?)?;
+	}
+	Ok(bytes.chunks_exact(16)
+		.map(|b| {
+			let base = u64::from_ne_bytes(b[..8].try_into().unwrap());
+			let len = u64::from_ne_bytes(b[8..].try_into().unwrap());
+			(base, len)
+		})
+		.collect())
+}
+
+/// Run `io` over each buffer of an iovec array until one comes up short.
+/// An error after some progress reports the bytes already transferred.
+fn do_iovecs(fd: i32, iov: u64, iovcnt: u64, io: fn(i32, u64, u64) -> Result<u64>) -> Result<u64> {
+	let mut total = 0;
+	for (base, len) in read_iovecs(iov, iovcnt)? {
+		if len == 0 {
+			continue;
+		}
+		match io(fd, base, len) {
+			Ok(done) => {
+				total += done;
+				if done < len {
+					break;
+				}
+			}
+			Err(_) if total > 0 => break,
+			Err(err) => return Err(err),
+		}
+	}
+	Ok(total)
+}
 
-		// Memory management
-		9 => sys_mmap(
-			args.arg0,
-			args.arg1,
-			args.arg2 as i32,
-			args.arg3 as i32,
-			args.arg4 as i32,
-			args.arg5 as i64,
-		), // mmap
-		11 => sys_munmap(args.arg0, args.arg1), // munmap
-		12 => sys_brk(args.arg0),               // brk
+pub fn sys_readv(fd: i32, iov: u64, iovcnt: u64) -> Result<u64> {
+	do_iovecs(fd, iov, iovcnt, sys_read)
+}
 
-		// Unimplemented syscalls
-		_ => Err(Error::ENOSYS),
-	};
+pub fn sys_writev(fd: i32, iov: u64, iovcnt: u64) -> Result<u64> {
+	do_iovecs(fd, iov, iovcnt, sys_write)
+}
 
-	match result {
-		Ok(value) => value,
-		Err(error) => (-error.to_errno()) as u64,
+pub fn sys_dup(oldfd: i32) -> Result<u64> {
+	let file = crate::fs::get_file_descriptor(oldfd).ok_or(Error::EBADF)?;
+	Ok(crate::fs::allocate_file_descriptor(file)? as u64)
+}
+
+pub fn sys_dup2(oldfd: i32, newfd: i32) -> Result<u64> {
+	let file = crate::fs::get_file_descriptor(oldfd).ok_or(Error::EBADF)?;
+	if oldfd != newfd {
+		crate::fs::replace_file_descriptor(newfd, file, false)?;
 	}
+	Ok(newfd as u64)
 }
 
-/// Process management syscalls
-pub fn sys_fork() -> Result<u64> {
-	use crate::process::create_process;
-	use crate::scheduler::add_task;
+pub fn sys_dup3(oldfd: i32, newfd: i32, flags: u32) -> Result<u64> {
+	use crate::fs::flags::O_CLOEXEC;
 
-	// Get current process
-	let current = current_process().ok_or(Error::ESRCH)?;
+	if flags & !O_CLOEXEC != 0 || oldfd == newfd {
+		return Err(Error::EINVAL);
+	}
+	let file = crate::fs::get_file_descriptor(oldfd).ok_or(Error::EBADF)?;
+	crate::fs::replace_file_descriptor(newfd, file, flags & O_CLOEXEC != 0)?;
+	Ok(newfd as u64)
+}
 
-	// Fork the process
-	let child = current.fork()?;
-	let child_pid = child.pid;
+/// fcntl commands
+const F_DUPFD: i32 = 0;
+const F_GETFD: i32 = 1;
+const F_SETFD: i32 = 2;
+const F_GETFL: i32 = 3;
+const F_SETFL: i32 = 4;
+const F_DUPFD_CLOEXEC: i32 = 1030;
+const FD_CLOEXEC: u64 = 1;
+
+pub fn sys_fcntl(fd: i32, cmd: i32, arg: u64) -> Result<u64> {
+	use core::sync::atomic::Ordering;
+
+	use crate::fs::flags::*;
+
+	// Status flags F_SETFL may change
+	const SETFL_MASK: u32 = O_APPEND | O_NONBLOCK | O_FASYNC | O_DIRECT | O_NOATIME;
+
+	let file = crate::fs::get_file_descriptor(fd).ok_or(Error::EBADF)?;
+	match cmd {
+		F_DUPFD | F_DUPFD_CLOEXEC => {
+			let min_fd = i32::try_from(arg).map_err(|_| Error::EINVAL)?;
+			let cloexec = cmd == F_DUPFD_CLOEXEC;
+			Ok(crate::fs::install_file_descriptor(file, min_fd, cloexec)? as u64)
+		}
+		F_GETFD => Ok(crate::fs::fd_cloexec(fd)? as u64),
+		F_SETFD => {
+			crate::fs::set_fd_cloexec(fd, arg & FD_CLOEXEC != 0)?;
+			Ok(0)
+		}
+		F_GETFL => Ok((file.get_flags()
+			& !(O_CREAT | O_EXCL | O_NOCTTY | O_TRUNC | O_CLOEXEC))
+			as u64),
+		F_SETFL => {
+			let old = file.get_flags();
+			let new = (old & !SETFL_MASK) | (arg as u32 & SETFL_MASK);
+			file.flags.store(new, Ordering::Relaxed);
+			Ok(0)
+		}
+		_ => Err(Error::EINVAL),
+	}
+}
 
-	// Add child to process table and scheduler
-	let mut table = crate::process::PROCESS_TABLE.lock();
-	table.add_process(child.clone());
-	drop(table);
+/// ioctls handled for every file
+const FIONCLEX: u32 = 0x5450;
+const FIOCLEX: u32 = 0x5451;
+const FIONBIO: u32 = 0x5421;
 
-	// Add to scheduler
-	add_task(child_pid)?;
+pub fn sys_ioctl(fd: i32, cmd: u32, arg: u64) -> Result<u64> {
+	use core::sync::atomic::Ordering;
 
-	// Return child PID to parent (in child, this would return 0)
-	Ok(child_pid.0 as u64)
+	use crate::fs::flags::O_NONBLOCK;
+	use crate::memory::{copy_from_user, UserPtr};
+
+	let file = crate::fs::get_file_descriptor(fd).ok_or(Error::EBADF)?;
+	match cmd {
+		FIOCLEX | FIONCLEX => {
+			crate::fs::set_fd_cloexec(fd, cmd == FIOCLEX)?;
+			Ok(0)
+		}
+		FIONBIO => {
+			let mut bytes = [0u8; 4];
+			copy_from_user(&mut bytes, UserPtr::from_const(arg as *const u8)?)?;
+			if i32::from_ne_bytes(bytes) != 0 {
+				file.flags.fetch_or(O_NONBLOCK, Ordering::Relaxed);
+			} else {
+				file.flags.fetch_and(!O_NONBLOCK, Ordering::Relaxed);
+			}
+			Ok(0)
+		}
+		_ => Ok(file.ioctl(cmd, arg as usize)? as u64),
+	}
 }
 
-pub fn sys_execve(filename: u64, argv: u64, envp: u64) -> Result<u64> {
-	use crate::memory::{copy_string_from_user, UserPtr};
+pub fn sys_faccessat(dirfd: i32, filename: u64, mode: u32, flags: u64) -> Result<u64> {
+	if mode & !0o7 != 0 || flags & !(AT_EACCESS | AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0 {
+		return Err(Error::EINVAL);
+	}
+	let st = crate::fs::path_stat(&resolve_at(dirfd, &user_path(filename)?)?)?;
+
+	// R_OK, W_OK and X_OK match MAY_READ, MAY_WRITE and MAY_EXEC; the
+	// check uses the real IDs unless AT_EACCESS asks for the fs IDs
+	let mut cred = crate::cred::current_cred();
+	if flags & AT_EACCESS == 0 {
+		cred.fsuid = cred.uid;
+		cred.fsgid = cred.gid;
+	}
+	crate::cred::generic_permission(&cred, st.st_mode, Uid(st.st_uid), Gid(st.st_gid), mode)?;
+	Ok(0)
+}
 
-	// Copy filename from user space
-	let user_ptr = UserPtr::from_const(filename as *const u8)?;
-	let filename_str = copy_string_from_user(user_ptr, 256)?;
+pub fn sys_getcwd(buf: u64, size: u64) -> Result<u64> {
+	use crate::memory::{copy_to_user, UserPtr};
 
-	// Get current process
-	let mut current = current_process().ok_or(Error::ESRCH)?;
+	let mut cwd = current_cwd().into_bytes();
+	cwd.push(0);
+	if (size as usize) < cwd.len() {
+		return Err(Error::ERANGE);
+	}
+	copy_to_user(UserPtr::new(buf as *mut u8)?, &cwd)?;
+	Ok(cwd.len() as u64)
+}
 
-	// Execute new program (with empty args for now)
-	current.exec(&filename_str, alloc::vec![])?;
+/// Make `path` the working directory of the current process
+fn set_cwd(path: alloc::string::String) -> Result<u64> {
+	let st = crate::fs::path_stat(&path)?;
+	if !crate::fs::mode::s_isdir(st.st_mode) {
+		return Err(Error::ENOTDIR);
+	}
+	let cred = crate::cred::current_cred();
+	let (uid, gid) = (Uid(st.st_uid), Gid(st.st_gid));
+	crate::cred::generic_permission(&cred, st.st_mode, uid, gid, crate::cred::MAY_EXEC)?;
 
-	// This doesn't return on success
+	let fs = crate::process::with_current(|p| p.fs.clone()).ok_or(Error::ESRCH)?;
+	fs.lock().cwd = path;
 	Ok(0)
 }
 
-pub fn sys_exit(exit_code: i32) -> Result<u64> {
-	use crate::scheduler::remove_task;
+pub fn sys_chdir(filename: u64) -> Result<u64> {
+	set_cwd(resolve_at(AT_FDCWD, &user_path(filename)?)?)
+}
 
-	// Get current process
-	if let Some(mut current) = current_process() {
-		// Set exit code and mark as zombie
-		current.exit(exit_code);
+pub fn sys_fchdir(fd: i32) -> Result<u64> {
+	let file = crate::fs::get_file_descriptor(fd).ok_or(Error::EBADF)?;
+	set_cwd(file.path.clone())
+}
+
+pub fn sys_mkdirat(dirfd: i32, filename: u64, mode: u32) -> Result<u64> {
+	crate::fs::memfile::mkdir(&resolve_at(dirfd, &user_path(filename)?)?, mode)?;
+	Ok(0)
+}
 
-		// Remove from scheduler
-		let _ = remove_task(current.pid);
+pub fn sys_unlinkat(dirfd: i32, filename: u64, flags: u64) -> Result<u64> {
+	if flags & !AT_REMOVEDIR != 0 {
+		return Err(Error::EINVAL);
+	}
+	let path = resolve_at(dirfd, &user_path(filename)?)?;
+	crate::fs::memfile::unlink(&path, flags & AT_REMOVEDIR != 0)?;
+	Ok(0)
+}
 
-		// In a real implementation, this would:
-		// 1. Free all process resources
-		// 2. Notify parent process
-		// 3. Reparent children to init
-		// 4. Schedule next process
+/// renameat2 flag refusing to replace an existing target
+const RENAME_NOREPLACE: u32 = 1;
 
-		// Signal scheduler to switch to next process
-		crate::scheduler::schedule();
+pub fn sys_renameat2(
+	olddirfd: i32,
+	oldname: u64,
+	newdirfd: i32,
+	newname: u64,
+	flags: u32,
+) -> Result<u64> {
+	if flags & !RENAME_NOREPLACE != 0 {
+		return Err(Error::EINVAL);
 	}
+	let old_path = resolve_at(olddirfd, &user_path(oldname)?)?;
+	let new_path = resolve_at(newdirfd, &user_path(newname)?)?;
+	// Device nodes aren't part of the root file system
+	if old_path.starts_with("/dev/") != new_path.starts_with("/dev/") {
+		return Err(Error::EXDEV);
+	}
+	crate::fs::memfile::rename(&old_path, &new_path, flags & RENAME_NOREPLACE != 0)?;
+	Ok(0)
+}
 
-	// This syscall doesn't return
-	loop {
-		unsafe { core::arch::asm!("hlt") };
+pub fn sys_readlinkat(dirfd: i32, filename: u64, _buf: u64, size: u64) -> Result<u64> {
+	if size as i64 <= 0 {
+		return Err(Error::EINVAL);
 	}
+	// There are no symbolic links, anything that exists is not one
+	crate::fs::path_stat(&resolve_at(dirfd, &user_path(filename)?)?)?;
+	Err(Error::EINVAL)
 }
 
-pub fn sys_wait4(pid: u64, status: u64, options: u64, rusage: u64) -> Result<u64> {
+pub fn sys_getdents64(fd: i32, dirp: u64, count: u64) -> Result<u64> {
 	use crate::memory::{copy_to_user, UserPtr};
 
-	// Get current process
-	let current = current_process().ok_or(Error::ESRCH)?;
+	let file = crate::fs::get_file_descriptor(fd).ok_or(Error::EBADF)?;
+	let mut ctx = crate::fs::DirContext::new(file.get_pos());
+	file.readdir(&mut ctx)?;
+
+	// struct linux_dirent64 records, 8-byte aligned
+	let mut records = alloc::vec::Vec::new();
+	let mut next_pos = file.get_pos();
+	for entry in &ctx.entries {
+		let reclen = (19 + entry.name.len() + 1 + 7) & !7;
+		if records.len() + reclen > count as usize {
+			break;
+		}
+		let start = records.len();
+		records.extend_from_slice(&entry.ino.to_ne_bytes());
+		records.extend_from_slice(&(entry.off + 1).to_ne_bytes());
+		records.extend_from_slice(&(reclen as u16).to_ne_bytes());
+		records.push(entry.d_type);
+		records.extend_from_slice(entry.name.as_bytes());
+		records.resize(start + reclen, 0);
+		next_pos = entry.off + 1;
+	}
+	if records.is_empty() && !ctx.entries.is_empty() {
+		return Err(Error::EINVAL);
+	}
 
-	// Wait for child process
-	let (child_pid, exit_status) = current.wait()?;
+	copy_to_user(UserPtr::new(dirp as *mut u8)?, &records)?;
+	file.set_pos(next_pos);
+	Ok(records.len() as u64)
+}
 
-	// If status pointer is provided, write exit status
-	if status != 0 {
-		let status_ptr = UserPtr::new(status as *mut i32)?;
-		copy_to_user(status_ptr.cast(), &exit_status.to_ne_bytes())?;
+pub fn sys_pipe2(fds: u64, flags: u32) -> Result<u64> {
+	use crate::fs::flags::{O_CLOEXEC, O_DIRECT, O_NONBLOCK};
+	use crate::memory::{copy_to_user, UserPtr};
+
+	if flags & !(O_CLOEXEC | O_NONBLOCK | O_DIRECT) != 0 {
+		return Err(Error::EINVAL);
+	}
+	let (read_end, write_end) = crate::fs::pipe::create_pipe(flags)?;
+	let cloexec = flags & O_CLOEXEC != 0;
+	let read_fd = crate::fs::install_file_descriptor(read_end, 0, cloexec)?;
+	let write_fd = match crate::fs::install_file_descriptor(write_end, 0, cloexec) {
+		Ok(fd) => fd,
+		Err(err) => {
+			let _ = crate::fs::close_file_descriptor(read_fd);
+			return Err(err);
+		}
+	};
+
+	let mut bytes = [0u8; 8];
+	bytes[..4].copy_from_slice(&read_fd.to_ne_bytes());
+	bytes[4..].copy_from_slice(&write_fd.to_ne_bytes());
+	if let Err(err) = copy_to_user(UserPtr::new(fds as *mut u8)?, &bytes) {
+		let _ = crate::fs::close_file_descriptor(read_fd);
+		let _ = crate::fs::close_file_descriptor(write_fd);
+		return Err(err);
 	}
+	Ok(0)
+}
 
-	Ok(child_pid.0 as u64)
+/// Deadline of a poll or epoll_wait timeout in milliseconds; negative
+/// waits forever
+fn deadline_ms(timeout: i32) -> Option<u64> {
+	u64::try_from(timeout)
+		.ok()
+		.map(|ms| crate::time::get_time_ns().saturating_add(ms * 1_000_000))
 }
 
-pub fn sys_kill(pid: i32, signal: i32) -> Result<u64> {
-	if let Some(mut process) = find_process(Pid(pid as u32)) {
-		process.send_signal(signal)?;
-		Ok(0)
-	} else {
-		Err(Error::ESRCH)
+/// Deadline of a timeout given as a user struct timespec; NULL waits
+/// forever
+fn deadline_timespec(tsp: u64) -> Result<Option<u64>> {
+	if tsp == 0 {
+		return Ok(None);
 	}
+	let timeout = read_timespec(tsp)?;
+	Ok(Some(crate::time::get_time_ns().saturating_add(timeout)))
 }
 
-/// Process info syscalls
-pub fn sys_getpid() -> u32 {
-	current_process().map(|p| p.pid.0).unwrap_or(0)
+/// Write the time left until `deadline` back to a user timespec, or a
+/// timeval with `micro`, as select, pselect6 and ppoll do
+fn put_time_left(addr: u64, deadline: Option<u64>, micro: bool) -> Result<()> {
+	use crate::memory::{copy_to_user, UserPtr};
+
+	let deadline = match deadline {
+		Some(deadline) if addr != 0 => deadline,
+		_ => return Ok(()),
+	};
+	let left = deadline.saturating_sub(crate::time::get_time_ns());
+	let fraction = if micro {
+		left % NSEC_PER_SEC / 1000
+	} else {
+		left % NSEC_PER_SEC
+	};
+	let mut bytes = [0u8; 16];
+	bytes[..8].copy_from_slice(&(left / NSEC_PER_SEC).to_ne_bytes());
+	bytes[8..].copy_from_slice(&fraction.to_ne_bytes());
+	copy_to_user(UserPtr::new(addr as *mut u8)?, &bytes)
 }
 
-pub fn sys_getppid() -> u32 {
-	current_process()
-		.and_then(|p| p.parent)
-		.map(|p| p.0)
-		.unwrap_or(0)
+/// Signal masks a call installs while it waits. There are no blocked
+/// signal masks yet, so only the size is checked.
+fn check_sigmask(sigmask: u64, sigsetsize: u64) -> Result<()> {
+	if sigmask != 0 && sigsetsize != 8 {
+		return Err(Error::EINVAL);
+	}
+	Ok(())
 }
 
-pub fn sys_getuid() -> u32 {
-	current_process().map(|p| p.uid.0).unwrap_or(0)
+/// Poll the user pollfd array `ufds`
+fn poll_user(ufds: u64, nfds: u64, deadline: Option<u64>) -> Result<u64> {
+	use crate::fs::select::{do_poll, PollFd};
+	use crate::memory::{copy_from_user, copy_to_user, UserPtr};
+
+	if nfds > crate::resource::rlimit(crate::resource::RLIMIT_NOFILE) {
+		return Err(Error::EINVAL);
+	}
+	let mut bytes = alloc::vec![0u8; nfds as usize * 8];
+	if nfds > 0 {
+		copy_from_user(&mut bytes, UserPtr::from_const(ufds as *const u8)?)?;
+	}
+	let mut fds: alloc::vec::Vec<PollFd> = bytes
+		.chunks_exact(8)
+		.map(|b| PollFd {
+			fd: i32::from_ne_bytes(b[..4].try_into().unwrap()),
+			events: u16::from_ne_bytes(b[4..6].try_into().unwrap()),
+			revents: 0,
+		})
+		.collect();
+
+	let count = do_poll(&mut fds, deadline)?;
+	for (chunk, pollfd) in bytes.chunks_exact_mut(8).zip(&fds) {
+		chunk[6..].copy_from_slice(&pollfd.revents.to_ne_bytes());
+	}
+	if nfds > 0 {
+		copy_to_user(UserPtr::new(ufds as *mut u8)?, &bytes)?;
+	}
+	Ok(count as u64)
 }
 
-pub fn sys_getgid() -> u32 {
-	current_process().map(|p| p.gid.0).unwrap_or(0)
+pub fn sys_poll(ufds: u64, nfds: u64, timeout: i32) -> Result<u64> {
+	poll_user(ufds, nfds, deadline_ms(timeout))
 }
 
-pub fn sys_gettid() -> u32 {
-	// For now, return PID (single-threaded processes)
-	sys_getpid()
+pub fn sys_ppoll(ufds: u64, nfds: u64, tsp: u64, sigmask: u64, sigsetsize: u64) -> Result<u64> {
+	check_sigmask(sigmask, sigsetsize)?;
+	let deadline = deadline_timespec(tsp)?;
+	let count = poll_user(ufds, nfds, deadline)?;
+	put_time_left(tsp, deadline, false)?;
+	Ok(count)
 }
 
-/// File operation syscalls
-pub fn sys_read(fd: i32, buf: u64, count: u64) -> Result<u64> {
-	use crate::fs::{get_file_descriptor, read_file};
-	use crate::memory::{copy_to_user, UserPtr};
+/// Run select over user fd_sets, each NULL or `(nfds + 63) / 64` words
+fn select_user(nfds: i32, inp: u64, outp: u64, exp: u64, deadline: Option<u64>) -> Result<u64> {
+	use crate::fs::select::{do_select, FdSets};
+	use crate::memory::{copy_from_user, copy_to_user, UserPtr};
 
-	// Validate parameters
-	if count == 0 {
-		return Ok(0);
+	if nfds < 0 {
+		return Err(Error::EINVAL);
 	}
+	let limit = crate::resource::rlimit(crate::resource::RLIMIT_NOFILE);
+	let nfds = (nfds as u64).min(limit) as usize;
+	let words = (nfds + 63) / 64;
+
+	let read_set = |addr: u64| -> Result<alloc::vec::Vec<u64>> {
+		let mut bytes = alloc::vec![0u8; words * 8];
+		if addr != 0 && words > 0 {
+			copy_from_user(&mut bytes, UserPtr::from_const(addr as *const u8)?)?;
+		}
+		Ok(bytes.chunks_exact(8)
+			.map(|b| u64::from_ne_bytes(b.try_into().unwrap()))
+			.collect())
+	};
+	let mut sets = FdSets {
+		read: read_set(inp)?,
+		write: read_set(outp)?,
+		except: read_set(exp)?,
+	};
 
-	// Get file from file descriptor table
-	let file = get_file_descriptor(fd).ok_or(Error::EBADF)?;
+	let count = do_select(nfds, &mut sets, deadline)?;
+	for (addr, set) in [(inp, &sets.read), (outp, &sets.write), (exp, &sets.except)] {
+		if addr != 0 && words > 0 {
+			let bytes: alloc::vec::Vec<u8> =
+				set.iter().flat_map(|word| word.to_ne_bytes()).collect();
+			copy_to_user(UserPtr::new(addr as *mut u8)?, &bytes)?;
+		}
+	}
+	Ok(count as u64)
+}
 
-	// Create a kernel buffer to read into
-	let mut kernel_buf = alloc::vec![0u8; count as usize];
+pub fn sys_select(nfds: i32, inp: u64, outp: u64, exp: u64, tvp: u64) -> Result<u64> {
+	let deadline = if tvp == 0 {
+		None
+	} else {
+		Some(crate::time::get_time_ns().saturating_add(read_timeval(tvp)?))
+	};
+	let count = select_user(nfds, inp, outp, exp, deadline)?;
+	put_time_left(tvp, deadline, true)?;
+	Ok(count)
+}
 
-	// Read from file
-	let bytes_read = read_file(&file, &mut kernel_buf)?;
+pub fn sys_pselect6(nfds: i32, inp: u64, outp: u64, exp: u64, tsp: u64, sig: u64) -> Result<u64> {
+	use crate::memory::{copy_from_user, UserPtr};
 
-	// Copy to user buffer
-	let user_ptr = UserPtr::new(buf as *mut u8)?;
-	copy_to_user(user_ptr, &kernel_buf[..bytes_read])?;
+	// sig points to the sigmask pointer and its size
+	if sig != 0 {
+		let mut bytes = [0u8; 16];
+		copy_from_user(&mut bytes, UserPtr::from_const(sig as *const u8)?)?;
+		let sigmask = u64::from_ne_bytes(bytes[..8].try_into().unwrap());
+		let sigsetsize = u64::from_ne_bytes(bytes[8..].try_into().unwrap());
+		check_sigmask(sigmask, sigsetsize)?;
+	}
+	let deadline = deadline_timespec(tsp)?;
+	let count = select_user(nfds, inp, outp, exp, deadline)?;
+	put_time_left(tsp, deadline, false)?;
+	Ok(count)
+}
 
-	Ok(bytes_read as u64)
+pub fn sys_epoll_create(size: i32) -> Result<u64> {
+	if size <= 0 {
+		return Err(Error::EINVAL);
+	}
+	sys_epoll_create1(0)
 }
 
-pub fn sys_write(fd: i32, buf: u64, count: u64) -> Result<u64> {
-	use crate::fs::{get_file_descriptor, write_file};
-	use crate::memory::{copy_from_user, UserPtr};
+pub fn sys_epoll_create1(flags: u32) -> Result<u64> {
+	use crate::fs::flags::O_CLOEXEC;
 
-	// Validate parameters
-	if count == 0 {
-		return Ok(0);
+	// EPOLL_CLOEXEC is O_CLOEXEC
+	if flags & !O_CLOEXEC != 0 {
+		return Err(Error::EINVAL);
 	}
+	let file = crate::fs::eventpoll::create_epoll()?;
+	Ok(crate::fs::install_file_descriptor(file, 0, flags & O_CLOEXEC != 0)? as u64)
+}
 
-	// Handle stdout/stderr specially for now
-	if fd == 1 || fd == 2 {
-		// Create kernel buffer and copy from user
-		let mut kernel_buf = alloc::vec![0u8; count as usize];
-		let user_ptr = UserPtr::from_const(buf as *const u8)?;
-		copy_from_user(&mut kernel_buf, user_ptr)?;
+/// Size of struct epoll_event, which is packed on x86_64
+const EPOLL_EVENT_SIZE: usize = 12;
 
-		// Write to console (for debugging)
-		if let Ok(s) = core::str::from_utf8(&kernel_buf) {
-			crate::print!("{}", s);
-		}
+pub fn sys_epoll_ctl(epfd: i32, op: i32, fd: i32, event: u64) -> Result<u64> {
+	use crate::fs::eventpoll::{EventPoll, EPOLL_CTL_DEL};
+	use crate::memory::{copy_from_user, UserPtr};
 
-		return Ok(count);
+	let epoll_file = crate::fs::get_file_descriptor(epfd).ok_or(Error::EBADF)?;
+	let file = crate::fs::get_file_descriptor(fd).ok_or(Error::EBADF)?;
+	let epoll = epoll_file.operations::<EventPoll>().ok_or(Error::EINVAL)?;
+	if fd == epfd {
+		return Err(Error::EINVAL);
 	}
 
-	// Get file from file descriptor table
-	let file = get_file_descriptor(fd).ok_or(Error::EBADF)?;
+	// The event is ignored for EPOLL_CTL_DEL
+	let mut bytes = [0u8; EPOLL_EVENT_SIZE];
+	if op != EPOLL_CTL_DEL {
+		copy_from_user(&mut bytes, UserPtr::from_const(event as *const u8)?)?;
+	}
+	let events = u32::from_ne_bytes(bytes[..4].try_into().unwrap());
+	let data = u64::from_ne_bytes(bytes[4..].try_into().unwrap());
+	epoll.ctl(op, fd, &file, events, data)?;
+	Ok(0)
+}
 
-	// Create kernel buffer and copy from user
-	let mut kernel_buf = alloc::vec![0u8; count as usize];
-	let user_ptr = UserPtr::from_const(buf as *const u8)?;
-	copy_from_user(&mut kernel_buf, user_ptr)?;
+pub fn sys_epoll_wait(epfd: i32, events: u64, maxevents: i32, timeout: i32) -> Result<u64> {
+	use crate::fs::eventpoll::EventPoll;
+	use crate::memory::{copy_to_user, UserPtr};
 
-	// Write to file
-	let bytes_written = write_file(&file, &kernel_buf)?;
+	if maxevents <= 0 || maxevents as usize > i32::MAX as usize / EPOLL_EVENT_SIZE {
+		return Err(Error::EINVAL);
+	}
+	let epoll_file = crate::fs::get_file_descriptor(epfd).ok_or(Error::EBADF)?;
+	let epoll = epoll_file.operations::<EventPoll>().ok_or(Error::EINVAL)?;
+
+	let ready = epoll.wait(maxevents as usize, deadline_ms(timeout))?;
+	let mut bytes = alloc::vec::Vec::with_capacity(ready.len() * EPOLL_EVENT_SIZE);
+	for (mask, data) in &ready {
+		bytes.extend_from_slice(&mask.to_ne_bytes());
+		bytes.extend_from_slice(&data.to_ne_bytes());
+	}
+	if !bytes.is_empty() {
+		copy_to_user(UserPtr::new(events as *mut u8)?, &bytes)?;
+	}
+	Ok(ready.len() as u64)
+}
 
-	Ok(bytes_written as u64)
+pub fn sys_epoll_pwait(
+	epfd: i32,
+	events: u64,
+	maxevents: i32,
+	timeout: i32,
+	sigmask: u64,
+	sigsetsize: u64,
+) -> Result<u64> {
+	check_sigmask(sigmask, sigsetsize)?;
+	sys_epoll_wait(epfd, events, maxevents, timeout)
 }
 
-pub fn sys_open(filename: u64, flags: i32, mode: u32) -> Result<u64> {
-	use crate::fs::{allocate_file_descriptor, open_file};
-	use crate::memory::{copy_string_from_user, UserPtr};
+pub fn sys_eventfd2(initval: u32, flags: u32) -> Result<u64> {
+	use crate::fs::eventfd::{create_eventfd, EFD_CLOEXEC, EFD_NONBLOCK, EFD_SEMAPHORE};
 
-	// Copy filename from user space
-	let user_ptr = UserPtr::from_const(filename as *const u8)?;
-	let filename_str = copy_string_from_user(user_ptr, 256)?; // Max 256 chars
+	if flags & !(EFD_SEMAPHORE | EFD_CLOEXEC | EFD_NONBLOCK) != 0 {
+		return Err(Error::EINVAL);
+	}
+	let file = create_eventfd(initval, flags)?;
+	Ok(crate::fs::install_file_descriptor(file, 0, flags & EFD_CLOEXEC != 0)? as u64)
+}
 
-	// Open file in VFS
-	let file = open_file(&filename_str, flags, mode)?;
+pub fn sys_timerfd_create(clock: i32, flags: u32) -> Result<u64> {
+	use crate::fs::timerfd::{create_timerfd, TFD_CLOEXEC, TFD_NONBLOCK};
 
-	// Allocate file descriptor and add to process file table
-	let fd = allocate_file_descriptor(file)?;
+	if flags & !(TFD_CLOEXEC | TFD_NONBLOCK) != 0 {
+		return Err(Error::EINVAL);
+	}
+	let file = create_timerfd(clock, flags)?;
+	Ok(crate::fs::install_file_descriptor(file, 0, flags & TFD_CLOEXEC != 0)? as u64)
+}
 
-	Ok(fd as u64)
+/// Write a struct itimerspec of (interval, value) in nanoseconds
+fn put_itimerspec(addr: u64, interval: u64, value: u64) -> Result<()> {
+	use crate::memory::{copy_to_user, UserPtr};
+
+	let mut bytes = [0u8; 32];
+	for (chunk, ns) in bytes.chunks_exact_mut(16).zip([interval, value]) {
+		chunk[..8].copy_from_slice(&(ns / NSEC_PER_SEC).to_ne_bytes());
+		chunk[8..].copy_from_slice(&(ns % NSEC_PER_SEC).to_ne_bytes());
+	}
+	copy_to_user(UserPtr::new(addr as *mut u8)?, &bytes)
 }
 
-pub fn sys_close(fd: i32) -> Result<u64> {
-	use crate::fs::close_file_descriptor;
+pub fn sys_timerfd_settime(fd: i32, flags: u32, new: u64, old: u64) -> Result<u64> {
+	use crate::fs::timerfd::{TimerFd, TFD_TIMER_ABSTIME, TFD_TIMER_CANCEL_ON_SET};
 
-	// Close file descriptor
-	close_file_descriptor(fd)?;
+	if flags & !(TFD_TIMER_ABSTIME | TFD_TIMER_CANCEL_ON_SET) != 0 {
+		return Err(Error::EINVAL);
+	}
+	let file = crate::fs::get_file_descriptor(fd).ok_or(Error::EBADF)?;
+	let timerfd = file.operations::<TimerFd>().ok_or(Error::EINVAL)?;
+	let interval = read_timespec(new)?;
+	let value = read_timespec(new + 16)?;
+
+	// Clock changes can't happen, so TFD_TIMER_CANCEL_ON_SET never cancels
+	let (old_interval, old_value) =
+		timerfd.settime(flags & TFD_TIMER_ABSTIME != 0, interval, value);
+	if old != 0 {
+		put_itimerspec(old, old_interval, old_value)?;
+	}
+	Ok(0)
+}
 
+pub fn sys_timerfd_gettime(fd: i32, curr: u64) -> Result<u64> {
+	use crate::fs::timerfd::TimerFd;
+
+	let file = crate::fs::get_file_descriptor(fd).ok_or(Error::EBADF)?;
+	let timerfd = file.operations::<TimerFd>().ok_or(Error::EINVAL)?;
+	let (interval, value) = timerfd.gettime();
+	put_itimerspec(curr, interval, value)?;
 	Ok(0)
 }
 
+pub fn sys_signalfd4(fd: i32, mask: u64, sizemask: u64, flags: u32) -> Result<u64> {
+	use crate::fs::signalfd::{create_signalfd, SignalFd, SFD_CLOEXEC, SFD_NONBLOCK};
+	use crate::memory::{copy_from_user, UserPtr};
+	use crate::signal::SigSet;
+
+	if sizemask != 8 || flags & !(SFD_CLOEXEC | SFD_NONBLOCK) != 0 {
+		return Err(Error::EINVAL);
+	}
+	let mut bytes = [0u8; 8];
+	copy_from_user(&mut bytes, UserPtr::from_const(mask as *const u8)?)?;
+	let mask = SigSet(u64::from_ne_bytes(bytes));
+
+	// An existing signalfd only gets the new mask
+	if fd != -1 {
+		let file = crate::fs::get_file_descriptor(fd).ok_or(Error::EBADF)?;
+		let signalfd = file.operations::<SignalFd>().ok_or(Error::EINVAL)?;
+		signalfd.set_mask(mask);
+		return Ok(fd as u64);
+	}
+	let file = create_signalfd(mask, flags)?;
+	Ok(crate::fs::install_file_descriptor(file, 0, flags & SFD_CLOEXEC != 0)? as u64)
+}
+
 /// Memory management syscalls
 pub fn sys_mmap(
 	addr: u64,
@@ -348,6 +2327,15 @@ pub fn sys_mmap(
 		vma
 	};
 
+	// Charge the mapping against RLIMIT_AS
+	if let Some(pid) = crate::process::current_process_pid() {
+		if let Err(err) = crate::process::expand_vm(pid, aligned_length) {
+			let _ = crate::memory::free_virtual_memory(vma.vm_start, aligned_length);
+			return Err(err);
+		}
+		crate::process::add_mapping(pid, vma.clone());
+	}
+
 	// Handle file mapping
 	if fd >= 0 {
 		// Map file into memory - simplified implementation
@@ -376,10 +2364,76 @@ pub fn sys_munmap(addr: u64, length: u64) -> Result<u64> {
 
 	// Free virtual memory region
 	free_virtual_memory(VirtAddr::new(aligned_addr as usize), aligned_length)?;
+	if let Some(pid) = crate::process::current_process_pid() {
+		crate::process::shrink_vm(pid, aligned_length);
+		crate::process::remove_mapping(
+			pid,
+			VirtAddr::new(aligned_addr as usize),
+			VirtAddr::new((aligned_addr + aligned_length) as usize),
+		);
+	}
+
+	Ok(0)
+}
 
+pub fn sys_mprotect(addr: u64, length: u64, prot: u64) -> Result<u64> {
+	use crate::memory::VirtAddr;
+
+	let page_size = 4096u64;
+	if addr & (page_size - 1) != 0 || prot & !0x7 != 0 {
+		return Err(Error::EINVAL);
+	}
+	let aligned_length =
+		length.checked_add(page_size - 1).ok_or(Error::ENOMEM)? & !(page_size - 1);
+	if aligned_length == 0 {
+		return Ok(0);
+	}
+	let end = addr.checked_add(aligned_length).ok_or(Error::ENOMEM)?;
+
+	let pid = crate::process::current_process_pid().ok_or(Error::ESRCH)?;
+	crate::process::protect_mapping(
+		pid,
+		VirtAddr::new(addr as usize),
+		VirtAddr::new(end as usize),
+		prot as u32,
+	)?;
 	Ok(0)
 }
 
+/// getrandom flags
+pub const GRND_NONBLOCK: u32 = 1;
+pub const GRND_RANDOM: u32 = 2;
+pub const GRND_INSECURE: u32 = 4;
+
+/// Most bytes one getrandom call returns
+const GETRANDOM_MAX: u64 = 33_554_431;
+
+pub fn sys_getrandom(buf: u64, count: u64, flags: u32) -> Result<u64> {
+	use crate::memory::{copy_to_user, UserPtr};
+
+	if flags & !(GRND_NONBLOCK | GRND_RANDOM | GRND_INSECURE) != 0
+		|| flags & (GRND_RANDOM | GRND_INSECURE) == GRND_RANDOM | GRND_INSECURE
+	{
+		return Err(Error::EINVAL);
+	}
+	// Until the generator is seeded, wait unless asked not to
+	if flags & GRND_INSECURE == 0 && !crate::random::crng_ready() {
+		if flags & GRND_NONBLOCK != 0 {
+			return Err(Error::WouldBlock);
+		}
+		crate::random::wait_for_random_bytes()?;
+	}
+	// Nothing to fill, so buf isn't looked at
+	if count == 0 {
+		return Ok(0);
+	}
+	let count = count.min(GETRANDOM_MAX) as usize;
+	let done = crate::random::get_random_bytes_chunked(count, |offset, chunk| {
+		copy_to_user(UserPtr::new((buf + offset as u64) as *mut u8)?, chunk)
+	})?;
+	Ok(done as u64)
+}
+
 pub fn sys_brk(addr: u64) -> Result<u64> {
 	use crate::memory::{get_heap_end, set_heap_end, VirtAddr};
 
@@ -399,9 +2453,18 @@ pub fn sys_brk(addr: u64) -> Result<u64> {
 		let pages_to_free = (current_brk.as_usize() - new_brk.as_usize() + 4095) / 4096;
 		crate::info!("Shrinking heap, freeing {} pages", pages_to_free);
 		// In a real implementation, this would free the actual pages
+		if let Some(pid) = crate::process::current_process_pid() {
+			crate::process::shrink_vm(pid, pages_to_free as u64 * 4096);
+		}
 	} else if new_brk > current_brk {
 		// Expanding heap - allocate pages
 		let pages_to_alloc = (new_brk.as_usize() - current_brk.as_usize() + 4095) / 4096;
+		// Over RLIMIT_AS, brk fails by returning the old break
+		if let Some(pid) = crate::process::current_process_pid() {
+			if crate::process::expand_vm(pid, pages_to_alloc as u64 * 4096).is_err() {
+				return Ok(current_brk.as_usize() as u64);
+			}
+		}
 		crate::info!("Expanding heap, allocating {} pages", pages_to_alloc);
 		// In a real implementation, this would allocate and map new
 		// pages
@@ -429,7 +2492,7 @@ pub mod arch {
 		arg4: u64,
 		arg5: u64,
 	) -> u64 {
-		let args = SyscallArgs {
+		let mut args = SyscallArgs {
 			syscall_num,
 			arg0,
 			arg1,
@@ -439,7 +2502,15 @@ pub mod arch {
 			arg5,
 		};
 
-		handle_syscall(args)
+		crate::process::account_kernel_entry();
+		let result = match crate::ptrace::syscall_enter(&mut args) {
+			// The tracer skipped the call
+			Some(result) => result,
+			None => handle_syscall(args),
+		};
+		let result = crate::ptrace::syscall_exit(result);
+		crate::process::account_kernel_exit();
+		result
 	}
 }
 
@@ -64,6 +64,8 @@ impl CpuInfo {
 			asm!("cpuid", inout("eax") 0 => eax, out("ecx") _, out("edx") _, options(preserves_flags));
 		}
 
+		let max_leaf = eax;
+
 		if eax >= 1 {
 			// Get basic CPU info - avoid using ebx directly due to LLVM restrictions
 			unsafe {
@@ -133,6 +135,28 @@ impl CpuInfo {
 			if ecx & (1 << 28) != 0 {
 				self.features.push("AVX".into());
 			}
+			if ecx & (1 << 30) != 0 {
+				self.features.push("RDRAND".into());
+			}
+		}
+
+		if max_leaf >= 7 {
+			// Structured extended features, subleaf 0
+			unsafe {
+				asm!("mov {ebx_save}, rbx",
+				     "cpuid",
+				     "mov {ebx_out:e}, ebx",
+				     "mov rbx, {ebx_save}",
+				     ebx_save = out(reg) _,
+				     ebx_out = out(reg) ebx,
+				     inout("eax") 7 => _,
+				     inout("ecx") 0 => _,
+				     out("edx") _,
+				     options(preserves_flags));
+			}
+			if ebx & (1 << 18) != 0 {
+				self.features.push("RDSEED".into());
+			}
 		}
 
 		// Try to get vendor string
@@ -217,7 +241,8 @@ pub struct SystemStats {
 
 impl SystemStats {
 	pub fn collect() -> Self {
-		let uptime = crate::time::get_jiffies().as_u64() / 1000; // Convert to seconds
+		let uptime = crate::time::get_time_ns() / crate::time::NSEC_PER_SEC;
+		let tasks = crate::process::task_counts();
 
 		// Collect performance counters
 		let context_switches =
@@ -229,10 +254,10 @@ impl SystemStats {
 
 		Self {
 			uptime_seconds: uptime,
-			boot_time: 0,                  // TODO: Get actual boot time
-			processes: 1,                  // TODO: Count actual processes
-			threads: 1,                    // TODO: Count actual threads
-			load_average: (0.0, 0.0, 0.0), // TODO: Calculate load average
+			boot_time: crate::time::boot_time_secs(),
+			processes: tasks.processes as u32,
+			threads: tasks.threads as u32,
+			load_average: crate::loadavg::get_loadavg(),
 			context_switches,
 			interrupts,
 		}
@@ -325,6 +350,12 @@ impl SystemInfo {
 		output.push_str("\nSystem Statistics:\n");
 		output.push_str(&format!("  Processes: {}\n", self.system_stats.processes));
 		output.push_str(&format!("  Threads: {}\n", self.system_stats.threads));
+		let (one, five, fifteen) = self.system_stats.load_average;
+		output.push_str(&format!(
+			"  Load Average: {:.2} {:.2} {:.2}\n",
+			one, five, fifteen
+		));
+		output.push_str(&format!("  Boot Time: {}\n", self.system_stats.boot_time));
 		output.push_str(&format!(
 			"  Context Switches: {}\n",
 			self.system_stats.context_switches
@@ -346,10 +377,11 @@ impl SystemInfo {
 
 	pub fn format_compact(&self) -> String {
 		format!(
-			"{} {} - Uptime: {}s, RAM: {}/{} KB, CPU: {}",
+			"{} {} - Uptime: {}s, Load: {:.2}, RAM: {}/{} KB, CPU: {}",
 			self.kernel_version,
 			self.architecture,
 			self.system_stats.uptime_seconds,
+			self.system_stats.load_average.0,
 			self.memory_info.used_ram / 1024,
 			self.memory_info.total_ram / 1024,
 			self.cpu_info.vendor
@@ -4,8 +4,11 @@
 
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, Ordering};
 
 use crate::error::{Error, Result};
+use crate::types::Pid;
+use crate::workqueue::Work;
 
 /// Test result structure
 #[derive(Debug, Clone)]
@@ -53,6 +56,24 @@ pub fn run_all_tests() -> Result<TestStats> {
 	// Timer tests
 	results.extend(test_timer_functionality()?);
 
+	// Random number generator tests
+	results.extend(test_random()?);
+
+	// Synchronization tests
+	results.extend(test_synchronization()?);
+
+	// Credential tests
+	results.extend(test_credentials()?);
+
+	// Namespace tests
+	results.extend(test_namespaces()?);
+
+	// Terminal tests
+	results.extend(test_terminal()?);
+
+	// User ABI tests
+	results.extend(test_user_abi()?);
+
 	let end_time = crate::time::get_time_ns();
 	let total_duration = (end_time - start_time) / 1_000_000; // Convert to ms
 
@@ -201,6 +222,7 @@ fn test_scheduler() -> Result<Vec<TestResult>> {
 
 	results.push(test_scheduler_stats());
 	results.push(test_task_creation());
+	results.push(test_group_shares());
 
 	Ok(results)
 }
@@ -269,12 +291,90 @@ fn test_task_creation() -> TestResult {
 	}
 }
 
+/// Set to end the spinners of test_group_shares
+static GROUP_SPINNERS_STOP: AtomicBool = AtomicBool::new(false);
+
+fn group_spinner() {
+	while !GROUP_SPINNERS_STOP.load(Ordering::Acquire) {
+		core::hint::spin_loop();
+	}
+}
+
+/// Test that two busy groups split the CPU by their shares
+fn test_group_shares() -> TestResult {
+	use crate::scheduler::{
+		create_task_group, destroy_task_group, move_task_to_group, set_group_shares,
+		task_group_stats, ROOT_TASK_GROUP,
+	};
+
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		GROUP_SPINNERS_STOP.store(false, Ordering::Release);
+		let light = create_task_group("test-light", ROOT_TASK_GROUP)?;
+		let heavy = create_task_group("test-heavy", ROOT_TASK_GROUP)?;
+		let usage = |group| task_group_stats(group).map_or(0, |stats| stats.usage_ns);
+
+		let mut spinners = Vec::new();
+		let used = (|| -> Result<(u64, u64)> {
+			set_group_shares(light, 512)?;
+			set_group_shares(heavy, 1536)?;
+			for group in [light, heavy] {
+				let tid = crate::working_task::spawn_kernel_task(
+					"group-spinner".to_string(),
+					group_spinner,
+					8192,
+				)?;
+				spinners.push(tid);
+				move_task_to_group(tid, group)?;
+			}
+
+			let (light0, heavy0) = (usage(light), usage(heavy));
+			let deadline = crate::time::get_time_ns() + 300_000_000;
+			crate::sync::wait_until(|| crate::time::get_time_ns() >= deadline);
+			Ok((usage(light) - light0, usage(heavy) - heavy0))
+		})();
+
+		// Spinners leave the groups so they can be removed
+		for &tid in &spinners {
+			let _ = move_task_to_group(tid, ROOT_TASK_GROUP);
+		}
+		GROUP_SPINNERS_STOP.store(true, Ordering::Release);
+		destroy_task_group(light)?;
+		destroy_task_group(heavy)?;
+
+		// Three times the shares, about three times the CPU time
+		let (light_ns, heavy_ns) = used?;
+		if light_ns == 0 || heavy_ns < 2 * light_ns || heavy_ns > 4 * light_ns {
+			return Err(Error::EIO);
+		}
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Group Shares".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Group CPU time didn't follow the shares".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
 /// Test IPC functionality
 fn test_ipc() -> Result<Vec<TestResult>> {
 	let mut results = Vec::new();
 
 	results.push(test_ipc_stats());
 	results.push(test_message_queue());
+	results.push(test_pi_handoff());
+	results.push(test_futex_requeue());
+	results.push(test_futex_sleep());
 
 	Ok(results)
 }
@@ -355,6 +455,174 @@ fn test_message_queue() -> TestResult {
 	}
 }
 
+/// Test that PI locks pass to the highest priority waiter on unlock
+fn test_pi_handoff() -> TestResult {
+	use crate::rtmutex::{LockState, RawRtMutex, Waiter};
+	use crate::types::Tid;
+
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		// Lower values are higher priority; equals are served FIFO
+		let mut lock = LockState::default();
+		lock.owner = Some(Tid(1));
+		for (tid, prio) in [(2, 120), (3, 100), (4, 100)] {
+			lock.enqueue(Waiter {
+				tid: Tid(tid),
+				prio,
+			});
+		}
+		for next in [Some(Tid(3)), Some(Tid(4)), Some(Tid(2)), None] {
+			if lock.hand_off() != next || lock.owner != next {
+				return Err(Error::EIO);
+			}
+		}
+
+		// Only the owner may unlock, and it may not wait on itself. The
+		// IDs are past any real task, as unlock resets the priority.
+		let (owner, other) = (Tid(0x3fff_fffe), Tid(0x3fff_ffff));
+		let mutex = RawRtMutex::new();
+		if !mutex.try_lock(owner) || mutex.try_lock(other) {
+			return Err(Error::EIO);
+		}
+		if mutex.unlock(other) != Err(Error::EPERM)
+			|| mutex.lock(owner, None) != Err(Error::EDEADLK)
+		{
+			return Err(Error::EIO);
+		}
+		if mutex.unlock(owner)? != None || mutex.owner().is_some() {
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "PI Lock Owner Handoff".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Lock went to the wrong task".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Futex hash of test_futex_sleep, which FUTEX_WAKE_WORK wakes from a
+/// kernel task
+static FUTEX_SLEEP_HASH: crate::sync::Spinlock<crate::futex::FutexHash> =
+	crate::sync::Spinlock::new(crate::futex::FutexHash::new());
+static FUTEX_WAKE_WORK: Work = Work::new(wake_futex_sleeper, 0x3000);
+
+fn wake_futex_sleeper(key: usize) {
+	FUTEX_SLEEP_HASH
+		.lock()
+		.wake(key as u64, 1, crate::futex::FUTEX_BITSET_MATCH_ANY);
+}
+
+/// Test that a futex waiter sleeps until woken or its deadline passes
+fn test_futex_sleep() -> TestResult {
+	use crate::futex::FutexWaiter;
+
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		// Nobody wakes this one, so it times out
+		let waiter = FutexWaiter::new(0x4000, u32::MAX);
+		FUTEX_SLEEP_HASH.lock().queue(waiter.clone());
+		let deadline = crate::time::get_time_ns() + 20_000_000;
+		let timed_out = !waiter.sleep(Some(deadline));
+		let _ = FUTEX_SLEEP_HASH.lock().wake(0x4000, 1, u32::MAX);
+		if !timed_out || crate::time::get_time_ns() < deadline {
+			return Err(Error::EIO);
+		}
+
+		// A wake from another task ends the sleep before the deadline
+		let waiter = FutexWaiter::new(0x3000, u32::MAX);
+		FUTEX_SLEEP_HASH.lock().queue(waiter.clone());
+		if !crate::workqueue::schedule_work(&FUTEX_WAKE_WORK) {
+			return Err(Error::WouldBlock);
+		}
+		let deadline = crate::time::get_time_ns() + 1_000_000_000;
+		let woken = waiter.sleep(Some(deadline));
+		crate::workqueue::flush_work(&FUTEX_WAKE_WORK);
+		if !woken || !waiter.is_woken() || crate::time::get_time_ns() >= deadline {
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Futex Sleep".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Futex waiter missed its wake-up or deadline".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test futex requeue between wait queues
+fn test_futex_requeue() -> TestResult {
+	use crate::futex::{FutexHash, FutexWaiter};
+
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		// Keys stand in for the physical addresses of two futex words
+		let (key, key2) = (0x1000, 0x2000);
+		let mut hash = FutexHash::new();
+		let waiters: Vec<_> = (0..4).map(|_| FutexWaiter::new(key, u32::MAX)).collect();
+		waiters.iter().for_each(|waiter| hash.queue(waiter.clone()));
+
+		// Wake the first, move the next two, leave the last
+		if hash.requeue(key, key2, 1, 2) != 3 {
+			return Err(Error::EIO);
+		}
+		let state: Vec<_> = waiters.iter().map(|w| (w.is_woken(), w.key())).collect();
+		if state != [(true, key), (false, key2), (false, key2), (false, key)] {
+			return Err(Error::EIO);
+		}
+
+		// Requeued waiters are woken from their new futex in order
+		if hash.wake(key2, 1, u32::MAX) != 1
+			|| !waiters[1].is_woken()
+			|| waiters[2].is_woken()
+		{
+			return Err(Error::EIO);
+		}
+		if hash.wake(key, usize::MAX, u32::MAX) != 1 || !waiters[3].is_woken() {
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Futex Requeue".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Waiters were woken or moved wrongly".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
 /// Test performance monitoring
 fn test_performance_monitoring() -> Result<Vec<TestResult>> {
 	let mut results = Vec::new();
@@ -432,6 +700,8 @@ fn test_filesystem() -> Result<Vec<TestResult>> {
 	let mut results = Vec::new();
 
 	results.push(test_fs_basic_ops());
+	results.push(test_poll_masks());
+	results.push(test_epoll_triggers());
 
 	Ok(results)
 }
@@ -462,6 +732,160 @@ fn test_fs_basic_ops() -> TestResult {
 	}
 }
 
+/// Test the readiness masks pipes and eventfds report to poll
+fn test_poll_masks() -> TestResult {
+	use crate::fs::eventfd::create_eventfd;
+	use crate::fs::pipe::create_pipe;
+	use crate::fs::{PollWait, POLLERR, POLLHUP, POLLIN, POLLOUT, POLLRDNORM, POLLWRNORM};
+	use crate::sync::Arc;
+
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		let poll = |file: &Arc<crate::fs::File>| file.poll(&mut PollWait::new());
+		let readable = POLLIN | POLLRDNORM;
+		let writable = POLLOUT | POLLWRNORM;
+
+		// An eventfd is writable below its maximum, readable above zero
+		if poll(&create_eventfd(0, 0)?)? != writable
+			|| poll(&create_eventfd(1, 0)?)? != readable | writable
+		{
+			return Err(Error::EIO);
+		}
+
+		// An empty pipe is only writable. Without writers the read end
+		// hangs up, without readers the write end reports an error.
+		let (read_end, write_end) = create_pipe(0)?;
+		if poll(&read_end)? != 0 || poll(&write_end)? != writable {
+			return Err(Error::EIO);
+		}
+		drop(write_end);
+		if poll(&read_end)? != POLLHUP {
+			return Err(Error::EIO);
+		}
+		let (read_end, write_end) = create_pipe(0)?;
+		drop(read_end);
+		if poll(&write_end)? != POLLERR {
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Poll Readiness Masks".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"File reported the wrong events".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test level-triggered, edge-triggered and one-shot epoll entries
+fn test_epoll_triggers() -> TestResult {
+	use crate::fs::eventfd::{create_eventfd, EFD_NONBLOCK};
+	use crate::fs::eventpoll::{
+		create_epoll, EventPoll, EPOLLET, EPOLLONESHOT, EPOLL_CTL_ADD, EPOLL_CTL_DEL,
+		EPOLL_CTL_MOD,
+	};
+	use crate::fs::pipe::create_pipe;
+	use crate::fs::{POLLHUP, POLLIN, POLLOUT};
+
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		let epfile = create_epoll()?;
+		let epoll = epfile.operations::<EventPoll>().ok_or(Error::EIO)?;
+		let efd = create_eventfd(1, EFD_NONBLOCK)?;
+		let bump = || {
+			let mut value = 1u64.to_ne_bytes();
+			let buf =
+				unsafe { crate::memory::UserSlicePtr::new(value.as_mut_ptr(), 8) };
+			efd.write(buf, 8).map(|_| ())
+		};
+		// Return at once, the deadline has passed
+		let wait = || epoll.wait(8, Some(0));
+
+		// Level-triggered entries are reported while ready, and only
+		// with the events asked for
+		epoll.ctl(EPOLL_CTL_ADD, 3, &efd, POLLIN, 7)?;
+		if epoll.ctl(EPOLL_CTL_ADD, 3, &efd, POLLIN, 7) != Err(Error::EEXIST) {
+			return Err(Error::EIO);
+		}
+		if wait()? != [(POLLIN, 7)] || wait()? != [(POLLIN, 7)] {
+			return Err(Error::EIO);
+		}
+
+		// Edge-triggered ones once, then again after the next wake-up
+		epoll.ctl(EPOLL_CTL_MOD, 3, &efd, POLLIN | EPOLLET, 7)?;
+		if wait()? != [(POLLIN, 7)] || !wait()?.is_empty() {
+			return Err(Error::EIO);
+		}
+		bump()?;
+		if wait()? != [(POLLIN, 7)] || !wait()?.is_empty() {
+			return Err(Error::EIO);
+		}
+
+		// One-shot ones once, until rearmed with EPOLL_CTL_MOD
+		epoll.ctl(EPOLL_CTL_MOD, 3, &efd, POLLIN | POLLOUT | EPOLLONESHOT, 7)?;
+		if wait()? != [(POLLIN | POLLOUT, 7)] {
+			return Err(Error::EIO);
+		}
+		bump()?;
+		if !wait()?.is_empty() {
+			return Err(Error::EIO);
+		}
+		epoll.ctl(EPOLL_CTL_MOD, 3, &efd, POLLOUT, 7)?;
+		if wait()? != [(POLLOUT, 7)] {
+			return Err(Error::EIO);
+		}
+		epoll.ctl(EPOLL_CTL_DEL, 3, &efd, 0, 0)?;
+
+		// Hang-ups are reported without being asked for
+		let (read_end, write_end) = create_pipe(0)?;
+		epoll.ctl(EPOLL_CTL_ADD, 4, &read_end, POLLIN, 9)?;
+		if !wait()?.is_empty() {
+			return Err(Error::EIO);
+		}
+		drop(write_end);
+		if wait()? != [(POLLHUP, 9)] {
+			return Err(Error::EIO);
+		}
+
+		// Watches between epoll instances can't form a cycle
+		let outer_file = create_epoll()?;
+		let outer = outer_file.operations::<EventPoll>().ok_or(Error::EIO)?;
+		outer.ctl(EPOLL_CTL_ADD, 5, &epfile, POLLIN, 0)?;
+		if epoll.ctl(EPOLL_CTL_ADD, 6, &outer_file, POLLIN, 0) != Err(Error::ELOOP)
+			|| epoll.ctl(EPOLL_CTL_ADD, 6, &epfile, POLLIN, 0) != Err(Error::ELOOP)
+		{
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Epoll Trigger Modes".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"epoll reported the wrong entries".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
 /// Test hardware detection
 fn test_hardware_detection() -> Result<Vec<TestResult>> {
 	let mut results = Vec::new();
@@ -541,10 +965,74 @@ fn test_timer_functionality() -> Result<Vec<TestResult>> {
 
 	results.push(test_timer_basic());
 	results.push(test_jiffies());
+	results.push(test_loadavg());
+	results.push(test_timer_overflow());
+	results.push(test_tickless_idle());
 
 	Ok(results)
 }
 
+/// Test that jiffies catch up on the ticks skipped while idle, and that
+/// the tick keeps running with NO_HZ off
+fn test_tickless_idle() -> TestResult {
+	use crate::time::{get_jiffies, HrTimer, HrTimerBase, TimeSpec, NSEC_PER_JIFFY};
+	use crate::timer::{cpu_idle, get_timer_stats, nohz_enabled, set_nohz_enabled};
+
+	fn wakeup() {}
+
+	let start = crate::time::get_time_ns();
+	let was_enabled = nohz_enabled();
+
+	let result = || -> Result<()> {
+		let idle_ns = 50_000_000;
+
+		// The timer bounds each idle period when the tick is stopped
+		set_nohz_enabled(true);
+		let t0 = crate::time::get_time_ns();
+		let jiffies0 = get_jiffies().0;
+		let calls = get_timer_stats().idle_calls;
+		let mut timer = HrTimer::new(HrTimerBase::Monotonic);
+		timer.expires = TimeSpec::from_ns(t0 + idle_ns);
+		timer.function = Some(wakeup);
+		crate::time::add_timer(timer);
+		while crate::time::get_time_ns() < t0 + idle_ns {
+			cpu_idle();
+		}
+		let elapsed = (crate::time::get_time_ns() - t0) / NSEC_PER_JIFFY;
+		let ticks = get_jiffies().0 - jiffies0;
+		// Allow for the TSC calibration being a little off
+		let idled = get_timer_stats().idle_calls > calls;
+		if !idled || ticks.abs_diff(elapsed) > elapsed / 10 + 2 {
+			return Err(Error::EIO);
+		}
+
+		set_nohz_enabled(false);
+		let sleeps = get_timer_stats().idle_sleeps;
+		cpu_idle();
+		if get_timer_stats().idle_sleeps != sleeps {
+			return Err(Error::EIO);
+		}
+		Ok(())
+	}();
+
+	set_nohz_enabled(was_enabled);
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Tickless Idle".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Jiffies fell behind while idle or the tick stopped with NO_HZ off"
+				.to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
 /// Test basic timer functionality
 fn test_timer_basic() -> TestResult {
 	let start = crate::time::get_time_ns();
@@ -618,6 +1106,1914 @@ fn test_jiffies() -> TestResult {
 	}
 }
 
+/// Test the fixed-point load average arithmetic
+fn test_loadavg() -> TestResult {
+	use crate::loadavg::{calc_load, load_frac, load_int, EXP_1, FIXED_1};
+
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		// One runnable task for a minute of 5s samples gives 1 - 1/e,
+		// shown as 0.63 like Linux
+		let mut load = 0;
+		for _ in 0..12 {
+			load = calc_load(load, EXP_1, FIXED_1);
+		}
+		if load != 1299 || (load_int(load), load_frac(load)) != (0, 63) {
+			return Err(Error::EIO);
+		}
+
+		// Rounding up on the way up and down on the way down lets the
+		// average settle on exactly the number of tasks, and on zero
+		for _ in 0..68 {
+			load = calc_load(load, EXP_1, FIXED_1);
+		}
+		if load != FIXED_1 {
+			return Err(Error::EIO);
+		}
+		for _ in 0..68 {
+			load = calc_load(load, EXP_1, 0);
+		}
+		if load != 0 {
+			return Err(Error::EIO);
+		}
+
+		let load = 3 * FIXED_1 + FIXED_1 / 4;
+		if (load_int(load), load_frac(load)) != (3, 25) {
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Load Average Fixed Point".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Load average arithmetic differs from Linux".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test that huge timer intervals disarm the timer instead of overflowing
+fn test_timer_overflow() -> TestResult {
+	use crate::posix_timers::{
+		exit_timers, forward_timer, timer_gettime, timer_settime, TimerKey, ITIMER_REAL,
+		TIMER_MAX_NS,
+	};
+	use crate::fs::timerfd::{create_timerfd, TimerFd};
+	use crate::time::CLOCK_MONOTONIC;
+
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		// Periodic expiries are counted, one-shots disarm
+		if forward_timer(100, 10, 125) != (3, 130)
+			|| forward_timer(100, 0, 100) != (1, 0)
+			|| forward_timer(100, 10, 99) != (0, 100)
+		{
+			return Err(Error::EIO);
+		}
+		// An expiry past any clock value disarms
+		if forward_timer(1, TIMER_MAX_NS, 2) != (1, 0)
+			|| forward_timer(1, u64::MAX, u64::MAX) != (1, 0)
+		{
+			return Err(Error::EIO);
+		}
+
+		// Intervals from user space are clamped when set
+		let pid = Pid(0x7fff_0001);
+		let key = TimerKey::Itimer(ITIMER_REAL);
+		timer_settime(pid, key, false, u64::MAX, u64::MAX)?;
+		let (interval, left) = timer_gettime(pid, key)?;
+		exit_timers(pid);
+		if interval != TIMER_MAX_NS || left > TIMER_MAX_NS {
+			return Err(Error::EIO);
+		}
+
+		// So does a timerfd, which stops once its next expiry overflows
+		let file = create_timerfd(CLOCK_MONOTONIC, 0)?;
+		let timerfd = file.operations::<TimerFd>().ok_or(Error::EINVAL)?;
+		timerfd.settime(true, u64::MAX, 1);
+		if timerfd.gettime() != (TIMER_MAX_NS, 0) {
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Timer Interval Overflow".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Timer expiry arithmetic overflowed".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test the ChaCha20 primitives of the random number generator
+fn test_random() -> Result<Vec<TestResult>> {
+	let mut results = Vec::new();
+
+	results.push(test_chacha_quarter_round());
+	results.push(test_chacha20_block());
+	results.push(test_random_chunked());
+
+	Ok(results)
+}
+
+/// Test that a large random read is generated in contiguous bounded chunks
+fn test_random_chunked() -> TestResult {
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		let mut next = 0;
+		let mut largest = 0;
+		let done = crate::random::get_random_bytes_chunked(1000, |offset, chunk| {
+			if offset != next {
+				return Err(crate::error::Error::EIO);
+			}
+			next += chunk.len();
+			largest = largest.max(chunk.len());
+			Ok(())
+		})?;
+
+		if done != 1000 || next != 1000 || largest > 256 {
+			return Err(crate::error::Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Chunked Random Read".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Random bytes were not handed out in bounded contiguous chunks".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test the ChaCha quarter round against RFC 7539 section 2.1.1
+fn test_chacha_quarter_round() -> TestResult {
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		let mut state = [0u32; 16];
+		state[..4].copy_from_slice(&[0x11111111, 0x01020304, 0x9b8d6f43, 0x01234567]);
+		crate::random::quarter_round(&mut state, 0, 1, 2, 3);
+
+		if state[..4] != [0xea2a92f4, 0xcb1cf8ce, 0x4581472e, 0x5881c4bb] {
+			return Err(crate::error::Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "ChaCha Quarter Round".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Quarter round does not match RFC 7539".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test the ChaCha20 block function against RFC 7539 section 2.3.2
+fn test_chacha20_block() -> TestResult {
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		// Key 00..1f, counter 1, nonce 000000090000004a00000000
+		let key: Vec<u8> = (0..32).collect();
+		let mut input = [0u32; 16];
+		input[..4].copy_from_slice(&[0x61707865, 0x3320646e, 0x79622d32, 0x6b206574]);
+		for (word, chunk) in input[4..12].iter_mut().zip(key.chunks_exact(4)) {
+			*word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+		}
+		input[12..].copy_from_slice(&[0x00000001, 0x09000000, 0x4a000000, 0x00000000]);
+
+		let expected: [u8; 64] = [
+			0x10, 0xf1, 0xe7, 0xe4, 0xd1, 0x3b, 0x59, 0x15, 0x50, 0x0f, 0xdd, 0x1f,
+			0xa3, 0x20, 0x71, 0xc4, 0xc7, 0xd1, 0xf4, 0xc7, 0x33, 0xc0, 0x68, 0x03,
+			0x04, 0x22, 0xaa, 0x9a, 0xc3, 0xd4, 0x6c, 0x4e, 0xd2, 0x82, 0x64, 0x46,
+			0x07, 0x9f, 0xaa, 0x09, 0x14, 0xc2, 0xd7, 0x05, 0xd9, 0x8b, 0x02, 0xa2,
+			0xb5, 0x12, 0x9c, 0xd1, 0xde, 0x16, 0x4e, 0xb9, 0xcb, 0xd0, 0x83, 0xe8,
+			0xa2, 0x50, 0x3c, 0x4e,
+		];
+		if crate::random::chacha20_core(&input) != expected {
+			return Err(crate::error::Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "ChaCha20 Block Function".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Block function does not match RFC 7539".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test synchronization primitives
+fn test_synchronization() -> Result<Vec<TestResult>> {
+	let mut results = Vec::new();
+
+	results.push(test_sleeping_locks());
+	results.push(test_rcu_grace_period());
+	results.push(test_deferred_work());
+	#[cfg(feature = "debug")]
+	results.push(test_lockdep_ordering());
+
+	Ok(results)
+}
+
+/// Test that RCU frees nothing a reader may still see, and that
+/// synchronize_rcu waits out a grace period
+fn test_rcu_grace_period() -> TestResult {
+	use crate::rcu::{call_rcu, gp_completed, rcu_process_callbacks, rcu_read_lock, RcuCell};
+	use crate::sync::Arc;
+
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		let cell = RcuCell::new(1u32);
+		let freed = Arc::new(AtomicBool::new(false));
+
+		// A callback queued while a reader runs waits for it
+		let guard = rcu_read_lock();
+		let old = cell.read();
+		cell.replace(2);
+		let flag = freed.clone();
+		call_rcu(move || flag.store(true, Ordering::Release));
+		rcu_process_callbacks();
+		if freed.load(Ordering::Acquire) || *old != 1 || *cell.read() != 2 {
+			return Err(Error::EIO);
+		}
+		drop(guard);
+
+		let completed = gp_completed();
+		crate::rcu::synchronize_rcu();
+		if !freed.load(Ordering::Acquire) || gp_completed() <= completed {
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "RCU Grace Period".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"A grace period ended under a reader or never ended".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Sum of the data of the deferred work test_deferred_work ran
+static DEFERRED_RUNS: AtomicU32 = AtomicU32::new(0);
+static DEFERRED_WORK: Work = Work::new(count_deferred_run, 1);
+static DEFERRED_TASKLET: crate::softirq::Tasklet =
+	crate::softirq::Tasklet::new(count_deferred_run, 0x100);
+
+fn count_deferred_run(data: usize) {
+	DEFERRED_RUNS.fetch_add(data as u32, Ordering::AcqRel);
+}
+
+/// Test that queued work and tasklets run once however often they were
+/// queued before running
+fn test_deferred_work() -> TestResult {
+	use crate::softirq::{tasklet_kill, tasklet_schedule};
+	use crate::workqueue::{alloc_workqueue, destroy_workqueue, flush_work, queue_work};
+
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		DEFERRED_RUNS.store(0, Ordering::Release);
+
+		// Nothing runs the work between the two queueings
+		let wq = alloc_workqueue("test")?;
+		let queued = crate::interrupt::without_interrupts(|| {
+			queue_work(&wq, &DEFERRED_WORK) && !queue_work(&wq, &DEFERRED_WORK)
+		});
+		flush_work(&DEFERRED_WORK);
+		destroy_workqueue(&wq);
+		if !queued || DEFERRED_WORK.is_pending() {
+			return Err(Error::EIO);
+		}
+
+		crate::interrupt::without_interrupts(|| {
+			tasklet_schedule(&DEFERRED_TASKLET);
+			tasklet_schedule(&DEFERRED_TASKLET);
+		});
+		tasklet_kill(&DEFERRED_TASKLET);
+
+		if DEFERRED_RUNS.load(Ordering::Acquire) != 0x101 {
+			return Err(Error::EIO);
+		}
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Deferred Work".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Queued work didn't run exactly once".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test that lockdep records a lock order once and reports the reverse
+/// order the first time it is taken, before anything deadlocks
+#[cfg(feature = "debug")]
+fn test_lockdep_ordering() -> TestResult {
+	use core::panic::Location;
+
+	use crate::lockdep::{acquire, debug_locks, release, reset_classes};
+
+	let start = crate::time::get_time_ns();
+
+	// A report from a real bug already turned lockdep off, keep it
+	let enabled = debug_locks();
+	let a = Location::caller();
+	let b = Location::caller();
+	let (lock_a, lock_b) = (0u8, 0u8);
+	let (addr_a, addr_b) = (&lock_a as *const u8 as usize, &lock_b as *const u8 as usize);
+
+	let result = || -> Result<()> {
+		if !enabled {
+			return Ok(());
+		}
+		for _ in 0..2 {
+			acquire(addr_a, a, a, false);
+			acquire(addr_b, b, b, false);
+			release(addr_b);
+			release(addr_a);
+		}
+		if !debug_locks() {
+			return Err(Error::EIO);
+		}
+
+		// B -> A closes the cycle without anyone waiting on a lock
+		acquire(addr_b, b, b, false);
+		acquire(addr_a, a, a, false);
+		release(addr_a);
+		release(addr_b);
+		if debug_locks() {
+			return Err(Error::EIO);
+		}
+		Ok(())
+	}();
+
+	// Only A -> B was recorded, the cycle was refused
+	let result = if enabled && reset_classes(&[a, b]) != 1 {
+		Err(Error::EIO)
+	} else {
+		result
+	};
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Lockdep Ordering".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"An AB-BA lock order went unreported".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test the exclusion rules of the sleeping locks, and that a waiter whose
+/// condition holds or that was woken meanwhile doesn't go to sleep
+fn test_sleeping_locks() -> TestResult {
+	use crate::sync::{Completion, Mutex, RwLock, Semaphore, WaitQueue};
+
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		let mutex = Mutex::new(1);
+		{
+			let mut guard = mutex.lock();
+			*guard += 1;
+			if mutex.try_lock().is_some() {
+				return Err(Error::EIO);
+			}
+		}
+		if mutex.try_lock().map(|guard| *guard) != Some(2) {
+			return Err(Error::EIO);
+		}
+
+		// Readers share the lock, a writer has it alone
+		let rwlock = RwLock::new(0);
+		{
+			let (_first, _second) = (rwlock.read(), rwlock.read());
+			if rwlock.reader_count() != 2 || rwlock.try_write().is_some() {
+				return Err(Error::EIO);
+			}
+		}
+		{
+			let _writer = rwlock.write();
+			if rwlock.try_read().is_some() || !rwlock.is_write_locked() {
+				return Err(Error::EIO);
+			}
+		}
+
+		let semaphore = Semaphore::new(1);
+		let guard = semaphore.acquire();
+		if semaphore.try_acquire().is_some() {
+			return Err(Error::EIO);
+		}
+		drop(guard);
+		if semaphore.count() != 1 {
+			return Err(Error::EIO);
+		}
+
+		// Each complete lets one wait through, complete_all every wait
+		let completion = Completion::new();
+		completion.complete();
+		completion.wait_for_completion();
+		if completion.completion_done() {
+			return Err(Error::EIO);
+		}
+		completion.complete_all();
+		completion.wait_for_completion();
+		if !completion.try_wait_for_completion() {
+			return Err(Error::EIO);
+		}
+
+		// A wake-up between looking and sleeping isn't lost
+		let queue = WaitQueue::new();
+		queue.wait_event(|| true);
+		let (seen, running) = (queue.wakeups(), crate::scheduler::current_tid());
+		queue.wake_up();
+		crate::scheduler::sleep_current(|| queue.wakeups() != seen);
+		if crate::scheduler::current_tid() != running {
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Sleeping Locks".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Lock let two holders in or a waiter slept".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test credential rules that need no process
+fn test_credentials() -> Result<Vec<TestResult>> {
+	let mut results = Vec::new();
+
+	results.push(test_file_permissions());
+	results.push(test_capset_rules());
+	results.push(test_rlimit_rules());
+	results.push(test_seccomp_matching());
+	results.push(test_forced_signals());
+
+	Ok(results)
+}
+
+/// Test the access checks of files against the credentials of the caller
+fn test_file_permissions() -> TestResult {
+	use crate::cred::{generic_permission, Credentials, MAY_EXEC, MAY_READ, MAY_WRITE};
+	use crate::fs::flags::O_WRONLY;
+	use crate::fs::mode::{S_IFDIR, S_IFREG, S_ISVTX};
+	use crate::fs::operations::{may_delete_owner, may_open_owner};
+	use crate::types::{Gid, Uid};
+
+	let start = crate::time::get_time_ns();
+	let user = add_test_process("permuser", 1000);
+	let previous = crate::process::current_process_pid();
+
+	let result = || -> Result<()> {
+		let mut cred = Credentials::new(Uid(1000), Gid(1000));
+		cred.groups.push(Gid(100));
+		let file = |mode: u32, uid: u32, gid: u32, mask: u32| {
+			generic_permission(&cred, S_IFREG | mode, Uid(uid), Gid(gid), mask)
+		};
+
+		// Only the first matching class counts: owner, group, others
+		if file(0o640, 1000, 0, MAY_READ | MAY_WRITE).is_err()
+			|| file(0o064, 1000, 100, MAY_READ) != Err(Error::EACCES)
+			|| file(0o640, 2000, 100, MAY_READ).is_err()
+			|| file(0o640, 2000, 100, MAY_WRITE) != Err(Error::EACCES)
+			|| file(0o644, 2000, 0, MAY_WRITE) != Err(Error::EACCES)
+		{
+			return Err(Error::EIO);
+		}
+
+		// Root may read and write anything, and execute what anyone can
+		let root = Credentials::root();
+		let check = |mode, mask| generic_permission(&root, mode, Uid(1), Gid(1), mask);
+		if check(S_IFREG, MAY_READ | MAY_WRITE).is_err()
+			|| check(S_IFREG | 0o644, MAY_EXEC) != Err(Error::EACCES)
+			|| check(S_IFREG | 0o744, MAY_EXEC).is_err()
+			|| check(S_IFDIR, MAY_EXEC).is_err()
+		{
+			return Err(Error::EIO);
+		}
+
+		// Only owners remove the entries of a sticky directory
+		crate::process::set_current_process(Some(user));
+		let tmp = (S_IFDIR | S_ISVTX | 0o777, Uid(0), Gid(0));
+		if may_delete_owner(tmp, (S_IFREG | 0o666, Uid(2000), Gid(0))) != Err(Error::EPERM)
+			|| may_delete_owner(tmp, (S_IFREG | 0o600, Uid(1000), Gid(0))).is_err()
+			|| may_open_owner((S_IFDIR | 0o777, Uid(1000), Gid(0)), O_WRONLY)
+				!= Err(Error::EISDIR)
+		{
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	crate::process::set_current_process(previous);
+	reap_test_process(user);
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "File Permissions".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Access was granted or refused against the mode bits".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test the subset rules capset applies to new capability sets
+fn test_capset_rules() -> TestResult {
+	use crate::capability::{CapSet, CAP_CHOWN, CAP_KILL, CAP_SETPCAP};
+	use crate::cred::Credentials;
+	use crate::types::{Gid, Uid};
+
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		let caps = |list: &[u32]| {
+			let mut set = CapSet::empty();
+			list.iter().for_each(|&cap| set.add(cap));
+			set
+		};
+		let mut user = Credentials::new(Uid(1000), Gid(1000));
+		user.cap_permitted = caps(&[CAP_CHOWN, CAP_KILL]);
+		user.cap_effective = caps(&[CAP_CHOWN]);
+
+		// Raising effective within permitted and dropping are allowed
+		let mut cred = user.clone();
+		cred.set_caps(caps(&[CAP_KILL]), caps(&[CAP_KILL]), CapSet::empty())?;
+		if cred.cap_permitted != caps(&[CAP_KILL]) || cred.capable(CAP_CHOWN) {
+			return Err(Error::EIO);
+		}
+		// A dropped capability cannot come back into permitted
+		if cred.set_caps(CapSet::empty(), caps(&[CAP_CHOWN]), CapSet::empty())
+			!= Err(Error::EPERM)
+		{
+			return Err(Error::EIO);
+		}
+
+		// Effective must stay within the new permitted set
+		let mut cred = user.clone();
+		if cred.set_caps(caps(&[CAP_KILL]), caps(&[CAP_CHOWN]), CapSet::empty())
+			!= Err(Error::EPERM)
+		{
+			return Err(Error::EIO);
+		}
+
+		// Inheritable comes from permitted without CAP_SETPCAP...
+		if cred.set_caps(CapSet::empty(), CapSet::empty(), caps(&[CAP_SETPCAP]))
+			!= Err(Error::EPERM)
+		{
+			return Err(Error::EIO);
+		}
+		cred.set_caps(CapSet::empty(), user.cap_permitted, caps(&[CAP_KILL]))?;
+
+		// ...and from the bounding set with it, never beyond
+		let mut cred = user.clone();
+		cred.cap_permitted.add(CAP_SETPCAP);
+		cred.cap_effective.add(CAP_SETPCAP);
+		cred.cap_bset.remove(CAP_KILL);
+		cred.set_caps(cred.cap_effective, cred.cap_permitted, caps(&[CAP_SETPCAP]))?;
+		let (effective, permitted) = (cred.cap_effective, cred.cap_permitted);
+		if cred.set_caps(effective, permitted, caps(&[CAP_SETPCAP, CAP_KILL]))
+			!= Err(Error::EPERM)
+		{
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Capset Subset Rules".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"capset accepted or refused the wrong sets".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test the soft and hard limit rules of setrlimit and prlimit64
+fn test_rlimit_rules() -> TestResult {
+	use crate::cred::Credentials;
+	use crate::resource::{RLimits, Rlimit, NR_OPEN, RLIMIT_NOFILE, RLIM_NLIMITS};
+	use crate::types::{Gid, Uid};
+
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		let user = Credentials::new(Uid(1000), Gid(1000));
+		let root = Credentials::root();
+		let mut limits = RLimits::new();
+		let expect = |result: Result<Rlimit>, errno: Error| {
+			if result.err() == Some(errno) {
+				Ok(())
+			} else {
+				Err(Error::EIO)
+			}
+		};
+
+		// The soft limit may not exceed the hard limit
+		expect(
+			limits.set(RLIMIT_NOFILE, Rlimit::new(2048, 1024), &user),
+			Error::EINVAL,
+		)?;
+		expect(
+			limits.set(RLIM_NLIMITS, Rlimit::new(1, 1), &root),
+			Error::EINVAL,
+		)?;
+
+		// Anyone may lower the hard limit and the soft limit under it
+		let old = limits.set(RLIMIT_NOFILE, Rlimit::new(2048, 2048), &user)?;
+		if old != Rlimit::new(1024, 4096) {
+			return Err(Error::EIO);
+		}
+		limits.set(RLIMIT_NOFILE, Rlimit::new(512, 2048), &user)?;
+
+		// Raising it again needs CAP_SYS_RESOURCE
+		expect(
+			limits.set(RLIMIT_NOFILE, Rlimit::new(512, 4096), &user),
+			Error::EPERM,
+		)?;
+		limits.set(RLIMIT_NOFILE, Rlimit::new(512, 4096), &root)?;
+
+		// Even then the file limit stays within NR_OPEN
+		let too_many = Rlimit::new(NR_OPEN + 1, NR_OPEN + 1);
+		expect(limits.set(RLIMIT_NOFILE, too_many, &root), Error::EPERM)?;
+		if limits.cur(RLIMIT_NOFILE) != 512 {
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Rlimit Soft/Hard Rules".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"setrlimit accepted or refused the wrong limits".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test seccomp rule matching and the stacking of filters
+fn test_seccomp_matching() -> TestResult {
+	use crate::seccomp::{
+		Action, ArgCmp, Filter, Rule, Seccomp, SECCOMP_CMP_GT, SECCOMP_CMP_MASKED_EQ,
+		SECCOMP_MODE_FILTER, SECCOMP_RET_ERRNO,
+	};
+	use crate::sync::Arc;
+	use crate::syscalls::SyscallArgs;
+
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		let rule = |nr, cmp: Option<(usize, u32, u64, u64)>, action| Rule {
+			nr,
+			cmp: cmp.map(|(arg, op, value, mask)| ArgCmp {
+				arg,
+				op,
+				value,
+				mask,
+			}),
+			action,
+		};
+		let call = |nr, arg0, arg2| SyscallArgs {
+			syscall_num: nr,
+			arg0,
+			arg1: 0,
+			arg2,
+			arg3: 0,
+			arg4: 0,
+			arg5: 0,
+		};
+
+		// write to fds above 2 fails, then write is allowed; open fails
+		// with EACCES and mmap with PROT_EXEC kills
+		let first = Filter {
+			default: Action::Allow,
+			rules: alloc::vec![
+				rule(1, Some((0, SECCOMP_CMP_GT, 2, 0)), Action::Errno(1)),
+				rule(1, None, Action::Allow),
+				rule(2, None, Action::Errno(13)),
+				rule(
+					9,
+					Some((2, SECCOMP_CMP_MASKED_EQ, 4, 4)),
+					Action::KillProcess
+				),
+			],
+		};
+		let mut seccomp = Seccomp {
+			mode: SECCOMP_MODE_FILTER,
+			filters: alloc::vec![Arc::new(first)],
+		};
+		let cases = [
+			(call(1, 1, 0), Action::Allow),
+			(call(1, 3, 0), Action::Errno(1)),
+			(call(2, 0, 0), Action::Errno(13)),
+			(call(9, 0, 3), Action::Allow),
+			(call(9, 0, 7), Action::KillProcess),
+			(call(60, 0, 0), Action::Allow),
+		];
+		if cases.iter()
+			.any(|(args, action)| seccomp.evaluate(args) != *action)
+		{
+			return Err(Error::EIO);
+		}
+
+		// A stacked filter can only make the result more restrictive
+		seccomp.filters.push(Arc::new(Filter {
+			default: Action::Log,
+			rules: alloc::vec![
+				rule(2, None, Action::Trap),
+				rule(60, None, Action::Allow)
+			],
+		}));
+		let cases = [
+			(call(2, 0, 0), Action::Trap),
+			(call(1, 3, 0), Action::Errno(1)),
+			(call(1, 1, 0), Action::Log),
+			(call(60, 0, 0), Action::Allow),
+		];
+		if cases.iter()
+			.any(|(args, action)| seccomp.evaluate(args) != *action)
+		{
+			return Err(Error::EIO);
+		}
+
+		// Errno data is capped like Linux, unknown actions are refused
+		if Action::from_ret(SECCOMP_RET_ERRNO | 5000)? != Action::Errno(4095)
+			|| Action::from_ret(0x0001_0000) != Err(Error::EINVAL)
+		{
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Seccomp Filter Matching".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Filter gave the wrong action".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test that the SIGSYS of a seccomp violation and the signals of user
+/// faults kill a process that blocks or ignores them
+fn test_forced_signals() -> TestResult {
+	use crate::signal::{SigAction, SigSet, SIGSEGV, SIGSYS, SIG_BLOCK, SIG_IGN};
+
+	let start = crate::time::get_time_ns();
+	let blocker = add_test_process("sigblock", 1000);
+	let ignorer = add_test_process("sigignore", 1000);
+	let previous = crate::process::current_process_pid();
+
+	let result = || -> Result<()> {
+		let mut set = SigSet::empty();
+		set.add(SIGSYS);
+		crate::process::set_current_process(Some(blocker));
+		crate::process::sigprocmask(SIG_BLOCK, Some(set))?;
+		crate::process::set_current_process(Some(ignorer));
+		let ignore = SigAction {
+			handler: SIG_IGN,
+			..Default::default()
+		};
+		crate::process::sigaction(SIGSEGV, Some(ignore))?;
+		crate::process::set_current_process(previous);
+
+		// Sent normally, a blocked signal only waits
+		crate::process::send_signal(blocker, SIGSYS)?;
+		let alive = |pid| crate::process::find_process(pid).is_some_and(|p| p.is_running());
+		if !alive(blocker) {
+			return Err(Error::EIO);
+		}
+
+		crate::process::force_sig(blocker, SIGSYS)?;
+		crate::process::force_sig(ignorer, SIGSEGV)?;
+		if alive(blocker) || alive(ignorer) {
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	crate::process::set_current_process(previous);
+	reap_test_process(blocker);
+	reap_test_process(ignorer);
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Forced Signals".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Blocked or ignored signal kept a forced kill away".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test namespaces and unsharing
+fn test_namespaces() -> Result<Vec<TestResult>> {
+	let mut results = Vec::new();
+
+	results.push(test_unshare());
+	results.push(test_setns());
+	results.push(test_orphan_adoption());
+
+	Ok(results)
+}
+
+/// Clone a process made by add_test_process into a child sharing the
+/// resources `flags` selects
+fn clone_test_process(parent: Pid, flags: u64) -> Result<Pid> {
+	use crate::process::clone_flags::CLONE_VM;
+	use crate::process::{clone_task, CloneArgs};
+
+	let tid = crate::process::find_process(parent)
+		.and_then(|p| p.main_thread().map(|t| t.tid))
+		.ok_or(Error::ESRCH)?;
+	let args = CloneArgs {
+		flags: flags | CLONE_VM | crate::signal::SIGCHLD as u64,
+		stack: 0,
+		parent_tid: 0,
+		child_tid: 0,
+		tls: 0,
+	};
+	Ok(clone_task(parent, tid, &args)?.0)
+}
+
+/// Kill a child made by clone_test_process and reap it
+fn reap_test_child(parent: Pid, child: Pid) {
+	let _ = crate::process::send_signal(child, crate::signal::SIGKILL);
+	let _ = crate::process::wait_child(parent, Some(child), true);
+}
+
+/// Test that unshare gives a process new namespaces and private copies of
+/// what it shared, and needs privilege for namespaces
+fn test_unshare() -> TestResult {
+	use crate::process::clone_flags::{CLONE_FS, CLONE_NEWNS, CLONE_NEWPID, CLONE_SIGHAND};
+	use crate::process::{find_process, unshare};
+	use crate::sync::Arc;
+
+	let start = crate::time::get_time_ns();
+	let parent = add_test_process("nsparent", 0);
+	let user = add_test_process("nsuser", 1000);
+	let mut child = None;
+
+	let result = || -> Result<()> {
+		if unshare(user, CLONE_NEWNS) != Err(Error::EPERM)
+			|| unshare(parent, CLONE_SIGHAND) != Err(Error::EINVAL)
+		{
+			return Err(Error::EIO);
+		}
+
+		// A new mount namespace takes the shared root and cwd with it
+		let pid = clone_test_process(parent, CLONE_FS)?;
+		child = Some(pid);
+		unshare(pid, CLONE_NEWNS)?;
+		let (old, new) = match (find_process(parent), find_process(pid)) {
+			(Some(old), Some(new)) => (old, new),
+			_ => return Err(Error::ESRCH),
+		};
+		if Arc::ptr_eq(&old.nsproxy.mnt_ns, &new.nsproxy.mnt_ns)
+			|| Arc::ptr_eq(&old.fs, &new.fs)
+		{
+			return Err(Error::EIO);
+		}
+
+		// A new PID namespace is for the children only, and only once
+		unshare(pid, CLONE_NEWPID)?;
+		let new = find_process(pid).ok_or(Error::ESRCH)?;
+		if new.pid_ns.id != old.pid_ns.id
+			|| new.nsproxy.pid_ns_for_children.id == old.pid_ns.id
+			|| unshare(pid, CLONE_NEWPID) != Err(Error::EINVAL)
+		{
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	if let Some(pid) = child {
+		reap_test_child(parent, pid);
+	}
+	reap_test_process(parent);
+	reap_test_process(user);
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Unshare".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Unshare shared state or namespaces it should not".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test joining the namespaces of another process with setns
+fn test_setns() -> TestResult {
+	use crate::process::clone_flags::{CLONE_FS, CLONE_NEWNS};
+	use crate::process::{find_process, setns, unshare, NsType};
+	use crate::sync::Arc;
+
+	let start = crate::time::get_time_ns();
+	let owner = add_test_process("nsowner", 0);
+	let joiner = add_test_process("nsjoiner", 0);
+	let user = add_test_process("nsuser", 1000);
+	let mut child = None;
+
+	let result = || -> Result<()> {
+		unshare(owner, CLONE_NEWNS)?;
+		if setns(user, owner, NsType::Mount) != Err(Error::EPERM) {
+			return Err(Error::EIO);
+		}
+
+		setns(joiner, owner, NsType::Mount)?;
+		setns(joiner, owner, NsType::Pid)?;
+		let (target, joined) = match (find_process(owner), find_process(joiner)) {
+			(Some(target), Some(joined)) => (target, joined),
+			_ => return Err(Error::ESRCH),
+		};
+		if !Arc::ptr_eq(&target.nsproxy.mnt_ns, &joined.nsproxy.mnt_ns)
+			|| joined.nsproxy.pid_ns_for_children.id != target.pid_ns.id
+		{
+			return Err(Error::EIO);
+		}
+
+		// Resetting a root and cwd shared with a child moves it too
+		child = Some(clone_test_process(joiner, CLONE_FS)?);
+		if setns(joiner, owner, NsType::Mount) != Err(Error::EINVAL) {
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	if let Some(pid) = child {
+		reap_test_child(joiner, pid);
+	}
+	reap_test_process(owner);
+	reap_test_process(joiner);
+	reap_test_process(user);
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Setns".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Setns joined the wrong namespaces or skipped a check".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test that init, the reaper of the root namespace, adopts the children
+/// of a process that exits
+fn test_orphan_adoption() -> TestResult {
+	use crate::process::{find_process, Process, Thread, INIT_PID, PROCESS_TABLE};
+	use crate::types::{Gid, Uid};
+
+	let start = crate::time::get_time_ns();
+
+	let parent = add_test_process("orphanparent", 0);
+	let child = crate::process::allocate_pid();
+	let mut process = Process::new(child, "orphanchild".into(), Uid(0), Gid(0));
+	process.parent = Some(parent);
+	process.add_thread(Thread::new(crate::process::allocate_tid(), child, 0));
+	PROCESS_TABLE.lock().add_process(process);
+
+	reap_test_process(parent);
+	let adopted = find_process(child).and_then(|p| p.parent) == Some(INIT_PID);
+
+	// A running init may reap it first
+	let _ = crate::process::send_signal(child, crate::signal::SIGKILL);
+	let _ = crate::process::wait_child(INIT_PID, Some(child), true);
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Orphan Adoption".to_string(),
+		passed: adopted,
+		message: if adopted {
+			"Passed".to_string()
+		} else {
+			"An orphan wasn't handed to init".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test terminal attributes and job control
+fn test_terminal() -> Result<Vec<TestResult>> {
+	let mut results = Vec::new();
+
+	results.push(test_tty_control_chars());
+	results.push(test_tty_background_access());
+
+	Ok(results)
+}
+
+/// Run `f` with a new session leader current and owning the serial
+/// terminal, restoring the terminal afterwards
+fn with_test_terminal(f: impl FnOnce(Pid) -> Result<()>) -> Result<()> {
+	use crate::tty::{self, SERIAL_TTY};
+
+	let leader = add_test_process("ttyleader", 0);
+	let previous = crate::process::set_current_process(Some(leader));
+	let result = tty::set_controlling_tty(SERIAL_TTY).and_then(|()| {
+		let saved = tty::termios(SERIAL_TTY)?;
+		let result = f(leader);
+		crate::process::set_current_process(Some(leader));
+		let restored = tty::set_termios(SERIAL_TTY, saved, true);
+		tty::release_controlling_tty()?;
+		result.and(restored)
+	});
+	crate::process::set_current_process(previous);
+	reap_test_process(leader);
+	result
+}
+
+/// Test that the control characters come from c_cc and obey ISIG
+fn test_tty_control_chars() -> TestResult {
+	use crate::tty::{self, ISIG, SERIAL_TTY, TOSTOP, VINTR};
+
+	let start = crate::time::get_time_ns();
+
+	let result = with_test_terminal(|_| {
+		let mut termios = tty::termios(SERIAL_TTY)?;
+		if termios.c_lflag & TOSTOP != 0 {
+			return Err(Error::EIO);
+		}
+
+		// Ctrl-C is plain input once VINTR is moved elsewhere
+		termios.c_cc[VINTR] = 0x07;
+		tty::set_termios(SERIAL_TTY, termios, true)?;
+		let mut buf = [0u8; 4];
+		if !tty::receive_char(SERIAL_TTY, 0x03) || tty::read(SERIAL_TTY, &mut buf)? != 1 {
+			return Err(Error::EIO);
+		}
+
+		// Without ISIG the new VINTR is plain input too
+		termios.c_lflag &= !ISIG;
+		tty::set_termios(SERIAL_TTY, termios, true)?;
+		if !tty::receive_char(SERIAL_TTY, 0x07) || tty::read(SERIAL_TTY, &mut buf)? != 1 {
+			return Err(Error::EIO);
+		}
+		if buf[0] != 0x07 || tty::termios(SERIAL_TTY)?.c_lflag & ISIG != 0 {
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	});
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Terminal Control Characters".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Control characters ignored the terminal attributes".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test that background access fails with EIO where stopping the caller
+/// would do no good, and that writes only stop with TOSTOP
+fn test_tty_background_access() -> TestResult {
+	use crate::process::{Process, Thread, PROCESS_TABLE};
+	use crate::signal::{SigSet, SIGKILL, SIGTTIN, SIGTTOU, SIG_BLOCK};
+	use crate::tty::{self, SERIAL_TTY, TOSTOP};
+	use crate::types::{Gid, Uid};
+
+	let start = crate::time::get_time_ns();
+
+	let result = with_test_terminal(|leader| {
+		// A background group of the session, in its own process group
+		let add_member = |name: &str, parent: Pid| {
+			let pid = crate::process::allocate_pid();
+			let mut process = Process::new(pid, name.into(), Uid(0), Gid(0));
+			process.parent = Some(parent);
+			process.sid = leader;
+			process.ctty = Some(SERIAL_TTY);
+			process.add_thread(Thread::new(crate::process::allocate_tid(), pid, 0));
+			PROCESS_TABLE.lock().add_process(process);
+			pid
+		};
+		let orphan = add_member("ttyorphan", Pid(0));
+		let child = add_member("ttychild", leader);
+
+		let result = || -> Result<()> {
+			if !crate::process::is_orphaned_pgrp(orphan)
+				|| crate::process::is_orphaned_pgrp(child)
+			{
+				return Err(Error::EIO);
+			}
+
+			// Nothing would continue an orphaned group, so no stop
+			let mut buf = [0u8; 4];
+			crate::process::set_current_process(Some(orphan));
+			if tty::read(SERIAL_TTY, &mut buf) != Err(Error::EIO) {
+				return Err(Error::EIO);
+			}
+			tty::write(SERIAL_TTY, b"")?;
+
+			crate::process::set_current_process(Some(leader));
+			let mut termios = tty::termios(SERIAL_TTY)?;
+			termios.c_lflag |= TOSTOP;
+			tty::set_termios(SERIAL_TTY, termios, false)?;
+			crate::process::set_current_process(Some(orphan));
+			if tty::write(SERIAL_TTY, b"") != Err(Error::EIO) {
+				return Err(Error::EIO);
+			}
+
+			// With both blocked, reads fail and writes go ahead
+			let mut set = SigSet::empty();
+			set.add(SIGTTIN);
+			set.add(SIGTTOU);
+			crate::process::set_current_process(Some(child));
+			crate::process::sigprocmask(SIG_BLOCK, Some(set))?;
+			if tty::read(SERIAL_TTY, &mut buf) != Err(Error::EIO) {
+				return Err(Error::EIO);
+			}
+			tty::write(SERIAL_TTY, b"")?;
+
+			Ok(())
+		}();
+
+		for (parent, pid) in [(Pid(0), orphan), (leader, child)] {
+			let _ = crate::process::send_signal(pid, SIGKILL);
+			let _ = crate::process::wait_child(parent, Some(pid), true);
+		}
+		result
+	});
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Background Terminal Access".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Background access was not stopped or refused as expected".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test the checks on state user space hands to the kernel
+fn test_user_abi() -> Result<Vec<TestResult>> {
+	let mut results = Vec::new();
+
+	results.push(test_ptrace_setregs());
+	results.push(test_ptrace_single_step());
+	results.push(test_ptrace_privileges());
+	results.push(test_core_file_mode());
+	results.push(test_user_fault_core());
+	results.push(test_stack_growth());
+	results.push(test_syscall_errno());
+	results.push(test_clone_without_vm());
+	results.push(test_vdso_clock_gettime());
+	results.push(test_exec_arg_limit());
+
+	Ok(results)
+}
+
+/// Test that PTRACE_SETREGS cannot raise privilege or load bad addresses
+fn test_ptrace_setregs() -> TestResult {
+	use crate::arch::x86_64::context::{Context, UserRegs};
+	use crate::usermode::{USER_CS, USER_DS};
+
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		let mut ctx = Context::new_user(0x40_0000, 0x7fff_0000, 0);
+		let mut regs = ctx.user_regs(u64::MAX, 0);
+
+		// The tracer owns TF and the arithmetic flags, not IOPL or IF
+		regs.eflags = (3 << 12) | (1 << 8) | 1;
+		regs.cs = 0x08;
+		regs.ss = 0x10;
+		regs.ds = 0x10;
+		ctx.set_user_regs(&regs)?;
+		if ctx.rflags & (3 << 12) != 0
+			|| ctx.rflags & (1 << 9) == 0
+			|| ctx.rflags & ((1 << 8) | 1) != (1 << 8) | 1
+			|| ctx.cs != USER_CS
+			|| ctx.ss != USER_DS
+			|| ctx.ds != USER_DS
+		{
+			return Err(Error::EIO);
+		}
+
+		// Non-canonical rip or fs_base is refused and changes nothing
+		let saved = ctx.rip;
+		let bad = UserRegs {
+			rip: 0x8000_0000_0000_0000,
+			..regs
+		};
+		let bad_fs = UserRegs {
+			fs_base: 0x0000_8000_0000_0000,
+			..regs
+		};
+		if ctx.set_user_regs(&bad) != Err(Error::EIO)
+			|| ctx.set_user_regs(&bad_fs) != Err(Error::EIO)
+			|| ctx.rip != saved
+		{
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Ptrace Register Sanitizing".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"PTRACE_SETREGS accepted unsafe registers".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Tracer and tracee of test_ptrace_single_step, for the work item
+/// playing the tracer
+static STEP_PIDS: [AtomicU32; 2] = [AtomicU32::new(0), AtomicU32::new(0)];
+static STEP_STATUS: AtomicI32 = AtomicI32::new(0);
+static STEP_DONE: AtomicBool = AtomicBool::new(false);
+static STEP_WORK: Work = Work::new(resume_single_step, 0);
+
+/// Tracer side of test_ptrace_single_step: collect the stop of the tracee
+/// and continue it
+fn resume_single_step(_: usize) {
+	use crate::ptrace::{ptrace, wait_tracee, PTRACE_CONT};
+
+	let tracer = Pid(STEP_PIDS[0].load(Ordering::Acquire));
+	let tracee = Pid(STEP_PIDS[1].load(Ordering::Acquire));
+	while !STEP_DONE.load(Ordering::Acquire) {
+		if let Some((pid, status)) = wait_tracee(tracer, Some(tracee), true) {
+			if pid == tracee {
+				STEP_STATUS.store(status, Ordering::Release);
+				if let Some(process) = crate::process::find_process(tracer) {
+					let _ = ptrace(&process, PTRACE_CONT, tracee, 0, 0);
+				}
+				return;
+			}
+		}
+		crate::scheduler::yield_now();
+		core::hint::spin_loop();
+	}
+}
+
+/// Test that a tracee resumed with PTRACE_SINGLESTEP stops at the debug
+/// exception that follows, and runs on without the trap flag once its
+/// tracer continues it
+fn test_ptrace_single_step() -> TestResult {
+	use crate::arch::x86_64::idt::{exception_handler, ExceptionContext};
+	use crate::ptrace::{ptrace, wait_tracee, PTRACE_ATTACH, PTRACE_SINGLESTEP};
+	use crate::usermode::{USER_CS, USER_DS};
+
+	const X86_EFLAGS_TF: u64 = 1 << 8;
+	let start = crate::time::get_time_ns();
+	let tracer = add_test_process("steptracer", 1000);
+	let tracee = add_test_process("steptracee", 1000);
+	let previous = crate::process::current_process_pid();
+
+	let result = || -> Result<()> {
+		let process = crate::process::find_process(tracer).ok_or(Error::ESRCH)?;
+		ptrace(&process, PTRACE_ATTACH, tracee, 0, 0)?;
+		wait_tracee(tracer, Some(tracee), true);
+		ptrace(&process, PTRACE_SINGLESTEP, tracee, 0, 0)?;
+		let rflags =
+			crate::process::with_thread(tracee, None, |thread| thread.context.rflags)?;
+		if rflags & X86_EFLAGS_TF == 0 {
+			return Err(Error::EIO);
+		}
+
+		// The debug exception after one instruction, as the entry stub
+		// saves it
+		let mut ctx = ExceptionContext {
+			vector: 1,
+			rip: 0x40_1000,
+			cs: USER_CS as u64,
+			eflags: rflags,
+			rsp: 0x7fff_0000,
+			ss: USER_DS as u64,
+			..Default::default()
+		};
+		STEP_PIDS[0].store(tracer.0, Ordering::Release);
+		STEP_PIDS[1].store(tracee.0, Ordering::Release);
+		STEP_STATUS.store(0, Ordering::Release);
+		STEP_DONE.store(false, Ordering::Release);
+		if !crate::workqueue::schedule_work(&STEP_WORK) {
+			return Err(Error::WouldBlock);
+		}
+		crate::process::set_current_process(Some(tracee));
+		let resumed = unsafe { exception_handler(&mut ctx) };
+		crate::process::set_current_process(previous);
+		STEP_DONE.store(true, Ordering::Release);
+		crate::workqueue::flush_work(&STEP_WORK);
+
+		let stopped = (crate::signal::SIGTRAP << 8) | 0x7f;
+		if !resumed
+			|| STEP_STATUS.load(Ordering::Acquire) != stopped
+			|| ctx.eflags & X86_EFLAGS_TF != 0
+			|| ctx.rip != 0x40_1000
+		{
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	crate::process::set_current_process(previous);
+	reap_test_process(tracer);
+	reap_test_process(tracee);
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Ptrace Single Step".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Single-stepped tracee did not stop at the debug exception".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test that only a tracer with CAP_SYS_PTRACE attaches to a process that
+/// changed credentials, and that exec by a process traced without it
+/// ignores set-user-ID bits
+fn test_ptrace_privileges() -> TestResult {
+	use crate::ptrace::{ptrace, unsafe_exec, PTRACE_ATTACH};
+	use crate::types::Uid;
+
+	let start = crate::time::get_time_ns();
+	let tracer = add_test_process("privtracer", 1000);
+	let root_tracer = add_test_process("roottracer", 0);
+	let target = add_test_process("privtarget", 1000);
+	let other = add_test_process("privother", 1000);
+
+	let result = || -> Result<()> {
+		let tracer = crate::process::find_process(tracer).ok_or(Error::ESRCH)?;
+		let root_tracer = crate::process::find_process(root_tracer).ok_or(Error::ESRCH)?;
+
+		// setfsuid makes the target non-dumpable, its IDs still match
+		crate::process::update_cred(target, |cred| {
+			cred.fsuid = Uid(0);
+			Ok(())
+		})?;
+		if ptrace(&tracer, PTRACE_ATTACH, target, 0, 0) != Err(Error::EPERM) {
+			return Err(Error::EIO);
+		}
+		ptrace(&root_tracer, PTRACE_ATTACH, target, 0, 0)?;
+		if unsafe_exec(target) {
+			return Err(Error::EIO);
+		}
+
+		ptrace(&tracer, PTRACE_ATTACH, other, 0, 0)?;
+		if !unsafe_exec(other) {
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	reap_test_process(tracer);
+	reap_test_process(root_tracer);
+	reap_test_process(target);
+	reap_test_process(other);
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Ptrace Privileges".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Unprivileged tracer attached to or kept a privileged process".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test that core files are private to their owner and that processes
+/// with changed credentials are not dumped
+fn test_core_file_mode() -> TestResult {
+	use crate::coredump::{do_coredump, CORE_DIR, CORE_FILE_MODE};
+	use crate::cred::Credentials;
+	use crate::memfs::FileMode;
+	use crate::process::Process;
+	use crate::resource::{Rlimit, RLIMIT_CORE};
+	use crate::types::{Gid, Uid};
+
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		let mut process =
+			Process::new(Pid(0x7fff_0000), "coretest".into(), Uid(1000), Gid(1000));
+		let root = Credentials::root();
+		process.rlimits
+			.set(RLIMIT_CORE, Rlimit::new(1 << 20, 1 << 20), &root)?;
+		let path = alloc::format!("{}/coretest.{}", CORE_DIR, process.pid);
+
+		let written = do_coredump(&process, crate::signal::SIGSEGV)?;
+		let stat = crate::memfs::fs_stat(&path);
+		let owner = crate::memfs::fs_owner(&path);
+		let _ = crate::memfs::fs_remove(&path);
+		if !written || stat?.1 != FileMode::new(CORE_FILE_MODE) || owner? != (1000, 1000) {
+			return Err(Error::EIO);
+		}
+
+		// A set-user-ID process is not dumped
+		process.dumpable = false;
+		if do_coredump(&process, crate::signal::SIGSEGV)?
+			|| crate::memfs::fs_stat(&path).is_ok()
+		{
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Core File Mode".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Core file readable by others or dumped without permission".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test that a general protection fault in user mode kills the process
+/// with SIGSEGV and dumps its core, instead of panicking the kernel
+fn test_user_fault_core() -> TestResult {
+	use crate::arch::x86_64::idt::{exception_handler, ExceptionContext};
+	use crate::coredump::CORE_DIR;
+	use crate::resource::{Rlimit, RLIMIT_CORE, RLIM_INFINITY};
+	use crate::signal::SIGSEGV;
+	use crate::usermode::{USER_CS, USER_DS};
+
+	let start = crate::time::get_time_ns();
+	let pid = add_test_process("faulttest", 1000);
+	let path = alloc::format!("{}/faulttest.{}", CORE_DIR, pid);
+	let previous = crate::process::current_process_pid();
+
+	let result = || -> Result<()> {
+		let process = crate::process::find_process(pid).ok_or(Error::ESRCH)?;
+		let limit = Rlimit::new(1 << 20, RLIM_INFINITY);
+		crate::process::prlimit(&process, pid, RLIMIT_CORE, Some(limit))?;
+
+		let mut ctx = ExceptionContext {
+			vector: 13,
+			rip: 0x40_1000,
+			cs: USER_CS as u64,
+			eflags: 0x202,
+			rsp: 0x7fff_0000,
+			ss: USER_DS as u64,
+			..Default::default()
+		};
+		crate::process::set_current_process(Some(pid));
+		let resumed = unsafe { exception_handler(&mut ctx) };
+		crate::process::set_current_process(previous);
+		crate::workqueue::flush_scheduled_work();
+
+		let status = crate::process::find_process(pid).map(|process| process.wait_status);
+		if resumed
+			|| status != Some(SIGSEGV | 0x80)
+			|| crate::memfs::fs_stat(&path).is_err()
+		{
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	crate::process::set_current_process(previous);
+	let _ = crate::memfs::fs_remove(&path);
+	reap_test_process(pid);
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "User Fault Core Dump".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"User fault did not kill the process with a core dump".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test that a user page fault just below the stack grows the stack down
+/// to the faulting page and resumes the process
+fn test_stack_growth() -> TestResult {
+	use crate::arch::x86_64::idt::{exception_handler, ExceptionContext};
+	use crate::memory::{VirtAddr, VmaArea, PROT_READ, PROT_WRITE};
+	use crate::usermode::{USER_CS, USER_DS, USER_STACK_TOP};
+
+	let start = crate::time::get_time_ns();
+	let pid = add_test_process("stacktest", 1000);
+	let previous = crate::process::current_process_pid();
+
+	let result = || -> Result<()> {
+		let top = VirtAddr::new(USER_STACK_TOP as usize);
+		let stack = VmaArea::new(
+			VirtAddr::new(USER_STACK_TOP as usize - 4096),
+			top,
+			PROT_READ | PROT_WRITE,
+		);
+		crate::process::add_mapping(pid, stack);
+		let total_vm = crate::process::find_process(pid).map_or(0, |p| p.total_vm);
+
+		// A write two pages below the stack, with the fault address
+		// where the CPU leaves it
+		let fault_addr = USER_STACK_TOP - 3 * 4096 + 8;
+		unsafe {
+			core::arch::asm!("mov cr2, {}", in(reg) fault_addr);
+		}
+		let mut ctx = ExceptionContext {
+			vector: 14,
+			error_code: 0x6, // Not present, write, user mode
+			rip: 0x40_1000,
+			cs: USER_CS as u64,
+			eflags: 0x202,
+			rsp: fault_addr,
+			ss: USER_DS as u64,
+			..Default::default()
+		};
+		crate::process::set_current_process(Some(pid));
+		let resumed = unsafe { exception_handler(&mut ctx) };
+		crate::process::set_current_process(previous);
+
+		let process = crate::process::find_process(pid).ok_or(Error::ESRCH)?;
+		let grown = process
+			.mappings
+			.iter()
+			.find(|vma| vma.vm_end == top)
+			.map(|vma| vma.vm_start.as_usize() as u64);
+		if !resumed
+			|| grown != Some(USER_STACK_TOP - 3 * 4096)
+			|| process.total_vm != total_vm + 2 * 4096
+		{
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	crate::process::set_current_process(previous);
+	reap_test_process(pid);
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "User Stack Growth".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Fault below the stack did not grow it".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test that the vDSO's clock_gettime reads the clocks of the system call
+fn test_vdso_clock_gettime() -> TestResult {
+	use crate::syscalls::clock_ns;
+	use crate::time::{CLOCK_MONOTONIC, CLOCK_REALTIME, NSEC_PER_JIFFY};
+
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		for clock in [CLOCK_MONOTONIC, CLOCK_REALTIME] {
+			let before = clock_ns(clock)?;
+			let vdso = crate::vdso::clock_gettime_copy(clock)?;
+			let after = clock_ns(clock)?;
+			// Without the TSC it reads the clock of the last tick
+			if vdso + NSEC_PER_JIFFY < before || vdso > after {
+				return Err(Error::EIO);
+			}
+		}
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "vDSO clock_gettime".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"The vDSO clock disagrees with clock_gettime".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test that clone refuses a child with its own address space, which can't
+/// be made yet, and still makes one sharing it
+fn test_clone_without_vm() -> TestResult {
+	use crate::process::clone_flags::CLONE_FS;
+	use crate::process::{clone_task, CloneArgs};
+
+	let start = crate::time::get_time_ns();
+	let parent = add_test_process("cloneparent", 0);
+	let mut child = None;
+
+	let result = || -> Result<()> {
+		let tid = crate::process::find_process(parent)
+			.and_then(|p| p.main_thread().map(|t| t.tid))
+			.ok_or(Error::ESRCH)?;
+		let args = CloneArgs {
+			flags: crate::signal::SIGCHLD as u64,
+			stack: 0,
+			parent_tid: 0,
+			child_tid: 0,
+			tls: 0,
+		};
+		if clone_task(parent, tid, &args) != Err(Error::ENOSYS) {
+			return Err(Error::EIO);
+		}
+
+		let pid = clone_test_process(parent, CLONE_FS)?;
+		child = Some(pid);
+		if crate::process::find_process(pid).and_then(|p| p.parent) != Some(parent) {
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	if let Some(pid) = child {
+		reap_test_child(parent, pid);
+	}
+	reap_test_process(parent);
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Clone Without CLONE_VM".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Clone made a copied address space it can't provide".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test that failed system calls return negative errnos, as the C library
+/// expects
+fn test_syscall_errno() -> TestResult {
+	use crate::syscalls::{handle_syscall, SyscallArgs};
+
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		let unknown = SyscallArgs {
+			syscall_num: 9999,
+			arg0: 0,
+			arg1: 0,
+			arg2: 0,
+			arg3: 0,
+			arg4: 0,
+			arg5: 0,
+		};
+		if handle_syscall(unknown) != -38i64 as u64
+			|| Error::EBADF.to_syscall_ret() != -9i64 as u64
+			|| Error::E2BIG.to_syscall_ret() != -7i64 as u64
+		{
+			return Err(Error::EIO);
+		}
+		// A zero-length getrandom doesn't look at its buffer
+		if crate::syscalls::sys_getrandom(0, 0, crate::syscalls::GRND_INSECURE) != Ok(0) {
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Syscall Errno Sign".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Syscall error returned as a positive value".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Test that execve stops copying arguments once they can't fit
+fn test_exec_arg_limit() -> TestResult {
+	use crate::syscalls::user_strings;
+	use crate::usermode::{arg_max, MAX_ARG_STRLEN};
+
+	let start = crate::time::get_time_ns();
+
+	let result = || -> Result<()> {
+		// Three strings of 60000 bytes against the 128 KiB minimum
+		let mut string = alloc::vec![b'a'; 60000];
+		string.push(0);
+		let ptr = string.as_ptr() as u64;
+		let argv = [ptr, ptr, ptr, 0];
+		if arg_max(0) != MAX_ARG_STRLEN as u64 {
+			return Err(Error::EIO);
+		}
+
+		let mut room = arg_max(0);
+		if user_strings(argv.as_ptr() as u64, &mut room).err() != Some(Error::E2BIG) {
+			return Err(Error::EIO);
+		}
+		// Two fit, and what they used is gone for envp
+		let argv = [ptr, ptr, 0];
+		let mut room = arg_max(0);
+		if user_strings(argv.as_ptr() as u64, &mut room)?.len() != 2
+			|| room != MAX_ARG_STRLEN as u64 - 2 * 60009
+		{
+			return Err(Error::EIO);
+		}
+
+		Ok(())
+	}();
+
+	let end = crate::time::get_time_ns();
+	let duration = (end - start) / 1_000_000;
+
+	TestResult {
+		name: "Exec Argument Limit".to_string(),
+		passed: result.is_ok(),
+		message: if result.is_ok() {
+			"Passed".to_string()
+		} else {
+			"Oversized argv was not refused with E2BIG".to_string()
+		},
+		duration_ms: duration,
+	}
+}
+
+/// Add a process for a test to the process table, a child of the kernel
+/// process so reap_test_process can remove it again
+fn add_test_process(name: &str, uid: u32) -> Pid {
+	use crate::process::{Process, Thread, PROCESS_TABLE};
+	use crate::types::{Gid, Uid};
+
+	let pid = crate::process::allocate_pid();
+	let mut process = Process::new(pid, name.into(), Uid(uid), Gid(uid));
+	process.parent = Some(Pid(0));
+	process.add_thread(Thread::new(crate::process::allocate_tid(), pid, 0));
+	PROCESS_TABLE.lock().add_process(process);
+	pid
+}
+
+/// Kill a process made by add_test_process and remove it
+fn reap_test_process(pid: Pid) {
+	let _ = crate::process::send_signal(pid, crate::signal::SIGKILL);
+	let _ = crate::process::wait_child(Pid(0), Some(pid), true);
+}
+
 /// Test task function for task creation test
 fn test_task_function() {
 	// Simple test task that does nothing
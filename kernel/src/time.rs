@@ -14,6 +14,16 @@ pub const HZ: u64 = 1000;
 /// Nanoseconds per second
 pub const NSEC_PER_SEC: u64 = 1_000_000_000;
 
+/// POSIX clock IDs
+pub const CLOCK_REALTIME: i32 = 0;
+pub const CLOCK_MONOTONIC: i32 = 1;
+pub const CLOCK_PROCESS_CPUTIME_ID: i32 = 2;
+pub const CLOCK_THREAD_CPUTIME_ID: i32 = 3;
+pub const CLOCK_MONOTONIC_RAW: i32 = 4;
+pub const CLOCK_REALTIME_COARSE: i32 = 5;
+pub const CLOCK_MONOTONIC_COARSE: i32 = 6;
+pub const CLOCK_BOOTTIME: i32 = 7;
+
 /// Nanoseconds per millisecond
 pub const NSEC_PER_MSEC: u64 = 1_000_000;
 
@@ -106,13 +116,35 @@ impl HrTimer {
 	}
 
 	pub fn is_expired(&self) -> bool {
-		let now = match self.base {
-			HrTimerBase::Monotonic => monotonic_time(),
-			HrTimerBase::Realtime => get_realtime(),
-			HrTimerBase::Boottime => get_boottime(),
-			HrTimerBase::Tai => get_realtime(), // Simplified
-		};
-		now >= self.expires
+		clock_now(self.base) >= self.expires
+	}
+
+	/// Nanoseconds until the timer expires, 0 if it already has
+	pub fn remaining_ns(&self) -> u64 {
+		self.expires
+			.to_ns()
+			.saturating_sub(clock_now(self.base).to_ns())
+	}
+}
+
+/// Current time of a timer base
+pub fn clock_now(base: HrTimerBase) -> TimeSpec {
+	match base {
+		// The clock of clock_gettime(CLOCK_MONOTONIC)
+		HrTimerBase::Monotonic => ktime_get(),
+		HrTimerBase::Realtime => get_realtime(),
+		HrTimerBase::Boottime => get_boottime(),
+		HrTimerBase::Tai => get_realtime(), // Simplified
+	}
+}
+
+/// Timer base of a POSIX clock that timers can be set on
+pub fn clock_base(clock: i32) -> Option<HrTimerBase> {
+	match clock {
+		CLOCK_REALTIME => Some(HrTimerBase::Realtime),
+		// Nothing suspends, so boot time runs with the monotonic clock
+		CLOCK_MONOTONIC | CLOCK_BOOTTIME => Some(HrTimerBase::Monotonic),
+		_ => None,
 	}
 }
 
@@ -141,6 +173,10 @@ fn init_high_res_timers() -> Result<()> {
 	// Store initial TSC value for relative timing
 	BOOT_TSC.store(read_tsc(), Ordering::Relaxed);
 
+	// Let the vDSO read the clock from the TSC too
+	crate::vdso::set_tsc_frequency(tsc_freq);
+	update_vdso();
+
 	crate::info!(
 		"High-resolution timers initialized, TSC frequency: {} Hz",
 		tsc_freq
@@ -219,34 +255,89 @@ pub fn init_time() -> Result<()> {
 	Ok(())
 }
 
-/// Read hardware clock implementation
-fn read_hardware_clock() -> u64 {
-	// Read from CMOS RTC (Real Time Clock)
-	// This is a simplified implementation
+/// CMOS RTC registers
+const RTC_SECONDS: u8 = 0x00;
+const RTC_MINUTES: u8 = 0x02;
+const RTC_HOURS: u8 = 0x04;
+const RTC_DAY_OF_MONTH: u8 = 0x07;
+const RTC_MONTH: u8 = 0x08;
+const RTC_YEAR: u8 = 0x09;
+const RTC_STATUS_A: u8 = 0x0A;
+const RTC_STATUS_B: u8 = 0x0B;
+
+/// Status register bits
+const RTC_UIP: u8 = 0x80; // Update in progress
+const RTC_24H: u8 = 0x02;
+const RTC_DM_BINARY: u8 = 0x04;
+
+/// Read a CMOS register
+fn cmos_read(reg: u8) -> u8 {
 	unsafe {
-		// Disable NMI and read seconds
-		crate::arch::x86_64::port::outb(0x70, 0x00);
-		let seconds = crate::arch::x86_64::port::inb(0x71);
-
-		// Read minutes
-		crate::arch::x86_64::port::outb(0x70, 0x02);
-		let minutes = crate::arch::x86_64::port::inb(0x71);
-
-		// Read hours
-		crate::arch::x86_64::port::outb(0x70, 0x04);
-		let hours = crate::arch::x86_64::port::inb(0x71);
-
-		// Convert BCD to binary if needed (simplified)
-		let sec = bcd_to_bin(seconds);
-		let min = bcd_to_bin(minutes);
-		let hr = bcd_to_bin(hours);
-
-		// Convert to nanoseconds since epoch (simplified calculation)
-		let total_seconds = (hr as u64 * 3600) + (min as u64 * 60) + sec as u64;
-		total_seconds * 1_000_000_000 // Convert to nanoseconds
+		crate::arch::x86_64::port::outb(0x70, reg);
+		crate::arch::x86_64::port::inb(0x71)
 	}
 }
 
+/// Days from 1970-01-01 to a civil date in the proleptic Gregorian
+/// calendar
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+	let year = if month <= 2 { year - 1 } else { year };
+	let era = year / 400;
+	let yoe = year - era * 400;
+	let mp = (month + 9) % 12;
+	let doy = (153 * mp + 2) / 5 + day - 1;
+	let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+	era * 146_097 + doe - 719_468
+}
+
+/// Read the wall-clock time from the CMOS RTC in nanoseconds since the
+/// Unix epoch
+fn read_hardware_clock() -> u64 {
+	// Wait for any update in progress, then read until two reads agree
+	let read_all = || {
+		while cmos_read(RTC_STATUS_A) & RTC_UIP != 0 {
+			core::hint::spin_loop();
+		}
+		[
+			cmos_read(RTC_SECONDS),
+			cmos_read(RTC_MINUTES),
+			cmos_read(RTC_HOURS),
+			cmos_read(RTC_DAY_OF_MONTH),
+			cmos_read(RTC_MONTH),
+			cmos_read(RTC_YEAR),
+		]
+	};
+	let mut regs = read_all();
+	loop {
+		let again = read_all();
+		if again == regs {
+			break;
+		}
+		regs = again;
+	}
+
+	let status = cmos_read(RTC_STATUS_B);
+	let pm = regs[2] & 0x80 != 0;
+	regs[2] &= 0x7F;
+	if status & RTC_DM_BINARY == 0 {
+		for reg in regs.iter_mut() {
+			*reg = bcd_to_bin(*reg);
+		}
+	}
+	if status & RTC_24H == 0 {
+		regs[2] %= 12;
+		if pm {
+			regs[2] += 12;
+		}
+	}
+
+	let [sec, min, hour, day, month, year] = regs.map(|r| r as u64);
+	// The RTC century register is not standardized, assume 20xx
+	let days = days_from_civil(2000 + year, month.clamp(1, 12), day.max(1));
+	let seconds = days * 86_400 + hour * 3600 + min * 60 + sec;
+	seconds * NSEC_PER_SEC
+}
+
 /// Convert BCD to binary
 fn bcd_to_bin(bcd: u8) -> u8 {
 	((bcd >> 4) * 10) + (bcd & 0x0F)
@@ -260,17 +351,35 @@ pub fn get_jiffies() -> Jiffies {
 /// Increment jiffies counter (called from timer interrupt)
 pub fn update_jiffies() {
 	JIFFIES_COUNTER.fetch_add(1, Ordering::Relaxed);
+	update_vdso();
+}
+
+/// Account ticks skipped while the periodic tick was stopped
+pub fn catch_up_jiffies(ticks: u64) {
+	JIFFIES_COUNTER.fetch_add(ticks, Ordering::Relaxed);
+	update_vdso();
+}
+
+/// Publish the current clock to the vDSO data page
+fn update_vdso() {
+	let tsc = read_tsc();
+	crate::vdso::update(tsc, tsc_to_ns(tsc), BOOTTIME_NS.load(Ordering::Relaxed));
 }
 
 /// Get current time in nanoseconds since boot
 pub fn get_time_ns() -> u64 {
+	tsc_to_ns(read_tsc())
+}
+
+/// Convert a TSC value to nanoseconds since boot
+fn tsc_to_ns(tsc: u64) -> u64 {
 	// Use TSC for high-resolution timing
 	let tsc_freq = TSC_FREQUENCY.load(Ordering::Relaxed);
 	if tsc_freq > 0 {
-		let tsc = read_tsc();
 		let boot_tsc = BOOT_TSC.load(Ordering::Relaxed);
 		if tsc >= boot_tsc {
-			((tsc - boot_tsc) * 1_000_000_000) / tsc_freq
+			// Widened so the product can't overflow within seconds
+			((tsc - boot_tsc) as u128 * NSEC_PER_SEC as u128 / tsc_freq as u128) as u64
 		} else {
 			// Handle TSC overflow (rare)
 			get_jiffies().0 * NSEC_PER_JIFFY
@@ -319,6 +428,11 @@ pub fn get_boottime() -> TimeSpec {
 	TimeSpec::from_ns(boot_ns + current_ns)
 }
 
+/// Wall-clock time of boot in seconds since the Unix epoch
+pub fn boot_time_secs() -> u64 {
+	BOOTTIME_NS.load(Ordering::Relaxed) / NSEC_PER_SEC
+}
+
 /// Get real time (wall clock time)
 pub fn get_realtime() -> TimeSpec {
 	get_boottime()
@@ -438,21 +552,38 @@ impl TimerWheel {
 	}
 
 	pub fn run_timers(&mut self) {
+		for timer in self.expire_timers() {
+			if let Some(function) = timer.function {
+				function();
+			}
+		}
+	}
+
+	/// Remove and return the expired timers
+	pub fn expire_timers(&mut self) -> Vec<HrTimer> {
 		self.current_jiffies = get_jiffies().0;
 
-		// Check and run expired timers
+		let mut expired = Vec::new();
 		for level in &mut self.levels {
 			level.retain(|timer| {
 				if timer.is_expired() {
-					if let Some(function) = timer.function {
-						function();
-					}
-					false // Remove expired timer
+					expired.push(timer.clone());
+					false
 				} else {
-					true // Keep timer
+					true
 				}
 			});
 		}
+		expired
+	}
+
+	/// Nanoseconds until the earliest timer expires
+	pub fn next_expiry_ns(&self) -> Option<u64> {
+		self.levels
+			.iter()
+			.flatten()
+			.map(|timer| timer.remaining_ns())
+			.min()
 	}
 }
 
@@ -487,11 +618,20 @@ pub fn add_timer(timer: HrTimer) {
 	wheel.add_timer(timer);
 }
 
-/// Run expired timers (called from timer interrupt)
+/// Run expired timers (called from timer interrupt). The functions run
+/// without the wheel locked, so they can add timers.
 pub fn run_timers() {
-	let timer_wheel = get_timer_wheel();
-	let mut wheel = timer_wheel.lock();
-	wheel.run_timers();
+	let expired = get_timer_wheel().lock().expire_timers();
+	for timer in expired {
+		if let Some(function) = timer.function {
+			function();
+		}
+	}
+}
+
+/// Nanoseconds until the next timer on the wheel expires
+pub fn next_timer_expiry_ns() -> Option<u64> {
+	get_timer_wheel().lock().next_expiry_ns()
 }
 
 /// Timer interrupt handler
@@ -6,12 +6,18 @@ use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
 use crate::enhanced_scheduler;
 use crate::sync::Spinlock;
-use crate::time::get_jiffies;
+use crate::time::{get_jiffies, NSEC_PER_JIFFY};
 use crate::types::Jiffies;
 
 /// Timer frequency (Hz) - how often timer interrupt fires
 const TIMER_FREQUENCY: u64 = 1000; // 1000 Hz = 1ms intervals
 
+/// PIT input clock (Hz)
+const PIT_FREQUENCY: u64 = 1193182;
+
+/// PIT counts per timer tick
+const PIT_COUNTS_PER_TICK: u64 = PIT_FREQUENCY / TIMER_FREQUENCY;
+
 /// Scheduler quantum (time slice) in timer ticks
 const SCHEDULER_QUANTUM: u64 = 10; // 10ms default quantum
 
@@ -22,6 +28,10 @@ pub struct TimerStats {
 	pub scheduler_invocations: u64,
 	pub context_switches: u64,
 	pub last_update: Jiffies,
+	pub idle_calls: u64,    // Times the CPU went idle
+	pub idle_sleeps: u64,   // Idle periods with the tick stopped
+	pub idle_time_ns: u64,  // Time spent halted
+	pub ticks_skipped: u64, // Ticks not taken while stopped
 }
 
 /// Global timer state
@@ -43,6 +53,10 @@ impl TimerState {
 				scheduler_invocations: 0,
 				context_switches: 0,
 				last_update: Jiffies(0),
+				idle_calls: 0,
+				idle_sleeps: 0,
+				idle_time_ns: 0,
+				ticks_skipped: 0,
 			}),
 		}
 	}
@@ -72,6 +86,21 @@ impl TimerState {
 
 		// Update current task runtime
 		enhanced_scheduler::update_current_task_runtime(1);
+
+		// Charge CPU time and enforce RLIMIT_CPU
+		crate::process::account_tick();
+
+		// Charge task groups and enforce their CPU bandwidth
+		crate::scheduler::task_tick_fair(NSEC_PER_JIFFY);
+
+		// Sample the run queue for the load average
+		crate::loadavg::calc_global_load();
+
+		// A tick is a quiescent state for RCU
+		crate::rcu::rcu_note_quiescent_state();
+
+		// Expire timer wheel timers once the interrupt is done
+		crate::softirq::raise_softirq(crate::softirq::TIMER_SOFTIRQ);
 	}
 
 	/// Invoke the scheduler for preemptive multitasking
@@ -130,6 +159,10 @@ impl TimerState {
 		stats.scheduler_invocations = 0;
 		stats.context_switches = 0;
 		stats.last_update = get_jiffies();
+		stats.idle_calls = 0;
+		stats.idle_sleeps = 0;
+		stats.idle_time_ns = 0;
+		stats.ticks_skipped = 0;
 	}
 
 	/// Check if preemption is enabled
@@ -171,6 +204,7 @@ pub fn init_timer() -> crate::error::Result<()> {
 
 /// Timer interrupt handler (called from IDT)
 pub extern "C" fn timer_interrupt_handler() {
+	crate::time::update_jiffies();
 	TIMER_STATE.handle_timer_interrupt();
 	increment_timer_interrupts();
 
@@ -184,9 +218,6 @@ pub extern "C" fn timer_interrupt_handler() {
 fn init_pit(frequency: u64) -> crate::error::Result<()> {
 	use crate::arch::x86_64::port::Port;
 
-	// PIT frequency is 1.193182 MHz
-	const PIT_FREQUENCY: u64 = 1193182;
-
 	// Calculate divisor for desired frequency
 	let divisor = PIT_FREQUENCY / frequency;
 	if divisor > 65535 {
@@ -209,6 +240,144 @@ fn init_pit(frequency: u64) -> crate::error::Result<()> {
 	Ok(())
 }
 
+/// Stop the periodic tick while idle
+static NOHZ_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Set while the periodic tick is stopped
+static TICK_STOPPED: AtomicBool = AtomicBool::new(false);
+
+/// PIT counts programmed for the one-shot wakeup
+static ONESHOT_COUNTS: AtomicU64 = AtomicU64::new(0);
+
+/// PIT counts elapsed beyond the whole ticks caught up so far
+static PARTIAL_COUNTS: AtomicU64 = AtomicU64::new(0);
+
+/// Program PIT channel 0 to interrupt once after `counts`
+fn program_oneshot(counts: u16) {
+	use crate::arch::x86_64::port::outb;
+
+	unsafe {
+		// Channel 0, lobyte/hibyte, mode 0 (interrupt on terminal count)
+		outb(0x43, 0x30);
+		outb(0x40, (counts & 0xFF) as u8);
+		outb(0x40, (counts >> 8) as u8);
+	}
+}
+
+/// Read whether the one-shot count has run out, and the counts left
+fn read_oneshot() -> (bool, u16) {
+	use crate::arch::x86_64::port::{inb, outb};
+
+	unsafe {
+		// Read-back command: latch status and count of channel 0
+		outb(0x43, 0xC2);
+		let status = inb(0x40);
+		let low = inb(0x40) as u16;
+		let high = inb(0x40) as u16;
+		(status & 0x80 != 0, (high << 8) | low)
+	}
+}
+
+/// Stop the periodic tick and program a one-shot timer for the next
+/// timer wheel expiry or sleeper deadline. Called with interrupts disabled.
+fn tick_nohz_idle_enter() -> bool {
+	if !NOHZ_ENABLED.load(Ordering::Relaxed) || TICK_STOPPED.load(Ordering::Relaxed) {
+		return false;
+	}
+	// Other runnable tasks need the tick for preemption
+	if crate::scheduler::nr_running() > 1 {
+		return false;
+	}
+
+	let delta = crate::time::next_timer_expiry_ns()
+		.into_iter()
+		.chain(crate::scheduler::next_timeout_ns())
+		.min()
+		.unwrap_or(u64::MAX);
+	let ticks = delta / NSEC_PER_JIFFY;
+	if ticks < 2 {
+		return false;
+	}
+
+	// The PIT counter limits how long one sleep can be
+	let counts = ticks.saturating_mul(PIT_COUNTS_PER_TICK).min(0xFFFF);
+	ONESHOT_COUNTS.store(counts, Ordering::Relaxed);
+	TICK_STOPPED.store(true, Ordering::Release);
+	program_oneshot(counts as u16);
+	true
+}
+
+/// Catch up on the ticks skipped while stopped and restart the periodic
+/// tick
+fn tick_nohz_restart() {
+	if !TICK_STOPPED.swap(false, Ordering::AcqRel) {
+		return;
+	}
+
+	let programmed = ONESHOT_COUNTS.load(Ordering::Relaxed);
+	let (fired, left) = read_oneshot();
+	// A one-shot that fired delivers its interrupt as a normal tick
+	let elapsed = if fired {
+		programmed.saturating_sub(PIT_COUNTS_PER_TICK)
+	} else {
+		programmed.saturating_sub(left as u64)
+	};
+	let counts = elapsed + PARTIAL_COUNTS.load(Ordering::Relaxed);
+	let ticks = counts / PIT_COUNTS_PER_TICK;
+	PARTIAL_COUNTS.store(counts % PIT_COUNTS_PER_TICK, Ordering::Relaxed);
+
+	let _ = init_pit(TIMER_FREQUENCY);
+	crate::time::catch_up_jiffies(ticks);
+	TIMER_STATE.tick_count.fetch_add(ticks, Ordering::SeqCst);
+	TIMER_STATE.stats.lock().ticks_skipped += ticks;
+}
+
+/// Called on interrupt entry to bring jiffies up to date if the tick was
+/// stopped
+pub fn tick_irq_enter() {
+	if TICK_STOPPED.load(Ordering::Acquire) {
+		tick_nohz_restart();
+	}
+}
+
+/// Halt the CPU until the next interrupt, stopping the periodic tick if
+/// nothing needs it before then
+pub fn cpu_idle() {
+	crate::interrupt::disable();
+	if crate::softirq::local_softirq_pending() != 0 {
+		crate::interrupt::enable();
+		crate::softirq::do_softirq();
+		return;
+	}
+
+	let start = crate::time::get_time_ns();
+	let stopped = tick_nohz_idle_enter();
+	unsafe {
+		// sti takes effect after hlt, so no wakeup is missed
+		core::arch::asm!("sti; hlt");
+	}
+	// In case we weren't woken by an interrupt
+	tick_irq_enter();
+	let idle_ns = crate::time::get_time_ns().saturating_sub(start);
+
+	crate::interrupt::without_interrupts(|| {
+		let mut stats = TIMER_STATE.stats.lock();
+		stats.idle_calls += 1;
+		stats.idle_sleeps += stopped as u64;
+		stats.idle_time_ns += idle_ns;
+	});
+}
+
+/// Enable or disable stopping the tick while idle
+pub fn set_nohz_enabled(enabled: bool) {
+	NOHZ_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Check whether the tick is stopped while idle
+pub fn nohz_enabled() -> bool {
+	NOHZ_ENABLED.load(Ordering::Relaxed)
+}
+
 /// Get timer statistics
 pub fn get_timer_stats() -> TimerStats {
 	TIMER_STATE.get_stats()
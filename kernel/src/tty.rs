@@ -0,0 +1,494 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Terminal layer - controlling terminals and job control
+
+use alloc::{collections::VecDeque, vec::Vec};
+
+use crate::error::{Error, Result};
+use crate::memory::{copy_from_user, copy_to_user, UserPtr};
+use crate::process::{self, current_process};
+use crate::signal::{SIGINT, SIGQUIT, SIGTSTP, SIGTTIN, SIGTTOU};
+use crate::softirq::{tasklet_schedule, Tasklet};
+use crate::sync::{Arc, Spinlock, WaitQueue};
+use crate::types::Pid;
+
+/// VGA/keyboard console
+pub const CONSOLE_TTY: usize = 0;
+/// First serial port
+pub const SERIAL_TTY: usize = 1;
+
+/// Terminal ioctl commands - Linux compatible
+pub mod ioctl {
+	pub const TCGETS: u32 = 0x5401;
+	pub const TCSETS: u32 = 0x5402;
+	pub const TCSETSW: u32 = 0x5403;
+	pub const TCSETSF: u32 = 0x5404;
+	pub const TIOCGWINSZ: u32 = 0x5413;
+	pub const TIOCSWINSZ: u32 = 0x5414;
+	pub const TIOCSCTTY: u32 = 0x540E;
+	pub const TIOCGPGRP: u32 = 0x540F;
+	pub const TIOCSPGRP: u32 = 0x5410;
+	pub const TIOCNOTTY: u32 = 0x5422;
+	pub const TIOCGSID: u32 = 0x5429;
+}
+
+/// c_cc indices of the job control characters
+pub const VINTR: usize = 0;
+pub const VQUIT: usize = 1;
+pub const VSUSP: usize = 10;
+/// A c_cc value matching no character
+const POSIX_VDISABLE: u8 = 0;
+
+const TTY_BUFFER_SIZE: usize = 4096;
+
+/// Terminal attributes, the kernel's struct termios. ISIG and TOSTOP in
+/// c_lflag and the control characters in c_cc drive job control.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Termios {
+	pub c_iflag: u32,
+	pub c_oflag: u32,
+	pub c_cflag: u32,
+	pub c_lflag: u32,
+	pub c_line: u8,
+	pub c_cc: [u8; 19],
+}
+
+/// c_lflag bit enabling INTR/QUIT/SUSP signals
+pub const ISIG: u32 = 0o000001;
+/// c_lflag bit sending SIGTTOU to background writers
+pub const TOSTOP: u32 = 0o000400;
+
+impl Termios {
+	/// Cooked mode with echo, as after boot
+	const fn new() -> Self {
+		let mut c_cc = [0u8; 19];
+		c_cc[VINTR] = 0x03; // Ctrl-C
+		c_cc[VQUIT] = 0x1C; // Ctrl-\
+		c_cc[2] = 0x7F; // VERASE
+		c_cc[3] = 0x15; // VKILL
+		c_cc[4] = 0x04; // VEOF
+		c_cc[6] = 1; // VMIN
+		c_cc[VSUSP] = 0x1A; // Ctrl-Z
+		Self {
+			c_iflag: 0o002400, // ICRNL | IXON
+			c_oflag: 0o000005, // OPOST | ONLCR
+			c_cflag: 0o000277, // B38400 | CS8 | CREAD
+			// ICANON | ECHO | ECHOE | ECHOK | ECHOCTL | ECHOKE | IEXTEN
+			c_lflag: 0o105072 | ISIG,
+			c_line: 0,
+			c_cc,
+		}
+	}
+
+	fn to_bytes(self) -> [u8; 36] {
+		let mut bytes = [0u8; 36];
+		for (i, flag) in [self.c_iflag, self.c_oflag, self.c_cflag, self.c_lflag]
+			.iter()
+			.enumerate()
+		{
+			bytes[i * 4..i * 4 + 4].copy_from_slice(&flag.to_ne_bytes());
+		}
+		bytes[16] = self.c_line;
+		bytes[17..].copy_from_slice(&self.c_cc);
+		bytes
+	}
+
+	fn from_bytes(bytes: &[u8; 36]) -> Self {
+		let flag = |i: usize| {
+			u32::from_ne_bytes([
+				bytes[i * 4],
+				bytes[i * 4 + 1],
+				bytes[i * 4 + 2],
+				bytes[i * 4 + 3],
+			])
+		};
+		let mut c_cc = [0u8; 19];
+		c_cc.copy_from_slice(&bytes[17..]);
+		Self {
+			c_iflag: flag(0),
+			c_oflag: flag(1),
+			c_cflag: flag(2),
+			c_lflag: flag(3),
+			c_line: bytes[16],
+			c_cc,
+		}
+	}
+}
+
+/// Terminal state
+#[derive(Debug)]
+pub struct Tty {
+	pub index: usize,
+	pub name: &'static str,
+	pub session: Option<Pid>,
+	pub pgrp: Option<Pid>, // Foreground process group
+	pub termios: Termios,
+	pub winsize: [u16; 4], // Rows, columns, width and height in pixels
+	input: VecDeque<u8>,
+	queue: Arc<WaitQueue>, // Woken when input arrives
+}
+
+impl Tty {
+	fn new(index: usize, name: &'static str) -> Self {
+		Self {
+			index,
+			name,
+			session: None,
+			pgrp: None,
+			termios: Termios::new(),
+			winsize: [25, 80, 0, 0],
+			input: VecDeque::new(),
+			queue: Arc::new(WaitQueue::new()),
+		}
+	}
+}
+
+/// Global terminal table
+static TTYS: Spinlock<Vec<Tty>> = Spinlock::new(Vec::new());
+
+/// Run `f` on the terminal table. The keyboard interrupt feeds input into
+/// it, so process context holds the lock with interrupts off.
+fn with_ttys<R>(f: impl FnOnce(&mut Vec<Tty>) -> R) -> R {
+	crate::interrupt::without_interrupts(|| f(&mut TTYS.lock()))
+}
+
+/// Job control signals raised by input, waiting to be sent
+static PENDING_SIGNALS: Spinlock<VecDeque<(Pid, i32)>> = Spinlock::new(VecDeque::new());
+
+/// Sends the signals of PENDING_SIGNALS. Sending takes the process table
+/// lock, which the interrupted code may hold, so it can't be done from the
+/// keyboard interrupt.
+static SIGNAL_TASKLET: Tasklet = Tasklet::new(send_pending_signals, 0);
+
+fn send_pending_signals(_data: usize) {
+	let pending = crate::interrupt::without_interrupts(|| {
+		core::mem::take(&mut *PENDING_SIGNALS.lock())
+	});
+	for (pgrp, signal) in pending {
+		let _ = process::signal_process_group(pgrp, signal);
+	}
+}
+
+/// Initialize the terminal layer
+pub fn init() -> Result<()> {
+	with_ttys(|ttys| {
+		ttys.push(Tty::new(CONSOLE_TTY, "tty0"));
+		ttys.push(Tty::new(SERIAL_TTY, "ttyS0"));
+
+		crate::info!("TTY layer initialized with {} terminals", ttys.len());
+	});
+	Ok(())
+}
+
+/// Map a control character to the signal it generates under `termios`
+fn control_signal(termios: &Termios, ch: u8) -> Option<i32> {
+	if termios.c_lflag & ISIG == 0 || ch == POSIX_VDISABLE {
+		return None;
+	}
+	[(VINTR, SIGINT), (VQUIT, SIGQUIT), (VSUSP, SIGTSTP)]
+		.iter()
+		.find(|&&(index, _)| termios.c_cc[index] == ch)
+		.map(|&(_, signal)| signal)
+}
+
+/// Feed a character received from the hardware into a terminal.
+///
+/// Returns true if the character was consumed, either as a job control
+/// character or as input for a user session owning the terminal. Returns
+/// false if nobody owns the terminal and the caller should handle it.
+///
+/// Called from the keyboard interrupt. Signals are sent later from a
+/// tasklet.
+pub fn receive_char(index: usize, ch: u8) -> bool {
+	let signal_target = with_ttys(|ttys| {
+		let tty = match ttys.get_mut(index) {
+			Some(tty) => tty,
+			None => return Err(()),
+		};
+
+		if tty.session.is_none() {
+			return Err(());
+		}
+
+		Ok(match control_signal(&tty.termios, ch) {
+			Some(signal) => {
+				tty.input.clear();
+				tty.pgrp.map(|pgrp| (pgrp, signal))
+			}
+			_ => {
+				if tty.input.len() < TTY_BUFFER_SIZE {
+					tty.input.push_back(ch);
+					tty.queue.wake_up();
+				}
+				None
+			}
+		})
+	});
+
+	match signal_target {
+		Ok(Some(target)) => {
+			crate::interrupt::without_interrupts(|| {
+				PENDING_SIGNALS.lock().push_back(target)
+			});
+			tasklet_schedule(&SIGNAL_TASKLET);
+			true
+		}
+		Ok(None) => true,
+		Err(()) => false,
+	}
+}
+
+/// Stop background process groups touching their controlling terminal.
+///
+/// A background reader blocking or ignoring SIGTTIN gets EIO, while a
+/// writer doing so with SIGTTOU may go ahead. A group that is orphaned
+/// would never be continued, so it gets EIO instead of being stopped.
+fn job_control_check(index: usize, signal: i32) -> Result<()> {
+	let current = match current_process() {
+		Some(process) => process,
+		None => return Ok(()),
+	};
+	if current.ctty != Some(index) {
+		return Ok(());
+	}
+
+	let foreground =
+		with_ttys(|ttys| ttys.get(index).map(|tty| tty.pgrp)).ok_or(Error::ENODEV)?;
+	match foreground {
+		Some(pgrp) if pgrp != current.pgid => {}
+		_ => return Ok(()),
+	}
+
+	if current.ignores_signal(signal) {
+		return if signal == SIGTTIN {
+			Err(Error::EIO)
+		} else {
+			Ok(())
+		};
+	}
+	if process::is_orphaned_pgrp(current.pgid) {
+		return Err(Error::EIO);
+	}
+	process::signal_process_group(current.pgid, signal)?;
+	Err(Error::Interrupted)
+}
+
+/// Read queued input from a terminal
+pub fn read(index: usize, buf: &mut [u8]) -> Result<usize> {
+	job_control_check(index, SIGTTIN)?;
+
+	with_ttys(|ttys| {
+		let tty = ttys.get_mut(index).ok_or(Error::ENODEV)?;
+		if tty.input.is_empty() {
+			return Err(Error::WouldBlock);
+		}
+
+		let mut count = 0;
+		while count < buf.len() {
+			match tty.input.pop_front() {
+				Some(byte) => {
+					buf[count] = byte;
+					count += 1;
+					if byte == b'\n' {
+						break;
+					}
+				}
+				None => break,
+			}
+		}
+		Ok(count)
+	})
+}
+
+/// Whether a terminal has input queued
+pub fn has_input(index: usize) -> bool {
+	with_ttys(|ttys| ttys.get(index).map_or(false, |tty| !tty.input.is_empty()))
+}
+
+/// Wait queue woken when input arrives at a terminal
+pub fn wait_queue(index: usize) -> Option<Arc<WaitQueue>> {
+	with_ttys(|ttys| ttys.get(index).map(|tty| tty.queue.clone()))
+}
+
+/// Write output to a terminal
+pub fn write(index: usize, data: &[u8]) -> Result<usize> {
+	// Background writes are only stopped with TOSTOP set
+	if termios(index)?.c_lflag & TOSTOP != 0 {
+		job_control_check(index, SIGTTOU)?;
+	}
+
+	// Both terminals currently share the kernel console output path
+	for &byte in data {
+		crate::console::_print(format_args!("{}", byte as char));
+	}
+	Ok(data.len())
+}
+
+/// Get the attributes of a terminal
+pub fn termios(index: usize) -> Result<Termios> {
+	with_ttys(|ttys| ttys.get(index).map(|tty| tty.termios)).ok_or(Error::ENODEV)
+}
+
+/// Change the attributes of a terminal, discarding queued input if
+/// `flush`. Like other changes to the terminal, a background caller is
+/// stopped.
+pub fn set_termios(index: usize, termios: Termios, flush: bool) -> Result<()> {
+	job_control_check(index, SIGTTOU)?;
+
+	with_ttys(|ttys| {
+		let tty = ttys.get_mut(index).ok_or(Error::ENODEV)?;
+		if flush {
+			tty.input.clear();
+		}
+		tty.termios = termios;
+		Ok(())
+	})
+}
+
+/// Get the foreground process group of a terminal
+pub fn foreground_pgrp(index: usize) -> Option<Pid> {
+	with_ttys(|ttys| ttys.get(index).and_then(|tty| tty.pgrp))
+}
+
+/// Make a terminal the controlling terminal of the caller's session
+pub fn set_controlling_tty(index: usize) -> Result<()> {
+	let current = current_process().ok_or(Error::ESRCH)?;
+
+	// Only a session leader without a terminal may acquire one
+	if current.sid != current.pid || current.ctty.is_some() {
+		return Err(Error::EPERM);
+	}
+
+	with_ttys(|ttys| {
+		let tty = ttys.get_mut(index).ok_or(Error::ENODEV)?;
+		if tty.session.is_some() {
+			return Err(Error::EPERM);
+		}
+		tty.session = Some(current.sid);
+		tty.pgrp = Some(current.pgid);
+		tty.input.clear();
+		Ok(())
+	})?;
+
+	process::set_session_ctty(current.sid, Some(index));
+	Ok(())
+}
+
+/// Detach the caller's session from its controlling terminal
+pub fn release_controlling_tty() -> Result<()> {
+	let current = current_process().ok_or(Error::ESRCH)?;
+	let index = current.ctty.ok_or(Error::ENOTTY)?;
+
+	// Only the session leader's release tears the terminal down
+	if current.sid != current.pid {
+		process::set_session_ctty(current.pid, None);
+		return Ok(());
+	}
+
+	let foreground = with_ttys(|ttys| -> Result<_> {
+		let tty = ttys.get_mut(index).ok_or(Error::ENODEV)?;
+		tty.session = None;
+		Ok(tty.pgrp.take())
+	})?;
+
+	process::set_session_ctty(current.sid, None);
+	if let Some(pgrp) = foreground {
+		let _ = process::signal_process_group(pgrp, crate::signal::SIGHUP);
+		let _ = process::signal_process_group(pgrp, crate::signal::SIGCONT);
+	}
+	Ok(())
+}
+
+/// Change the foreground process group of the caller's terminal
+pub fn set_foreground_pgrp(index: usize, pgrp: Pid) -> Result<()> {
+	let current = current_process().ok_or(Error::ESRCH)?;
+	if current.ctty != Some(index) {
+		return Err(Error::ENOTTY);
+	}
+	job_control_check(index, SIGTTOU)?;
+
+	// The new foreground group must belong to the terminal's session
+	if process::get_sid(pgrp)? != current.sid || process::get_pgid(pgrp)? != pgrp {
+		return Err(Error::EPERM);
+	}
+
+	with_ttys(|ttys| {
+		let tty = ttys.get_mut(index).ok_or(Error::ENODEV)?;
+		tty.pgrp = Some(pgrp);
+		Ok(())
+	})
+}
+
+/// Handle terminal ioctls
+pub fn tty_ioctl(index: usize, cmd: u32, arg: usize) -> Result<usize> {
+	match cmd {
+		ioctl::TIOCSCTTY => {
+			set_controlling_tty(index)?;
+			Ok(0)
+		}
+		ioctl::TIOCNOTTY => {
+			release_controlling_tty()?;
+			Ok(0)
+		}
+		ioctl::TIOCGPGRP => {
+			let current = current_process().ok_or(Error::ESRCH)?;
+			if current.ctty != Some(index) {
+				return Err(Error::ENOTTY);
+			}
+			let pgrp = foreground_pgrp(index).map(|p| p.0).unwrap_or(0);
+			let ptr = UserPtr::new(arg as *mut u8)?;
+			copy_to_user(ptr, &pgrp.to_ne_bytes())?;
+			Ok(0)
+		}
+		ioctl::TIOCSPGRP => {
+			let mut bytes = [0u8; 4];
+			let ptr = UserPtr::from_const(arg as *const u8)?;
+			copy_from_user(&mut bytes, ptr)?;
+			set_foreground_pgrp(index, Pid(u32::from_ne_bytes(bytes)))?;
+			Ok(0)
+		}
+		ioctl::TCGETS => {
+			copy_to_user(UserPtr::new(arg as *mut u8)?, &termios(index)?.to_bytes())?;
+			Ok(0)
+		}
+		// Output is written synchronously, so there is nothing to drain
+		ioctl::TCSETS | ioctl::TCSETSW | ioctl::TCSETSF => {
+			let mut bytes = [0u8; 36];
+			copy_from_user(&mut bytes, UserPtr::from_const(arg as *const u8)?)?;
+			set_termios(index, Termios::from_bytes(&bytes), cmd == ioctl::TCSETSF)?;
+			Ok(0)
+		}
+		ioctl::TIOCGWINSZ => {
+			let winsize = with_ttys(|ttys| ttys.get(index).map(|tty| tty.winsize))
+				.ok_or(Error::ENODEV)?;
+			let mut bytes = [0u8; 8];
+			for (i, value) in winsize.iter().enumerate() {
+				bytes[i * 2..i * 2 + 2].copy_from_slice(&value.to_ne_bytes());
+			}
+			copy_to_user(UserPtr::new(arg as *mut u8)?, &bytes)?;
+			Ok(0)
+		}
+		ioctl::TIOCSWINSZ => {
+			let mut bytes = [0u8; 8];
+			copy_from_user(&mut bytes, UserPtr::from_const(arg as *const u8)?)?;
+			with_ttys(|ttys| {
+				let tty = ttys.get_mut(index).ok_or(Error::ENODEV)?;
+				for (value, pair) in
+					tty.winsize.iter_mut().zip(bytes.chunks_exact(2))
+				{
+					*value = u16::from_ne_bytes([pair[0], pair[1]]);
+				}
+				Ok(0)
+			})
+		}
+		ioctl::TIOCGSID => {
+			let session = with_ttys(|ttys| ttys.get(index).and_then(|tty| tty.session))
+				.ok_or(Error::ENOTTY)?;
+			let ptr = UserPtr::new(arg as *mut u8)?;
+			copy_to_user(ptr, &session.0.to_ne_bytes())?;
+			Ok(0)
+		}
+		_ => Err(Error::ENOTTY),
+	}
+}
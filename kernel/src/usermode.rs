@@ -5,10 +5,11 @@
 use alloc::{boxed::Box, string::String, vec, vec::Vec};
 
 use crate::arch::x86_64::context::Context;
+use crate::cred::Credentials;
 use crate::error::{Error, Result};
-use crate::memory::{PageFlags, PhysAddr, VirtAddr};
+use crate::memory::{PageFlags, PhysAddr, VirtAddr, VmaArea, PROT_EXEC, PROT_READ, PROT_WRITE};
 use crate::process::{Process, ProcessState, Thread};
-use crate::types::{Gid, Uid};
+use crate::types::{Gid, Pid, Uid};
 
 /// User mode privilege level
 pub const USER_CS: u16 = 0x1B; // GDT selector for user code segment
@@ -17,9 +18,32 @@ pub const USER_DS: u16 = 0x23; // GDT selector for user data segment
 /// User mode stack size
 pub const USER_STACK_SIZE: usize = 8 * 1024 * 1024; // 8MB stack
 
+/// Top of the user stack; it grows down from here
+pub const USER_STACK_TOP: u64 = 0x7FFFFFFFF000;
+
+/// Part of the stack mapped at exec, the rest is mapped on fault
+pub const USER_STACK_INITIAL: usize = 128 * 1024;
+
 /// User mode heap start address
 pub const USER_HEAP_START: u64 = 0x40000000; // 1GB
 
+/// Longest argument or environment string exec takes, with its NUL
+pub const MAX_ARG_STRLEN: usize = 32 * 4096;
+
+/// Most argument and environment strings exec takes
+pub const MAX_ARG_STRINGS: usize = 0x7FFF_FFFF;
+
+/// Most bytes of argument and environment strings and their pointers exec
+/// takes with a stack limit of `stack_limit`: a quarter of it, but at
+/// least one full string and, like Linux, at most three quarters of the
+/// default 8 MiB stack
+pub fn arg_max(stack_limit: u64) -> u64 {
+	const STACK_DEFAULT: u64 = 8 * 1024 * 1024;
+	(stack_limit / 4)
+		.min(STACK_DEFAULT / 4 * 3)
+		.max(MAX_ARG_STRLEN as u64)
+}
+
 /// Simple ELF header for user programs
 #[repr(C)]
 #[derive(Debug, Clone)]
@@ -54,6 +78,49 @@ pub struct ProgramHeader {
 	pub align: u64,  // Alignment
 }
 
+/// ELF identification and header values
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ET_EXEC: u16 = 2;
+const EM_X86_64: u16 = 62;
+
+/// Program header types and flags
+const PT_LOAD: u32 = 1;
+const PT_INTERP: u32 = 3;
+const PT_PHDR: u32 = 6;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+/// Auxiliary vector entry types
+pub const AT_NULL: u64 = 0;
+pub const AT_PHDR: u64 = 3;
+pub const AT_PHENT: u64 = 4;
+pub const AT_PHNUM: u64 = 5;
+pub const AT_PAGESZ: u64 = 6;
+pub const AT_ENTRY: u64 = 9;
+pub const AT_UID: u64 = 11;
+pub const AT_EUID: u64 = 12;
+pub const AT_GID: u64 = 13;
+pub const AT_EGID: u64 = 14;
+pub const AT_PLATFORM: u64 = 15;
+pub const AT_CLKTCK: u64 = 17;
+pub const AT_SECURE: u64 = 23;
+pub const AT_RANDOM: u64 = 25;
+pub const AT_EXECFN: u64 = 31;
+pub const AT_SYSINFO_EHDR: u64 = 33;
+
+/// Clock ticks per second reported to user space (USER_HZ)
+const USER_HZ: u64 = 100;
+
+/// Loadable segment of an ELF program
+pub struct Segment {
+	pub vaddr: u64,
+	pub data: Vec<u8>, // File contents, zero-filled up to mem_size
+	pub mem_size: usize,
+	pub prot: u32,
+}
+
 /// User program structure
 pub struct UserProgram {
 	pub name: String,
@@ -61,6 +128,12 @@ pub struct UserProgram {
 	pub code: Vec<u8>,
 	pub data: Vec<u8>,
 	pub bss_size: usize,
+	pub segments: Vec<Segment>, // From an ELF file, instead of code/data/bss
+	pub phdr: u64,              // Address of the ELF program headers, or 0
+	pub phnum: usize,           // Number of ELF program headers
+	pub mode: u32,              // Permission bits, including S_ISUID/S_ISGID
+	pub uid: Uid,               // Owner
+	pub gid: Gid,
 }
 
 impl UserProgram {
@@ -72,7 +145,89 @@ impl UserProgram {
 			code,
 			data: Vec::new(),
 			bss_size: 0,
+			segments: Vec::new(),
+			phdr: 0,
+			phnum: 0,
+			mode: 0o755,
+			uid: Uid(0),
+			gid: Gid(0),
+		}
+	}
+
+	/// Load a statically linked ELF64 x86-64 executable
+	pub fn from_elf(name: String, image: &[u8]) -> Result<Self> {
+		if image.get(..6) != Some(&[0x7f, b'E', b'L', b'F', ELFCLASS64, ELFDATA2LSB][..])
+			|| read_u16(image, 16)? != ET_EXEC
+			|| read_u16(image, 18)? != EM_X86_64
+			|| read_u16(image, 54)? as usize != core::mem::size_of::<ProgramHeader>()
+		{
+			return Err(Error::ENOEXEC);
+		}
+		let entry = read_u64(image, 24)?;
+		let phoff = read_u64(image, 32)? as usize;
+		let phnum = read_u16(image, 56)? as usize;
+
+		let phdrs_size = (phnum * core::mem::size_of::<ProgramHeader>()) as u64;
+		let mut phdr_addr = None;
+		let mut segments = Vec::new();
+		for i in 0..phnum {
+			let phdr = program_header(
+				image,
+				phoff + i * core::mem::size_of::<ProgramHeader>(),
+			)?;
+			match phdr.type_ {
+				PT_LOAD => {}
+				PT_PHDR => {
+					phdr_addr = Some(phdr.vaddr);
+					continue;
+				}
+				// There is no dynamic linker to hand the program to
+				PT_INTERP => return Err(Error::ENOEXEC),
+				_ => continue,
+			}
+			let end = phdr.vaddr.checked_add(phdr.memsz).ok_or(Error::ENOEXEC)?;
+			let file_end =
+				phdr.offset.checked_add(phdr.filesz).ok_or(Error::ENOEXEC)?;
+			if phdr.filesz > phdr.memsz
+				|| end > USER_STACK_TOP
+				|| file_end > image.len() as u64
+			{
+				return Err(Error::ENOEXEC);
+			}
+
+			// Without PT_PHDR, find the headers in the loaded image
+			if phdr_addr.is_none()
+				&& phdr.offset <= phoff as u64
+				&& phoff as u64 + phdrs_size <= file_end
+			{
+				phdr_addr = Some(phdr.vaddr + (phoff as u64 - phdr.offset));
+			}
+
+			let mut prot = 0;
+			for (flag, bit) in
+				[(PF_R, PROT_READ), (PF_W, PROT_WRITE), (PF_X, PROT_EXEC)]
+			{
+				if phdr.flags & flag != 0 {
+					prot |= bit;
+				}
+			}
+			segments.push(Segment {
+				vaddr: phdr.vaddr,
+				data: image[phdr.offset as usize..file_end as usize].to_vec(),
+				mem_size: phdr.memsz as usize,
+				prot,
+			});
+		}
+		if segments.is_empty() {
+			return Err(Error::ENOEXEC);
 		}
+
+		let mut program = Self::new(name, Vec::new());
+		program.entry_point = entry;
+		program.segments = segments;
+		program.phdr = phdr_addr.unwrap_or(0);
+		program.phnum = phnum;
+		Ok(program)
 	}
 
 	/// Set entry point
@@ -92,6 +247,176 @@ impl UserProgram {
 		self.bss_size = size;
 		self
 	}
+
+	/// Set owner and permission bits
+	pub fn with_owner(mut self, uid: Uid, gid: Gid, mode: u32) -> Self {
+		self.uid = uid;
+		self.gid = gid;
+		self.mode = mode;
+		self
+	}
+}
+
+fn read_u16(image: &[u8], offset: usize) -> Result<u16> {
+	let bytes = image.get(offset..offset + 2).ok_or(Error::ENOEXEC)?;
+	Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(image: &[u8], offset: usize) -> Result<u32> {
+	let bytes = image.get(offset..offset + 4).ok_or(Error::ENOEXEC)?;
+	Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u64(image: &[u8], offset: usize) -> Result<u64> {
+	let bytes = image.get(offset..offset + 8).ok_or(Error::ENOEXEC)?;
+	Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Read the program header at `offset` of an ELF image
+fn program_header(image: &[u8], offset: usize) -> Result<ProgramHeader> {
+	Ok(ProgramHeader {
+		type_: read_u32(image, offset)?,
+		flags: read_u32(image, offset + 4)?,
+		offset: read_u64(image, offset + 8)?,
+		vaddr: read_u64(image, offset + 16)?,
+		paddr: read_u64(image, offset + 24)?,
+		filesz: read_u64(image, offset + 32)?,
+		memsz: read_u64(image, offset + 40)?,
+		align: read_u64(image, offset + 48)?,
+	})
+}
+
+/// Initial user stack of a new program image
+pub struct InitialStack {
+	pub image: Vec<u8>,        // Contents, ending at USER_STACK_TOP
+	pub sp: u64,               // Stack pointer at entry, pointing at argc
+	pub auxv: Vec<(u64, u64)>, // Auxiliary vector, ending with AT_NULL
+}
+
+impl InitialStack {
+	/// Lay out the stack the System V ABI specifies at process entry:
+	/// argc, the argv and envp pointer arrays and the auxiliary vector,
+	/// with the strings and AT_RANDOM bytes above them. `stack_limit`
+	/// is RLIMIT_STACK, a quarter of which the arguments may take.
+	pub fn new(
+		program: &UserProgram,
+		cred: &Credentials,
+		argv: &[String],
+		envp: &[String],
+		execfn: &str,
+		stack_limit: u64,
+	) -> Result<Self> {
+		// Strings area: argv, envp, execfn, platform, random bytes
+		let mut strings = Vec::new();
+		let mut offsets = Vec::with_capacity(argv.len() + envp.len());
+		for arg in argv.iter().chain(envp) {
+			offsets.push(strings.len());
+			strings.extend_from_slice(arg.as_bytes());
+			strings.push(0);
+		}
+		let execfn_offset = strings.len();
+		strings.extend_from_slice(execfn.as_bytes());
+		strings.push(0);
+		let platform_offset = strings.len();
+		strings.extend_from_slice(b"x86_64\0");
+		let random_offset = strings.len();
+		strings.extend_from_slice(&random_bytes());
+
+		// A NULL word marks the end of the stack
+		let strings_start = USER_STACK_TOP - 8 - strings.len() as u64;
+		let address = |offset: usize| strings_start + offset as u64;
+
+		let secure = cred.euid != cred.uid || cred.egid != cred.gid;
+		let mut auxv = Vec::new();
+		if program.phdr != 0 {
+			auxv.push((AT_PHDR, program.phdr));
+			auxv.push((AT_PHENT, core::mem::size_of::<ProgramHeader>() as u64));
+			auxv.push((AT_PHNUM, program.phnum as u64));
+		}
+		auxv.extend([
+			(AT_SYSINFO_EHDR, crate::vdso::VDSO_ADDR),
+			(AT_PAGESZ, 4096),
+			(AT_CLKTCK, USER_HZ),
+			(AT_ENTRY, program.entry_point),
+			(AT_UID, cred.uid.0 as u64),
+			(AT_EUID, cred.euid.0 as u64),
+			(AT_GID, cred.gid.0 as u64),
+			(AT_EGID, cred.egid.0 as u64),
+			(AT_SECURE, secure as u64),
+			(AT_RANDOM, address(random_offset)),
+			(AT_PLATFORM, address(platform_offset)),
+			(AT_EXECFN, address(execfn_offset)),
+			(AT_NULL, 0),
+		]);
+
+		// argc, argv[] and NULL, envp[] and NULL, then the auxv pairs
+		let mut table = Vec::new();
+		table.extend_from_slice(&(argv.len() as u64).to_ne_bytes());
+		for pointers in [&offsets[..argv.len()], &offsets[argv.len()..]] {
+			for offset in pointers {
+				table.extend_from_slice(&address(*offset).to_ne_bytes());
+			}
+			table.extend_from_slice(&0u64.to_ne_bytes());
+		}
+		for (key, value) in &auxv {
+			table.extend_from_slice(&key.to_ne_bytes());
+			table.extend_from_slice(&value.to_ne_bytes());
+		}
+
+		if (strings.len() + table.len()) as u64 > arg_max(stack_limit) {
+			return Err(Error::E2BIG);
+		}
+
+		// The stack pointer is 16-byte aligned at entry
+		let sp = (strings_start - table.len() as u64) & !0xF;
+		let mut image = vec![0u8; (USER_STACK_TOP - sp) as usize];
+		image[..table.len()].copy_from_slice(&table);
+		let start = (strings_start - sp) as usize;
+		image[start..start + strings.len()].copy_from_slice(&strings);
+
+		Ok(Self { image, sp, auxv })
+	}
+}
+
+/// Bytes for AT_RANDOM, which the C library seeds its stack protector
+/// and pointer guard from
+fn random_bytes() -> [u8; 16] {
+	let mut bytes = [0u8; 16];
+	crate::random::get_random_bytes(&mut bytes);
+	bytes
+}
+
+/// Credentials a program runs with when `cred` executes it: adjusted by
+/// its set-user-ID/set-group-ID bits unless `ignore_set_id` is set
+fn exec_cred(
+	program: &UserProgram,
+	mut cred: Credentials,
+	ignore_set_id: bool,
+) -> Result<Credentials> {
+	let mut file_mode = crate::fs::mode::S_IFREG | program.mode;
+	if ignore_set_id {
+		file_mode &= !(crate::fs::mode::S_ISUID | crate::fs::mode::S_ISGID);
+	}
+	crate::cred::generic_permission(
+		&cred,
+		file_mode,
+		program.uid,
+		program.gid,
+		crate::cred::MAY_EXEC,
+	)?;
+	cred.apply_exec(file_mode, program.uid, program.gid);
+	Ok(cred)
+}
+
+/// Context a thread enters a program image with
+fn entry_context(program: &UserProgram, stack: &InitialStack) -> Context {
+	let mut context = Context::new();
+	context.rip = program.entry_point;
+	context.rsp = stack.sp;
+	context.cs = USER_CS;
+	context.ss = USER_DS;
+	context.rflags = 0x202; // Enable interrupts
+	context
 }
 
 /// User mode manager
@@ -122,28 +447,55 @@ impl UserModeManager {
 			.find(|p| p.name == name)
 			.ok_or(Error::NotFound)?;
 
+		let mut argv = vec![String::from(name)];
+		argv.extend(args);
+		Ok(self.start_program(program, false, &argv, &[])?.0)
+	}
+
+	/// Start a program in a new process, as PID 1 for `init`
+	pub fn start_program(
+		&self,
+		program: &UserProgram,
+		init: bool,
+		argv: &[String],
+		envp: &[String],
+	) -> Result<Pid> {
+		let name = program.name.as_str();
 		crate::info!("Loading user program: {}", name);
 
+		// The program runs with the caller's credentials, adjusted by
+		// its set-user-ID/set-group-ID bits unless no_new_privs is set.
+		// Syscall filters are inherited too.
+		let (no_new_privs, seccomp) =
+			crate::process::with_current(|p| (p.no_new_privs, p.seccomp.clone()))
+				.unwrap_or_default();
+		let caller = crate::cred::current_cred();
+		let cred = exec_cred(program, caller.clone(), no_new_privs)?;
+
 		// Create a new process
-		let pid = crate::process::allocate_pid();
-		let mut process = Process::new(pid, name.into(), Uid(0), Gid(0)); // Use dummy uid/gid
+		let pid = if init {
+			crate::process::allocate_init_pid()?
+		} else {
+			crate::process::allocate_pid()
+		};
+		let mut process = Process::new(pid, name.into(), cred.uid, cred.gid);
+		let stack_limit = process.rlimits.cur(crate::resource::RLIMIT_STACK);
+		let stack = InitialStack::new(program, &cred, argv, envp, name, stack_limit)?;
+		process.dumpable = !caller.changes_identity(&cred);
+		process.cred = cred;
+		process.no_new_privs = no_new_privs;
+		process.seccomp = seccomp;
 
 		// Set up user mode address space
-		self.setup_user_address_space(&mut process, program)?;
+		self.setup_user_address_space(&mut process, program, &stack.image)?;
+		process.auxv = stack.auxv.clone();
 
 		// Create initial thread
 		let tid = crate::process::allocate_tid();
 		let mut thread = Thread::new(tid, pid, 0);
 
 		// Set up user mode context
-		let mut context = Context::new();
-		context.rip = program.entry_point;
-		context.rsp = 0x7FFFFFFFFFFF - 16; // Near top of user space
-		context.cs = USER_CS;
-		context.ss = USER_DS;
-		context.rflags = 0x202; // Enable interrupts
-
-		thread.context = context;
+		thread.context = entry_context(program, &stack);
 		thread.state = ProcessState::Running;
 
 		// Add thread to process
@@ -157,15 +509,66 @@ impl UserModeManager {
 		crate::scheduler::add_task(pid)?;
 
 		crate::info!("User program {} loaded and scheduled", name);
-		Ok(pid.0)
+		Ok(pid)
 	}
 
-	/// Set up user mode address space
+	/// Replace the program of the current process (execve). The old
+	/// image stays intact until everything that can fail short of running
+	/// out of memory has been checked; after that, failures kill the
+	/// process.
+	pub fn exec_current(
+		&self,
+		program: &UserProgram,
+		path: &str,
+		argv: &[String],
+		envp: &[String],
+	) -> Result<()> {
+		let current = crate::process::current_process().ok_or(Error::ESRCH)?;
+		let tid = crate::process::current_thread_tid().ok_or(Error::ESRCH)?;
+		// A tracer that couldn't attach to a privileged process mustn't
+		// get one either
+		let ignore_set_id = current.no_new_privs || crate::ptrace::unsafe_exec(current.pid);
+		let cred = exec_cred(program, current.cred.clone(), ignore_set_id)?;
+		let stack_limit = current.rlimits.cur(crate::resource::RLIMIT_STACK);
+		let stack = InitialStack::new(program, &cred, argv, envp, path, stack_limit)?;
+
+		// Point of no return: other threads go away first
+		for other in crate::process::exit_other_threads(current.pid, tid)? {
+			let _ = crate::scheduler::remove_thread(other);
+		}
+
+		let mut image = Process::new(current.pid, program.name.clone(), cred.uid, cred.gid);
+		image.cred = cred;
+		image.rlimits = current.rlimits.clone();
+		if let Err(e) = self.setup_user_address_space(&mut image, program, &stack.image) {
+			crate::warn!("exec of {} by PID {} failed: {:?}", path, current.pid, e);
+			let _ = crate::process::send_signal(current.pid, crate::signal::SIGKILL);
+			return Err(e);
+		}
+		image.auxv = stack.auxv.clone();
+		crate::process::replace_image(current.pid, image, entry_context(program, &stack))?;
+		crate::arch::x86_64::context::set_fs_base(0);
+
+		// replace_image reset the caught signals; pending signals carry
+		// over like on Linux
+		crate::fs::close_on_exec();
+		crate::posix_timers::exec_timers(current.pid);
+		Ok(())
+	}
+
+	/// Set up user mode address space, with `stack` at the top of the
+	/// user stack
 	fn setup_user_address_space(
 		&self,
 		process: &mut Process,
 		program: &UserProgram,
+		stack: &[u8],
 	) -> Result<()> {
+		// Map the segments of an ELF program
+		for segment in &program.segments {
+			self.map_segment(process, segment)?;
+		}
+
 		// Map code segment (executable)
 		let code_pages = (program.code.len() + 4095) / 4096;
 		for i in 0..code_pages {
@@ -191,6 +594,14 @@ impl UserModeManager {
 				PageFlags::USER | PageFlags::PRESENT | PageFlags::EXECUTABLE,
 			)?;
 		}
+		if code_pages > 0 {
+			let code_start = VirtAddr::new(program.entry_point as usize);
+			process.mappings.push(VmaArea::new(
+				code_start,
+				code_start + code_pages * 4096,
+				PROT_READ | PROT_EXEC,
+			));
+		}
 
 		// Map data segment (read/write)
 		if !program.data.is_empty() {
@@ -220,6 +631,12 @@ impl UserModeManager {
 					PageFlags::USER | PageFlags::PRESENT | PageFlags::WRITABLE,
 				)?;
 			}
+			let data_start = VirtAddr::new(data_start as usize);
+			process.mappings.push(VmaArea::new(
+				data_start,
+				data_start + data_pages * 4096,
+				PROT_READ | PROT_WRITE,
+			));
 		}
 
 		// Map BSS segment (zero-initialized)
@@ -244,20 +661,44 @@ impl UserModeManager {
 					PageFlags::USER | PageFlags::PRESENT | PageFlags::WRITABLE,
 				)?;
 			}
+			let bss_start = VirtAddr::new(bss_start as usize);
+			process.mappings.push(VmaArea::new(
+				bss_start,
+				bss_start + bss_pages * 4096,
+				PROT_READ | PROT_WRITE,
+			));
 		}
 
-		// Map user stack
-		let stack_pages = USER_STACK_SIZE / 4096;
-		let stack_start = 0x7FFFFFFFF000 - USER_STACK_SIZE as u64; // Near top of user space
+		// Map the initial user stack, within RLIMIT_STACK, and at least
+		// what the arguments take
+		let stack_limit = process.rlimits.cur(crate::resource::RLIMIT_STACK);
+		let stack_size = (USER_STACK_INITIAL as u64).min(stack_limit) as usize & !0xFFF;
+		let stack_size = stack_size.max((stack.len() + 0xFFF) & !0xFFF);
+		let stack_pages = stack_size / 4096;
+		let image_start = USER_STACK_TOP - stack.len() as u64;
+		let stack_start = USER_STACK_TOP - stack_size as u64;
+		process.total_vm += stack_size as u64;
+		process.hiwater_vm = process.hiwater_vm.max(process.total_vm);
 
 		for i in 0..stack_pages {
 			let vaddr = VirtAddr::new((stack_start + (i * 4096) as u64) as usize);
 			let paddr = crate::memory::allocate_page()?;
 
-			// Zero-initialize stack
+			// Zero the page, then copy in its part of the image
+			let page = stack_start + (i * 4096) as u64;
+			let copy_start = page.max(image_start);
+			let copy_end = page + 4096;
 			unsafe {
 				let dst = paddr.as_u64() as *mut u8;
 				core::ptr::write_bytes(dst, 0, 4096);
+				if copy_start < copy_end {
+					let src = &stack[(copy_start - image_start) as usize..];
+					core::ptr::copy_nonoverlapping(
+						src.as_ptr(),
+						dst.add((copy_start - page) as usize),
+						(copy_end - copy_start) as usize,
+					);
+				}
 			}
 
 			// Map with read/write permissions
@@ -267,11 +708,61 @@ impl UserModeManager {
 				PageFlags::USER | PageFlags::PRESENT | PageFlags::WRITABLE,
 			)?;
 		}
+		process.mappings.push(VmaArea::new(
+			VirtAddr::new(stack_start as usize),
+			VirtAddr::new(USER_STACK_TOP as usize),
+			PROT_READ | PROT_WRITE,
+		));
+
+		// Map the vDSO and its data page
+		crate::vdso::map_vdso(process)?;
 
 		crate::info!("User address space set up for process {}", process.pid);
 		Ok(())
 	}
 
+	/// Map an ELF segment, copying its file contents and zeroing the rest
+	fn map_segment(&self, process: &mut Process, segment: &Segment) -> Result<()> {
+		let start = segment.vaddr & !0xFFF;
+		let end = (segment.vaddr + segment.mem_size as u64 + 0xFFF) & !0xFFF;
+		let data_end = segment.vaddr + segment.data.len() as u64;
+
+		let mut flags = PageFlags::USER | PageFlags::PRESENT;
+		if segment.prot & PROT_WRITE != 0 {
+			flags = flags | PageFlags::WRITABLE;
+		}
+		if segment.prot & PROT_EXEC != 0 {
+			flags = flags | PageFlags::EXECUTABLE;
+		}
+
+		for page in (start..end).step_by(4096) {
+			let paddr = crate::memory::allocate_page()?;
+			let copy_start = page.max(segment.vaddr);
+			let copy_end = (page + 4096).min(data_end);
+			unsafe {
+				let dst = paddr.as_u64() as *mut u8;
+				core::ptr::write_bytes(dst, 0, 4096);
+				if copy_start < copy_end {
+					let offset = (copy_start - segment.vaddr) as usize;
+					let src = &segment.data[offset..];
+					core::ptr::copy_nonoverlapping(
+						src.as_ptr(),
+						dst.add((copy_start - page) as usize),
+						(copy_end - copy_start) as usize,
+					);
+				}
+			}
+			crate::memory::map_page(VirtAddr::new(page as usize), paddr, flags)?;
+		}
+
+		process.mappings.push(VmaArea::new(
+			VirtAddr::new(start as usize),
+			VirtAddr::new(end as usize),
+			segment.prot,
+		));
+		Ok(())
+	}
+
 	/// List available programs
 	pub fn list_programs(&self) -> Vec<&str> {
 		self.programs.iter().map(|p| p.name.as_str()).collect()
@@ -311,6 +802,27 @@ pub fn get_user_mode_manager() -> Result<&'static mut UserModeManager> {
 	unsafe { USER_MODE_MANAGER.as_mut().ok_or(Error::OutOfMemory) }
 }
 
+/// Load an executable from the root filesystem
+pub fn load_program_file(path: &str) -> Result<UserProgram> {
+	let (file_type, mode, _) = crate::memfs::fs_stat(path)?;
+	if !matches!(file_type, crate::memfs::FileType::RegularFile) {
+		return Err(Error::EACCES);
+	}
+	let image = crate::memfs::fs_read_all(path)?;
+	let name = path.rsplit('/').next().unwrap_or(path);
+	let program = UserProgram::from_elf(name.into(), &image)?;
+	Ok(program.with_owner(Uid(0), Gid(0), mode.0 & 0o7777))
+}
+
+/// Start the program at `path` in the root filesystem as init, PID 1
+pub fn exec_init(path: &str) -> Result<Pid> {
+	init_usermode()?;
+	let program = load_program_file(path)?;
+	let argv = [String::from(path)];
+	let envp = [String::from("HOME=/"), String::from("TERM=linux")];
+	get_user_mode_manager()?.start_program(&program, true, &argv, &envp)
+}
+
 /// Create test user programs
 fn create_test_programs() -> Result<()> {
 	let manager = get_user_mode_manager()?;
@@ -366,3 +878,48 @@ pub fn list_user_programs() -> Result<Vec<&'static str>> {
 	let manager = get_user_mode_manager()?;
 	Ok(manager.list_programs())
 }
+
+/// Grow the user stack of the current process to cover `addr`.
+///
+/// Called on a page fault below the mapped stack. Every page from the
+/// fault up to the current bottom of the stack is mapped, so a large stack
+/// frame leaves no holes. Fails if the address is outside the stack area
+/// or the stack would exceed RLIMIT_STACK.
+pub fn grow_user_stack(addr: u64) -> Result<()> {
+	let current = crate::process::current_process().ok_or(Error::ESRCH)?;
+	let page = addr & !0xFFF;
+	if addr >= USER_STACK_TOP || page < USER_STACK_TOP - USER_STACK_SIZE as u64 {
+		return Err(Error::EINVAL);
+	}
+	let bottom = current
+		.mappings
+		.iter()
+		.find(|vma| vma.vm_end.as_usize() as u64 == USER_STACK_TOP)
+		.map_or(USER_STACK_TOP, |vma| vma.vm_start.as_usize() as u64);
+	// A fault inside the stack area maps just the missing page
+	let end = if page < bottom { bottom } else { page + 4096 };
+
+	let limit = current.rlimits.get(crate::resource::RLIMIT_STACK)?;
+	if limit.exceeded_by(USER_STACK_TOP - page) {
+		return Err(Error::ENOMEM);
+	}
+	crate::process::expand_vm(current.pid, end - page)?;
+
+	for vaddr in (page..end).step_by(4096) {
+		let paddr = crate::memory::allocate_page()?;
+		unsafe {
+			core::ptr::write_bytes(paddr.as_u64() as *mut u8, 0, 4096);
+		}
+		crate::memory::map_page(
+			VirtAddr::new(vaddr as usize),
+			paddr,
+			PageFlags::USER | PageFlags::PRESENT | PageFlags::WRITABLE,
+		)?;
+	}
+	crate::process::grow_mapping_down(
+		current.pid,
+		VirtAddr::new(USER_STACK_TOP as usize),
+		VirtAddr::new(page as usize),
+	);
+	Ok(())
+}
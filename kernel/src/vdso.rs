@@ -0,0 +1,448 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Virtual dynamic shared object
+//!
+//! Every user process gets a small ELF shared object mapped read-only,
+//! advertised with AT_SYSINFO_EHDR, that implements clock_gettime,
+//! gettimeofday, time and getcpu without entering the kernel. It reads the
+//! vvar page mapped right before it, which the timer tick keeps up to date
+//! with the clock at the last tick and the TSC parameters to interpolate
+//! from there. Clocks the vDSO can't read fall back to the system call.
+
+use alloc::vec::Vec;
+use core::sync::atomic::{fence, AtomicU32, AtomicU64, Ordering};
+
+use crate::error::{Error, Result};
+use crate::memory::{PageFlags, PhysAddr, VirtAddr, VmaArea, PROT_EXEC, PROT_READ};
+use crate::process::Process;
+use crate::sync::Spinlock;
+
+core::arch::global_asm!(include_str!("arch/x86_64/vdso.s"), options(att_syntax));
+
+extern "C" {
+	static vdso_text_start: u8;
+	static vdso_text_end: u8;
+	static vdso_clock_gettime: u8;
+	static vdso_gettimeofday: u8;
+	static vdso_time: u8;
+	static vdso_getcpu: u8;
+}
+
+const PAGE_SIZE: usize = 4096;
+
+/// Address of the vvar data page, with the vDSO image right after it
+pub const VVAR_ADDR: u64 = 0x7FFF_E000_0000;
+/// Address of the vDSO image
+pub const VDSO_ADDR: u64 = VVAR_ADDR + PAGE_SIZE as u64;
+
+/// Pages of the image: headers and symbols, then the text
+const VDSO_PAGES: usize = 2;
+const TEXT_OFFSET: usize = PAGE_SIZE;
+
+// vvar flags, mirrored in vdso.s
+const VDSO_TSC: u32 = 1; // The TSC can be read
+const VDSO_RDTSCP: u32 = 2; // RDTSCP returns the CPU number
+
+/// IA32_TSC_AUX, returned in ecx by RDTSCP
+const MSR_TSC_AUX: u32 = 0xC000_0103;
+
+/// Data shared with the vDSO. The layout is fixed by vdso.s.
+#[repr(C)]
+struct VdsoData {
+	seq: AtomicU32,         // Odd while an update is in progress
+	flags: AtomicU32,       // VDSO_*
+	cycle_last: AtomicU64,  // TSC at the last update
+	mono_ns: AtomicU64,     // CLOCK_MONOTONIC at cycle_last
+	real_offset: AtomicU64, // CLOCK_REALTIME - CLOCK_MONOTONIC
+	mult: AtomicU64,        // ns = cycles * mult >> 32
+}
+
+#[repr(C, align(4096))]
+struct VvarPage(VdsoData);
+
+static VVAR: VvarPage = VvarPage(VdsoData {
+	seq: AtomicU32::new(0),
+	flags: AtomicU32::new(0),
+	cycle_last: AtomicU64::new(0),
+	mono_ns: AtomicU64::new(0),
+	real_offset: AtomicU64::new(0),
+	mult: AtomicU64::new(0),
+});
+
+/// Physical pages of the vDSO image, shared by all processes
+static IMAGE_PAGES: Spinlock<Vec<PhysAddr>> = Spinlock::new(Vec::new());
+
+/// Set up the vDSO image and getcpu support
+pub fn init() -> Result<()> {
+	image_pages()?;
+
+	// With RDTSCP, getcpu reads the CPU number from TSC_AUX. This is a
+	// uniprocessor kernel: CPU 0 on node 0.
+	let rdtscp = core::arch::x86_64::__cpuid(0x8000_0001).edx & (1 << 27) != 0;
+	if rdtscp {
+		unsafe {
+			core::arch::asm!(
+			    "wrmsr",
+			    in("ecx") MSR_TSC_AUX,
+			    in("eax") 0u32,
+			    in("edx") 0u32,
+			    options(nostack, preserves_flags)
+			);
+		}
+		VVAR.0.flags.fetch_or(VDSO_RDTSCP, Ordering::Relaxed);
+	}
+
+	crate::info!("vDSO mapped at 0x{:x} in new processes", VDSO_ADDR);
+	Ok(())
+}
+
+/// Let the vDSO interpolate the clock with the TSC
+pub fn set_tsc_frequency(tsc_freq: u64) {
+	let data = &VVAR.0;
+	if tsc_freq == 0 {
+		data.flags.fetch_and(!VDSO_TSC, Ordering::Relaxed);
+		return;
+	}
+	let mult = ((crate::time::NSEC_PER_SEC as u128) << 32) / tsc_freq as u128;
+	data.mult.store(mult as u64, Ordering::Relaxed);
+	data.flags.fetch_or(VDSO_TSC, Ordering::Relaxed);
+}
+
+/// Publish the clock at a tick: `mono_ns` is CLOCK_MONOTONIC at TSC value
+/// `cycles`, and CLOCK_REALTIME is `real_offset` ahead of it
+pub fn update(cycles: u64, mono_ns: u64, real_offset: u64) {
+	let data = &VVAR.0;
+	let seq = data.seq.load(Ordering::Relaxed);
+	data.seq.store(seq.wrapping_add(1), Ordering::Relaxed);
+	fence(Ordering::Release);
+	data.cycle_last.store(cycles, Ordering::Relaxed);
+	data.mono_ns.store(mono_ns, Ordering::Relaxed);
+	data.real_offset.store(real_offset, Ordering::Relaxed);
+	data.seq.store(seq.wrapping_add(2), Ordering::Release);
+}
+
+/// Map the vvar page and the vDSO into a new process
+pub fn map_vdso(process: &mut Process) -> Result<()> {
+	let pages = image_pages()?;
+
+	let vvar = crate::memory::virt_to_phys(VirtAddr::new(&VVAR as *const VvarPage as usize))?;
+	crate::memory::map_page(
+		VirtAddr::new(VVAR_ADDR as usize),
+		vvar,
+		PageFlags::USER | PageFlags::PRESENT,
+	)?;
+	for (i, page) in pages.iter().enumerate() {
+		crate::memory::map_page(
+			VirtAddr::new(VDSO_ADDR as usize + i * PAGE_SIZE),
+			*page,
+			PageFlags::USER | PageFlags::PRESENT | PageFlags::EXECUTABLE,
+		)?;
+	}
+
+	let vvar_start = VirtAddr::new(VVAR_ADDR as usize);
+	let vdso_start = VirtAddr::new(VDSO_ADDR as usize);
+	process.mappings
+		.push(VmaArea::new(vvar_start, vvar_start + PAGE_SIZE, PROT_READ));
+	process.mappings.push(VmaArea::new(
+		vdso_start,
+		vdso_start + VDSO_PAGES * PAGE_SIZE,
+		PROT_READ | PROT_EXEC,
+	));
+	process.vdso_base = Some(vdso_start);
+	Ok(())
+}
+
+/// Run the vDSO's clock_gettime in the kernel, on a copy of the image
+/// placed after a snapshot of the data page as in a process. Returns the
+/// time in ns. Only for clocks the vDSO reads without a system call.
+pub(crate) fn clock_gettime_copy(clock: i32) -> Result<u64> {
+	use crate::time::*;
+
+	if !matches!(
+		clock,
+		CLOCK_REALTIME
+			| CLOCK_MONOTONIC
+			| CLOCK_MONOTONIC_RAW
+			| CLOCK_REALTIME_COARSE
+			| CLOCK_MONOTONIC_COARSE
+			| CLOCK_BOOTTIME
+	) {
+		return Err(Error::EINVAL);
+	}
+
+	let image = build_image()?;
+	let mut copy = alloc::vec![0u8; PAGE_SIZE + image.len()];
+	copy[PAGE_SIZE..].copy_from_slice(&image);
+	let entry = unsafe {
+		&vdso_clock_gettime as *const u8 as usize - &vdso_text_start as *const u8 as usize
+	};
+	// The tick can't update the data page halfway through the snapshot
+	crate::interrupt::without_interrupts(|| unsafe {
+		core::ptr::copy_nonoverlapping(
+			&VVAR as *const VvarPage as *const u8,
+			copy.as_mut_ptr(),
+			core::mem::size_of::<VdsoData>(),
+		);
+	});
+
+	let mut ts = [0i64; 2];
+	let ret = unsafe {
+		let func: extern "C" fn(i32, *mut [i64; 2]) -> i64 =
+			core::mem::transmute(copy.as_ptr().add(PAGE_SIZE + TEXT_OFFSET + entry));
+		func(clock, &mut ts)
+	};
+	if ret != 0 {
+		return Err(Error::EIO);
+	}
+	Ok(ts[0] as u64 * NSEC_PER_SEC + ts[1] as u64)
+}
+
+/// The image pages, built on first use
+fn image_pages() -> Result<Vec<PhysAddr>> {
+	let mut pages = IMAGE_PAGES.lock();
+	if pages.is_empty() {
+		let image = build_image()?;
+		for chunk in image.chunks(PAGE_SIZE) {
+			let page = crate::memory::allocate_page()?;
+			unsafe {
+				let dst = page.as_usize() as *mut u8;
+				core::ptr::write_bytes(dst, 0, PAGE_SIZE);
+				core::ptr::copy_nonoverlapping(chunk.as_ptr(), dst, chunk.len());
+			}
+			pages.push(page);
+		}
+	}
+	Ok(pages.clone())
+}
+
+// ELF values for the image
+const ET_DYN: u16 = 3;
+const EM_X86_64: u16 = 62;
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+const SHDR_SIZE: usize = 64;
+const SYM_SIZE: usize = 24;
+const DYN_SIZE: usize = 16;
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PF_X: u32 = 1;
+const PF_R: u32 = 4;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_STRTAB: u32 = 3;
+const SHT_HASH: u32 = 5;
+const SHT_DYNAMIC: u32 = 6;
+const SHT_DYNSYM: u32 = 11;
+const SHF_ALLOC: u64 = 2;
+const SHF_EXECINSTR: u64 = 4;
+
+const DT_NULL: u64 = 0;
+const DT_HASH: u64 = 4;
+const DT_STRTAB: u64 = 5;
+const DT_SYMTAB: u64 = 6;
+const DT_STRSZ: u64 = 10;
+const DT_SYMENT: u64 = 11;
+
+const STB_GLOBAL: u8 = 1;
+const STB_WEAK: u8 = 2;
+const STT_FUNC: u8 = 2;
+
+/// Section indexes
+const SHN_TEXT: u16 = 4;
+const SHN_SHSTRTAB: u16 = 6;
+
+fn put_u16(buf: &mut Vec<u8>, value: u16) {
+	buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+	buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, value: u64) {
+	buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn pad_to(buf: &mut Vec<u8>, align: usize) {
+	let len = (buf.len() + align - 1) / align * align;
+	buf.resize(len, 0);
+}
+
+/// Append a string to a string table and return its offset
+fn add_string(table: &mut Vec<u8>, name: &str) -> u32 {
+	let offset = table.len() as u32;
+	table.extend_from_slice(name.as_bytes());
+	table.push(0);
+	offset
+}
+
+#[allow(clippy::too_many_arguments)]
+fn put_shdr(
+	buf: &mut Vec<u8>,
+	name: u32,
+	sh_type: u32,
+	flags: u64,
+	offset: usize,
+	size: usize,
+	link: u32,
+	info: u32,
+	entsize: usize,
+) {
+	put_u32(buf, name);
+	put_u32(buf, sh_type);
+	put_u64(buf, flags);
+	// Allocated sections are loaded at their file offset
+	let addr = if flags & SHF_ALLOC != 0 { offset } else { 0 };
+	put_u64(buf, addr as u64);
+	put_u64(buf, offset as u64);
+	put_u64(buf, size as u64);
+	put_u32(buf, link);
+	put_u32(buf, info);
+	put_u64(buf, if entsize != 0 { entsize as u64 } else { 1 });
+	put_u64(buf, entsize as u64);
+}
+
+/// Build the vDSO: an ELF shared object linked at address 0, with the
+/// dynamic symbol table a dynamic linker needs to look up its functions
+fn build_image() -> Result<Vec<u8>> {
+	let (text, symbols) = unsafe {
+		let start = &vdso_text_start as *const u8;
+		let len = &vdso_text_end as *const u8 as usize - start as usize;
+		let offset = |sym: &u8| (sym as *const u8 as usize - start as usize) as u64;
+		(
+			core::slice::from_raw_parts(start, len),
+			[
+				("clock_gettime", offset(&vdso_clock_gettime)),
+				("gettimeofday", offset(&vdso_gettimeofday)),
+				("time", offset(&vdso_time)),
+				("getcpu", offset(&vdso_getcpu)),
+			],
+		)
+	};
+	if text.len() > (VDSO_PAGES - 1) * PAGE_SIZE {
+		return Err(Error::ENOMEM);
+	}
+
+	// Each function is exported as __vdso_<name>, and as a weak <name>
+	let mut dynstr = alloc::vec![0u8];
+	let mut dynsym = alloc::vec![0u8; SYM_SIZE];
+	for (name, offset) in symbols {
+		for (name, bind) in [
+			(alloc::format!("__vdso_{}", name), STB_GLOBAL),
+			(name.into(), STB_WEAK),
+		] {
+			put_u32(&mut dynsym, add_string(&mut dynstr, &name));
+			dynsym.push(bind << 4 | STT_FUNC);
+			dynsym.push(0);
+			put_u16(&mut dynsym, SHN_TEXT);
+			put_u64(&mut dynsym, (TEXT_OFFSET as u64) + offset);
+			put_u64(&mut dynsym, 0);
+		}
+	}
+	let nsyms = dynsym.len() / SYM_SIZE;
+
+	// A single hash bucket chaining all symbols
+	let mut hash = Vec::new();
+	put_u32(&mut hash, 1);
+	put_u32(&mut hash, nsyms as u32);
+	put_u32(&mut hash, nsyms as u32 - 1);
+	put_u32(&mut hash, 0);
+	for i in 1..nsyms {
+		put_u32(&mut hash, i as u32 - 1);
+	}
+
+	let mut shstrtab = alloc::vec![0u8];
+	let names = [
+		".hash",
+		".dynsym",
+		".dynstr",
+		".text",
+		".dynamic",
+		".shstrtab",
+	]
+	.map(|name| add_string(&mut shstrtab, name));
+
+	// Lay out the first page
+	let hash_off = EHDR_SIZE + 2 * PHDR_SIZE;
+	let dynsym_off = (hash_off + hash.len() + 7) & !7;
+	let dynstr_off = dynsym_off + dynsym.len();
+	let dynamic_off = (dynstr_off + dynstr.len() + 7) & !7;
+	let dynamic_len = 6 * DYN_SIZE;
+	let shstrtab_off = dynamic_off + dynamic_len;
+	let shdr_off = (shstrtab_off + shstrtab.len() + 7) & !7;
+	if shdr_off + 7 * SHDR_SIZE > TEXT_OFFSET {
+		return Err(Error::ENOMEM);
+	}
+	let image_len = TEXT_OFFSET + text.len();
+
+	let mut image = Vec::with_capacity(VDSO_PAGES * PAGE_SIZE);
+	image.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+	image.resize(16, 0);
+	put_u16(&mut image, ET_DYN);
+	put_u16(&mut image, EM_X86_64);
+	put_u32(&mut image, 1); // e_version
+	put_u64(&mut image, 0); // e_entry
+	put_u64(&mut image, EHDR_SIZE as u64);
+	put_u64(&mut image, shdr_off as u64);
+	put_u32(&mut image, 0); // e_flags
+	put_u16(&mut image, EHDR_SIZE as u16);
+	put_u16(&mut image, PHDR_SIZE as u16);
+	put_u16(&mut image, 2);
+	put_u16(&mut image, SHDR_SIZE as u16);
+	put_u16(&mut image, 7);
+	put_u16(&mut image, SHN_SHSTRTAB);
+
+	for (p_type, flags, offset, size, align) in [
+		(PT_LOAD, PF_R | PF_X, 0, image_len, PAGE_SIZE),
+		(PT_DYNAMIC, PF_R, dynamic_off, dynamic_len, 8),
+	] {
+		put_u32(&mut image, p_type);
+		put_u32(&mut image, flags);
+		put_u64(&mut image, offset as u64);
+		put_u64(&mut image, offset as u64); // p_vaddr
+		put_u64(&mut image, offset as u64); // p_paddr
+		put_u64(&mut image, size as u64);
+		put_u64(&mut image, size as u64);
+		put_u64(&mut image, align as u64);
+	}
+
+	image.extend_from_slice(&hash);
+	pad_to(&mut image, 8);
+	image.extend_from_slice(&dynsym);
+	image.extend_from_slice(&dynstr);
+	pad_to(&mut image, 8);
+	for (tag, value) in [
+		(DT_HASH, hash_off),
+		(DT_STRTAB, dynstr_off),
+		(DT_SYMTAB, dynsym_off),
+		(DT_STRSZ, dynstr.len()),
+		(DT_SYMENT, SYM_SIZE),
+		(DT_NULL, 0),
+	] {
+		put_u64(&mut image, tag);
+		put_u64(&mut image, value as u64);
+	}
+	image.extend_from_slice(&shstrtab);
+	pad_to(&mut image, 8);
+
+	image.resize(image.len() + SHDR_SIZE, 0);
+	#[rustfmt::skip]
+	let sections = [
+		(names[0], SHT_HASH, SHF_ALLOC, hash_off, hash.len(), 2, 0, 4),
+		(names[1], SHT_DYNSYM, SHF_ALLOC, dynsym_off, dynsym.len(), 3, 1, SYM_SIZE),
+		(names[2], SHT_STRTAB, SHF_ALLOC, dynstr_off, dynstr.len(), 0, 0, 0),
+		(names[3], SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, TEXT_OFFSET, text.len(), 0, 0, 0),
+		(names[4], SHT_DYNAMIC, SHF_ALLOC, dynamic_off, dynamic_len, 3, 0, DYN_SIZE),
+		(names[5], SHT_STRTAB, 0, shstrtab_off, shstrtab.len(), 0, 0, 0),
+	];
+	for (name, sh_type, flags, offset, size, link, info, entsize) in sections {
+		put_shdr(
+			&mut image, name, sh_type, flags, offset, size, link, info, entsize,
+		);
+	}
+
+	image.resize(TEXT_OFFSET, 0);
+	image.extend_from_slice(text);
+	Ok(image)
+}
@@ -271,9 +271,7 @@ pub fn get_task_count() -> usize {
 /// Idle task that runs when no other tasks are ready
 pub fn idle_task() {
 	loop {
-		unsafe {
-			core::arch::asm!("hlt"); // Halt until interrupt
-		}
+		crate::timer::cpu_idle();
 	}
 }
 
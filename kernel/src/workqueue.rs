@@ -0,0 +1,270 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Workqueues - deferred work run by kernel worker threads
+//!
+//! Unlike softirqs and tasklets, work items run in process context and may
+//! sleep. Each workqueue has its own worker thread. Delayed work is queued
+//! by a timer on the time::TimerWheel once its delay has passed.
+
+use alloc::{
+	collections::BTreeMap, collections::VecDeque, format, string::String, sync::Arc, vec::Vec,
+};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use spin::once::Once;
+
+use crate::error::Result;
+use crate::sync::Spinlock;
+use crate::time::{HrTimer, HrTimerBase, TimeSpec};
+use crate::types::Tid;
+
+/// A function to run from a workqueue
+pub struct Work {
+	func: fn(usize),
+	data: usize,
+	pending: AtomicBool, // Queued or waiting for its delay
+	running: AtomicBool,
+}
+
+impl Work {
+	pub const fn new(func: fn(usize), data: usize) -> Self {
+		Self {
+			func,
+			data,
+			pending: AtomicBool::new(false),
+			running: AtomicBool::new(false),
+		}
+	}
+
+	/// Check whether the work is queued or waiting for its delay
+	pub fn is_pending(&self) -> bool {
+		self.pending.load(Ordering::Acquire)
+	}
+
+	fn is_running(&self) -> bool {
+		self.running.load(Ordering::Acquire)
+	}
+}
+
+/// A queue of work run in order by its worker thread
+pub struct Workqueue {
+	name: String,
+	queue: Spinlock<VecDeque<&'static Work>>,
+	queued: AtomicU64, // Works ever queued
+	done: AtomicU64,   // Works ever finished
+	dying: AtomicBool,
+}
+
+impl Workqueue {
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	fn insert(&self, work: &'static Work) {
+		// Also called from interrupt and softirq context
+		crate::interrupt::without_interrupts(|| {
+			self.queued.fetch_add(1, Ordering::AcqRel);
+			self.queue.lock().push_back(work);
+		});
+	}
+
+	fn next(&self) -> Option<&'static Work> {
+		crate::interrupt::without_interrupts(|| self.queue.lock().pop_front())
+	}
+
+	/// Run queued work until the queue is empty
+	fn process(&self) {
+		while let Some(work) = self.next() {
+			work.running.store(true, Ordering::Release);
+			// Cleared first so the work can queue itself again
+			work.pending.store(false, Ordering::Release);
+			(work.func)(work.data);
+			work.running.store(false, Ordering::Release);
+			self.done.fetch_add(1, Ordering::AcqRel);
+		}
+	}
+}
+
+/// Worker thread of each workqueue
+static WORKERS: Spinlock<BTreeMap<Tid, Arc<Workqueue>>> = Spinlock::new(BTreeMap::new());
+
+/// Workqueue for work that doesn't need its own
+static SYSTEM_WQ: Once<Arc<Workqueue>> = Once::new();
+
+fn worker_thread() {
+	let tid = crate::scheduler::current_tid();
+	let mut wq = None;
+	// The thread can start before its workqueue is registered
+	crate::sync::wait_until(|| {
+		wq = tid.and_then(|tid| WORKERS.lock().get(&tid).cloned());
+		wq.is_some()
+	});
+	let wq = match wq {
+		Some(wq) => wq,
+		None => return,
+	};
+
+	loop {
+		crate::sync::wait_until(|| {
+			wq.dying.load(Ordering::Acquire)
+				|| wq.queued.load(Ordering::Acquire)
+					!= wq.done.load(Ordering::Acquire)
+		});
+		wq.process();
+		if wq.dying.load(Ordering::Acquire) {
+			break;
+		}
+	}
+	if let Some(tid) = tid {
+		WORKERS.lock().remove(&tid);
+	}
+}
+
+/// Create a workqueue with its own worker thread
+pub fn alloc_workqueue(name: &str) -> Result<Arc<Workqueue>> {
+	let wq = Arc::new(Workqueue {
+		name: String::from(name),
+		queue: Spinlock::new(VecDeque::new()),
+		queued: AtomicU64::new(0),
+		done: AtomicU64::new(0),
+		dying: AtomicBool::new(false),
+	});
+	let tid = crate::working_task::spawn_kernel_task(
+		format!("kworker/{}", name),
+		worker_thread,
+		16384,
+	)?;
+	WORKERS.lock().insert(tid, wq.clone());
+	Ok(wq)
+}
+
+/// Finish queued work and stop the worker thread
+#[track_caller]
+pub fn destroy_workqueue(wq: &Arc<Workqueue>) {
+	flush_workqueue(wq);
+	wq.dying.store(true, Ordering::Release);
+}
+
+/// Queue work to run on `wq`. Returns false if it was already pending.
+pub fn queue_work(wq: &Workqueue, work: &'static Work) -> bool {
+	if wq.dying.load(Ordering::Acquire) || work.pending.swap(true, Ordering::AcqRel) {
+		return false;
+	}
+	wq.insert(work);
+	true
+}
+
+/// Wait for all work queued on `wq` so far to finish
+#[track_caller]
+pub fn flush_workqueue(wq: &Workqueue) {
+	crate::sync::might_sleep();
+	let target = wq.queued.load(Ordering::Acquire);
+	crate::sync::wait_until(|| wq.done.load(Ordering::Acquire) >= target);
+}
+
+/// Wait for `work` to finish running. Returns false if it was idle.
+#[track_caller]
+pub fn flush_work(work: &Work) -> bool {
+	if !work.is_pending() && !work.is_running() {
+		return false;
+	}
+	crate::sync::might_sleep();
+	crate::sync::wait_until(|| !work.is_pending() && !work.is_running());
+	true
+}
+
+/// Work waiting for its delay to pass
+struct DelayedEntry {
+	expires: u64, // Boot time in ns
+	wq: Arc<Workqueue>,
+	work: &'static Work,
+}
+
+static DELAYED: Spinlock<Vec<DelayedEntry>> = Spinlock::new(Vec::new());
+
+/// Timer callback queueing delayed work whose delay has passed
+fn delayed_work_timer_fn() {
+	let now = crate::time::get_boottime().to_ns();
+	let due: Vec<DelayedEntry> = crate::interrupt::without_interrupts(|| {
+		let mut delayed = DELAYED.lock();
+		let (due, waiting) = core::mem::take(&mut *delayed)
+			.into_iter()
+			.partition(|entry| entry.expires <= now);
+		*delayed = waiting;
+		due
+	});
+	for entry in due {
+		entry.wq.insert(entry.work);
+	}
+}
+
+/// Queue work to run on `wq` after `delay_ns` nanoseconds. Returns false
+/// if it was already pending.
+pub fn queue_delayed_work(wq: &Arc<Workqueue>, work: &'static Work, delay_ns: u64) -> bool {
+	if delay_ns == 0 {
+		return queue_work(wq, work);
+	}
+	if wq.dying.load(Ordering::Acquire) || work.pending.swap(true, Ordering::AcqRel) {
+		return false;
+	}
+
+	let expires = crate::time::get_boottime().to_ns() + delay_ns;
+	crate::interrupt::without_interrupts(|| {
+		DELAYED.lock().push(DelayedEntry {
+			expires,
+			wq: wq.clone(),
+			work,
+		})
+	});
+	let mut timer = HrTimer::new(HrTimerBase::Boottime);
+	timer.expires = TimeSpec::from_ns(expires);
+	timer.function = Some(delayed_work_timer_fn);
+	crate::time::add_timer(timer);
+	true
+}
+
+/// Cancel delayed work whose delay hasn't passed. Returns whether it was
+/// cancelled.
+pub fn cancel_delayed_work(work: &Work) -> bool {
+	let cancelled = crate::interrupt::without_interrupts(|| {
+		let mut delayed = DELAYED.lock();
+		let before = delayed.len();
+		delayed.retain(|entry| !core::ptr::eq(entry.work, work));
+		delayed.len() != before
+	});
+	if cancelled {
+		work.pending.store(false, Ordering::Release);
+	}
+	cancelled
+}
+
+/// Get the system workqueue
+pub fn system_wq() -> Option<&'static Arc<Workqueue>> {
+	SYSTEM_WQ.get()
+}
+
+/// Queue work on the system workqueue
+pub fn schedule_work(work: &'static Work) -> bool {
+	system_wq().map_or(false, |wq| queue_work(wq, work))
+}
+
+/// Queue work on the system workqueue after `delay_ns` nanoseconds
+pub fn schedule_delayed_work(work: &'static Work, delay_ns: u64) -> bool {
+	system_wq().map_or(false, |wq| queue_delayed_work(wq, work, delay_ns))
+}
+
+/// Wait for work queued on the system workqueue to finish
+#[track_caller]
+pub fn flush_scheduled_work() {
+	if let Some(wq) = system_wq() {
+		flush_workqueue(wq);
+	}
+}
+
+/// Create the system workqueue
+pub fn init() -> Result<()> {
+	let wq = alloc_workqueue("events")?;
+	SYSTEM_WQ.call_once(|| wq);
+	crate::info!("Workqueues initialized");
+	Ok(())
+}